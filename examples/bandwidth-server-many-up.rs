@@ -0,0 +1,29 @@
+use libmodbus::{Modbus, ModbusMapping, ModbusTCP, TcpServer};
+
+const NB_CONNECTION: i32 = 5;
+
+fn run() -> Result<(), libmodbus::Error> {
+    let mut modbus =
+        Modbus::new_tcp("127.0.0.1", 1502).expect("Could not create modbus TCP context");
+
+    let mut mb_mapping =
+        ModbusMapping::new(Modbus::MAX_READ_BITS, 0, Modbus::MAX_READ_REGISTERS, 0)
+            .expect("Failed to allocate the mapping");
+
+    let server_socket = modbus
+        .tcp_listen(NB_CONNECTION)
+        .expect("Unable to listen TCP connection");
+
+    let mut server = TcpServer::new(modbus, server_socket);
+    server.set_max_connections(NB_CONNECTION as usize);
+
+    server.serve(&mut mb_mapping)
+}
+
+fn main() {
+    if let Err(ref err) = run() {
+        println!("Server error: {}", err);
+
+        std::process::exit(1);
+    }
+}
@@ -1,5 +1,5 @@
 use clap::{App, Arg, ArgMatches};
-use libmodbus::{Modbus, ModbusMapping, ModbusRTU, ModbusServer, ModbusTCP, ModbusTCPPI};
+use libmodbus::{serve, Modbus, ModbusMapping, ModbusRTU, ModbusTCP, ModbusTCPPI, StopToken};
 
 #[derive(Debug, Eq, PartialEq)]
 enum Backend {
@@ -13,7 +13,6 @@ const SERVER_ID: u8 = 247;
 fn run(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
     let backend;
     let mut modbus;
-    let mut query;
 
     match matches.value_of("backend").unwrap() {
         "tcp" => backend = Backend::TCP,
@@ -24,7 +23,6 @@ fn run(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
 
     match backend {
         Backend::RTU => {
-            query = vec![0u8; Modbus::RTU_MAX_ADU_LENGTH as usize];
             let serial_interface = matches
                 .value_of("serial_interface")
                 .unwrap_or("/dev/ttyUSB0");
@@ -32,14 +30,12 @@ fn run(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
             modbus.set_slave(SERVER_ID)?;
         }
         Backend::TCP => {
-            query = vec![0u8; Modbus::TCP_MAX_ADU_LENGTH as usize];
             modbus = Modbus::new_tcp("127.0.0.1", 1502)?;
 
             let mut socket = modbus.tcp_listen(1)?;
             modbus.tcp_accept(&mut socket)?;
         }
         Backend::TCPPI => {
-            query = vec![0u8; Modbus::TCP_MAX_ADU_LENGTH as usize];
             modbus = Modbus::new_tcp_pi("::0", "1502")?;
 
             let mut socket = modbus.tcp_listen(1)?;
@@ -52,17 +48,16 @@ fn run(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
 
     let modbus_mapping = ModbusMapping::new(500, 500, 500, 500).unwrap();
 
-    loop {
-        match modbus.receive(&mut query) {
-            Ok(num) => modbus.reply(&query, num, &modbus_mapping),
-            Err(err) => {
-                println!("ERROR while parsing: {}", err);
-                break;
-            }
-        }
-        .expect("could not receive message");
-    }
-    println!("Quit the loop: ");
+    // stop with Ctrl-C
+    let mut stop = StopToken::new();
+    stop.set_stop_on_signals(true);
+
+    let statistics = serve(&mut modbus, &modbus_mapping, &stop)?;
+    println!(
+        "Quit the loop: {} requests, {} exceptions",
+        statistics.total_requests(),
+        statistics.total_exceptions()
+    );
 
     Ok(())
 }
@@ -0,0 +1,88 @@
+use clap::{App, Arg, ArgMatches};
+use libmodbus::{Modbus, ModbusTCP, ModbusTCPPI, PseudoTerminal, Simulator, StopToken};
+
+const SERVER_ID: u8 = 247;
+
+fn run(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let mut simulator = Simulator::load(matches.value_of("simulation").unwrap())?;
+
+    // stop with Ctrl-C
+    let mut stop = StopToken::new();
+    stop.set_stop_on_signals(true);
+
+    let statistics = match matches.value_of("backend").unwrap() {
+        "rtu" => {
+            let pty = PseudoTerminal::open()?;
+            println!(
+                "RTU clients connect to {} as slave {}",
+                pty.path(),
+                SERVER_ID
+            );
+            let mut modbus = pty.into_modbus(SERVER_ID)?;
+            simulator.serve(&mut modbus, &stop)?
+        }
+        backend => {
+            let mut modbus = if backend == "tcp" {
+                Modbus::new_tcp("127.0.0.1", 1502)?
+            } else {
+                Modbus::new_tcp_pi("::0", "1502")?
+            };
+            let mut socket = if backend == "tcp" {
+                modbus.tcp_listen(1)?
+            } else {
+                modbus.tcp_pi_listen(1)?
+            };
+            println!("TCP clients connect to port 1502");
+
+            // serve one client after the other
+            while !stop.is_stopped() {
+                if backend == "tcp" {
+                    modbus.tcp_accept(&mut socket)?;
+                } else {
+                    modbus.tcp_pi_accept(&mut socket)?;
+                }
+                if let Err(err) = simulator.serve(&mut modbus, &stop) {
+                    println!("Client disconnected: {}", err);
+                }
+            }
+            modbus.statistics()
+        }
+    };
+
+    println!(
+        "Quit the simulation: {} requests, {} exceptions",
+        statistics.total_requests(),
+        statistics.total_exceptions()
+    );
+    Ok(())
+}
+
+fn main() {
+    let matches = App::new("simulator")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Simulated Modbus device, see examples/simulator.toml")
+        .arg(
+            Arg::with_name("backend")
+                .help("which backend shoud be used")
+                .long("backend")
+                .short("b")
+                .possible_values(&["rtu", "tcp", "tcppi"])
+                .default_value("tcp")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("simulation")
+                .help("the simulation file, TOML or YAML")
+                .long("simulation")
+                .short("s")
+                .default_value("examples/simulator.toml")
+                .takes_value(true),
+        )
+        .get_matches();
+
+    if let Err(ref err) = run(&matches) {
+        println!("Error: {}", err);
+
+        std::process::exit(1)
+    }
+}
@@ -1,8 +1,11 @@
+use crate::{Exception, Statistics};
+use libmodbus_sys as ffi;
 use std::fmt;
 use std::io;
 
 #[derive(Debug)]
 #[non_exhaustive]
+#[rustfmt::skip]
 pub enum Error {
     Client { msg: String, source: io::Error },
     Mapping { msg: String, source: io::Error },
@@ -11,6 +14,14 @@ pub enum Error {
     TcpPi { msg: String, source: io::Error },
     Tcp { msg: String, source: io::Error },
     Modbus { msg: String, source: io::Error },
+    Simulator { msg: String, source: io::Error },
+    Ascii { msg: String, source: io::Error },
+    Udp { msg: String, source: io::Error },
+    Tls { msg: String, source: io::Error },
+    Verify { msg: String, address: u16, written: u16, read_back: u16 },
+    Request { msg: String, exception: Exception },
+    /// A [`serve()`](fn.serve.html) loop ended by `source`, with the statistics gathered until then
+    Serve { statistics: Box<Statistics>, source: Box<Error> },
     IoError(io::Error),
 }
 
@@ -24,11 +35,63 @@ impl fmt::Display for Error {
             Error::TcpPi { ref msg, source: _ } => write!(f, "TcpPi Error: {:?}", msg),
             Error::Tcp { ref msg, source: _ } => write!(f, "Tcp Error: {:?}", msg),
             Error::Modbus { ref msg, source: _ } => write!(f, "Modbus Error: {:?}", msg),
+            Error::Simulator { ref msg, source: _ } => write!(f, "Simulator Error: {:?}", msg),
+            Error::Ascii { ref msg, source: _ } => write!(f, "Ascii Error: {:?}", msg),
+            Error::Udp { ref msg, source: _ } => write!(f, "Udp Error: {:?}", msg),
+            Error::Tls { ref msg, source: _ } => write!(f, "Tls Error: {:?}", msg),
+            Error::Verify {
+                ref msg,
+                address,
+                written,
+                read_back,
+            } => write!(
+                f,
+                "Verify Error: {:?}, wrote {} to address {} but read back {}",
+                msg, written, address, read_back
+            ),
+            Error::Request { ref msg, exception } => {
+                write!(f, "Request Error: {:?}, answer with {:?}", msg, exception)
+            }
+            Error::Serve { ref source, .. } => write!(f, "Serve Error: {}", source),
             Error::IoError(ref err) => write!(f, "IO Error: {:?}", err),
         }
     }
 }
 
+/// A violation of the timing of the Modbus serial line specification
+///
+/// Only detected by contexts enforcing it, see
+/// [`ModbusStream::set_rtu_timing()`](struct.ModbusStream.html#method.set_rtu_timing). The frame is discarded, the
+/// violation is reported by [`Error::framing_violation()`](enum.Error.html#method.framing_violation).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FramingViolation {
+    /// A silence of more than 1.5 characters between two characters of a frame
+    InterCharacterGap,
+    /// A silence of 3.5 characters ended a frame before it was complete
+    IncompleteFrame,
+}
+
+impl fmt::Display for FramingViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FramingViolation::InterCharacterGap => {
+                write!(f, "silence of more than 1.5 characters within a frame")
+            }
+            FramingViolation::IncompleteFrame => {
+                write!(f, "silence of 3.5 characters before the end of a frame")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FramingViolation {}
+
+impl From<FramingViolation> for io::Error {
+    fn from(violation: FramingViolation) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, violation)
+    }
+}
+
 impl From<io::Error> for Error {
     fn from(err: io::Error) -> Error {
         Error::IoError(err)
@@ -36,3 +99,91 @@ impl From<io::Error> for Error {
 }
 
 impl std::error::Error for Error {}
+
+impl Error {
+    /// `exception` - the Modbus exception answered by the remote device
+    ///
+    /// libmodbus reports exception responses through errno (`MODBUS_ENOBASE` + exception code). This function
+    /// returns the matching [`Exception`](enum.Exception.html) if the error is such an exception response, otherwise
+    /// `None`. For an invalid request, see [`Request::parse()`](enum.Request.html#method.parse), it returns the
+    /// exception the server should answer with.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use libmodbus::{Error, Exception, Modbus};
+    ///
+    /// let err = Error::Client {
+    ///     msg: "read_registers".to_owned(),
+    ///     source: std::io::Error::from_raw_os_error(Modbus::ENOBASE as i32 + 2),
+    /// };
+    /// assert_eq!(err.exception(), Some(Exception::IllegalDataAddress));
+    /// ```
+    pub fn exception(&self) -> Option<Exception> {
+        match *self {
+            Error::Request { exception, .. } => Some(exception),
+            Error::Serve { ref source, .. } => source.exception(),
+            _ => exception_from_errno(self.io_error().and_then(io::Error::raw_os_error)),
+        }
+    }
+
+    /// `framing_violation` - the violation of the serial line timing the error reports
+    ///
+    /// `None` if the error isn't such a violation, see [`FramingViolation`](enum.FramingViolation.html).
+    pub fn framing_violation(&self) -> Option<FramingViolation> {
+        self.io_error().and_then(framing_violation)
+    }
+
+    fn io_error(&self) -> Option<&io::Error> {
+        match *self {
+            Error::Client { ref source, .. }
+            | Error::Mapping { ref source, .. }
+            | Error::Rtu { ref source, .. }
+            | Error::Server { ref source, .. }
+            | Error::TcpPi { ref source, .. }
+            | Error::Tcp { ref source, .. }
+            | Error::Modbus { ref source, .. }
+            | Error::Simulator { ref source, .. }
+            | Error::Ascii { ref source, .. }
+            | Error::Udp { ref source, .. }
+            | Error::Tls { ref source, .. }
+            | Error::IoError(ref source) => Some(source),
+            Error::Serve { ref source, .. } => source.io_error(),
+            Error::Verify { .. } | Error::Request { .. } => None,
+        }
+    }
+}
+
+// libmodbus specific errno values, see modbus.h
+const ENOBASE: i32 = ffi::MODBUS_ENOBASE as i32;
+pub(crate) const EMBBADCRC: i32 = ENOBASE + 12;
+pub(crate) const EMBBADDATA: i32 = ENOBASE + 13;
+pub(crate) const EMBBADEXC: i32 = ENOBASE + 14;
+pub(crate) const EMBUNKEXC: i32 = ENOBASE + 15;
+pub(crate) const EMBMDATA: i32 = ENOBASE + 16;
+pub(crate) const EMBBADSLAVE: i32 = ENOBASE + 17;
+
+/// Map a libmodbus errno to the exception it reports, if any.
+pub(crate) fn exception_from_errno(errno: Option<i32>) -> Option<Exception> {
+    match errno {
+        Some(errno) if errno > ENOBASE && errno <= ENOBASE + 11 => {
+            Exception::from_code((errno - ENOBASE) as u8)
+        }
+        _ => None,
+    }
+}
+
+/// The violation of the serial line timing `err` reports, if any
+pub(crate) fn framing_violation(err: &io::Error) -> Option<FramingViolation> {
+    err.get_ref()
+        .and_then(|inner| inner.downcast_ref::<FramingViolation>())
+        .copied()
+}
+
+/// True if the libmodbus errno reports a malformed or unexpected message.
+pub(crate) fn is_framing_errno(errno: Option<i32>) -> bool {
+    match errno {
+        Some(errno) => [EMBBADDATA, EMBBADEXC, EMBUNKEXC, EMBMDATA, EMBBADSLAVE].contains(&errno),
+        None => false,
+    }
+}
@@ -42,6 +42,11 @@
 //! * [RTU Context](trait.ModbusRTU.html)
 //! * [TCP (IPv4) Context](trait.ModbusTCP.html)
 //! * [TCP PI (IPv4 and IPv6) Context](trait.ModbusTCPPI.html)
+//! * [ASCII Context](struct.ModbusStream.html#method.new_ascii)
+//! * [RTU over TCP Context](struct.ModbusStream.html#method.new_rtu_tcp)
+//! * [UDP Context](struct.ModbusStream.html#method.new_udp)
+//! * [TLS Context](struct.ModbusStream.html#method.new_tls)
+//! * [Custom Transport](struct.ModbusStream.html#method.from_transport)
 //!
 //! ### [RTU Context](trait.ModbusRTU.html)
 //!
@@ -67,8 +72,17 @@
 //! than response timeout of master
 //! (ortherwise other slaves may ignore master requests when one of the slave is not responding).
 //!
+//! An RTU context implemented in Rust, [`ModbusStream::new_rtu()`](struct.ModbusStream.html#method.new_rtu), can
+//! enforce the timing of the specification instead: the silence of 3.5 characters between frames and at most 1.5
+//! characters between the characters of a frame, see
+//! [`set_rtu_timing()`](struct.ModbusStream.html#method.set_rtu_timing). The libmodbus contexts of
+//! [`Modbus::new_rtu()`](struct.Modbus.html#method.new_rtu) don't support it.
+//!
 //! * Create a Modbus RTU context
-//!     - [`new_rtu()`](struct.Modbus.html#method.new_rtu)
+//!     - [`new_rtu()`](struct.Modbus.html#method.new_rtu), [`new_rtu_config()`](struct.Modbus.html#method.new_rtu_config)
+//!
+//! * Get the parameters of the serial line
+//!     - [`rtu_get_serial_config()`](struct.Modbus.html#method.rtu_get_serial_config)
 //!
 //! * Set the serial mode
 //!     - [`rtu_get_serial_mode()`](struct.Modbus.html#method.rtu_get_serial_mode),
@@ -78,6 +92,9 @@
 //! [`rtu_get_rts_delay()`](struct.Modbus.html#method.rtu_get_rts_delay),
 //! [`rtu_set_rts_delay()`](struct.Modbus.html#method.rtu_set_rts_delay)
 //!
+//! * Watch the traffic of other masters and slaves without taking part
+//!     - [`RtuMonitor`](struct.RtuMonitor.html)
+//!
 //! ### [TCP (IPv4) Context](trait.ModbusTCP.html)
 //! The TCP backend implements a Modbus variant used for communications over TCP/IPv4 networks.
 //! It does not require a checksum calculation as lower layer takes care of the same.
@@ -96,6 +113,62 @@
 //! * Create a Modbus TCP context
 //!     - [`new_tcp_pi()`](struct.Modbus.html#method.new_tcp_pi)
 //!
+//! ### [ASCII Context](struct.ModbusStream.html#method.new_ascii)
+//! The ASCII backend is used in serial communication with legacy devices. Each frame is a colon, the slave id, the
+//! PDU and a longitudinal redundancy check (LRC) as hex digits and CR LF. libmodbus has no ASCII backend, it is
+//! implemented in Rust by a [`ModbusStream`](struct.ModbusStream.html), with the same client and server operations.
+//!
+//! * Create a Modbus ASCII context
+//!     - [`new_ascii()`](struct.ModbusStream.html#method.new_ascii)
+//!
+//! ### [RTU over TCP Context](struct.ModbusStream.html#method.new_rtu_tcp)
+//! Serial device servers in transparent mode carry RTU frames, with slave id and CRC, on a TCP connection instead of
+//! the MBAP header of Modbus TCP. Like the ASCII backend, it is implemented in Rust by a
+//! [`ModbusStream`](struct.ModbusStream.html).
+//!
+//! * Create a Modbus RTU over TCP context
+//!     - [`new_rtu_tcp()`](struct.ModbusStream.html#method.new_rtu_tcp)
+//!
+//! * Accept a connection of an RTU over TCP client
+//!     - [`rtu_tcp_accept()`](struct.ModbusStream.html#method.rtu_tcp_accept)
+//!
+//! ### [UDP Context](struct.ModbusStream.html#method.new_udp)
+//! Modbus UDP sends the MBAP header of Modbus TCP and the PDU in one datagram. Lost requests are sent again after the
+//! response timeout, responses are matched to their request by the transaction id. A server answers each datagram
+//! to the address it came from.
+//!
+//! * Create a Modbus UDP client context
+//!     - [`new_udp()`](struct.ModbusStream.html#method.new_udp)
+//!
+//! * Create a Modbus UDP server context
+//!     - [`udp_bind()`](struct.ModbusStream.html#method.udp_bind)
+//!
+//! ### [TLS Context](struct.ModbusStream.html#method.new_tls)
+//! The Modbus/TCP Security protocol runs Modbus TCP over TLS, usually on port 802. Server and client authenticate
+//! each other with X.509 certificates, configured by a [`TlsConfig`](struct.TlsConfig.html), and the certificate of
+//! the client may carry a role the server authorizes requests by. Only available with the `tls` feature, built on
+//! rustls.
+//!
+//! * Create a Modbus/TCP Security client context
+//!     - [`new_tls()`](struct.ModbusStream.html#method.new_tls)
+//!
+//! * Accept a connection of a Modbus/TCP Security client
+//!     - [`tls_accept()`](struct.ModbusStream.html#method.tls_accept)
+//!     - [`peer_role()`](struct.ModbusStream.html#method.peer_role)
+//!
+//! ### [Custom Transport](struct.ModbusStream.html#method.from_transport)
+//! Modbus runs over a channel of your own, e.g. a USB HID bridge, an SSH tunnel or an in-process pipe, by
+//! implementing the [`Transport`](trait.Transport.html) trait, or over any `Read + Write` stream wrapped in an
+//! [`IoTransport`](struct.IoTransport.html). The [`Framing`](enum.Framing.html), RTU, MBAP or ASCII, is chosen
+//! separately.
+//!
+//! * Create a context on a custom transport
+//!     - [`from_transport()`](struct.ModbusStream.html#method.from_transport)
+//!
+//! * Connect a client and a server in one process, to test without sockets or serial devices, with injected
+//!   latency, dropped frames and corrupted bytes
+//!     - [`LoopbackTransport`](struct.LoopbackTransport.html)
+//!
 //! ### Common
 //!
 //! Common methods to modify or change the current modbus context. Some of these function are not nessesary in Rust
@@ -121,6 +194,10 @@
 //!     - [`set_socket()`](struct.Modbus.html#method.set_socket) [`get_socket()`](struct.Modbus.html#method.get_socket)
 //! * Information about header
 //!     - [`get_header_length()`](struct.Modbus.html#method.get_header_length)
+//! * Communication statistics
+//!     - [`statistics()`](struct.Modbus.html#method.statistics),
+//! [`reset_statistics()`](struct.Modbus.html#method.reset_statistics),
+//! [`statistics_handle()`](struct.Modbus.html#method.statistics_handle)
 //!
 //! ### Connection
 //!
@@ -142,6 +219,13 @@
 //! [`write_registers()`](struct.Modbus.html#method.write_registers)
 //! * Write and read data
 //!     - [`write_and_read_registers()`](struct.Modbus.html#method.write_and_read_registers)
+//! * Write and verify data
+//!     - [`write_bit_verified()`](trait.ModbusClient.html#method.write_bit_verified),
+//! [`write_register_verified()`](trait.ModbusClient.html#method.write_register_verified),
+//! [`write_bits_verified()`](trait.ModbusClient.html#method.write_bits_verified),
+//! [`write_registers_verified()`](trait.ModbusClient.html#method.write_registers_verified)
+//! * Mask a register, emulated on devices without function code 0x16
+//!     - [`mask_write_register_with_fallback()`](trait.ModbusClient.html#method.mask_write_register_with_fallback)
 //! * Raw requests
 //!     - [`send_raw_request()`](struct.Modbus.html#method.send_raw_request),
 //! [`receive_confirmation()`](struct.Modbus.html#method.receive_confirmation)
@@ -159,10 +243,19 @@
 //! [`tcp_pi_listen`()](struct.Modbus.html#method.tcp_pi_listen),
 //! [`tcp_pi_accept`()](struct.Modbus.html#method.tcp_pi_accept)
 //!
+//! or, to serve each client with a context of its own, e.g. in a thread per client
+//!
+//! * [`tcp_listener()`](struct.Modbus.html#method.tcp_listener),
+//!   [`tcp_pi_listener()`](struct.Modbus.html#method.tcp_pi_listener) and
+//!   [`ModbusListener::accept()`](struct.ModbusListener.html#method.accept), with a
+//!   [`ListenerBuilder`](struct.ListenerBuilder.html) to choose the address and socket options
+//!
 //! then the data can be received with
 //!
 //! * [`receive()`](struct.Modbus.html#method.receive)
 //!
+//! and decoded with [`Request::parse()`](enum.Request.html#method.parse) to inspect, log or filter it
+//!
 //! and a response can be send with
 //!
 //! * [`reply()`](struct.Modbus.html#method.reply), [`reply_exception()`](struct.Modbus.html#method.reply_exception)
@@ -170,6 +263,42 @@
 //! To handle the mapping of your Modbus data, you must use a [`ModbusMapping`](struct.ModbusMapping.html) struct:
 //! [`ModbusMapping::new()`](struct.ModbusMapping.html#method.new)
 //!
+//! Large address spaces with gaps are served from several blocks per table by a
+//! [`SparseMapping`](struct.SparseMapping.html)
+//!
+//! Address regions of the mapping are declared read-only, write-only or unmapped with
+//! [`set_access()`](struct.ModbusMapping.html#method.set_access), the values clients may write are restricted with
+//! [`add_validator()`](struct.ModbusMapping.html#method.add_validator)
+//!
+//! Writes of the clients to the mapping are reported to the observers registered with
+//! [`add_observer()`](struct.ModbusMapping.html#method.add_observer) or
+//! [`subscribe()`](struct.ModbusMapping.html#method.subscribe)
+//!
+//! The contents of a mapping are saved with [`snapshot()`](struct.ModbusMapping.html#method.snapshot) and
+//! loaded back with [`restore()`](struct.ModbusMapping.html#method.restore), servers save them periodically with
+//! [`set_autosave()`](struct.ModbusMapping.html#method.set_autosave)
+//!
+//! To compute the responses on demand instead, implement a [`RequestHandler`](trait.RequestHandler.html) and
+//! answer with [`reply_with()`](struct.Modbus.html#method.reply_with)
+//!
+//! Several devices, each with its own mapping or request handler, are emulated behind one server with a
+//! [`UnitTable`](struct.UnitTable.html)
+//!
+//! A TCP server for many clients at once, answering them with a mapping or a request handler, is provided by
+//! [`TcpServer`](struct.TcpServer.html)
+//!
+//! A receive and reply loop which stops on request, on SIGINT or SIGTERM or after an idle timeout is run with
+//! [`serve()`](fn.serve.html) and a [`StopToken`](struct.StopToken.html)
+//!
+//! Devices whose values follow behaviours like counters, ramps or sine waves are simulated by a
+//! [`Simulator`](struct.Simulator.html), defined in code or, with the `simulator` feature, in TOML or YAML files.
+//! RTU and ASCII are served without a serial port on a [`PseudoTerminal`](struct.PseudoTerminal.html)
+//!
+//! `serve()`, `TcpServer` and the `Simulator` drive [`Modbus`](struct.Modbus.html) contexts only. A
+//! [`ModbusStream`](struct.ModbusStream.html) server, e.g. ASCII, RTU over TCP, UDP or TLS, answers its requests in
+//! a loop of [`receive()`](trait.ModbusServer.html#tymethod.receive) and
+//! [`reply()`](trait.ModbusServer.html#tymethod.reply) of its own
+//!
 
 // `error_chain!` can recurse deeply(3)
 #![recursion_limit = "1024"]
@@ -180,18 +309,66 @@ extern crate libmodbus_sys;
 pub mod error;
 mod modbus;
 mod modbus_client;
+mod modbus_frame;
+mod modbus_handler;
+mod modbus_loopback;
 mod modbus_mapping;
+#[cfg(unix)]
+mod modbus_monitor;
+#[cfg(unix)]
+mod modbus_pty;
+mod modbus_request;
 mod modbus_rtu;
+mod modbus_serial;
+mod modbus_serve;
 mod modbus_server;
+mod modbus_simulator;
+mod modbus_snapshot;
+mod modbus_sparse_mapping;
+mod modbus_stats;
+mod modbus_stream;
 mod modbus_tcp;
+#[cfg(unix)]
+mod modbus_tcp_listener;
 mod modbus_tcp_pi;
+#[cfg(unix)]
+mod modbus_tcp_server;
+#[cfg(feature = "tls")]
+mod modbus_tls;
+mod modbus_transport;
+mod modbus_units;
 pub mod prelude;
 
 pub use self::error::*;
-pub use self::modbus::{ErrorRecoveryMode, Exception, FunctionCode, Modbus, Timeout, *};
-pub use self::modbus_client::ModbusClient;
-pub use self::modbus_mapping::ModbusMapping;
+pub use self::modbus::{
+    ByteOrder, ErrorRecoveryMode, Exception, FunctionCode, IntoModbusSocket, Modbus, Timeout, *,
+};
+pub use self::modbus_client::{MaskWrite, ModbusClient};
+pub use self::modbus_frame::Framing;
+pub use self::modbus_handler::{RequestHandler, UnitSelection};
+pub use self::modbus_loopback::{LoopbackFault, LoopbackTransport};
+pub use self::modbus_mapping::{Access, ModbusMapping, Table, Validator, WriteEvent};
+#[cfg(unix)]
+pub use self::modbus_monitor::{MonitorEvent, MonitorResponse, RtuMonitor, Transaction};
+#[cfg(unix)]
+pub use self::modbus_pty::PseudoTerminal;
+pub use self::modbus_request::Request;
 pub use self::modbus_rtu::{ModbusRTU, RequestToSendMode, SerialMode};
+pub use self::modbus_serial::{BaudRate, DataBits, Parity, SerialConfig, StopBits};
+pub use self::modbus_serve::{serve, StopToken};
 pub use self::modbus_server::ModbusServer;
+pub use self::modbus_simulator::{Behaviour, Simulator};
+pub use self::modbus_snapshot::Snapshot;
+pub use self::modbus_sparse_mapping::SparseMapping;
+pub use self::modbus_stats::{LatencyHistogram, Statistics, StatisticsHandle};
+pub use self::modbus_stream::ModbusStream;
 pub use self::modbus_tcp::ModbusTCP;
+#[cfg(unix)]
+pub use self::modbus_tcp_listener::{ListenerBuilder, ModbusListener, TcpSession};
 pub use self::modbus_tcp_pi::ModbusTCPPI;
+#[cfg(unix)]
+pub use self::modbus_tcp_server::TcpServer;
+#[cfg(feature = "tls")]
+pub use self::modbus_tls::TlsConfig;
+pub use self::modbus_transport::{IoTransport, Transport};
+pub use self::modbus_units::{Broadcast, UnitTable};
@@ -1,4 +1,6 @@
+use crate::modbus_rtu::CustomRts;
 use crate::prelude::*;
+use crate::{SerialConfig, Statistics, StatisticsHandle};
 use libc::{c_int, c_uint};
 use libmodbus_sys as ffi;
 
@@ -36,10 +38,36 @@ pub enum Exception {
     GatewayTarget = 11,
 }
 
+impl Exception {
+    /// Exception for the exception code of a Modbus exception response, `None` for unknown codes.
+    pub(crate) fn from_code(code: u8) -> Option<Exception> {
+        use Exception::*;
+
+        match code {
+            1 => Some(IllegalFunction),
+            2 => Some(IllegalDataAddress),
+            3 => Some(IllegalDataValue),
+            4 => Some(SlaveOrServerFailure),
+            5 => Some(Acknowledge),
+            6 => Some(SlaveDeviceBusy),
+            7 => Some(NegativeAcknowledge),
+            8 => Some(MemoryParity),
+            9 => Some(NotDefined),
+            10 => Some(GatewayPath),
+            11 => Some(GatewayTarget),
+            _ => None,
+        }
+    }
+}
+
 /// Modbus function codes
 ///
 /// Documentation source: https://en.wikipedia.org/wiki/Modbus#Supported_function_codes
-#[derive(Clone, Copy, PartialEq, Eq)]
+///
+/// Breaking change: earlier versions gave `WriteMultipleCoils`, `WriteMultipleRegisters`, `ReportSlaveId`,
+/// `MaskWriteRegister` and `WriteAndReadRegisters` the values 0x15, 0x16, 0x17, 0x22 and 0x23, their codes written
+/// in decimal. They now have the codes on the wire, 0x0F, 0x10, 0x11, 0x16 and 0x17.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum FunctionCode {
     /// 0x01 Read Coils
     ReadCoils = 0x01,
@@ -57,16 +85,16 @@ pub enum FunctionCode {
     ReadExceptionStatus = 0x07,
     /// 0x08 Diagnostic
     Diagnostic = 0x08,
-    /// 0x15 Write Multiple Coils
-    WriteMultipleCoils = 0x15,
-    /// 0x16 Write Multiple Holding Registers
-    WriteMultipleRegisters = 0x16,
-    /// 0x17 Report Slave ID
-    ReportSlaveId = 0x17,
-    /// 0x22 Mask Write Register
-    MaskWriteRegister = 0x22,
-    /// 0x23 Read/Write Multiple Registers
-    WriteAndReadRegisters = 0x23,
+    /// 0x0F Write Multiple Coils
+    WriteMultipleCoils = 0x0F,
+    /// 0x10 Write Multiple Holding Registers
+    WriteMultipleRegisters = 0x10,
+    /// 0x11 Report Slave ID
+    ReportSlaveId = 0x11,
+    /// 0x16 Mask Write Register
+    MaskWriteRegister = 0x16,
+    /// 0x17 Read/Write Multiple Registers
+    WriteAndReadRegisters = 0x17,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -168,6 +196,42 @@ impl Default for Timeout {
     }
 }
 
+/// A socket or file descriptor for [`set_socket()`](struct.Modbus.html#method.set_socket)
+///
+/// The context closes every descriptor it is given, a raw descriptor as well as a Rust socket or file, with
+/// [`close()`](struct.Modbus.html#method.close) or on drop. Don't close a raw descriptor given to a context again.
+pub trait IntoModbusSocket {
+    /// The descriptor the context uses
+    fn into_modbus_socket(self) -> i32;
+}
+
+impl IntoModbusSocket for i32 {
+    fn into_modbus_socket(self) -> i32 {
+        self
+    }
+}
+
+#[cfg(unix)]
+impl IntoModbusSocket for std::net::TcpStream {
+    fn into_modbus_socket(self) -> i32 {
+        std::os::unix::io::IntoRawFd::into_raw_fd(self)
+    }
+}
+
+#[cfg(unix)]
+impl IntoModbusSocket for std::net::TcpListener {
+    fn into_modbus_socket(self) -> i32 {
+        std::os::unix::io::IntoRawFd::into_raw_fd(self)
+    }
+}
+
+#[cfg(unix)]
+impl IntoModbusSocket for std::fs::File {
+    fn into_modbus_socket(self) -> i32 {
+        std::os::unix::io::IntoRawFd::into_raw_fd(self)
+    }
+}
+
 /// Safe interface for [libmodbus](http://libmodbus.org)
 ///
 /// The different parts of libmodbus are implemented as traits. The modules of this crate contains these
@@ -176,9 +240,24 @@ impl Default for Timeout {
 #[derive(Debug)]
 pub struct Modbus {
     pub ctx: *mut ffi::modbus_t,
+    pub(crate) stats: StatisticsHandle,
+    /// Parameters of the serial line of an RTU context
+    pub(crate) serial: Option<SerialConfig>,
+    /// The function set with `rtu_set_custom_rts()`, owned by the context
+    pub(crate) custom_rts: *mut CustomRts,
 }
 
 impl Modbus {
+    /// Wrap a freshly allocated libmodbus context.
+    pub(crate) fn from_ctx(ctx: *mut ffi::modbus_t) -> Modbus {
+        Modbus {
+            ctx,
+            stats: StatisticsHandle::default(),
+            serial: None,
+            custom_rts: std::ptr::null_mut(),
+        }
+    }
+
     // Constants
     /// Modbus_Application_Protocol_V1_1b.pdf (chapter 6 section 1 page 12)
     /// Quantity of Coils to read (2 bytes): 1 to 2000 (0x7D0)
@@ -253,7 +332,10 @@ impl Modbus {
                     msg: "connect".to_owned(),
                     source: ::std::io::Error::last_os_error(),
                 }),
-                0 => Ok(()),
+                0 => {
+                    self.stats.record_connect();
+                    Ok(())
+                }
                 _ => panic!("libmodbus API incompatible response"),
             }
         }
@@ -563,6 +645,79 @@ impl Modbus {
         }
     }
 
+    /// `get_indication_timeout` - get timeout used to wait for an indication
+    ///
+    /// The [`get_indication_timeout()`](#method.get_indication_timeout) function shall return the timeout interval
+    /// used by a server to wait for an indication request from a client.
+    ///
+    /// # Return value
+    ///
+    /// The function return a Result containing a [`Timeout`](struct.Timeout.html) if successful.
+    /// Otherwise it contains an Error.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use libmodbus::{Modbus, ModbusTCP, Timeout};
+    /// let modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+    ///
+    /// assert_eq!(modbus.get_indication_timeout().unwrap(), Timeout { sec: 0, usec: 0 });
+    /// ```
+    pub fn get_indication_timeout(&self) -> Result<Timeout, Error> {
+        let mut timeout = Timeout { sec: 0, usec: 0 };
+        unsafe {
+            match ffi::modbus_get_indication_timeout(self.ctx, &mut timeout.sec, &mut timeout.usec)
+            {
+                -1 => Err(Error::Modbus {
+                    msg: "get_indication_timeout".to_owned(),
+                    source: ::std::io::Error::last_os_error(),
+                }),
+                0 => Ok(timeout),
+                _ => panic!("libmodbus API incompatible response"),
+            }
+        }
+    }
+
+    /// `set_indication_timeout` - set timeout used to wait for an indication
+    ///
+    /// The [`set_indication_timeout()`](#method.set_indication_timeout) function shall set the timeout interval
+    /// used by a server to wait for a request from a client. When the timeout elapses,
+    /// [`receive()`](struct.Modbus.html#method.receive) fails with an ETIMEDOUT error, so the server can do other
+    /// work, or stop, between requests.
+    ///
+    /// If both **sec** and **usec** are zero, the default, the server waits for a request forever.
+    ///
+    /// # Return value
+    ///
+    /// The function return an OK Result if successful. Otherwise it contains an Error.
+    ///
+    /// # Parameters
+    ///
+    /// * [`Timeout`](struct.Timeout.html)  - Timeout
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use libmodbus::{Modbus, ModbusTCP, Timeout};
+    /// let mut modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+    /// let timeout = Timeout { sec: 1, usec: 0 };
+    ///
+    /// assert!(modbus.set_indication_timeout(timeout).is_ok());
+    /// assert_eq!(modbus.get_indication_timeout().unwrap(), timeout);
+    /// ```
+    pub fn set_indication_timeout(&mut self, timeout: Timeout) -> Result<(), Error> {
+        unsafe {
+            match ffi::modbus_set_indication_timeout(self.ctx, timeout.sec, timeout.usec) {
+                -1 => Err(Error::Modbus {
+                    msg: "set_indication_timeout".to_owned(),
+                    source: ::std::io::Error::last_os_error(),
+                }),
+                0 => Ok(()),
+                _ => panic!("libmodbus API incompatible response"),
+            }
+        }
+    }
+
     /// `set_error_recovery` - set the error recovery mode
     ///
     /// The [`set_error_recovery()`](#method.set_error_recovery) function shall set the error recovery mode to apply
@@ -630,6 +785,10 @@ impl Modbus {
     /// context.
     /// This function is useful for managing multiple client connections to the same server.
     ///
+    /// Besides a raw descriptor, it takes a `TcpStream`, a `TcpListener` or a `File` on unix platforms, e.g. a
+    /// connection accepted or a serial device opened without libmodbus. The context owns the descriptor in any case
+    /// and closes it, see [`IntoModbusSocket`](trait.IntoModbusSocket.html).
+    ///
     /// # Return values
     ///
     /// The function return an OK Result if successful. Otherwise it contains an Error.
@@ -642,9 +801,18 @@ impl Modbus {
     ///
     /// assert!(modbus.set_socket(1337).is_ok());
     /// ```
-    pub fn set_socket(&mut self, socket: i32) -> Result<(), Error> {
+    ///
+    /// ```rust,no_run
+    /// use libmodbus::{Modbus, ModbusTCP};
+    /// use std::net::TcpStream;
+    /// let mut modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+    ///
+    /// let stream = TcpStream::connect("127.0.0.1:1502").unwrap();
+    /// modbus.set_socket(stream).unwrap();
+    /// ```
+    pub fn set_socket<S: IntoModbusSocket>(&mut self, socket: S) -> Result<(), Error> {
         unsafe {
-            match ffi::modbus_set_socket(self.ctx, socket) {
+            match ffi::modbus_set_socket(self.ctx, socket.into_modbus_socket()) {
                 -1 => Err(Error::Modbus {
                     msg: "set_socket".to_owned(),
                     source: ::std::io::Error::last_os_error(),
@@ -750,15 +918,82 @@ impl Modbus {
         unsafe {
             match ffi::modbus_reply_exception(self.ctx, request.as_ptr(), exception_code as c_uint)
             {
-                -1 => Err(Error::Modbus {
-                    msg: "reply_exception".to_owned(),
-                    source: ::std::io::Error::last_os_error(),
-                }),
-                len => Ok(len),
+                -1 => {
+                    let source = ::std::io::Error::last_os_error();
+                    self.stats.record_error(&source);
+                    Err(Error::Modbus {
+                        msg: "reply_exception".to_owned(),
+                        source,
+                    })
+                }
+                len => {
+                    self.stats.record_reply(len as usize, Some(exception_code));
+                    Ok(len)
+                }
             }
         }
     }
 
+    /// `statistics` - communication statistics of the context
+    ///
+    /// The [`statistics()`](#method.statistics) function returns a snapshot of the counters every context keeps:
+    /// requests by function code, exceptions by [`Exception`](enum.Exception.html), timeouts, CRC and framing
+    /// errors, reconnects, bytes sent and received and a latency histogram.
+    ///
+    /// Client contexts record every request sent with the [`ModbusClient`](trait.ModbusClient.html) functions, with
+    /// the bytes sent and received estimated from the requests, see [`Statistics`](struct.Statistics.html).
+    /// Server contexts record every indication received with [`receive()`](#method.receive), the replies and
+    /// exception replies sent and, as latency, the time between receiving an indication and sending the reply.
+    ///
+    /// # Return value
+    ///
+    /// A [`Statistics`](struct.Statistics.html) snapshot.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use libmodbus::{Modbus, ModbusTCP};
+    /// let modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+    ///
+    /// assert_eq!(modbus.statistics().total_requests(), 0);
+    /// ```
+    pub fn statistics(&self) -> Statistics {
+        self.stats.snapshot()
+    }
+
+    /// `reset_statistics` - reset all statistics of the context to zero
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use libmodbus::{Modbus, ModbusTCP};
+    /// let modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+    ///
+    /// modbus.reset_statistics();
+    /// assert_eq!(modbus.statistics().timeouts, 0);
+    /// ```
+    pub fn reset_statistics(&self) {
+        self.stats.reset()
+    }
+
+    /// `statistics_handle` - shared handle to the statistics of the context
+    ///
+    /// The returned [`StatisticsHandle`](struct.StatisticsHandle.html) can be moved to a monitoring thread, it
+    /// always sees the current statistics of this context.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use libmodbus::{Modbus, ModbusTCP};
+    /// let modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+    /// let handle = modbus.statistics_handle();
+    ///
+    /// assert_eq!(handle.snapshot(), modbus.statistics());
+    /// ```
+    pub fn statistics_handle(&self) -> StatisticsHandle {
+        self.stats.clone()
+    }
+
     /// `strerror`  - return the error message
     ///
     /// The [`strerror()`](#method.strerror) function shall return a message `String` corresponding to the error number
@@ -809,6 +1044,7 @@ impl Modbus {
     /// modbus.free();
     /// ```
     pub fn free(&mut self) {
+        self.clear_custom_rts();
         unsafe {
             ffi::modbus_free(self.ctx);
             self.ctx = std::ptr::null_mut();
@@ -1121,6 +1357,64 @@ pub fn set_float_dcba(src: f32, dest: &mut [u16]) {
     unsafe { ffi::modbus_set_float_dcba(src, dest.as_mut_ptr()) }
 }
 
+/// Byte order of a float stored in 2 registers
+///
+/// `A` is the most significant byte of the float. Every order is read and written with the matching
+/// function above, e.g. [`get_float_cdab()`](fn.get_float_cdab.html) for `CDAB`.
+///
+/// # Examples
+///
+/// ```rust
+/// use libmodbus::prelude::*;
+/// let mut dest = vec![0; 2];
+/// ByteOrder::CDAB.set_float(123456.0, &mut dest);
+///
+/// assert_eq!(&dest, &[0xF147, 0x0020]);
+/// assert_eq!(ByteOrder::CDAB.get_float(&dest), 123456.0);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+    /// Usual Modbus format, see [`get_float_abcd()`](fn.get_float_abcd.html)
+    ABCD,
+    /// Bytes swapped in each register, see [`get_float_badc()`](fn.get_float_badc.html)
+    BADC,
+    /// Registers swapped, see [`get_float_cdab()`](fn.get_float_cdab.html)
+    CDAB,
+    /// Bytes and registers swapped, see [`get_float_dcba()`](fn.get_float_dcba.html)
+    DCBA,
+}
+
+impl ByteOrder {
+    /// `get_float` - get a float value from 2 registers in this byte order
+    ///
+    /// # Parameters
+    ///
+    /// * `src`   - slice of two `u16` values
+    pub fn get_float(self, src: &[u16]) -> f32 {
+        match self {
+            ByteOrder::ABCD => get_float_abcd(src),
+            ByteOrder::BADC => get_float_badc(src),
+            ByteOrder::CDAB => get_float_cdab(src),
+            ByteOrder::DCBA => get_float_dcba(src),
+        }
+    }
+
+    /// `set_float` - set a float value in 2 registers using this byte order
+    ///
+    /// # Parameters
+    ///
+    /// * `src`   - float to 4 bytes (`f32`)
+    /// * `dest`  - slice must contain two `u16` values
+    pub fn set_float(self, src: f32, dest: &mut [u16]) {
+        match self {
+            ByteOrder::ABCD => set_float_abcd(src, dest),
+            ByteOrder::BADC => set_float_badc(src, dest),
+            ByteOrder::CDAB => set_float_cdab(src, dest),
+            ByteOrder::DCBA => set_float_dcba(src, dest),
+        }
+    }
+}
+
 impl Drop for Modbus {
     fn drop(&mut self) {
         self.close();
@@ -1,6 +1,10 @@
+use crate::error::EMBMDATA;
 use crate::prelude::*;
+use crate::{Exception, FunctionCode};
 use libc::c_int;
 use libmodbus_sys as ffi;
+use std::io;
+use std::time::{Duration, Instant};
 
 /// The Modbus protocol defines different data types and functions to read and write them from/to remote devices.
 /// The following functions are used by the clients to send Modbus requests:
@@ -18,6 +22,13 @@ use libmodbus_sys as ffi;
 /// [`write_registers()`](struct.Modbus.html#method.write_registers)
 /// * Write and read data
 ///     - [`write_and_read_registers()`](struct.Modbus.html#method.write_and_read_registers)
+/// * Write and verify data
+///     - [`write_bit_verified()`](trait.ModbusClient.html#method.write_bit_verified),
+/// [`write_register_verified()`](trait.ModbusClient.html#method.write_register_verified),
+/// [`write_bits_verified()`](trait.ModbusClient.html#method.write_bits_verified),
+/// [`write_registers_verified()`](trait.ModbusClient.html#method.write_registers_verified)
+/// * Mask a register, emulated on devices without function code 0x16
+///     - [`mask_write_register_with_fallback()`](trait.ModbusClient.html#method.mask_write_register_with_fallback)
 /// * Raw requests
 ///     - [`send_raw_request()`](struct.Modbus.html#method.send_raw_request),
 /// [`receive_confirmation()`](struct.Modbus.html#method.receive_confirmation)
@@ -46,6 +57,251 @@ pub trait ModbusClient {
     fn mask_write_register(&self, address: u16, and_mask: u16, or_mask: u16) -> Result<(), Error>;
     fn send_raw_request(&self, raw_request: &mut [u8], lenght: usize) -> Result<u16, Error>;
     fn receive_confirmation(&self, response: &mut [u8]) -> Result<u16, Error>;
+
+    /// `mask_write_register_with_fallback` - mask a single register, emulate it if the device can't
+    ///
+    /// Works like [`mask_write_register()`](#tymethod.mask_write_register), but if the device answers the
+    /// **Modbus function code 0x16** with `Exception::IllegalFunction`, as many older PLCs do, the function falls
+    /// back to a [`read_registers()`](#tymethod.read_registers) followed by a
+    /// [`write_register()`](#tymethod.write_register) with the masked value.
+    ///
+    /// **The emulation is not atomic.** Another master writing the register between the read and the write loses
+    /// its change. The time between both requests is reported as `race_window` in the
+    /// [`MaskWrite::Emulated`](enum.MaskWrite.html) result, so callers can log or reject it.
+    ///
+    /// # Return value
+    ///
+    /// The function returns a Result containing a [`MaskWrite`](enum.MaskWrite.html), which tells whether the
+    /// device did the masking itself, if successful. Otherwise it contains an Error.
+    ///
+    /// # Parameters
+    ///
+    /// * `address`    - address of the remote device
+    /// * `and_mask`   - AND mask
+    /// * `or_mask`    - OR mask
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use libmodbus::{Modbus, ModbusClient, ModbusTCP, MaskWrite};
+    /// let modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+    ///
+    /// match modbus.mask_write_register_with_fallback(1, 0xF2, 0x25).unwrap() {
+    ///     MaskWrite::Native => {}
+    ///     MaskWrite::Emulated { race_window, .. } => println!("not atomic, race window {:?}", race_window),
+    /// }
+    /// ```
+    fn mask_write_register_with_fallback(
+        &self,
+        address: u16,
+        and_mask: u16,
+        or_mask: u16,
+    ) -> Result<MaskWrite, Error> {
+        match self.mask_write_register(address, and_mask, or_mask) {
+            Ok(()) => return Ok(MaskWrite::Native),
+            Err(ref err) if err.exception() == Some(Exception::IllegalFunction) => {}
+            Err(err) => return Err(err),
+        }
+
+        let mut current = [0u16; 1];
+        self.read_registers(address, 1, &mut current)?;
+        let read_at = Instant::now();
+        let previous = current[0];
+        let written = (previous & and_mask) | (or_mask & !and_mask);
+        self.write_register(address, written)?;
+
+        Ok(MaskWrite::Emulated {
+            previous,
+            written,
+            race_window: read_at.elapsed(),
+        })
+    }
+
+    /// `write_register_verified` - write a single register and read it back
+    ///
+    /// The function writes `value` with [`write_register()`](#tymethod.write_register), reads the register back with
+    /// [`read_registers()`](#tymethod.read_registers) and compares both.
+    ///
+    /// # Return value
+    ///
+    /// The function return an OK Result if the device holds the written value. If the read back value differs it
+    /// returns an `Error::Verify`, every other failure is returned as usual.
+    ///
+    /// # Parameters
+    ///
+    /// * `address` - address of the remote device
+    /// * `value`   - 16-bit value to write
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use libmodbus::{Modbus, ModbusClient, ModbusTCP};
+    /// let modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+    ///
+    /// assert!(modbus.write_register_verified(1, 42).is_ok());
+    /// ```
+    fn write_register_verified(&self, address: u16, value: u16) -> Result<(), Error> {
+        self.write_register(address, value)?;
+        let mut read_back = [0u16; 1];
+        self.read_registers(address, 1, &mut read_back)?;
+        verify("write_register_verified", address, &[value], &read_back)
+    }
+
+    /// `write_registers_verified` - write many registers and read them back
+    ///
+    /// The function writes the first `num` values of `src` with [`write_registers()`](#tymethod.write_registers),
+    /// reads the registers back with [`read_registers()`](#tymethod.read_registers) and compares them.
+    ///
+    /// # Return value
+    ///
+    /// The function returns a Result containing the number of written registers if the device holds the written
+    /// values. On the first differing register it returns an `Error::Verify`, every other failure is returned as
+    /// usual.
+    ///
+    /// # Parameters
+    ///
+    /// * `address` - address of the remote device
+    /// * `num`     - number of holding registers
+    /// * `src`     - holding registers to write
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use libmodbus::{Modbus, ModbusClient, ModbusTCP};
+    /// let modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+    ///
+    /// assert_eq!(modbus.write_registers_verified(1, 2, &[1, 2]).unwrap(), 2);
+    /// ```
+    fn write_registers_verified(&self, address: u16, num: u16, src: &[u16]) -> Result<u16, Error> {
+        check_length("write_registers_verified", src.len(), num)?;
+        let written = self.write_registers(address, num, src)?;
+        let mut read_back = vec![0u16; num as usize];
+        self.read_registers(address, num, &mut read_back)?;
+        verify(
+            "write_registers_verified",
+            address,
+            &src[..num as usize],
+            &read_back,
+        )?;
+        Ok(written)
+    }
+
+    /// `write_bit_verified` - write a single bit and read it back
+    ///
+    /// The function writes `status` with [`write_bit()`](#tymethod.write_bit), reads the coil back with
+    /// [`read_bits()`](#tymethod.read_bits) and compares both.
+    ///
+    /// # Return value
+    ///
+    /// The function return an OK Result if the device holds the written status. If the read back status differs it
+    /// returns an `Error::Verify`, every other failure is returned as usual.
+    ///
+    /// # Parameters
+    ///
+    /// * `address` - address of the remote device
+    /// * `status`  - status that should write at the address `addr`
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use libmodbus::{Modbus, ModbusClient, ModbusTCP};
+    /// let modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+    ///
+    /// assert!(modbus.write_bit_verified(1, true).is_ok());
+    /// ```
+    fn write_bit_verified(&self, address: u16, status: bool) -> Result<(), Error> {
+        self.write_bit(address, status)?;
+        let mut read_back = [0u8; 1];
+        self.read_bits(address, 1, &mut read_back)?;
+        verify(
+            "write_bit_verified",
+            address,
+            &[status as u16],
+            &[(read_back[0] != 0) as u16],
+        )
+    }
+
+    /// `write_bits_verified` - write many bits and read them back
+    ///
+    /// The function writes the first `num` bits of `src` with [`write_bits()`](#tymethod.write_bits), reads the coils
+    /// back with [`read_bits()`](#tymethod.read_bits) and compares them. Like in `write_bits()` every byte of `src`
+    /// is one bit, any value other than zero is `true`.
+    ///
+    /// # Return value
+    ///
+    /// The function returns a Result containing the number of written bits if the device holds the written
+    /// values. On the first differing coil it returns an `Error::Verify`, every other failure is returned as usual.
+    ///
+    /// # Parameters
+    ///
+    /// * `address` - address of the remote device
+    /// * `num`     - number or bits that should be writen at the address `address`
+    /// * `src`     - vector of `u8` the values to set
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use libmodbus::{Modbus, ModbusClient, ModbusTCP};
+    /// let modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+    ///
+    /// assert_eq!(modbus.write_bits_verified(1, 3, &[1, 0, 1]).unwrap(), 3);
+    /// ```
+    fn write_bits_verified(&self, address: u16, num: u16, src: &[u8]) -> Result<u16, Error> {
+        check_length("write_bits_verified", src.len(), num)?;
+        let written = self.write_bits(address, num, src)?;
+        let mut read_back = vec![0u8; num as usize];
+        self.read_bits(address, num, &mut read_back)?;
+        let expected: Vec<u16> = src[..num as usize]
+            .iter()
+            .map(|&bit| (bit != 0) as u16)
+            .collect();
+        let actual: Vec<u16> = read_back.iter().map(|&bit| (bit != 0) as u16).collect();
+        verify("write_bits_verified", address, &expected, &actual)?;
+        Ok(written)
+    }
+}
+
+/// Outcome of [`mask_write_register_with_fallback()`](trait.ModbusClient.html#method.mask_write_register_with_fallback)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaskWrite {
+    /// The device executed the mask write request (function code 0x16) itself, the change was atomic.
+    Native,
+    /// The device didn't support function code 0x16, the masking was emulated with a read and a write request.
+    /// `previous` is the value read, `written` the masked value written and `race_window` the time between both
+    /// requests, in which a write of another master gets lost.
+    Emulated {
+        previous: u16,
+        written: u16,
+        race_window: Duration,
+    },
+}
+
+/// Fail with `EMBMDATA` if fewer than `num` values are given, before anything is sent
+fn check_length(msg: &str, len: usize, num: u16) -> Result<(), Error> {
+    if len < num as usize {
+        return Err(Error::Client {
+            msg: msg.to_owned(),
+            source: io::Error::from_raw_os_error(EMBMDATA),
+        });
+    }
+    Ok(())
+}
+
+/// Compare written and read back values, report the first difference as `Error::Verify`.
+fn verify(msg: &str, address: u16, written: &[u16], read_back: &[u16]) -> Result<(), Error> {
+    match written
+        .iter()
+        .zip(read_back)
+        .position(|(written, read_back)| written != read_back)
+    {
+        Some(offset) => Err(Error::Verify {
+            msg: msg.to_owned(),
+            address: address + offset as u16,
+            written: written[offset],
+            read_back: read_back[offset],
+        }),
+        None => Ok(()),
+    }
 }
 
 // TODO: add real, working examples
@@ -78,15 +334,14 @@ impl ModbusClient for Modbus {
     /// assert!(modbus.read_bits(0, 1, &mut dest).is_ok());
     /// ```
     fn read_bits(&self, address: u16, num: u16, dest: &mut [u8]) -> Result<u16, Error> {
-        unsafe {
-            match ffi::modbus_read_bits(self.ctx, address as c_int, num as c_int, dest.as_mut_ptr())
-            {
-                -1 => Err(Error::Client {
-                    msg: "read_bits failure".to_owned(),
-                    source: ::std::io::Error::last_os_error(),
-                }),
-                len => Ok(len as u16),
-            }
+        match self.instrument(FunctionCode::ReadCoils, 5, 2 + packed_len(num), || unsafe {
+            ffi::modbus_read_bits(self.ctx, address as c_int, num as c_int, dest.as_mut_ptr())
+        }) {
+            Err(source) => Err(Error::Client {
+                msg: "read_bits failure".to_owned(),
+                source,
+            }),
+            Ok(len) => Ok(len as u16),
         }
     }
 
@@ -118,19 +373,24 @@ impl ModbusClient for Modbus {
     /// assert!(modbus.read_input_bits(0, 1, &mut dest).is_ok());
     /// ```
     fn read_input_bits(&self, address: u16, num: u16, dest: &mut [u8]) -> Result<u16, Error> {
-        unsafe {
-            match ffi::modbus_read_input_bits(
-                self.ctx,
-                address as c_int,
-                num as c_int,
-                dest.as_mut_ptr(),
-            ) {
-                -1 => Err(Error::Client {
-                    msg: "read_input_bits".to_owned(),
-                    source: ::std::io::Error::last_os_error(),
-                }),
-                len => Ok(len as u16),
-            }
+        match self.instrument(
+            FunctionCode::ReadDiscreteInputs,
+            5,
+            2 + packed_len(num),
+            || unsafe {
+                ffi::modbus_read_input_bits(
+                    self.ctx,
+                    address as c_int,
+                    num as c_int,
+                    dest.as_mut_ptr(),
+                )
+            },
+        ) {
+            Err(source) => Err(Error::Client {
+                msg: "read_input_bits".to_owned(),
+                source,
+            }),
+            Ok(len) => Ok(len as u16),
         }
     }
 
@@ -161,19 +421,24 @@ impl ModbusClient for Modbus {
     /// assert!(modbus.read_registers(0, 1, &mut dest).is_ok());
     /// ```
     fn read_registers(&self, address: u16, num: u16, dest: &mut [u16]) -> Result<u16, Error> {
-        unsafe {
-            match ffi::modbus_read_registers(
-                self.ctx,
-                address as c_int,
-                num as c_int,
-                dest.as_mut_ptr(),
-            ) {
-                -1 => Err(Error::Client {
-                    msg: "read_registers".to_owned(),
-                    source: ::std::io::Error::last_os_error(),
-                }),
-                len => Ok(len as u16),
-            }
+        match self.instrument(
+            FunctionCode::ReadHoldingRegisters,
+            5,
+            2 + 2 * num as usize,
+            || unsafe {
+                ffi::modbus_read_registers(
+                    self.ctx,
+                    address as c_int,
+                    num as c_int,
+                    dest.as_mut_ptr(),
+                )
+            },
+        ) {
+            Err(source) => Err(Error::Client {
+                msg: "read_registers".to_owned(),
+                source,
+            }),
+            Ok(len) => Ok(len as u16),
         }
     }
 
@@ -206,19 +471,24 @@ impl ModbusClient for Modbus {
     /// assert!(modbus.read_input_registers(0, 1, &mut dest).is_ok());
     /// ```
     fn read_input_registers(&self, address: u16, num: u16, dest: &mut [u16]) -> Result<u16, Error> {
-        unsafe {
-            match ffi::modbus_read_input_registers(
-                self.ctx,
-                address as c_int,
-                num as c_int,
-                dest.as_mut_ptr(),
-            ) {
-                -1 => Err(Error::Client {
-                    msg: "read_input_registers".to_owned(),
-                    source: ::std::io::Error::last_os_error(),
-                }),
-                len => Ok(len as u16),
-            }
+        match self.instrument(
+            FunctionCode::ReadInputRegisters,
+            5,
+            2 + 2 * num as usize,
+            || unsafe {
+                ffi::modbus_read_input_registers(
+                    self.ctx,
+                    address as c_int,
+                    num as c_int,
+                    dest.as_mut_ptr(),
+                )
+            },
+        ) {
+            Err(source) => Err(Error::Client {
+                msg: "read_input_registers".to_owned(),
+                source,
+            }),
+            Ok(len) => Ok(len as u16),
         }
     }
 
@@ -255,14 +525,14 @@ impl ModbusClient for Modbus {
     /// // assert_eq!(bytes, vec![180, 255, 76, 77, 66, 51, 46, 49, 46, 52]));
     /// ```
     fn report_slave_id(&self, max_dest: usize, dest: &mut [u8]) -> Result<u16, Error> {
-        unsafe {
-            match ffi::modbus_report_slave_id(self.ctx, max_dest as c_int, dest.as_mut_ptr()) {
-                -1 => Err(Error::Client {
-                    msg: "report_slave_id".to_owned(),
-                    source: ::std::io::Error::last_os_error(),
-                }),
-                len => Ok(len as u16),
-            }
+        match self.instrument(FunctionCode::ReportSlaveId, 1, 0, || unsafe {
+            ffi::modbus_report_slave_id(self.ctx, max_dest as c_int, dest.as_mut_ptr())
+        }) {
+            Err(source) => Err(Error::Client {
+                msg: "report_slave_id".to_owned(),
+                source,
+            }),
+            Ok(len) => Ok(len as u16),
         }
     }
 
@@ -292,15 +562,15 @@ impl ModbusClient for Modbus {
     /// assert!(modbus.write_bit(address, true).is_ok());
     /// ```
     fn write_bit(&self, address: u16, status: bool) -> Result<(), Error> {
-        unsafe {
-            match ffi::modbus_write_bit(self.ctx, address as c_int, status as c_int) {
-                -1 => Err(Error::Client {
-                    msg: "write_bit".to_owned(),
-                    source: ::std::io::Error::last_os_error(),
-                }),
-                1 => Ok(()),
-                _ => panic!("libmodbus API incompatible response"),
-            }
+        match self.instrument(FunctionCode::WriteSingleCoil, 5, 5, || unsafe {
+            ffi::modbus_write_bit(self.ctx, address as c_int, status as c_int)
+        }) {
+            Err(source) => Err(Error::Client {
+                msg: "write_bit".to_owned(),
+                source,
+            }),
+            Ok(1) => Ok(()),
+            Ok(_) => panic!("libmodbus API incompatible response"),
         }
     }
 
@@ -331,15 +601,15 @@ impl ModbusClient for Modbus {
     /// assert!(modbus.write_register(address, value).is_ok());
     /// ```
     fn write_register(&self, address: u16, value: u16) -> Result<(), Error> {
-        unsafe {
-            match ffi::modbus_write_register(self.ctx, address as c_int, value) {
-                -1 => Err(Error::Client {
-                    msg: "write_register".to_owned(),
-                    source: ::std::io::Error::last_os_error(),
-                }),
-                1 => Ok(()),
-                _ => panic!("libmodbus API incompatible response"),
-            }
+        match self.instrument(FunctionCode::WriteSingleRegister, 5, 5, || unsafe {
+            ffi::modbus_write_register(self.ctx, address as c_int, value)
+        }) {
+            Err(source) => Err(Error::Client {
+                msg: "write_register".to_owned(),
+                source,
+            }),
+            Ok(1) => Ok(()),
+            Ok(_) => panic!("libmodbus API incompatible response"),
         }
     }
 
@@ -371,14 +641,20 @@ impl ModbusClient for Modbus {
     /// assert_eq!(modbus.write_bits(address, 1, &tab_bytes).unwrap(), 1);
     /// ```
     fn write_bits(&self, address: u16, num: u16, src: &[u8]) -> Result<u16, Error> {
-        unsafe {
-            match ffi::modbus_write_bits(self.ctx, address as c_int, num as c_int, src.as_ptr()) {
-                -1 => Err(Error::Client {
-                    msg: "write_bits".to_owned(),
-                    source: ::std::io::Error::last_os_error(),
-                }),
-                num => Ok(num as u16),
-            }
+        check_length("write_bits", src.len(), num)?;
+        match self.instrument(
+            FunctionCode::WriteMultipleCoils,
+            6 + packed_len(num),
+            5,
+            || unsafe {
+                ffi::modbus_write_bits(self.ctx, address as c_int, num as c_int, src.as_ptr())
+            },
+        ) {
+            Err(source) => Err(Error::Client {
+                msg: "write_bits".to_owned(),
+                source,
+            }),
+            Ok(num) => Ok(num as u16),
         }
     }
 
@@ -411,19 +687,20 @@ impl ModbusClient for Modbus {
     /// assert_eq!(modbus.write_registers(address, 1, &tab_bytes).unwrap(), 1);
     /// ```
     fn write_registers(&self, address: u16, num: u16, src: &[u16]) -> Result<u16, Error> {
-        unsafe {
-            match ffi::modbus_write_registers(
-                self.ctx,
-                address as c_int,
-                num as c_int,
-                src.as_ptr(),
-            ) {
-                -1 => Err(Error::Client {
-                    msg: "write_registers".to_owned(),
-                    source: ::std::io::Error::last_os_error(),
-                }),
-                num => Ok(num as u16),
-            }
+        check_length("write_registers", src.len(), num)?;
+        match self.instrument(
+            FunctionCode::WriteMultipleRegisters,
+            6 + 2 * num as usize,
+            5,
+            || unsafe {
+                ffi::modbus_write_registers(self.ctx, address as c_int, num as c_int, src.as_ptr())
+            },
+        ) {
+            Err(source) => Err(Error::Client {
+                msg: "write_registers".to_owned(),
+                source,
+            }),
+            Ok(num) => Ok(num as u16),
         }
     }
 
@@ -471,22 +748,29 @@ impl ModbusClient for Modbus {
         read_num: u16,
         dest: &mut [u16],
     ) -> Result<u16, Error> {
-        unsafe {
-            match ffi::modbus_write_and_read_registers(
-                self.ctx,
-                write_address as c_int,
-                write_num as c_int,
-                src.as_ptr(),
-                read_address as c_int,
-                read_num as c_int,
-                dest.as_mut_ptr(),
-            ) {
-                -1 => Err(Error::Client {
-                    msg: "write_and_read_registers".to_owned(),
-                    source: ::std::io::Error::last_os_error(),
-                }),
-                num => Ok(num as u16),
-            }
+        let request_pdu = 10 + 2 * write_num as usize;
+        let response_pdu = 2 + 2 * read_num as usize;
+        match self.instrument(
+            FunctionCode::WriteAndReadRegisters,
+            request_pdu,
+            response_pdu,
+            || unsafe {
+                ffi::modbus_write_and_read_registers(
+                    self.ctx,
+                    write_address as c_int,
+                    write_num as c_int,
+                    src.as_ptr(),
+                    read_address as c_int,
+                    read_num as c_int,
+                    dest.as_mut_ptr(),
+                )
+            },
+        ) {
+            Err(source) => Err(Error::Client {
+                msg: "write_and_read_registers".to_owned(),
+                source,
+            }),
+            Ok(num) => Ok(num as u16),
         }
     }
 
@@ -520,15 +804,15 @@ impl ModbusClient for Modbus {
     /// assert!(modbus.mask_write_register(1, 0xF2, 0x25).is_ok());
     /// ```
     fn mask_write_register(&self, address: u16, and_mask: u16, or_mask: u16) -> Result<(), Error> {
-        unsafe {
-            match ffi::modbus_mask_write_register(self.ctx, address as c_int, and_mask, or_mask) {
-                -1 => Err(Error::Client {
-                    msg: "mask_write_register".to_owned(),
-                    source: ::std::io::Error::last_os_error(),
-                }),
-                1 => Ok(()),
-                _ => panic!("libmodbus API incompatible response"),
-            }
+        match self.instrument(FunctionCode::MaskWriteRegister, 7, 7, || unsafe {
+            ffi::modbus_mask_write_register(self.ctx, address as c_int, and_mask, or_mask)
+        }) {
+            Err(source) => Err(Error::Client {
+                msg: "mask_write_register".to_owned(),
+                source,
+            }),
+            Ok(1) => Ok(()),
+            Ok(_) => panic!("libmodbus API incompatible response"),
         }
     }
 
@@ -569,6 +853,7 @@ impl ModbusClient for Modbus {
     /// assert!(modbus.receive_confirmation(&mut response).is_ok());
     /// ```
     fn send_raw_request(&self, raw_request: &mut [u8], lenght: usize) -> Result<u16, Error> {
+        let function = raw_request.get(1).cloned().unwrap_or(0);
         unsafe {
             match ffi::modbus_send_raw_request(self.ctx, raw_request.as_mut_ptr(), lenght as c_int)
             {
@@ -576,7 +861,10 @@ impl ModbusClient for Modbus {
                     msg: "send_raw_request".to_owned(),
                     source: ::std::io::Error::last_os_error(),
                 }),
-                num => Ok(num as u16),
+                num => {
+                    self.stats.record_sent(function, num as usize);
+                    Ok(num as u16)
+                }
             }
         }
     }
@@ -616,12 +904,24 @@ impl ModbusClient for Modbus {
     fn receive_confirmation(&self, response: &mut [u8]) -> Result<u16, Error> {
         unsafe {
             match ffi::modbus_receive_confirmation(self.ctx, response.as_mut_ptr()) {
-                -1 => Err(Error::Client {
-                    msg: "receive_confirmation".to_owned(),
-                    source: ::std::io::Error::last_os_error(),
-                }),
-                len => Ok(len as u16),
+                -1 => {
+                    let source = ::std::io::Error::last_os_error();
+                    self.stats.record_error(&source);
+                    Err(Error::Client {
+                        msg: "receive_confirmation".to_owned(),
+                        source,
+                    })
+                }
+                len => {
+                    self.stats.record_received(len as usize);
+                    Ok(len as u16)
+                }
             }
         }
     }
 }
+
+/// Number of bytes needed to pack `num` bits.
+fn packed_len(num: u16) -> usize {
+    (num as usize).div_ceil(8)
+}
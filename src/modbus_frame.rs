@@ -0,0 +1,333 @@
+use crate::error::{EMBBADCRC, EMBBADDATA};
+use std::io;
+
+/// Longest ASCII frame: colon, 256 bytes as hex digits and CR LF
+const ASCII_MAX_FRAME_LENGTH: usize = 1 + 2 * 256 + 2;
+
+/// Longest RTU frame: slave id, 253 bytes PDU and CRC
+const RTU_MAX_FRAME_LENGTH: usize = 256;
+
+/// Longest value of the length field of an MBAP header: unit id and 253 bytes PDU
+const MBAP_MAX_LENGTH: usize = 254;
+
+/// How the ADUs of a [`ModbusStream`](struct.ModbusStream.html) are delimited on its transport
+///
+/// Chosen for a custom transport with
+/// [`ModbusStream::from_transport()`](struct.ModbusStream.html#method.from_transport), the other backends have the
+/// framing of their protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Framing {
+    /// Modbus ASCII: a colon, the slave id, the PDU and the LRC as hex digits, CR LF
+    Ascii,
+    /// Modbus RTU: the slave id, the PDU and the CRC, here on a stream instead of a serial line
+    Rtu,
+    /// The MBAP header of Modbus TCP: transaction id, protocol id 0, length and unit id, followed by the PDU
+    Mbap,
+}
+
+/// Length of the RTU frame at the start of a buffer, given by its function code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RtuLength {
+    /// The frame is this long, CRC included
+    Known(usize),
+    /// Not enough bytes yet to tell the length
+    Pending,
+    /// The length of the function isn't known, a silence ends the frame
+    Unknown,
+}
+
+/// A frame received on a transport
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Adu {
+    pub(crate) unit: u8,
+    pub(crate) transaction: u16,
+    pub(crate) pdu: Vec<u8>,
+}
+
+/// Longitudinal redundancy check of Modbus ASCII, the two's complement of the sum of `bytes`
+pub(crate) fn lrc(bytes: &[u8]) -> u8 {
+    bytes
+        .iter()
+        .fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+        .wrapping_neg()
+}
+
+/// CRC-16 of Modbus RTU, sent low byte first
+pub(crate) fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0xFFFF, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u16, |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+/// Length of the RTU frame at the start of `buf`, a request if `indication` or else a response
+fn rtu_length(buf: &[u8], indication: bool) -> RtuLength {
+    let function = match buf.get(1) {
+        Some(&function) => function,
+        None => return RtuLength::Pending,
+    };
+    // slave id, function code, the fields and the CRC
+    let fixed = |fields: usize| RtuLength::Known(2 + fields + 2);
+    // the same, followed by as many bytes as the byte count at `offset` of the frame says
+    let counted = |offset: usize| match buf.get(offset) {
+        Some(&count) => RtuLength::Known(offset + 1 + count as usize + 2),
+        None => RtuLength::Pending,
+    };
+    if indication {
+        match function {
+            0x01..=0x06 | 0x08 => fixed(4),
+            0x07 | 0x0B | 0x0C | 0x11 => fixed(0),
+            0x0F | 0x10 => counted(6),
+            0x16 => fixed(6),
+            0x17 => counted(10),
+            _ => RtuLength::Unknown,
+        }
+    } else {
+        match function {
+            function if function & 0x80 != 0 => fixed(1),
+            0x01..=0x04 | 0x0C | 0x11 | 0x14 | 0x15 | 0x17 => counted(2),
+            0x05 | 0x06 | 0x08 | 0x0B | 0x0F | 0x10 => fixed(4),
+            0x07 => fixed(1),
+            0x16 => fixed(6),
+            _ => RtuLength::Unknown,
+        }
+    }
+}
+
+/// Length of the RTU frame at the start of `buf` as its function code gives it, a request if `indication` or else
+/// a response, `None` if the function code doesn't give it or `buf` is too short to tell
+pub(crate) fn rtu_frame_length(buf: &[u8], indication: bool) -> Option<usize> {
+    match rtu_length(buf, indication) {
+        RtuLength::Known(len) => Some(len),
+        RtuLength::Pending | RtuLength::Unknown => None,
+    }
+}
+
+/// True if `frame` is an RTU frame of a valid length with a matching CRC
+pub(crate) fn rtu_crc_valid(frame: &[u8]) -> bool {
+    if frame.len() < 4 || frame.len() > RTU_MAX_FRAME_LENGTH {
+        return false;
+    }
+    let (binary, crc) = frame.split_at(frame.len() - 2);
+    crc16(binary).to_le_bytes() == crc
+}
+
+fn framing_error(errno: i32) -> io::Error {
+    io::Error::from_raw_os_error(errno)
+}
+
+fn hex_digit(digit: u8) -> Option<u8> {
+    match digit {
+        b'0'..=b'9' => Some(digit - b'0'),
+        b'A'..=b'F' => Some(digit - b'A' + 10),
+        b'a'..=b'f' => Some(digit - b'a' + 10),
+        _ => None,
+    }
+}
+
+impl Framing {
+    /// Serial line framings don't answer broadcasts and ignore requests for other slaves
+    pub(crate) fn is_serial(self) -> bool {
+        match self {
+            Framing::Ascii | Framing::Rtu => true,
+            Framing::Mbap => false,
+        }
+    }
+
+    /// Bytes before the PDU in the binary ADU, see [`binary()`](#method.binary)
+    pub(crate) fn header_length(self) -> usize {
+        match self {
+            Framing::Ascii | Framing::Rtu => 1,
+            Framing::Mbap => 7,
+        }
+    }
+
+    /// Bytes after the PDU in the binary ADU
+    pub(crate) fn checksum_length(self) -> usize {
+        match self {
+            Framing::Ascii => 1,
+            Framing::Rtu => 2,
+            Framing::Mbap => 0,
+        }
+    }
+
+    /// The ADU as bytes, as `receive()` and `receive_confirmation()` store it, e.g. slave id, PDU and LRC
+    pub(crate) fn binary(self, adu: &Adu) -> Vec<u8> {
+        self.adu_bytes(adu.unit, adu.transaction, &adu.pdu)
+    }
+
+    /// Header, PDU and checksum
+    fn adu_bytes(self, unit: u8, transaction: u16, pdu: &[u8]) -> Vec<u8> {
+        let mut binary = Vec::with_capacity(pdu.len() + 7);
+        if self == Framing::Mbap {
+            binary.extend_from_slice(&transaction.to_be_bytes());
+            binary.extend_from_slice(&[0, 0]);
+            binary.extend_from_slice(&(pdu.len() as u16 + 1).to_be_bytes());
+        }
+        binary.push(unit);
+        binary.extend_from_slice(pdu);
+        match self {
+            Framing::Ascii => binary.push(lrc(&binary)),
+            Framing::Rtu => {
+                let crc = crc16(&binary);
+                binary.extend_from_slice(&crc.to_le_bytes());
+            }
+            Framing::Mbap => {}
+        }
+        binary
+    }
+
+    /// Split a binary ADU stored by [`binary()`](#method.binary), `None` if it is too short
+    pub(crate) fn split(self, binary: &[u8]) -> Option<Adu> {
+        let end = binary.len().checked_sub(self.checksum_length())?;
+        if end <= self.header_length() {
+            return None;
+        }
+        match self {
+            Framing::Ascii | Framing::Rtu => Some(Adu {
+                unit: binary[0],
+                transaction: 0,
+                pdu: binary[1..end].to_vec(),
+            }),
+            Framing::Mbap => Some(Adu {
+                unit: binary[6],
+                transaction: u16::from_be_bytes([binary[0], binary[1]]),
+                pdu: binary[7..end].to_vec(),
+            }),
+        }
+    }
+
+    /// The frame sent on the transport
+    pub(crate) fn encode(self, unit: u8, transaction: u16, pdu: &[u8]) -> Vec<u8> {
+        match self {
+            Framing::Ascii => {
+                let binary = self.adu_bytes(unit, transaction, pdu);
+                let mut frame = Vec::with_capacity(2 * binary.len() + 3);
+                frame.push(b':');
+                for byte in binary {
+                    frame.extend_from_slice(format!("{:02X}", byte).as_bytes());
+                }
+                frame.extend_from_slice(b"\r\n");
+                frame
+            }
+            Framing::Rtu | Framing::Mbap => self.adu_bytes(unit, transaction, pdu),
+        }
+    }
+
+    /// Number of leading bytes of `buf` which can't start a frame and are dropped
+    pub(crate) fn sync(self, buf: &[u8]) -> usize {
+        match self {
+            Framing::Ascii => buf
+                .iter()
+                .position(|&byte| byte == b':')
+                .unwrap_or(buf.len()),
+            Framing::Rtu | Framing::Mbap => 0,
+        }
+    }
+
+    /// A silence of the inter-frame timeout ends the frame at the start of `buf`, see
+    /// [`decode_silent()`](#method.decode_silent)
+    pub(crate) fn ends_on_silence(self, buf: &[u8], indication: bool) -> bool {
+        match self {
+            Framing::Ascii | Framing::Mbap => false,
+            Framing::Rtu => rtu_length(buf, indication) == RtuLength::Unknown,
+        }
+    }
+
+    /// Decode all of `buf` as one frame, after a silence ended it
+    pub(crate) fn decode_silent(self, buf: &[u8]) -> io::Result<Adu> {
+        match self {
+            Framing::Ascii | Framing::Mbap => Err(framing_error(EMBBADDATA)),
+            Framing::Rtu => self.check_rtu(buf),
+        }
+    }
+
+    /// Check the length and the CRC of a complete RTU frame
+    fn check_rtu(self, frame: &[u8]) -> io::Result<Adu> {
+        if frame.len() < 4 || frame.len() > RTU_MAX_FRAME_LENGTH {
+            return Err(framing_error(EMBBADDATA));
+        }
+        let (binary, crc) = frame.split_at(frame.len() - 2);
+        if crc16(binary).to_le_bytes() != crc {
+            return Err(framing_error(EMBBADCRC));
+        }
+        Ok(self.split(frame).expect("frame of 4 bytes at least"))
+    }
+
+    /// Decode the frame at the start of `buf`, a request if `indication` or else a response, `None` while it is
+    /// incomplete
+    ///
+    /// Returns the length of the frame, which is consumed even if it is invalid, and the ADU or the error in
+    /// it: `EMBBADCRC` for a wrong checksum, `EMBBADDATA` for a malformed frame. An invalid RTU or MBAP frame
+    /// consumes all of `buf`, where the next frame starts is unknown.
+    pub(crate) fn decode(self, buf: &[u8], indication: bool) -> Option<(usize, io::Result<Adu>)> {
+        match self {
+            Framing::Ascii => {
+                let body = buf.get(1..)?;
+                // a colon before the end starts the next frame, this one was cut off
+                let end = match body.iter().position(|&byte| byte == b'\n' || byte == b':') {
+                    Some(end) if body[end] == b':' => {
+                        return Some((end + 1, Err(framing_error(EMBBADDATA))))
+                    }
+                    Some(end) => end,
+                    None if buf.len() >= ASCII_MAX_FRAME_LENGTH => {
+                        return Some((buf.len(), Err(framing_error(EMBBADDATA))))
+                    }
+                    None => return None,
+                };
+                let len = end + 2;
+                let digits = match &body[..end] {
+                    [digits @ .., b'\r'] => digits,
+                    digits => digits,
+                };
+
+                let binary: Option<Vec<u8>> = digits
+                    .chunks(2)
+                    .map(|pair| match *pair {
+                        [high, low] => Some(hex_digit(high)? << 4 | hex_digit(low)?),
+                        _ => None,
+                    })
+                    .collect();
+                let binary = match binary {
+                    Some(binary) if binary.len() >= 3 => binary,
+                    _ => return Some((len, Err(framing_error(EMBBADDATA)))),
+                };
+                if lrc(&binary) != 0 {
+                    return Some((len, Err(framing_error(EMBBADCRC))));
+                }
+                Some((
+                    len,
+                    Ok(self.split(&binary).expect("frame of 3 bytes at least")),
+                ))
+            }
+            Framing::Rtu => match rtu_length(buf, indication) {
+                RtuLength::Known(len) if len > RTU_MAX_FRAME_LENGTH => {
+                    Some((buf.len(), Err(framing_error(EMBBADDATA))))
+                }
+                RtuLength::Known(len) if len <= buf.len() => match self.check_rtu(&buf[..len]) {
+                    Ok(adu) => Some((len, Ok(adu))),
+                    Err(err) => Some((buf.len(), Err(err))),
+                },
+                _ => None,
+            },
+            Framing::Mbap => {
+                let header = buf.get(..7)?;
+                let length = u16::from_be_bytes([header[4], header[5]]) as usize;
+                if header[2..4] != [0, 0] || !(2..=MBAP_MAX_LENGTH).contains(&length) {
+                    return Some((buf.len(), Err(framing_error(EMBBADDATA))));
+                }
+                let len = 6 + length;
+                let frame = buf.get(..len)?;
+                Some((
+                    len,
+                    Ok(self.split(frame).expect("frame of 8 bytes at least")),
+                ))
+            }
+        }
+    }
+}
@@ -0,0 +1,682 @@
+use crate::modbus_request::Request;
+use crate::prelude::*;
+use crate::Exception;
+use libc::c_int;
+use libmodbus_sys as ffi;
+use std::io::Write;
+use std::mem::ManuallyDrop;
+use std::net::TcpStream;
+use std::ptr;
+
+/// Decision of a [`RequestHandler`](trait.RequestHandler.html) about the requests sent to a unit id
+///
+/// Returned by [`select_unit()`](trait.RequestHandler.html#method.select_unit) before a request is decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitSelection {
+    /// Answer the request
+    Handle,
+    /// Drop the request without any response
+    Ignore,
+    /// Answer with this exception without calling the handler
+    Exception(Exception),
+    /// There is no such unit: ignored on a serial line, answered with `Exception::GatewayTarget` on TCP
+    Unknown,
+}
+
+/// Server side callbacks, one per Modbus function code
+///
+/// A `RequestHandler` answers the requests received by a server instead of a fixed
+/// [`ModbusMapping`](struct.ModbusMapping.html) memory block, so values can be computed on demand, forwarded to
+/// another device or writes can be rejected. Every method gets the decoded arguments of the request and returns
+/// either the data to send back or the [`Exception`](enum.Exception.html) the server should answer with. Encoding
+/// the response or exception response is done by
+/// [`reply_with()`](trait.ModbusServer.html#tymethod.reply_with).
+///
+/// All methods have a default implementation: reads answer with `Exception::IllegalFunction`, single writes are
+/// forwarded to the multiple writes, and mask write and read/write multiple registers are emulated with the read
+/// and write methods. So a handler only has to implement the tables it actually serves.
+///
+/// Reads must return exactly `quantity` values, otherwise `Exception::SlaveOrServerFailure` is sent.
+///
+/// # Examples
+///
+/// ```rust
+/// use libmodbus::{Exception, RequestHandler};
+///
+/// struct Clock;
+///
+/// impl RequestHandler for Clock {
+///     fn read_input_registers(&mut self, address: u16, quantity: u16) -> Result<Vec<u16>, Exception> {
+///         match (address, quantity) {
+///             (0, 1) => Ok(vec![42]),
+///             _ => Err(Exception::IllegalDataAddress),
+///         }
+///     }
+/// }
+/// ```
+pub trait RequestHandler {
+    /// Called with the unit id of every request before the request itself, answers every unit by default
+    ///
+    /// A handler serving several units, like [`UnitTable`](struct.UnitTable.html), routes the following
+    /// call to the unit selected here.
+    fn select_unit(&mut self, unit: u8) -> UnitSelection {
+        let _ = unit;
+        UnitSelection::Handle
+    }
+
+    /// Called instead of `select_unit()` for requests to the broadcast address on a serial line
+    ///
+    /// Unit id 0 isn't a broadcast on TCP, requests to it are passed to `select_unit()` there. Selects unit 0 by
+    /// default.
+    fn select_broadcast(&mut self) -> UnitSelection {
+        self.select_unit(ffi::MODBUS_BROADCAST_ADDRESS as u8)
+    }
+
+    /// Read Coils (0x01)
+    fn read_coils(&mut self, address: u16, quantity: u16) -> Result<Vec<bool>, Exception> {
+        let _ = (address, quantity);
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Read Discrete Inputs (0x02)
+    fn read_discrete_inputs(
+        &mut self,
+        address: u16,
+        quantity: u16,
+    ) -> Result<Vec<bool>, Exception> {
+        let _ = (address, quantity);
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Read Holding Registers (0x03)
+    fn read_holding_registers(
+        &mut self,
+        address: u16,
+        quantity: u16,
+    ) -> Result<Vec<u16>, Exception> {
+        let _ = (address, quantity);
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Read Input Registers (0x04)
+    fn read_input_registers(&mut self, address: u16, quantity: u16) -> Result<Vec<u16>, Exception> {
+        let _ = (address, quantity);
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Write Single Coil (0x05), forwarded to [`write_multiple_coils()`](#method.write_multiple_coils) by default
+    fn write_single_coil(&mut self, address: u16, value: bool) -> Result<(), Exception> {
+        self.write_multiple_coils(address, &[value])
+    }
+
+    /// Write Single Register (0x06), forwarded to
+    /// [`write_multiple_registers()`](#method.write_multiple_registers) by default
+    fn write_single_register(&mut self, address: u16, value: u16) -> Result<(), Exception> {
+        self.write_multiple_registers(address, &[value])
+    }
+
+    /// Write Multiple Coils (0x0F)
+    fn write_multiple_coils(&mut self, address: u16, values: &[bool]) -> Result<(), Exception> {
+        let _ = (address, values);
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Write Multiple Registers (0x10)
+    fn write_multiple_registers(&mut self, address: u16, values: &[u16]) -> Result<(), Exception> {
+        let _ = (address, values);
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Mask Write Register (0x16), the new value is `(current & and_mask) | (or_mask & !and_mask)`
+    ///
+    /// Emulated with [`read_holding_registers()`](#method.read_holding_registers) and
+    /// [`write_single_register()`](#method.write_single_register) by default.
+    fn mask_write_register(
+        &mut self,
+        address: u16,
+        and_mask: u16,
+        or_mask: u16,
+    ) -> Result<(), Exception> {
+        let current = match self.read_holding_registers(address, 1)?.as_slice() {
+            [current] => *current,
+            _ => return Err(Exception::SlaveOrServerFailure),
+        };
+        self.write_single_register(address, (current & and_mask) | (or_mask & !and_mask))
+    }
+
+    /// Read/Write Multiple Registers (0x17), the write is performed before the read
+    ///
+    /// Emulated with [`write_multiple_registers()`](#method.write_multiple_registers) and
+    /// [`read_holding_registers()`](#method.read_holding_registers) by default.
+    fn write_and_read_registers(
+        &mut self,
+        write_address: u16,
+        values: &[u16],
+        read_address: u16,
+        read_quantity: u16,
+    ) -> Result<Vec<u16>, Exception> {
+        self.write_multiple_registers(write_address, values)?;
+        self.read_holding_registers(read_address, read_quantity)
+    }
+
+    /// Any other function code, `data` is the PDU without the function code
+    ///
+    /// The returned bytes are sent back after the function code.
+    fn custom(&mut self, function: u8, data: &[u8]) -> Result<Vec<u8>, Exception> {
+        let _ = (function, data);
+        Err(Exception::IllegalFunction)
+    }
+}
+
+impl<H: RequestHandler + ?Sized> RequestHandler for Box<H> {
+    fn select_unit(&mut self, unit: u8) -> UnitSelection {
+        (**self).select_unit(unit)
+    }
+
+    fn select_broadcast(&mut self) -> UnitSelection {
+        (**self).select_broadcast()
+    }
+
+    fn read_coils(&mut self, address: u16, quantity: u16) -> Result<Vec<bool>, Exception> {
+        (**self).read_coils(address, quantity)
+    }
+
+    fn read_discrete_inputs(
+        &mut self,
+        address: u16,
+        quantity: u16,
+    ) -> Result<Vec<bool>, Exception> {
+        (**self).read_discrete_inputs(address, quantity)
+    }
+
+    fn read_holding_registers(
+        &mut self,
+        address: u16,
+        quantity: u16,
+    ) -> Result<Vec<u16>, Exception> {
+        (**self).read_holding_registers(address, quantity)
+    }
+
+    fn read_input_registers(&mut self, address: u16, quantity: u16) -> Result<Vec<u16>, Exception> {
+        (**self).read_input_registers(address, quantity)
+    }
+
+    fn write_single_coil(&mut self, address: u16, value: bool) -> Result<(), Exception> {
+        (**self).write_single_coil(address, value)
+    }
+
+    fn write_single_register(&mut self, address: u16, value: u16) -> Result<(), Exception> {
+        (**self).write_single_register(address, value)
+    }
+
+    fn write_multiple_coils(&mut self, address: u16, values: &[bool]) -> Result<(), Exception> {
+        (**self).write_multiple_coils(address, values)
+    }
+
+    fn write_multiple_registers(&mut self, address: u16, values: &[u16]) -> Result<(), Exception> {
+        (**self).write_multiple_registers(address, values)
+    }
+
+    fn mask_write_register(
+        &mut self,
+        address: u16,
+        and_mask: u16,
+        or_mask: u16,
+    ) -> Result<(), Exception> {
+        (**self).mask_write_register(address, and_mask, or_mask)
+    }
+
+    fn write_and_read_registers(
+        &mut self,
+        write_address: u16,
+        values: &[u16],
+        read_address: u16,
+        read_quantity: u16,
+    ) -> Result<Vec<u16>, Exception> {
+        (**self).write_and_read_registers(write_address, values, read_address, read_quantity)
+    }
+
+    fn custom(&mut self, function: u8, data: &[u8]) -> Result<Vec<u8>, Exception> {
+        (**self).custom(function, data)
+    }
+}
+
+/// Data produced by a handler, encoded into the response by `Modbus::respond()`
+pub(crate) enum Response {
+    Bits(Vec<bool>),
+    Registers(Vec<u16>),
+    Written,
+    Custom(Vec<u8>),
+}
+
+/// Call the handler method matching `request`
+pub(crate) fn dispatch(
+    handler: &mut dyn RequestHandler,
+    request: &Request,
+) -> Result<Response, Exception> {
+    let expect = |quantity: u16, len: usize| {
+        if quantity as usize == len {
+            Ok(())
+        } else {
+            Err(Exception::SlaveOrServerFailure)
+        }
+    };
+
+    match *request {
+        Request::ReadCoils {
+            address, quantity, ..
+        } => {
+            let bits = handler.read_coils(address, quantity)?;
+            expect(quantity, bits.len())?;
+            Ok(Response::Bits(bits))
+        }
+        Request::ReadDiscreteInputs {
+            address, quantity, ..
+        } => {
+            let bits = handler.read_discrete_inputs(address, quantity)?;
+            expect(quantity, bits.len())?;
+            Ok(Response::Bits(bits))
+        }
+        Request::ReadHoldingRegisters {
+            address, quantity, ..
+        } => {
+            let registers = handler.read_holding_registers(address, quantity)?;
+            expect(quantity, registers.len())?;
+            Ok(Response::Registers(registers))
+        }
+        Request::ReadInputRegisters {
+            address, quantity, ..
+        } => {
+            let registers = handler.read_input_registers(address, quantity)?;
+            expect(quantity, registers.len())?;
+            Ok(Response::Registers(registers))
+        }
+        Request::WriteSingleCoil { address, value, .. } => handler
+            .write_single_coil(address, value)
+            .map(|_| Response::Written),
+        Request::WriteSingleRegister { address, value, .. } => handler
+            .write_single_register(address, value)
+            .map(|_| Response::Written),
+        Request::WriteMultipleCoils {
+            address,
+            ref values,
+            ..
+        } => handler
+            .write_multiple_coils(address, values)
+            .map(|_| Response::Written),
+        Request::WriteMultipleRegisters {
+            address,
+            ref values,
+            ..
+        } => handler
+            .write_multiple_registers(address, values)
+            .map(|_| Response::Written),
+        Request::MaskWriteRegister {
+            address,
+            and_mask,
+            or_mask,
+            ..
+        } => handler
+            .mask_write_register(address, and_mask, or_mask)
+            .map(|_| Response::Written),
+        Request::WriteAndReadRegisters {
+            write_address,
+            ref values,
+            read_address,
+            read_quantity,
+            ..
+        } => {
+            let registers = handler.write_and_read_registers(
+                write_address,
+                values,
+                read_address,
+                read_quantity,
+            )?;
+            expect(read_quantity, registers.len())?;
+            Ok(Response::Registers(registers))
+        }
+        Request::Custom {
+            function, ref data, ..
+        } => {
+            let data = handler.custom(function, data)?;
+            if data.len() + 1 > Modbus::MAX_PDU_LENGTH {
+                return Err(Exception::SlaveOrServerFailure);
+            }
+            Ok(Response::Custom(data))
+        }
+    }
+}
+
+/// Pack bits eight per byte, least significant bit first
+pub(crate) fn pack_bits(bits: &[bool]) -> Vec<u8> {
+    bits.chunks(8)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .fold(0u8, |byte, (bit, &value)| byte | ((value as u8) << bit))
+        })
+        .collect()
+}
+
+/// Encode the PDU of the response to `request`, for the backends implemented in Rust
+///
+/// Returns `None` if the handler data doesn't fit the request, which is answered with
+/// `Exception::SlaveOrServerFailure`.
+pub(crate) fn encode_response(request: &Request, response: Response) -> Option<Vec<u8>> {
+    let mut pdu = vec![request.function()];
+    match (request, response) {
+        (_, Response::Custom(data)) => pdu.extend_from_slice(&data),
+        (Request::ReadCoils { .. }, Response::Bits(bits))
+        | (Request::ReadDiscreteInputs { .. }, Response::Bits(bits)) => {
+            let packed = pack_bits(&bits);
+            pdu.push(packed.len() as u8);
+            pdu.extend_from_slice(&packed);
+        }
+        (Request::ReadHoldingRegisters { .. }, Response::Registers(registers))
+        | (Request::ReadInputRegisters { .. }, Response::Registers(registers))
+        | (Request::WriteAndReadRegisters { .. }, Response::Registers(registers)) => {
+            pdu.push(2 * registers.len() as u8);
+            for register in registers {
+                pdu.extend_from_slice(&register.to_be_bytes());
+            }
+        }
+        (Request::WriteSingleCoil { address, value, .. }, Response::Written) => {
+            pdu.extend_from_slice(&address.to_be_bytes());
+            pdu.extend_from_slice(if *value { &[0xFF, 0x00] } else { &[0x00, 0x00] });
+        }
+        (Request::WriteSingleRegister { address, value, .. }, Response::Written) => {
+            pdu.extend_from_slice(&address.to_be_bytes());
+            pdu.extend_from_slice(&value.to_be_bytes());
+        }
+        (
+            Request::WriteMultipleCoils {
+                address, values, ..
+            },
+            Response::Written,
+        ) => {
+            pdu.extend_from_slice(&address.to_be_bytes());
+            pdu.extend_from_slice(&(values.len() as u16).to_be_bytes());
+        }
+        (
+            Request::WriteMultipleRegisters {
+                address, values, ..
+            },
+            Response::Written,
+        ) => {
+            pdu.extend_from_slice(&address.to_be_bytes());
+            pdu.extend_from_slice(&(values.len() as u16).to_be_bytes());
+        }
+        (
+            Request::MaskWriteRegister {
+                address,
+                and_mask,
+                or_mask,
+                ..
+            },
+            Response::Written,
+        ) => {
+            pdu.extend_from_slice(&address.to_be_bytes());
+            pdu.extend_from_slice(&and_mask.to_be_bytes());
+            pdu.extend_from_slice(&or_mask.to_be_bytes());
+        }
+        _ => return None,
+    }
+    Some(pdu)
+}
+
+impl Modbus {
+    /// Length of the checksum trailing every ADU, 2 bytes CRC for RTU and none for TCP
+    fn checksum_length(&self) -> usize {
+        if self.is_tcp() {
+            0
+        } else {
+            2
+        }
+    }
+
+    /// TCP and TCP PI contexts use the 7 bytes MBAP header, RTU contexts a single address byte
+    pub(crate) fn is_tcp(&self) -> bool {
+        self.get_header_length() == 7
+    }
+
+    /// Unit id and PDU of a request received with `receive()`
+    pub(crate) fn split_request<'a>(
+        &self,
+        request: &'a [u8],
+        request_len: i32,
+    ) -> Result<(u8, &'a [u8]), Error> {
+        let header_length = self.get_header_length() as usize;
+        let end = (request_len.max(0) as usize).min(request.len());
+        match end.checked_sub(self.checksum_length()) {
+            Some(end) if end > header_length => {
+                Ok((request[header_length - 1], &request[header_length..end]))
+            }
+            _ => Err(Error::Request {
+                msg: "request too short".to_owned(),
+                exception: Exception::IllegalDataValue,
+            }),
+        }
+    }
+
+    /// Decode `request`, let `handler` answer it and send the response or exception response
+    pub(crate) fn respond(
+        &self,
+        request: &[u8],
+        request_len: i32,
+        handler: &mut dyn RequestHandler,
+    ) -> Result<i32, Error> {
+        let (unit, pdu) = self.split_request(request, request_len)?;
+        // Requests to the broadcast address are executed but never answered on a serial line
+        let answer = self.is_tcp() || unit != ffi::MODBUS_BROADCAST_ADDRESS as u8;
+
+        let selection = if answer {
+            handler.select_unit(unit)
+        } else {
+            handler.select_broadcast()
+        };
+        let outcome = match selection {
+            UnitSelection::Handle => Request::decode(unit, pdu)
+                .map_err(|err| err.exception().unwrap_or(Exception::IllegalDataValue))
+                .and_then(|decoded| {
+                    let response = dispatch(handler, &decoded)?;
+                    Ok((decoded, response))
+                }),
+            UnitSelection::Exception(exception) => Err(exception),
+            UnitSelection::Unknown if self.is_tcp() => Err(Exception::GatewayTarget),
+            UnitSelection::Unknown | UnitSelection::Ignore => return Ok(0),
+        };
+        if !answer {
+            return Ok(0);
+        }
+
+        match outcome {
+            Ok((decoded, response)) => self.send_response(request, request_len, &decoded, response),
+            Err(exception) => self.reply_exception(request, exception),
+        }
+    }
+
+    /// Encode the data of a handler into the response of `request`
+    ///
+    /// The standard function codes are answered by `modbus_reply()` from a mapping built around the handler data,
+    /// so the responses are byte for byte those of a `ModbusMapping` server. Read/Write Multiple Registers and custom
+    /// function codes are encoded here.
+    fn send_response(
+        &self,
+        request: &[u8],
+        request_len: i32,
+        decoded: &Request,
+        response: Response,
+    ) -> Result<i32, Error> {
+        let mut bits: Vec<u8>;
+        let mut registers: Vec<u16>;
+        let mut mapping = ffi::modbus_mapping_t {
+            nb_bits: 0,
+            start_bits: 0,
+            nb_input_bits: 0,
+            start_input_bits: 0,
+            nb_input_registers: 0,
+            start_input_registers: 0,
+            nb_registers: 0,
+            start_registers: 0,
+            tab_bits: ptr::null_mut(),
+            tab_input_bits: ptr::null_mut(),
+            tab_input_registers: ptr::null_mut(),
+            tab_registers: ptr::null_mut(),
+        };
+
+        match (decoded, response) {
+            (_, Response::Custom(data)) => {
+                let mut pdu = vec![decoded.function()];
+                pdu.extend_from_slice(&data);
+                return self.send_pdu(request, decoded.unit(), &pdu);
+            }
+            (Request::ReadCoils { address, .. }, Response::Bits(values)) => {
+                bits = values.into_iter().map(u8::from).collect();
+                mapping.start_bits = *address as c_int;
+                mapping.nb_bits = bits.len() as c_int;
+                mapping.tab_bits = bits.as_mut_ptr();
+            }
+            (Request::ReadDiscreteInputs { address, .. }, Response::Bits(values)) => {
+                bits = values.into_iter().map(u8::from).collect();
+                mapping.start_input_bits = *address as c_int;
+                mapping.nb_input_bits = bits.len() as c_int;
+                mapping.tab_input_bits = bits.as_mut_ptr();
+            }
+            (Request::ReadHoldingRegisters { address, .. }, Response::Registers(values)) => {
+                registers = values;
+                mapping.start_registers = *address as c_int;
+                mapping.nb_registers = registers.len() as c_int;
+                mapping.tab_registers = registers.as_mut_ptr();
+            }
+            (Request::ReadInputRegisters { address, .. }, Response::Registers(values)) => {
+                registers = values;
+                mapping.start_input_registers = *address as c_int;
+                mapping.nb_input_registers = registers.len() as c_int;
+                mapping.tab_input_registers = registers.as_mut_ptr();
+            }
+            (Request::WriteSingleCoil { address, .. }, Response::Written) => {
+                bits = vec![0; 1];
+                mapping.start_bits = *address as c_int;
+                mapping.nb_bits = 1;
+                mapping.tab_bits = bits.as_mut_ptr();
+            }
+            (
+                Request::WriteMultipleCoils {
+                    address, values, ..
+                },
+                Response::Written,
+            ) => {
+                bits = vec![0; values.len()];
+                mapping.start_bits = *address as c_int;
+                mapping.nb_bits = bits.len() as c_int;
+                mapping.tab_bits = bits.as_mut_ptr();
+            }
+            (Request::WriteSingleRegister { address, .. }, Response::Written)
+            | (Request::MaskWriteRegister { address, .. }, Response::Written) => {
+                registers = vec![0; 1];
+                mapping.start_registers = *address as c_int;
+                mapping.nb_registers = 1;
+                mapping.tab_registers = registers.as_mut_ptr();
+            }
+            (
+                Request::WriteMultipleRegisters {
+                    address, values, ..
+                },
+                Response::Written,
+            ) => {
+                registers = vec![0; values.len()];
+                mapping.start_registers = *address as c_int;
+                mapping.nb_registers = registers.len() as c_int;
+                mapping.tab_registers = registers.as_mut_ptr();
+            }
+            // `modbus_reply()` writes the values of the request into the mapping before it reads, so the response of
+            // overlapping ranges would echo them instead of the data of the handler
+            (Request::WriteAndReadRegisters { .. }, response @ Response::Registers(_)) => {
+                return match encode_response(decoded, response) {
+                    Some(pdu) => self.send_pdu(request, decoded.unit(), &pdu),
+                    None => self.reply_exception(request, Exception::SlaveOrServerFailure),
+                };
+            }
+            _ => return self.reply_exception(request, Exception::SlaveOrServerFailure),
+        }
+
+        // `bits` and `registers` back the mapping and live until the end of this function
+        let len =
+            unsafe { ffi::modbus_reply(self.ctx, request.as_ptr(), request_len, &mut mapping) };
+        match len {
+            -1 => {
+                let source = ::std::io::Error::last_os_error();
+                self.stats.record_error(&source);
+                Err(Error::Server {
+                    msg: "reply_with".to_owned(),
+                    source,
+                })
+            }
+            len => {
+                self.stats.record_reply(len as usize, None);
+                Ok(len)
+            }
+        }
+    }
+
+    /// Send a response PDU built here instead of by `modbus_reply()`
+    ///
+    /// `modbus_send_raw_request()` builds the header of a response but always with a transaction id of 0, so on
+    /// TCP the MBAP header is built here and written to the socket directly.
+    fn send_pdu(&self, request: &[u8], unit: u8, pdu: &[u8]) -> Result<i32, Error> {
+        let result = if self.is_tcp() {
+            let length = (pdu.len() + 1) as u16;
+            let mut adu = Vec::with_capacity(pdu.len() + 7);
+            adu.extend_from_slice(&request[0..2]);
+            adu.extend_from_slice(&[0, 0]);
+            adu.extend_from_slice(&length.to_be_bytes());
+            adu.push(unit);
+            adu.extend_from_slice(pdu);
+            self.write_socket(&adu).map(|_| adu.len() as i32)
+        } else {
+            let mut raw = Vec::with_capacity(pdu.len() + 1);
+            raw.push(unit);
+            raw.extend_from_slice(pdu);
+            match unsafe {
+                ffi::modbus_send_raw_request(self.ctx, raw.as_ptr(), raw.len() as c_int)
+            } {
+                -1 => Err(::std::io::Error::last_os_error()),
+                len => Ok(len),
+            }
+        };
+
+        match result {
+            Ok(len) => {
+                self.stats.record_reply(len as usize, None);
+                Ok(len)
+            }
+            Err(source) => {
+                self.stats.record_error(&source);
+                Err(Error::Server {
+                    msg: "reply_with".to_owned(),
+                    source,
+                })
+            }
+        }
+    }
+
+    /// Write `bytes` to the socket of the context without taking ownership of it
+    fn write_socket(&self, bytes: &[u8]) -> ::std::io::Result<()> {
+        let socket = unsafe { ffi::modbus_get_socket(self.ctx) };
+        if socket < 0 {
+            return Err(::std::io::Error::from(::std::io::ErrorKind::NotConnected));
+        }
+        #[cfg(unix)]
+        let mut stream = {
+            use std::os::unix::io::FromRawFd;
+            ManuallyDrop::new(unsafe { TcpStream::from_raw_fd(socket) })
+        };
+        #[cfg(windows)]
+        let mut stream = {
+            use std::os::windows::io::FromRawSocket;
+            ManuallyDrop::new(unsafe { TcpStream::from_raw_socket(socket as _) })
+        };
+        stream.write_all(bytes)
+    }
+}
@@ -0,0 +1,255 @@
+use crate::modbus_transport::{timed_out, Transport};
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// A fault a [`LoopbackTransport`](struct.LoopbackTransport.html) injects into one frame it sends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopbackFault {
+    /// The frame never arrives
+    Drop,
+    /// The byte at `offset` of the frame arrives XORed with `mask`
+    Corrupt { offset: usize, mask: u8 },
+    /// The frame arrives late by this much, on top of the latency, and holds back the frames after it
+    Delay(Duration),
+}
+
+/// The frames on their way in one direction
+#[derive(Debug, Default)]
+struct Queue {
+    /// Each frame with the time it arrives
+    frames: VecDeque<(Instant, Vec<u8>)>,
+    /// The sending end is closed, nothing arrives after the frames queued
+    closed: bool,
+}
+
+/// One direction of a loopback pair
+#[derive(Debug, Default)]
+struct Channel {
+    queue: Mutex<Queue>,
+    arrived: Condvar,
+}
+
+impl Channel {
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        // the queue stays consistent even if a thread panicked holding the lock
+        self.queue
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// One end of an in-memory connection between two contexts of one process
+///
+/// [`pair()`](#method.pair) connects two ends without any OS resources, no socket, no serial device. Each end
+/// backs a context, see [`ModbusStream::from_transport()`](struct.ModbusStream.html#method.from_transport), e.g. a
+/// client and a server answering it in another thread, so Modbus code can be tested anywhere.
+///
+/// Faults are injected by the sending end and are deterministic: each end delays the frames it sends by its
+/// [latency](#method.set_latency), and a [fault](enum.LoopbackFault.html) can be injected into its n-th frame,
+/// counted from 0, see [`inject()`](#method.inject).
+///
+/// Only a [`ModbusStream`](struct.ModbusStream.html) runs on a transport, the libmodbus contexts of
+/// [`Modbus`](struct.Modbus.html) need a socket or serial device. To test code with a loopback, make it generic over
+/// [`ModbusClient`](trait.ModbusClient.html) or [`ModbusServer`](trait.ModbusServer.html), e.g.
+/// `fn poll<C: ModbusClient>(client: &C)`, so it takes either context.
+///
+/// # Examples
+///
+/// ```rust
+/// use libmodbus::{
+///     Framing, LoopbackFault, LoopbackTransport, Modbus, ModbusClient, ModbusMapping, ModbusServer,
+///     ModbusStream,
+/// };
+/// use std::thread;
+///
+/// let (client, mut server) = LoopbackTransport::pair();
+/// // the first response is lost
+/// server.inject(0, LoopbackFault::Drop);
+///
+/// let server_thread = thread::spawn(move || {
+///     let mut server = ModbusStream::from_transport(server, Framing::Rtu);
+///     server.set_slave(1).unwrap();
+///     let mapping = ModbusMapping::new(0, 0, 10, 0).unwrap();
+///     let mut query = vec![0u8; Modbus::MAX_ADU_LENGTH];
+///     for _ in 0..2 {
+///         let rc = server.receive(&mut query).unwrap();
+///         server.reply(&query, rc, &mapping).unwrap();
+///     }
+/// });
+///
+/// let mut client = ModbusStream::from_transport(client, Framing::Rtu);
+/// client.set_slave(1).unwrap();
+/// let mut registers = [0u16; 2];
+/// assert!(client.read_registers(0, 2, &mut registers).is_err());
+/// assert_eq!(client.read_registers(0, 2, &mut registers).unwrap(), 2);
+/// server_thread.join().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct LoopbackTransport {
+    incoming: Arc<Channel>,
+    outgoing: Arc<Channel>,
+    latency: Duration,
+    faults: HashMap<usize, LoopbackFault>,
+    /// Number of frames sent so far
+    sent: usize,
+    /// The bytes of the first incoming frame already received
+    offset: usize,
+    closed: bool,
+}
+
+impl LoopbackTransport {
+    /// `pair` - create two connected ends
+    ///
+    /// What one end sends the other receives.
+    pub fn pair() -> (LoopbackTransport, LoopbackTransport) {
+        let (a, b) = (Arc::new(Channel::default()), Arc::new(Channel::default()));
+        (
+            LoopbackTransport::new(a.clone(), b.clone()),
+            LoopbackTransport::new(b, a),
+        )
+    }
+
+    fn new(incoming: Arc<Channel>, outgoing: Arc<Channel>) -> LoopbackTransport {
+        LoopbackTransport {
+            incoming,
+            outgoing,
+            latency: Duration::default(),
+            faults: HashMap::new(),
+            sent: 0,
+            offset: 0,
+            closed: false,
+        }
+    }
+
+    /// `get_latency` - get the delay of the frames this end sends
+    pub fn get_latency(&self) -> Duration {
+        self.latency
+    }
+
+    /// `set_latency` - set the delay of the frames this end sends, none by default
+    pub fn set_latency(&mut self, latency: Duration) {
+        self.latency = latency;
+    }
+
+    /// `inject` - inject a fault into a frame this end sends
+    ///
+    /// A fault injected before replaces the one of the same frame.
+    ///
+    /// # Parameters
+    ///
+    /// * `frame`   - number of the frame, counted from 0 for the first frame this end sends
+    /// * `fault`   - what happens to the frame
+    pub fn inject(&mut self, frame: usize, fault: LoopbackFault) {
+        self.faults.insert(frame, fault);
+    }
+
+    fn check_open(&self) -> io::Result<()> {
+        if self.closed {
+            Err(io::Error::from_raw_os_error(libc::EBADF))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Transport for LoopbackTransport {
+    fn send(&mut self, adu: &[u8]) -> io::Result<()> {
+        self.check_open()?;
+        let mut frame = adu.to_vec();
+        let mut arrival = Instant::now() + self.latency;
+        match self.faults.remove(&self.sent) {
+            Some(LoopbackFault::Drop) => frame.clear(),
+            Some(LoopbackFault::Corrupt { offset, mask }) => {
+                if let Some(byte) = frame.get_mut(offset) {
+                    *byte ^= mask;
+                }
+            }
+            Some(LoopbackFault::Delay(delay)) => arrival += delay,
+            None => {}
+        }
+        self.sent += 1;
+        if !frame.is_empty() {
+            self.outgoing.lock().frames.push_back((arrival, frame));
+            self.outgoing.arrived.notify_all();
+        }
+        Ok(())
+    }
+
+    fn receive(&mut self, buf: &mut [u8], timeout: Option<Duration>) -> io::Result<usize> {
+        self.check_open()?;
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut queue = self.incoming.lock();
+        loop {
+            let now = Instant::now();
+            let arrival = match queue.frames.front() {
+                Some((arrival, frame)) if *arrival <= now => {
+                    let rest = &frame[self.offset..];
+                    let len = rest.len().min(buf.len());
+                    buf[..len].copy_from_slice(&rest[..len]);
+                    self.offset += len;
+                    if self.offset == frame.len() {
+                        queue.frames.pop_front();
+                        self.offset = 0;
+                    }
+                    return Ok(len);
+                }
+                Some((arrival, _)) => Some(*arrival),
+                None if queue.closed => return Ok(0),
+                None => None,
+            };
+            if deadline.is_some_and(|deadline| deadline <= now) {
+                return Err(timed_out());
+            }
+            // wake up when the first frame arrives or the timeout elapses, whatever comes first
+            let wake = match (arrival, deadline) {
+                (Some(arrival), Some(deadline)) => Some(arrival.min(deadline)),
+                (arrival, deadline) => arrival.or(deadline),
+            };
+            queue = match wake {
+                Some(wake) => {
+                    self.incoming
+                        .arrived
+                        .wait_timeout(queue, wake - now)
+                        .unwrap_or_else(|poisoned| poisoned.into_inner())
+                        .0
+                }
+                None => self
+                    .incoming
+                    .arrived
+                    .wait(queue)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner()),
+            };
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.check_open()?;
+        let now = Instant::now();
+        let mut queue = self.incoming.lock();
+        while queue
+            .frames
+            .front()
+            .is_some_and(|(arrival, _)| *arrival <= now)
+        {
+            queue.frames.pop_front();
+        }
+        self.offset = 0;
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        if !self.closed {
+            self.closed = true;
+            self.outgoing.lock().closed = true;
+            self.outgoing.arrived.notify_all();
+        }
+    }
+}
+
+impl Drop for LoopbackTransport {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
@@ -1,12 +1,168 @@
+use crate::modbus_request::Request;
 use crate::prelude::*;
+use crate::{Exception, RequestHandler, Snapshot};
 use libc::{c_int, c_uint};
 use libmodbus_sys as ffi;
+use std::cell::RefCell;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+/// The four data tables of the Modbus data model
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "simulator", derive(serde::Deserialize))]
+#[cfg_attr(feature = "simulator", serde(rename_all = "snake_case"))]
+pub enum Table {
+    /// Read/write bits
+    Coils,
+    /// Read only bits
+    DiscreteInputs,
+    /// Read/write registers
+    HoldingRegisters,
+    /// Read only registers
+    InputRegisters,
+}
+
+/// A write of a client that changed coils or holding registers of a [`ModbusMapping`](struct.ModbusMapping.html)
+///
+/// See [`add_observer()`](struct.ModbusMapping.html#method.add_observer).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteEvent {
+    /// `Table::Coils` or `Table::HoldingRegisters`
+    pub table: Table,
+    /// Address of the first value written
+    pub address: u16,
+    /// Values before the write, coils as 0 or 1
+    pub old: Vec<u16>,
+    /// Values after the write, coils as 0 or 1
+    pub new: Vec<u16>,
+}
+
+/// Access rights of an address region of a [`ModbusMapping`](struct.ModbusMapping.html)
+///
+/// See [`set_access()`](struct.ModbusMapping.html#method.set_access).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Readable and writable, the default for every address
+    ReadWrite,
+    /// Writes are answered with `Exception::IllegalDataAddress`
+    ReadOnly,
+    /// Reads are answered with `Exception::IllegalDataAddress`
+    WriteOnly,
+    /// A hole in the address space, reads and writes are answered with `Exception::IllegalDataAddress`
+    Unmapped,
+}
+
+impl Access {
+    fn readable(self) -> bool {
+        self == Access::ReadWrite || self == Access::ReadOnly
+    }
+
+    fn writable(self) -> bool {
+        self == Access::ReadWrite || self == Access::WriteOnly
+    }
+}
+
+/// Valid values of holding registers of a [`ModbusMapping`](struct.ModbusMapping.html)
+///
+/// See [`add_validator()`](struct.ModbusMapping.html#method.add_validator).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Validator {
+    /// Values from `min` to `max`, both included
+    Range { min: u16, max: u16 },
+    /// Values from `min` to `max`, both included, of registers holding signed values
+    SignedRange { min: i16, max: i16 },
+    /// One of the listed values, e.g. the states of an enumeration
+    OneOf(Vec<u16>),
+}
+
+impl Validator {
+    /// `is_valid` - check a value against the validator
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libmodbus::Validator;
+    ///
+    /// assert!(Validator::Range { min: 10, max: 20 }.is_valid(20));
+    /// assert!(!Validator::SignedRange { min: -5, max: 5 }.is_valid(0xFFF0));
+    /// assert!(Validator::OneOf(vec![0, 1, 4]).is_valid(4));
+    /// ```
+    pub fn is_valid(&self, value: u16) -> bool {
+        match *self {
+            Validator::Range { min, max } => min <= value && value <= max,
+            Validator::SignedRange { min, max } => min <= value as i16 && value as i16 <= max,
+            Validator::OneOf(ref values) => values.contains(&value),
+        }
+    }
+}
+
+/// Access rights and validators of the addresses of a `ModbusMapping`
+#[derive(Debug, Default)]
+struct Rules {
+    access: Vec<(Table, Range<u32>, Access)>,
+    validators: Vec<(Range<u32>, Validator)>,
+}
+
+impl Rules {
+    fn is_empty(&self) -> bool {
+        self.access.is_empty() && self.validators.is_empty()
+    }
+
+    /// Access of an address, the region declared last wins
+    fn access(&self, table: Table, address: u32) -> Access {
+        self.access
+            .iter()
+            .rev()
+            .find(|(t, range, _)| *t == table && range.contains(&address))
+            .map_or(Access::ReadWrite, |&(_, _, access)| access)
+    }
+}
+
+/// Callback registered with `ModbusMapping::add_observer()`
+type Observer = Box<dyn FnMut(&WriteEvent)>;
+
+/// Values a request is about to overwrite, see `ModbusMapping::prepare_write()`
+pub(crate) struct PendingWrite {
+    table: Table,
+    address: u16,
+    range: Range<usize>,
+    old: Vec<u16>,
+}
 
 /// To handle the mapping of your Modbus data, you must use this struct
 ///
-#[derive(Debug)]
 pub struct ModbusMapping {
     pub modbus_mapping: *mut ffi::modbus_mapping_t,
+    observers: RefCell<Vec<Observer>>,
+    rules: Rules,
+    autosave: RefCell<Option<Autosave>>,
+}
+
+/// Periodic saving of the tables, see [`set_autosave()`](struct.ModbusMapping.html#method.set_autosave)
+#[derive(Debug)]
+struct Autosave {
+    path: PathBuf,
+    interval: Duration,
+    last: Instant,
+    /// The snapshot saved last, to skip saving unchanged tables
+    saved: Option<Vec<u8>>,
+}
+
+/// Write a binary snapshot to a temporary file and rename it to `path`
+fn write_snapshot(path: &Path, bytes: &[u8]) -> Result<(), Error> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, bytes)
+        .and_then(|()| fs::rename(&tmp, path))
+        .map_err(|source| Error::Mapping {
+            msg: format!("could not save {}", path.display()),
+            source,
+        })
 }
 
 impl ModbusMapping {
@@ -59,6 +215,9 @@ impl ModbusMapping {
             } else {
                 Ok(ModbusMapping {
                     modbus_mapping: modbus_mapping,
+                    observers: RefCell::new(Vec::new()),
+                    rules: Rules::default(),
+                    autosave: RefCell::new(None),
                 })
             }
         }
@@ -138,6 +297,9 @@ impl ModbusMapping {
             } else {
                 Ok(ModbusMapping {
                     modbus_mapping: modbus_mapping,
+                    observers: RefCell::new(Vec::new()),
+                    rules: Rules::default(),
+                    autosave: RefCell::new(None),
                 })
             }
         }
@@ -390,6 +552,938 @@ impl ModbusMapping {
     }
 }
 
+/// Accessors by Modbus address
+///
+/// Unlike the slices of [`get_bits()`](#method.get_bits) and the like, which start at index 0, these accessors
+/// take the addresses clients use, so the start addresses of
+/// [`new_start_address()`](#method.new_start_address) are taken into account. An address outside of the table
+/// is an Error. Like the slices, they are not subject to access rules or validators and don't notify observers.
+impl ModbusMapping {
+    /// Slice range of `count` values from `address` in `table`
+    fn address_range(
+        &self,
+        table: Table,
+        address: u16,
+        count: usize,
+    ) -> Result<Range<usize>, Error> {
+        self.table_range(table, address, count)
+            .map_err(|_| Error::Mapping {
+                msg: format!(
+                    "addresses {}..{} outside of the {:?}",
+                    address,
+                    address as usize + count,
+                    table
+                ),
+                source: io::Error::from(io::ErrorKind::InvalidInput),
+            })
+    }
+
+    /// Slice range of the addresses `range` in `table`, for `len` values to write
+    fn write_range(
+        &self,
+        table: Table,
+        range: Range<u16>,
+        len: usize,
+    ) -> Result<Range<usize>, Error> {
+        if range.len() != len {
+            return Err(Error::Mapping {
+                msg: format!(
+                    "{} values for the {} addresses {}..{}",
+                    len,
+                    range.len(),
+                    range.start,
+                    range.end
+                ),
+                source: io::Error::from(io::ErrorKind::InvalidInput),
+            });
+        }
+        self.address_range(table, range.start, len)
+    }
+
+    /// `coil` - get the coil at `address`
+    pub fn coil(&self, address: u16) -> Result<bool, Error> {
+        let index = self.address_range(Table::Coils, address, 1)?.start;
+        Ok(self.get_bits()[index] != 0)
+    }
+
+    /// `set_coil` - set the coil at `address`
+    pub fn set_coil(&self, address: u16, value: bool) -> Result<(), Error> {
+        let index = self.address_range(Table::Coils, address, 1)?.start;
+        self.get_bits_mut()[index] = value as u8;
+        Ok(())
+    }
+
+    /// `bits` - get the coils at the addresses `range`
+    pub fn bits(&self, range: Range<u16>) -> Result<Vec<bool>, Error> {
+        let range = self.address_range(Table::Coils, range.start, range.len())?;
+        Ok(self.get_bits()[range].iter().map(|&bit| bit != 0).collect())
+    }
+
+    /// `set_bits` - set the coils at the addresses `range`
+    ///
+    /// # Parameters
+    ///
+    /// * `range`   - addresses of the coils
+    /// * `values`  - one value per address
+    pub fn set_bits(&self, range: Range<u16>, values: &[bool]) -> Result<(), Error> {
+        let range = self.write_range(Table::Coils, range, values.len())?;
+        for (bit, &value) in self.get_bits_mut()[range].iter_mut().zip(values) {
+            *bit = value as u8;
+        }
+        Ok(())
+    }
+
+    /// `discrete_input` - get the discrete input at `address`
+    pub fn discrete_input(&self, address: u16) -> Result<bool, Error> {
+        let index = self.address_range(Table::DiscreteInputs, address, 1)?.start;
+        Ok(self.get_input_bits()[index] != 0)
+    }
+
+    /// `set_discrete_input` - set the discrete input at `address`
+    pub fn set_discrete_input(&self, address: u16, value: bool) -> Result<(), Error> {
+        let index = self.address_range(Table::DiscreteInputs, address, 1)?.start;
+        self.get_input_bits_mut()[index] = value as u8;
+        Ok(())
+    }
+
+    /// `input_bits` - get the discrete inputs at the addresses `range`
+    pub fn input_bits(&self, range: Range<u16>) -> Result<Vec<bool>, Error> {
+        let range = self.address_range(Table::DiscreteInputs, range.start, range.len())?;
+        Ok(self.get_input_bits()[range]
+            .iter()
+            .map(|&bit| bit != 0)
+            .collect())
+    }
+
+    /// `set_input_bits` - set the discrete inputs at the addresses `range`
+    ///
+    /// # Parameters
+    ///
+    /// * `range`   - addresses of the discrete inputs
+    /// * `values`  - one value per address
+    pub fn set_input_bits(&self, range: Range<u16>, values: &[bool]) -> Result<(), Error> {
+        let range = self.write_range(Table::DiscreteInputs, range, values.len())?;
+        for (bit, &value) in self.get_input_bits_mut()[range].iter_mut().zip(values) {
+            *bit = value as u8;
+        }
+        Ok(())
+    }
+
+    /// `holding` - get the holding register at `address`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libmodbus::ModbusMapping;
+    /// let modbus_mapping = ModbusMapping::new_start_address(0, 0, 0, 0, 40000, 100, 0, 0).unwrap();
+    ///
+    /// modbus_mapping.set_holding(40010, 17).unwrap();
+    /// assert_eq!(modbus_mapping.holding(40010).unwrap(), 17);
+    /// assert_eq!(modbus_mapping.get_registers()[10], 17);
+    /// assert!(modbus_mapping.holding(10).is_err());
+    /// ```
+    pub fn holding(&self, address: u16) -> Result<u16, Error> {
+        let index = self
+            .address_range(Table::HoldingRegisters, address, 1)?
+            .start;
+        Ok(self.get_registers()[index])
+    }
+
+    /// `set_holding` - set the holding register at `address`
+    pub fn set_holding(&self, address: u16, value: u16) -> Result<(), Error> {
+        let index = self
+            .address_range(Table::HoldingRegisters, address, 1)?
+            .start;
+        self.get_registers_mut()[index] = value;
+        Ok(())
+    }
+
+    /// `holding_f32` - get a float from the holding registers at `address` and `address + 1`
+    ///
+    /// # Parameters
+    ///
+    /// * `address` - address of the first register
+    /// * `order`   - byte order of the float
+    pub fn holding_f32(&self, address: u16, order: ByteOrder) -> Result<f32, Error> {
+        let range = self.address_range(Table::HoldingRegisters, address, 2)?;
+        Ok(order.get_float(&self.get_registers()[range]))
+    }
+
+    /// `set_holding_f32` - set a float in the holding registers at `address` and `address + 1`
+    ///
+    /// # Parameters
+    ///
+    /// * `address` - address of the first register
+    /// * `value`   - float to set
+    /// * `order`   - byte order of the float
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libmodbus::{ByteOrder, ModbusMapping};
+    /// let modbus_mapping = ModbusMapping::new_start_address(0, 0, 0, 0, 40000, 100, 0, 0).unwrap();
+    ///
+    /// modbus_mapping.set_holding_f32(40020, 123456.0, ByteOrder::CDAB).unwrap();
+    /// assert_eq!(modbus_mapping.registers(40020..40022).unwrap(), [0xF147, 0x0020]);
+    /// assert_eq!(modbus_mapping.holding_f32(40020, ByteOrder::CDAB).unwrap(), 123456.0);
+    /// ```
+    pub fn set_holding_f32(&self, address: u16, value: f32, order: ByteOrder) -> Result<(), Error> {
+        let range = self.address_range(Table::HoldingRegisters, address, 2)?;
+        order.set_float(value, &mut self.get_registers_mut()[range]);
+        Ok(())
+    }
+
+    /// `registers` - get the holding registers at the addresses `range`
+    pub fn registers(&self, range: Range<u16>) -> Result<Vec<u16>, Error> {
+        let range = self.address_range(Table::HoldingRegisters, range.start, range.len())?;
+        Ok(self.get_registers()[range].to_vec())
+    }
+
+    /// `set_registers` - set the holding registers at the addresses `range`
+    ///
+    /// # Parameters
+    ///
+    /// * `range`   - addresses of the holding registers
+    /// * `values`  - one value per address
+    pub fn set_registers(&self, range: Range<u16>, values: &[u16]) -> Result<(), Error> {
+        let range = self.write_range(Table::HoldingRegisters, range, values.len())?;
+        self.get_registers_mut()[range].copy_from_slice(values);
+        Ok(())
+    }
+
+    /// `input_register` - get the input register at `address`
+    pub fn input_register(&self, address: u16) -> Result<u16, Error> {
+        let index = self.address_range(Table::InputRegisters, address, 1)?.start;
+        Ok(self.get_input_registers()[index])
+    }
+
+    /// `set_input_register` - set the input register at `address`
+    pub fn set_input_register(&self, address: u16, value: u16) -> Result<(), Error> {
+        let index = self.address_range(Table::InputRegisters, address, 1)?.start;
+        self.get_input_registers_mut()[index] = value;
+        Ok(())
+    }
+
+    /// `input_register_f32` - get a float from the input registers at `address` and `address + 1`
+    ///
+    /// # Parameters
+    ///
+    /// * `address` - address of the first register
+    /// * `order`   - byte order of the float
+    pub fn input_register_f32(&self, address: u16, order: ByteOrder) -> Result<f32, Error> {
+        let range = self.address_range(Table::InputRegisters, address, 2)?;
+        Ok(order.get_float(&self.get_input_registers()[range]))
+    }
+
+    /// `set_input_register_f32` - set a float in the input registers at `address` and `address + 1`
+    ///
+    /// # Parameters
+    ///
+    /// * `address` - address of the first register
+    /// * `value`   - float to set
+    /// * `order`   - byte order of the float
+    pub fn set_input_register_f32(
+        &self,
+        address: u16,
+        value: f32,
+        order: ByteOrder,
+    ) -> Result<(), Error> {
+        let range = self.address_range(Table::InputRegisters, address, 2)?;
+        order.set_float(value, &mut self.get_input_registers_mut()[range]);
+        Ok(())
+    }
+
+    /// `input_registers` - get the input registers at the addresses `range`
+    pub fn input_registers(&self, range: Range<u16>) -> Result<Vec<u16>, Error> {
+        let range = self.address_range(Table::InputRegisters, range.start, range.len())?;
+        Ok(self.get_input_registers()[range].to_vec())
+    }
+
+    /// `set_input_registers` - set the input registers at the addresses `range`
+    ///
+    /// # Parameters
+    ///
+    /// * `range`   - addresses of the input registers
+    /// * `values`  - one value per address
+    pub fn set_input_registers(&self, range: Range<u16>, values: &[u16]) -> Result<(), Error> {
+        let range = self.write_range(Table::InputRegisters, range, values.len())?;
+        self.get_input_registers_mut()[range].copy_from_slice(values);
+        Ok(())
+    }
+}
+
+impl ModbusMapping {
+    /// `add_observer` - call `observer` after every write that changed coils or holding registers
+    ///
+    /// The observer gets a [`WriteEvent`](struct.WriteEvent.html) with the table, the first address and the values
+    /// before and after the write. Writes are observed if they are answered by
+    /// [`reply()`](struct.Modbus.html#method.reply) or by this mapping as
+    /// [`RequestHandler`](trait.RequestHandler.html), not if the tables are modified directly, e.g. with
+    /// [`get_registers_mut()`](#method.get_registers_mut). Writes that leave all values as they were are not
+    /// reported.
+    ///
+    /// An observer may write to the mapping it observes, e.g. through a shared `Rc<ModbusMapping>`. The writes it
+    /// makes are not reported to the observers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libmodbus::{ModbusMapping, Table};
+    /// let mut modbus_mapping = ModbusMapping::new(5, 5, 5, 5).unwrap();
+    ///
+    /// modbus_mapping.add_observer(|event| {
+    ///     if event.table == Table::HoldingRegisters {
+    ///         println!("setpoint {} changed to {:?}", event.address, event.new);
+    ///     }
+    /// });
+    /// ```
+    pub fn add_observer<F: FnMut(&WriteEvent) + 'static>(&mut self, observer: F) {
+        self.observers.borrow_mut().push(Box::new(observer));
+    }
+
+    /// `subscribe` - receive a [`WriteEvent`](struct.WriteEvent.html) for every write that changed coils or
+    /// holding registers on a channel
+    ///
+    /// Same as [`add_observer()`](#method.add_observer), but the events are sent to the returned receiver, e.g.
+    /// to handle them in another thread. Events are dropped once the receiver is gone.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libmodbus::ModbusMapping;
+    /// let mut modbus_mapping = ModbusMapping::new(5, 5, 5, 5).unwrap();
+    ///
+    /// let events = modbus_mapping.subscribe();
+    /// assert!(events.try_recv().is_err());
+    /// ```
+    pub fn subscribe(&mut self) -> Receiver<WriteEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.add_observer(move |event| {
+            let _ = sender.send(event.clone());
+        });
+        receiver
+    }
+
+    /// `clear_observers` - remove all observers and subscriptions
+    pub fn clear_observers(&mut self) {
+        self.observers.borrow_mut().clear();
+    }
+
+    /// `set_access` - declare an address region read-only, write-only or unmapped
+    ///
+    /// Every address is readable and writable by default. Requests touching an address the access does not allow
+    /// are answered with `Exception::IllegalDataAddress`, without reading or writing any value. Regions may
+    /// overlap, the region declared last wins.
+    ///
+    /// The access applies to requests answered by [`reply()`](struct.Modbus.html#method.reply) and by this
+    /// mapping as [`RequestHandler`](trait.RequestHandler.html), not to the local accessors like
+    /// [`get_registers_mut()`](#method.get_registers_mut).
+    ///
+    /// # Parameters
+    ///
+    /// * `table`   - table of the region
+    /// * `address` - first address of the region
+    /// * `count`   - number of addresses of the region
+    /// * `access`  - access rights of the region
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libmodbus::{Access, ModbusMapping, Table};
+    /// let mut modbus_mapping = ModbusMapping::new(0, 0, 100, 0).unwrap();
+    ///
+    /// // measured values, clients must not overwrite them
+    /// modbus_mapping.set_access(Table::HoldingRegisters, 0, 10, Access::ReadOnly);
+    /// // reserved
+    /// modbus_mapping.set_access(Table::HoldingRegisters, 50, 50, Access::Unmapped);
+    /// ```
+    pub fn set_access(&mut self, table: Table, address: u16, count: u16, access: Access) {
+        let start = address as u32;
+        self.rules
+            .access
+            .push((table, start..start + count as u32, access));
+    }
+
+    /// `add_validator` - restrict the values clients may write to holding registers
+    ///
+    /// Writes of values the validator rejects are answered with `Exception::IllegalDataValue`, without writing any
+    /// value. A register covered by several validators only accepts values all of them accept. Mask writes are
+    /// checked with the value resulting from the masks.
+    ///
+    /// # Parameters
+    ///
+    /// * `address`     - first holding register the validator applies to
+    /// * `count`       - number of holding registers the validator applies to
+    /// * `validator`   - valid values
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libmodbus::{ModbusMapping, Validator};
+    /// let mut modbus_mapping = ModbusMapping::new(0, 0, 100, 0).unwrap();
+    ///
+    /// // setpoint in 0.1°C
+    /// modbus_mapping.add_validator(10, 1, Validator::SignedRange { min: -200, max: 800 });
+    /// // operating mode: off, manual or automatic
+    /// modbus_mapping.add_validator(11, 1, Validator::OneOf(vec![0, 1, 2]));
+    /// ```
+    pub fn add_validator(&mut self, address: u16, count: u16, validator: Validator) {
+        let start = address as u32;
+        self.rules
+            .validators
+            .push((start..start + count as u32, validator));
+    }
+
+    /// `clear_rules` - remove all access regions and validators
+    pub fn clear_rules(&mut self) {
+        self.rules = Rules::default();
+    }
+
+    /// `snapshot` - copy the contents of the four tables, with their start addresses
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libmodbus::ModbusMapping;
+    /// let modbus_mapping = ModbusMapping::new_start_address(0, 0, 0, 0, 100, 2, 0, 0).unwrap();
+    /// modbus_mapping.get_registers_mut().copy_from_slice(&[1, 2]);
+    ///
+    /// let snapshot = modbus_mapping.snapshot();
+    /// assert_eq!((snapshot.start_registers, snapshot.registers), (100, vec![1, 2]));
+    /// ```
+    pub fn snapshot(&self) -> Snapshot {
+        let mapping = unsafe { &*self.modbus_mapping };
+        Snapshot {
+            start_bits: mapping.start_bits as u16,
+            bits: self.get_bits().to_vec(),
+            start_input_bits: mapping.start_input_bits as u16,
+            input_bits: self.get_input_bits().to_vec(),
+            start_registers: mapping.start_registers as u16,
+            registers: self.get_registers().to_vec(),
+            start_input_registers: mapping.start_input_registers as u16,
+            input_registers: self.get_input_registers().to_vec(),
+        }
+    }
+
+    /// `restore` - load the contents of a snapshot back into the tables
+    ///
+    /// Values are restored by address: values of the snapshot at addresses outside of the mapping are skipped,
+    /// addresses of the mapping not in the snapshot keep their values. So a snapshot taken before the mapping grew
+    /// or moved still restores the values it has. Neither access rules nor validators apply and observers are not
+    /// notified.
+    ///
+    /// # Return value
+    ///
+    /// The number of values restored.
+    ///
+    /// # Parameters
+    ///
+    /// * `snapshot`    - snapshot, e.g. taken with [`snapshot()`](#method.snapshot) before a restart
+    pub fn restore(&self, snapshot: &Snapshot) -> usize {
+        let bits = |bits: &[u8]| bits.iter().map(|&bit| bit as u16).collect::<Vec<_>>();
+        let tables = [
+            (Table::Coils, snapshot.start_bits, bits(&snapshot.bits)),
+            (
+                Table::DiscreteInputs,
+                snapshot.start_input_bits,
+                bits(&snapshot.input_bits),
+            ),
+            (
+                Table::HoldingRegisters,
+                snapshot.start_registers,
+                snapshot.registers.clone(),
+            ),
+            (
+                Table::InputRegisters,
+                snapshot.start_input_registers,
+                snapshot.input_registers.clone(),
+            ),
+        ];
+        let mut restored = 0;
+        for (table, start, values) in tables.iter() {
+            for (address, &value) in (*start as usize..0x1_0000).zip(values) {
+                if self.set_value(*table, address as u16, value).is_ok() {
+                    restored += 1;
+                }
+            }
+        }
+        restored
+    }
+
+    /// `save` - write a snapshot of the tables to a file, in the binary format of
+    /// [`Snapshot::to_bytes()`](struct.Snapshot.html#method.to_bytes)
+    ///
+    /// The snapshot is written to a temporary file next to `path` first and renamed to `path` then, so a crash
+    /// while saving leaves the previous file intact.
+    ///
+    /// # Return value
+    ///
+    /// The function returns an Error if writing the file failed.
+    ///
+    /// # Parameters
+    ///
+    /// * `path`    - file to write
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        write_snapshot(path.as_ref(), &self.snapshot().to_bytes())
+    }
+
+    /// `load` - restore the tables from a file written by [`save()`](#method.save), see
+    /// [`restore()`](#method.restore)
+    ///
+    /// # Return value
+    ///
+    /// The number of values restored, or an Error if the file can't be read or is not a snapshot.
+    ///
+    /// # Parameters
+    ///
+    /// * `path`    - file to read
+    pub fn load<P: AsRef<Path>>(&self, path: P) -> Result<usize, Error> {
+        let bytes = fs::read(path.as_ref()).map_err(|source| Error::Mapping {
+            msg: format!("could not read {}", path.as_ref().display()),
+            source,
+        })?;
+        Ok(self.restore(&Snapshot::from_bytes(&bytes)?))
+    }
+
+    /// `set_autosave` - save the tables to a file periodically while serving
+    ///
+    /// The server loops [`serve()`](fn.serve.html) and [`Simulator::serve()`](struct.Simulator.html#method.serve)
+    /// [`save()`](#method.save) the mapping to `path` every `interval`, if it changed since it was saved last, and
+    /// when they stop. Load the file with [`load()`](#method.load) before serving, so e.g. retained holding
+    /// registers survive restarts.
+    ///
+    /// # Parameters
+    ///
+    /// * `path`        - file to save to
+    /// * `interval`    - time between two saves
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libmodbus::ModbusMapping;
+    /// use std::time::Duration;
+    ///
+    /// let mut modbus_mapping = ModbusMapping::new(0, 0, 100, 0).unwrap();
+    /// // the file doesn't exist on the first start
+    /// let _ = modbus_mapping.load("retain.bin");
+    /// modbus_mapping.set_autosave("retain.bin", Duration::from_secs(10));
+    /// ```
+    pub fn set_autosave<P: Into<PathBuf>>(&mut self, path: P, interval: Duration) {
+        *self.autosave.get_mut() = Some(Autosave {
+            path: path.into(),
+            interval,
+            last: Instant::now(),
+            saved: None,
+        });
+    }
+
+    /// `clear_autosave` - stop saving the tables periodically
+    pub fn clear_autosave(&mut self) {
+        *self.autosave.get_mut() = None;
+    }
+
+    /// Save the tables if autosave is set and the interval elapsed, or right away if `force` is set
+    pub(crate) fn autosave(&self, force: bool) -> Result<(), Error> {
+        let mut autosave = self.autosave.borrow_mut();
+        let autosave = match autosave.as_mut() {
+            Some(autosave) if force || autosave.last.elapsed() >= autosave.interval => autosave,
+            _ => return Ok(()),
+        };
+        autosave.last = Instant::now();
+        let bytes = self.snapshot().to_bytes();
+        if autosave.saved.as_ref() != Some(&bytes) {
+            write_snapshot(&autosave.path, &bytes)?;
+            autosave.saved = Some(bytes);
+        }
+        Ok(())
+    }
+
+    /// Reject reads of addresses that are not readable
+    fn check_read(&self, table: Table, address: u16, quantity: usize) -> Result<(), Exception> {
+        let start = address as u32;
+        if (start..start + quantity as u32)
+            .all(|address| self.rules.access(table, address).readable())
+        {
+            Ok(())
+        } else {
+            Err(Exception::IllegalDataAddress)
+        }
+    }
+
+    /// Reject writes to addresses that are not writable and of values a validator rejects
+    fn check_write(&self, table: Table, address: u16, values: &[u16]) -> Result<(), Exception> {
+        let start = address as u32;
+        let addresses = start..start + values.len() as u32;
+        if !addresses
+            .clone()
+            .all(|address| self.rules.access(table, address).writable())
+        {
+            return Err(Exception::IllegalDataAddress);
+        }
+        if table == Table::HoldingRegisters {
+            let valid = addresses.zip(values).all(|(address, &value)| {
+                self.rules
+                    .validators
+                    .iter()
+                    .filter(|(range, _)| range.contains(&address))
+                    .all(|(_, validator)| validator.is_valid(value))
+            });
+            if !valid {
+                return Err(Exception::IllegalDataValue);
+            }
+        }
+        Ok(())
+    }
+
+    /// Check a request received by `modbus` against the access regions and validators
+    ///
+    /// Requests libmodbus rejects anyway, e.g. malformed ones or outside of the tables, pass so libmodbus answers
+    /// them as usual.
+    pub(crate) fn check_request(
+        &self,
+        modbus: &Modbus,
+        request: &[u8],
+        request_len: i32,
+    ) -> Result<(), Exception> {
+        if self.rules.is_empty() {
+            return Ok(());
+        }
+        let request = match modbus
+            .split_request(request, request_len)
+            .ok()
+            .and_then(|(unit, pdu)| Request::decode(unit, pdu).ok())
+        {
+            Some(request) => request,
+            None => return Ok(()),
+        };
+        let bits = |values: &[bool]| {
+            values
+                .iter()
+                .map(|&value| value as u16)
+                .collect::<Vec<u16>>()
+        };
+
+        match request {
+            Request::ReadCoils {
+                address, quantity, ..
+            } => self.check_read(Table::Coils, address, quantity as usize),
+            Request::ReadDiscreteInputs {
+                address, quantity, ..
+            } => self.check_read(Table::DiscreteInputs, address, quantity as usize),
+            Request::ReadHoldingRegisters {
+                address, quantity, ..
+            } => self.check_read(Table::HoldingRegisters, address, quantity as usize),
+            Request::ReadInputRegisters {
+                address, quantity, ..
+            } => self.check_read(Table::InputRegisters, address, quantity as usize),
+            Request::WriteSingleCoil { address, value, .. } => {
+                self.check_write(Table::Coils, address, &bits(&[value]))
+            }
+            Request::WriteMultipleCoils {
+                address, values, ..
+            } => self.check_write(Table::Coils, address, &bits(&values)),
+            Request::WriteSingleRegister { address, value, .. } => {
+                self.check_write(Table::HoldingRegisters, address, &[value])
+            }
+            Request::WriteMultipleRegisters {
+                address, values, ..
+            } => self.check_write(Table::HoldingRegisters, address, &values),
+            Request::MaskWriteRegister {
+                address,
+                and_mask,
+                or_mask,
+                ..
+            } => match self.table_range(Table::HoldingRegisters, address, 1) {
+                Ok(range) => {
+                    let current = self.get_registers()[range.start];
+                    let value = (current & and_mask) | (or_mask & !and_mask);
+                    self.check_write(Table::HoldingRegisters, address, &[value])
+                }
+                Err(_) => Ok(()),
+            },
+            Request::WriteAndReadRegisters {
+                write_address,
+                values,
+                read_address,
+                read_quantity,
+                ..
+            } => {
+                self.check_write(Table::HoldingRegisters, write_address, &values)?;
+                self.check_read(
+                    Table::HoldingRegisters,
+                    read_address,
+                    read_quantity as usize,
+                )
+            }
+            Request::Custom { .. } => Ok(()),
+        }
+    }
+
+    /// Slice range of `quantity` values from `address` in `table`
+    ///
+    /// Requests outside of the table are rejected with `Exception::IllegalDataAddress`, like `modbus_reply()` does.
+    fn table_range(
+        &self,
+        table: Table,
+        address: u16,
+        quantity: usize,
+    ) -> Result<Range<usize>, Exception> {
+        let mapping = unsafe { &*self.modbus_mapping };
+        let (start, nb) = match table {
+            Table::Coils => (mapping.start_bits, mapping.nb_bits),
+            Table::DiscreteInputs => (mapping.start_input_bits, mapping.nb_input_bits),
+            Table::HoldingRegisters => (mapping.start_registers, mapping.nb_registers),
+            Table::InputRegisters => (mapping.start_input_registers, mapping.nb_input_registers),
+        };
+        let offset = address as c_int - start;
+        if offset < 0 || offset as usize + quantity > nb as usize {
+            Err(Exception::IllegalDataAddress)
+        } else {
+            Ok(offset as usize..offset as usize + quantity)
+        }
+    }
+
+    /// Values of a table range, bits as 0 or 1
+    fn table_values(&self, table: Table, range: Range<usize>) -> Vec<u16> {
+        match table {
+            Table::Coils => self.get_bits()[range]
+                .iter()
+                .map(|&bit| bit as u16)
+                .collect(),
+            Table::DiscreteInputs => self.get_input_bits()[range]
+                .iter()
+                .map(|&bit| bit as u16)
+                .collect(),
+            Table::HoldingRegisters => self.get_registers()[range].to_vec(),
+            Table::InputRegisters => self.get_input_registers()[range].to_vec(),
+        }
+    }
+
+    /// Value at `address` in `table`, bits as 0 or 1
+    pub(crate) fn get_value(&self, table: Table, address: u16) -> Result<u16, Exception> {
+        let range = self.table_range(table, address, 1)?;
+        Ok(self.table_values(table, range)[0])
+    }
+
+    /// Set the value at `address` in `table`, bits are set for every value other than 0
+    ///
+    /// Neither access rules nor validators apply and observers are not notified, it's the server setting the value.
+    pub(crate) fn set_value(
+        &self,
+        table: Table,
+        address: u16,
+        value: u16,
+    ) -> Result<(), Exception> {
+        let index = self.table_range(table, address, 1)?.start;
+        match table {
+            Table::Coils => self.get_bits_mut()[index] = (value != 0) as u8,
+            Table::DiscreteInputs => self.get_input_bits_mut()[index] = (value != 0) as u8,
+            Table::HoldingRegisters => self.get_registers_mut()[index] = value,
+            Table::InputRegisters => self.get_input_registers_mut()[index] = value,
+        }
+        Ok(())
+    }
+
+    /// Call the observers if a write changed values
+    ///
+    /// The observers are taken out of the mapping while they run, so an observer writing to the mapping doesn't
+    /// find it borrowed. Such nested writes are not observed.
+    fn notify(&self, table: Table, address: u16, old: Vec<u16>, new: Vec<u16>) {
+        if old != new {
+            let event = WriteEvent {
+                table,
+                address,
+                old,
+                new,
+            };
+            let mut observers = self.observers.take();
+            for observer in observers.iter_mut() {
+                observer(&event);
+            }
+            let mut current = self.observers.borrow_mut();
+            observers.append(&mut current);
+            *current = observers;
+        }
+    }
+
+    /// Remember the values a request received by `modbus` is about to overwrite, if anybody observes them
+    pub(crate) fn prepare_write(
+        &self,
+        modbus: &Modbus,
+        request: &[u8],
+        request_len: i32,
+    ) -> Option<PendingWrite> {
+        if self.observers.borrow().is_empty() {
+            return None;
+        }
+        let (unit, pdu) = modbus.split_request(request, request_len).ok()?;
+        let (table, address, quantity) = match Request::decode(unit, pdu).ok()? {
+            Request::WriteSingleCoil { address, .. } => (Table::Coils, address, 1),
+            Request::WriteMultipleCoils {
+                address, values, ..
+            } => (Table::Coils, address, values.len()),
+            Request::WriteSingleRegister { address, .. }
+            | Request::MaskWriteRegister { address, .. } => (Table::HoldingRegisters, address, 1),
+            Request::WriteMultipleRegisters {
+                address, values, ..
+            } => (Table::HoldingRegisters, address, values.len()),
+            Request::WriteAndReadRegisters {
+                write_address,
+                values,
+                ..
+            } => (Table::HoldingRegisters, write_address, values.len()),
+            _ => return None,
+        };
+        let range = self.table_range(table, address, quantity).ok()?;
+        Some(PendingWrite {
+            table,
+            address,
+            old: self.table_values(table, range.clone()),
+            range,
+        })
+    }
+
+    /// Report a write prepared with `prepare_write()` once libmodbus performed it
+    pub(crate) fn notify_write(&self, pending: PendingWrite) {
+        let new = self.table_values(pending.table, pending.range);
+        self.notify(pending.table, pending.address, pending.old, new);
+    }
+}
+
+/// A `ModbusMapping` answers requests from its four tables, exactly like [`reply()`](struct.Modbus.html#method.reply)
+/// does, so it can be used wherever a [`RequestHandler`](trait.RequestHandler.html) is expected.
+impl RequestHandler for ModbusMapping {
+    fn read_coils(&mut self, address: u16, quantity: u16) -> Result<Vec<bool>, Exception> {
+        (&*self).read_coils(address, quantity)
+    }
+
+    fn read_discrete_inputs(
+        &mut self,
+        address: u16,
+        quantity: u16,
+    ) -> Result<Vec<bool>, Exception> {
+        (&*self).read_discrete_inputs(address, quantity)
+    }
+
+    fn read_holding_registers(
+        &mut self,
+        address: u16,
+        quantity: u16,
+    ) -> Result<Vec<u16>, Exception> {
+        (&*self).read_holding_registers(address, quantity)
+    }
+
+    fn read_input_registers(&mut self, address: u16, quantity: u16) -> Result<Vec<u16>, Exception> {
+        (&*self).read_input_registers(address, quantity)
+    }
+
+    fn write_multiple_coils(&mut self, address: u16, values: &[bool]) -> Result<(), Exception> {
+        (&*self).write_multiple_coils(address, values)
+    }
+
+    fn write_multiple_registers(&mut self, address: u16, values: &[u16]) -> Result<(), Exception> {
+        (&*self).write_multiple_registers(address, values)
+    }
+
+    fn mask_write_register(
+        &mut self,
+        address: u16,
+        and_mask: u16,
+        or_mask: u16,
+    ) -> Result<(), Exception> {
+        (&*self).mask_write_register(address, and_mask, or_mask)
+    }
+}
+
+/// The tables of a mapping are modified through a shared reference, so a shared mapping answers requests as well,
+/// e.g. in [`reply()`](trait.ModbusServer.html#tymethod.reply) of the backends implemented in Rust.
+impl RequestHandler for &ModbusMapping {
+    fn read_coils(&mut self, address: u16, quantity: u16) -> Result<Vec<bool>, Exception> {
+        let range = self.table_range(Table::Coils, address, quantity as usize)?;
+        self.check_read(Table::Coils, address, quantity as usize)?;
+        Ok(self.get_bits()[range].iter().map(|&bit| bit != 0).collect())
+    }
+
+    fn read_discrete_inputs(
+        &mut self,
+        address: u16,
+        quantity: u16,
+    ) -> Result<Vec<bool>, Exception> {
+        let range = self.table_range(Table::DiscreteInputs, address, quantity as usize)?;
+        self.check_read(Table::DiscreteInputs, address, quantity as usize)?;
+        Ok(self.get_input_bits()[range]
+            .iter()
+            .map(|&bit| bit != 0)
+            .collect())
+    }
+
+    fn read_holding_registers(
+        &mut self,
+        address: u16,
+        quantity: u16,
+    ) -> Result<Vec<u16>, Exception> {
+        let range = self.table_range(Table::HoldingRegisters, address, quantity as usize)?;
+        self.check_read(Table::HoldingRegisters, address, quantity as usize)?;
+        Ok(self.get_registers()[range].to_vec())
+    }
+
+    fn read_input_registers(&mut self, address: u16, quantity: u16) -> Result<Vec<u16>, Exception> {
+        let range = self.table_range(Table::InputRegisters, address, quantity as usize)?;
+        self.check_read(Table::InputRegisters, address, quantity as usize)?;
+        Ok(self.get_input_registers()[range].to_vec())
+    }
+
+    fn write_multiple_coils(&mut self, address: u16, values: &[bool]) -> Result<(), Exception> {
+        let range = self.table_range(Table::Coils, address, values.len())?;
+        let new: Vec<u16> = values.iter().map(|&value| value as u16).collect();
+        self.check_write(Table::Coils, address, &new)?;
+        let old = self.table_values(Table::Coils, range.clone());
+        for (bit, &value) in self.get_bits_mut()[range].iter_mut().zip(values) {
+            *bit = value as u8;
+        }
+        self.notify(Table::Coils, address, old, new);
+        Ok(())
+    }
+
+    fn write_multiple_registers(&mut self, address: u16, values: &[u16]) -> Result<(), Exception> {
+        let range = self.table_range(Table::HoldingRegisters, address, values.len())?;
+        self.check_write(Table::HoldingRegisters, address, values)?;
+        let old = self.table_values(Table::HoldingRegisters, range.clone());
+        self.get_registers_mut()[range].copy_from_slice(values);
+        self.notify(Table::HoldingRegisters, address, old, values.to_vec());
+        Ok(())
+    }
+
+    fn mask_write_register(
+        &mut self,
+        address: u16,
+        and_mask: u16,
+        or_mask: u16,
+    ) -> Result<(), Exception> {
+        // no read access needed to mask a register
+        let range = self.table_range(Table::HoldingRegisters, address, 1)?;
+        let current = self.get_registers()[range.start];
+        self.write_multiple_registers(address, &[(current & and_mask) | (or_mask & !and_mask)])
+    }
+}
+
+impl fmt::Debug for ModbusMapping {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ModbusMapping")
+            .field("modbus_mapping", &self.modbus_mapping)
+            .field("observers", &self.observers.borrow().len())
+            .field(
+                "autosave",
+                &self
+                    .autosave
+                    .borrow()
+                    .as_ref()
+                    .map(|autosave| &autosave.path),
+            )
+            .finish()
+    }
+}
+
 impl Drop for ModbusMapping {
     fn drop(&mut self) {
         self.free()
@@ -0,0 +1,424 @@
+use crate::modbus_frame::{rtu_crc_valid, rtu_frame_length, Framing};
+use crate::modbus_transport::{SerialPort, Transport};
+use crate::prelude::*;
+use crate::{Exception, Request, SerialConfig};
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use std::time::{Duration, Instant, SystemTime};
+
+/// A response seen on the line, decoded with the request it answers
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MonitorResponse {
+    /// The coils or discrete inputs read, as many as requested
+    Bits(Vec<bool>),
+    /// The registers read, also by Read/Write Multiple Registers
+    Registers(Vec<u16>),
+    /// The confirmation of a write
+    Written,
+    /// An exception response
+    Exception(Exception),
+    /// The response of a function without a variant of its own, the data following the function code
+    Custom(Vec<u8>),
+}
+
+impl MonitorResponse {
+    /// Decode the response PDU `pdu` to `request`, `None` if it doesn't answer it
+    fn decode(request: &Request, pdu: &[u8]) -> Option<MonitorResponse> {
+        let (&function, data) = pdu.split_first()?;
+        if function == request.function() | 0x80 {
+            return match *data {
+                [code] => Exception::from_code(code).map(MonitorResponse::Exception),
+                _ => None,
+            };
+        }
+        if function != request.function() {
+            return None;
+        }
+        let counted = |expected: usize| match data {
+            [count, values @ ..] if *count as usize == expected && values.len() == expected => {
+                Some(values)
+            }
+            _ => None,
+        };
+        match *request {
+            Request::ReadCoils { quantity, .. } | Request::ReadDiscreteInputs { quantity, .. } => {
+                let bits = counted((quantity as usize).div_ceil(8))?;
+                Some(MonitorResponse::Bits(
+                    (0..quantity as usize)
+                        .map(|bit| (bits[bit / 8] >> (bit % 8)) & 1 != 0)
+                        .collect(),
+                ))
+            }
+            Request::ReadHoldingRegisters { quantity, .. }
+            | Request::ReadInputRegisters { quantity, .. }
+            | Request::WriteAndReadRegisters {
+                read_quantity: quantity,
+                ..
+            } => {
+                let registers = counted(2 * quantity as usize)?;
+                Some(MonitorResponse::Registers(
+                    registers
+                        .chunks(2)
+                        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+                        .collect(),
+                ))
+            }
+            Request::WriteSingleCoil { .. }
+            | Request::WriteSingleRegister { .. }
+            | Request::WriteMultipleCoils { .. }
+            | Request::WriteMultipleRegisters { .. } => {
+                (data.len() == 4).then_some(MonitorResponse::Written)
+            }
+            Request::MaskWriteRegister { .. } => {
+                (data.len() == 6).then_some(MonitorResponse::Written)
+            }
+            Request::Custom { .. } => Some(MonitorResponse::Custom(data.to_vec())),
+        }
+    }
+}
+
+/// A request seen on the line and the response to it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transaction {
+    /// The request, with the slave it was sent to
+    pub request: Request,
+    /// The response, `None` for broadcasts and for requests not answered within the response timeout
+    pub response: Option<MonitorResponse>,
+    /// When the request was seen
+    pub time: SystemTime,
+}
+
+impl Transaction {
+    /// `slave` - the slave the request was sent to, 0 for a broadcast
+    pub fn slave(&self) -> u8 {
+        self.request.unit()
+    }
+
+    /// `function` - the function code of the request
+    pub fn function(&self) -> u8 {
+        self.request.function()
+    }
+}
+
+/// What an [`RtuMonitor`](struct.RtuMonitor.html) saw on the line
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MonitorEvent {
+    /// A request and the response to it
+    Transaction(Transaction),
+    /// A frame with a wrong CRC, or bytes too short to be a frame
+    CrcError(Vec<u8>),
+    /// A frame with a valid CRC which is neither a valid request nor the response to the last request
+    Unexpected(Vec<u8>),
+}
+
+/// A request waiting for its response
+#[derive(Debug)]
+struct Pending {
+    request: Request,
+    time: SystemTime,
+    deadline: Instant,
+}
+
+/// Passive monitor of the traffic on a Modbus RTU line
+///
+/// The monitor only listens, it never sends anything, so it can watch a shared RS485 line between other masters
+/// and slaves. The bytes received are split into frames by the silence between them and by their CRC, two frames
+/// sent without a silence in between are split by the length their function code gives. Each request is matched
+/// with the response of the same slave following it.
+///
+/// The monitor is an iterator of the [events](enum.MonitorEvent.html) on the line: transactions with the decoded
+/// request and response, and frames with a wrong CRC. It waits for the next event, the iteration ends when the
+/// receiving fails, after the error.
+///
+/// Only available on unix platforms.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use libmodbus::{MonitorEvent, RtuMonitor};
+///
+/// let monitor = RtuMonitor::open("/dev/ttyUSB0", 19200, 'E', 8, 1).unwrap();
+/// for event in monitor {
+///     match event.unwrap() {
+///         MonitorEvent::Transaction(transaction) => {
+///             println!("{:?} -> {:?}", transaction.request, transaction.response)
+///         }
+///         event => println!("{:?}", event),
+///     }
+/// }
+/// ```
+pub struct RtuMonitor {
+    transport: Box<dyn Transport>,
+    config: SerialConfig,
+    /// Silence ending a frame
+    frame_silence: Duration,
+    response_timeout: Duration,
+    /// Bytes received since the last silence
+    burst: Vec<u8>,
+    pending: Option<Pending>,
+    events: VecDeque<MonitorEvent>,
+    /// Receiving failed, the iteration ends after the error
+    failed: bool,
+    error: Option<io::Error>,
+}
+
+impl RtuMonitor {
+    /// `open` - open a serial line to monitor
+    ///
+    /// The [`open()`](#method.open) function opens the serial device with the same parameters as
+    /// [`new_rtu()`](struct.Modbus.html#method.new_rtu), they must be the ones of the line.
+    ///
+    /// # Return value
+    ///
+    /// The function returns a Result containing the monitor if successful. Otherwise it contains an Error if one of
+    /// the parameters is invalid or the device can't be opened.
+    ///
+    /// # Parameters
+    ///
+    /// * `device`      - name of the serial port, e.g. `/dev/ttyS0` or `/dev/ttyUSB0`
+    /// * `baud`        - baud rate of the communication, e.g. 9600
+    /// * `parity`      - `'N'` for none, `'E'` for even or `'O'` for odd
+    /// * `data_bit`    - number of data bits, 5, 6, 7 or 8
+    /// * `stop_bit`    - number of stop bits, 1 or 2
+    pub fn open(
+        device: &str,
+        baud: i32,
+        parity: char,
+        data_bit: i32,
+        stop_bit: i32,
+    ) -> Result<RtuMonitor, Error> {
+        let config = SerialConfig::from_parts(baud, parity, data_bit, stop_bit)?;
+        RtuMonitor::open_config(device, config)
+    }
+
+    /// `open_config` - open a serial line to monitor, with typed parameters
+    ///
+    /// # Return value
+    ///
+    /// The function returns a Result containing the monitor if successful. Otherwise it contains an Error if the
+    /// device can't be opened.
+    ///
+    /// # Parameters
+    ///
+    /// * `device`  - name of the serial port, e.g. `/dev/ttyS0` or `/dev/ttyUSB0`
+    /// * `config`  - parameters of the serial line
+    pub fn open_config(device: &str, config: SerialConfig) -> Result<RtuMonitor, Error> {
+        let port = SerialPort::open(device, &config).map_err(|source| Error::Rtu {
+            msg: "open".to_owned(),
+            source,
+        })?;
+        Ok(RtuMonitor::with_transport(Box::new(port), config))
+    }
+
+    /// Monitor on an open transport, e.g. the master end of a pseudo-terminal
+    pub(crate) fn with_transport(
+        transport: Box<dyn Transport>,
+        config: SerialConfig,
+    ) -> RtuMonitor {
+        RtuMonitor {
+            transport,
+            config,
+            frame_silence: config.inter_frame_delay(),
+            response_timeout: Duration::from_millis(500),
+            burst: Vec::new(),
+            pending: None,
+            events: VecDeque::new(),
+            failed: false,
+            error: None,
+        }
+    }
+
+    /// `serial_config` - the parameters of the monitored line
+    pub fn serial_config(&self) -> SerialConfig {
+        self.config
+    }
+
+    /// `get_frame_silence` - get the silence ending a frame
+    pub fn get_frame_silence(&self) -> Duration {
+        self.frame_silence
+    }
+
+    /// `set_frame_silence` - set the silence ending a frame
+    ///
+    /// 3.5 characters by default, see
+    /// [`SerialConfig::inter_frame_delay()`](struct.SerialConfig.html#method.inter_frame_delay). USB serial adapters
+    /// deliver the bytes in chunks, e.g. every 16 ms, the silence must be longer with them or frames are cut apart.
+    pub fn set_frame_silence(&mut self, silence: Duration) {
+        self.frame_silence = silence;
+    }
+
+    /// `get_response_timeout` - get how long a request waits for its response
+    pub fn get_response_timeout(&self) -> Duration {
+        self.response_timeout
+    }
+
+    /// `set_response_timeout` - set how long a request waits for its response
+    ///
+    /// A request without a response within the timeout, 500 ms by default, is reported without one. A request
+    /// seen before the timeout ends the wait as well.
+    pub fn set_response_timeout(&mut self, timeout: Duration) {
+        self.response_timeout = timeout;
+    }
+
+    /// Wait for bytes on the line, the frames of the burst are split off when a silence ends it
+    fn receive(&mut self) -> io::Result<()> {
+        let timeout = if !self.burst.is_empty() {
+            Some(self.frame_silence)
+        } else {
+            self.pending
+                .as_ref()
+                .map(|pending| pending.deadline.saturating_duration_since(Instant::now()))
+        };
+        let mut chunk = [0u8; 512];
+        match self.transport.receive(&mut chunk, timeout) {
+            Ok(0) => {
+                self.split_burst();
+                self.flush_pending();
+                Err(io::Error::from(io::ErrorKind::UnexpectedEof))
+            }
+            Ok(len) => {
+                self.burst.extend_from_slice(&chunk[..len]);
+                // a line never silent, split what arrived so far
+                if self.burst.len() >= 2 * Modbus::RTU_MAX_ADU_LENGTH {
+                    self.split_burst();
+                }
+                Ok(())
+            }
+            Err(err) if err.raw_os_error() == Some(libc::ETIMEDOUT) => {
+                if self.burst.is_empty() {
+                    self.flush_pending();
+                } else {
+                    self.split_burst();
+                }
+                Ok(())
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Split the bytes received since the last silence into frames
+    fn split_burst(&mut self) {
+        let burst = std::mem::take(&mut self.burst);
+        let mut rest = &burst[..];
+        while !rest.is_empty() {
+            let (frame, tail) = rest.split_at(self.frame_length(rest));
+            self.frame(frame);
+            rest = tail;
+        }
+    }
+
+    /// Length of the frame at the start of `buf`: the length its function code gives, if the CRC of that many bytes
+    /// is valid, or else all of `buf`
+    ///
+    /// A response is tried first while a request waits for one, a request otherwise.
+    fn frame_length(&self, buf: &[u8]) -> usize {
+        let response_first = self.pending.is_some();
+        [!response_first, response_first]
+            .iter()
+            .filter_map(|&indication| rtu_frame_length(buf, indication))
+            .find(|&len| len <= buf.len() && rtu_crc_valid(&buf[..len]))
+            .unwrap_or(buf.len())
+    }
+
+    /// Match a frame with the waiting request, or decode it as a request
+    fn frame(&mut self, frame: &[u8]) {
+        if !rtu_crc_valid(frame) {
+            self.flush_pending();
+            self.events
+                .push_back(MonitorEvent::CrcError(frame.to_vec()));
+            return;
+        }
+        let adu = Framing::Rtu
+            .split(frame)
+            .expect("frame of 4 bytes at least");
+        if let Some(pending) = self.pending.take() {
+            if adu.unit == pending.request.unit() {
+                if let Some(response) = MonitorResponse::decode(&pending.request, &adu.pdu) {
+                    self.events
+                        .push_back(MonitorEvent::Transaction(Transaction {
+                            request: pending.request,
+                            response: Some(response),
+                            time: pending.time,
+                        }));
+                    return;
+                }
+            }
+            self.pending = Some(pending);
+            self.flush_pending();
+        }
+        match Request::decode(adu.unit, &adu.pdu) {
+            // broadcasts are never answered
+            Ok(request) if adu.unit == Modbus::BROADCAST_ADDRESS => {
+                self.events
+                    .push_back(MonitorEvent::Transaction(Transaction {
+                        request,
+                        response: None,
+                        time: SystemTime::now(),
+                    }))
+            }
+            Ok(request) => {
+                self.pending = Some(Pending {
+                    request,
+                    time: SystemTime::now(),
+                    deadline: Instant::now() + self.response_timeout,
+                })
+            }
+            Err(_) => self
+                .events
+                .push_back(MonitorEvent::Unexpected(frame.to_vec())),
+        }
+    }
+
+    /// Report the waiting request without a response
+    fn flush_pending(&mut self) {
+        if let Some(pending) = self.pending.take() {
+            self.events
+                .push_back(MonitorEvent::Transaction(Transaction {
+                    request: pending.request,
+                    response: None,
+                    time: pending.time,
+                }));
+        }
+    }
+}
+
+impl Iterator for RtuMonitor {
+    type Item = Result<MonitorEvent, Error>;
+
+    /// Wait for the next event on the line
+    fn next(&mut self) -> Option<Result<MonitorEvent, Error>> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Some(Ok(event));
+            }
+            if let Some(source) = self.error.take() {
+                return Some(Err(Error::Rtu {
+                    msg: "monitor".to_owned(),
+                    source,
+                }));
+            }
+            if self.failed {
+                return None;
+            }
+            // the events before the error are reported first
+            if let Err(err) = self.receive() {
+                self.failed = true;
+                self.error = Some(err);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for RtuMonitor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RtuMonitor")
+            .field("config", &self.config)
+            .field("frame_silence", &self.frame_silence)
+            .field("response_timeout", &self.response_timeout)
+            .field("pending", &self.pending)
+            .finish()
+    }
+}
@@ -0,0 +1,201 @@
+use crate::modbus_frame::Framing;
+use crate::modbus_transport::SerialPort;
+use crate::prelude::*;
+use crate::{ModbusRTU, ModbusStream, RtuMonitor, SerialConfig};
+use libc::c_int;
+use std::ffi::CStr;
+use std::io;
+
+/// Pseudo-terminal to serve RTU or ASCII without a serial port
+///
+/// A pseudo-terminal is a pair of connected character devices: the master end, held by the `PseudoTerminal`,
+/// and the slave end at [`path()`](#method.path), e.g. `/dev/pts/3`. A server answering on the master end, see
+/// [`into_modbus()`](#method.into_modbus), [`into_rtu()`](#method.into_rtu) or
+/// [`into_ascii()`](#method.into_ascii), is reached by clients opening the slave end like a serial port.
+/// Baud rate, parity and the like don't matter on a pseudo-terminal.
+///
+/// Only available on unix platforms.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use libmodbus::{serve, ModbusMapping, PseudoTerminal, StopToken};
+///
+/// let pty = PseudoTerminal::open().unwrap();
+/// println!("RTU clients connect to {}", pty.path());
+///
+/// let mut modbus = pty.into_modbus(1).unwrap();
+/// let mapping = ModbusMapping::new(500, 500, 500, 500).unwrap();
+/// serve(&mut modbus, &mapping, &StopToken::new()).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct PseudoTerminal {
+    master: c_int,
+    path: String,
+}
+
+/// Path of the slave end of the pseudo-terminal `master`
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn slave_path(master: c_int) -> io::Result<String> {
+    let mut name = [0 as libc::c_char; 128];
+    match unsafe { libc::ptsname_r(master, name.as_mut_ptr(), name.len()) } {
+        0 => Ok(unsafe { CStr::from_ptr(name.as_ptr()) }
+            .to_string_lossy()
+            .into_owned()),
+        errno => Err(io::Error::from_raw_os_error(errno)),
+    }
+}
+
+/// Path of the slave end of the pseudo-terminal `master`
+///
+/// Without `ptsname_r()` the static buffer of `ptsname()` is guarded by a lock, so pseudo-terminals can be opened by
+/// several threads at once.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn slave_path(master: c_int) -> io::Result<String> {
+    static PTSNAME: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    let _guard = PTSNAME
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let name = unsafe { libc::ptsname(master) };
+    if name.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { CStr::from_ptr(name) }
+        .to_string_lossy()
+        .into_owned())
+}
+
+impl PseudoTerminal {
+    /// `open` - open a new pseudo-terminal
+    ///
+    /// # Return value
+    ///
+    /// The function returns the pseudo-terminal if successful, otherwise an Error.
+    pub fn open() -> Result<PseudoTerminal, Error> {
+        let error = |msg: &str| Error::Rtu {
+            msg: msg.to_owned(),
+            source: io::Error::last_os_error(),
+        };
+        unsafe {
+            let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+            if master == -1 {
+                return Err(error("posix_openpt"));
+            }
+            let fail = |msg: &str| {
+                let err = error(msg);
+                libc::close(master);
+                err
+            };
+            if libc::grantpt(master) == -1 {
+                return Err(fail("grantpt"));
+            }
+            if libc::unlockpt(master) == -1 {
+                return Err(fail("unlockpt"));
+            }
+            let path = match slave_path(master) {
+                Ok(path) => path,
+                Err(source) => {
+                    libc::close(master);
+                    return Err(Error::Rtu {
+                        msg: "ptsname".to_owned(),
+                        source,
+                    });
+                }
+            };
+            Ok(PseudoTerminal { master, path })
+        }
+    }
+
+    /// `path` - path of the slave end, the serial device of the clients
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// `into_modbus` - create a RTU context answering on the master end as `slave`
+    ///
+    /// The context takes ownership of the master end and closes it on drop. Don't call
+    /// [`connect()`](struct.Modbus.html#method.connect) on it, it would open the slave end instead.
+    ///
+    /// # Return value
+    ///
+    /// The function returns the context if successful, otherwise an Error.
+    ///
+    /// # Parameters
+    ///
+    /// * `slave`   - slave id of the server
+    pub fn into_modbus(mut self, slave: u8) -> Result<Modbus, Error> {
+        let mut modbus = Modbus::new_rtu(&self.path, 115200, 'N', 8, 1)?;
+        modbus.set_slave(slave)?;
+        modbus.set_socket(self.master)?;
+        // the context owns the master end now
+        self.master = -1;
+        Ok(modbus)
+    }
+
+    /// `into_ascii` - create an ASCII context answering on the master end as `slave`
+    ///
+    /// The context takes ownership of the master end and closes it on drop. ASCII clients, see
+    /// [`ModbusStream::new_ascii()`](struct.ModbusStream.html#method.new_ascii), open the slave end at
+    /// [`path()`](#method.path).
+    ///
+    /// # Return value
+    ///
+    /// The function returns the context if successful, otherwise an Error.
+    ///
+    /// # Parameters
+    ///
+    /// * `slave`   - slave id of the server
+    pub fn into_ascii(mut self, slave: u8) -> Result<ModbusStream, Error> {
+        let mut modbus = ModbusStream::with_pty_master(self.master, Framing::Ascii, None);
+        // the context owns the master end now
+        self.master = -1;
+        modbus.set_slave(slave)?;
+        Ok(modbus)
+    }
+
+    /// `into_rtu` - create an RTU context of the Rust backend answering on the master end as `slave`
+    ///
+    /// The context takes ownership of the master end and closes it on drop. A pseudo-terminal transmits without
+    /// delay, `config` is the serial line it stands for, only used for the timing, see
+    /// [`ModbusStream::set_rtu_timing()`](struct.ModbusStream.html#method.set_rtu_timing).
+    ///
+    /// # Return value
+    ///
+    /// The function returns the context if successful, otherwise an Error.
+    ///
+    /// # Parameters
+    ///
+    /// * `slave`   - slave id of the server
+    /// * `config`  - parameters of the serial line
+    pub fn into_rtu(mut self, slave: u8, config: SerialConfig) -> Result<ModbusStream, Error> {
+        let mut modbus = ModbusStream::with_pty_master(self.master, Framing::Rtu, Some(config));
+        // the context owns the master end now
+        self.master = -1;
+        modbus.set_slave(slave)?;
+        Ok(modbus)
+    }
+
+    /// `into_monitor` - create a monitor of the traffic the clients on the slave end send
+    ///
+    /// The monitor takes ownership of the master end and closes it on drop, see
+    /// [`RtuMonitor`](struct.RtuMonitor.html). `config` is the serial line the pseudo-terminal stands for, it gives
+    /// the silence ending a frame.
+    ///
+    /// # Parameters
+    ///
+    /// * `config`  - parameters of the serial line
+    pub fn into_monitor(mut self, config: SerialConfig) -> RtuMonitor {
+        let port = SerialPort::from_pty_master(self.master);
+        // the monitor owns the master end now
+        self.master = -1;
+        RtuMonitor::with_transport(Box::new(port), config)
+    }
+}
+
+impl Drop for PseudoTerminal {
+    fn drop(&mut self) {
+        if self.master != -1 {
+            unsafe { libc::close(self.master) };
+        }
+    }
+}
@@ -0,0 +1,312 @@
+use crate::prelude::*;
+use crate::{Exception, FunctionCode};
+
+/// A request received by a server, decoded
+///
+/// After [`receive()`](struct.Modbus.html#method.receive) the request is a slice of bytes, with the header and,
+/// for RTU, the CRC of the backend around it. [`Request::parse()`](#method.parse) decodes it into one variant per
+/// function code, so servers can log, filter or route requests before they reply.
+///
+/// Function codes without a variant of their own, e.g. Report Slave ID or user defined ones, are decoded as
+/// `Request::Custom` with the data following the function code.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use libmodbus::{Modbus, ModbusServer, ModbusTCP, Request};
+///
+/// let mut modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+/// let mut socket = modbus.tcp_listen(1).unwrap();
+/// modbus.tcp_accept(&mut socket).unwrap();
+///
+/// let mut query = vec![0; Modbus::MAX_ADU_LENGTH as usize];
+/// let len = modbus.receive(&mut query).unwrap();
+///
+/// match Request::parse(&modbus, &query[..len as usize]) {
+///     Ok(Request::WriteMultipleRegisters { address, values, .. }) => {
+///         println!("write {:?} from {}", values, address)
+///     }
+///     Ok(request) => println!("{:?}", request),
+///     Err(err) => {
+///         modbus.reply_exception(&query, err.exception().unwrap()).unwrap();
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Request {
+    /// Read Coils (0x01)
+    ReadCoils {
+        unit: u8,
+        address: u16,
+        quantity: u16,
+    },
+    /// Read Discrete Inputs (0x02)
+    ReadDiscreteInputs {
+        unit: u8,
+        address: u16,
+        quantity: u16,
+    },
+    /// Read Holding Registers (0x03)
+    ReadHoldingRegisters {
+        unit: u8,
+        address: u16,
+        quantity: u16,
+    },
+    /// Read Input Registers (0x04)
+    ReadInputRegisters {
+        unit: u8,
+        address: u16,
+        quantity: u16,
+    },
+    /// Write Single Coil (0x05)
+    WriteSingleCoil { unit: u8, address: u16, value: bool },
+    /// Write Single Register (0x06)
+    WriteSingleRegister { unit: u8, address: u16, value: u16 },
+    /// Write Multiple Coils (0x0F)
+    WriteMultipleCoils {
+        unit: u8,
+        address: u16,
+        values: Vec<bool>,
+    },
+    /// Write Multiple Registers (0x10)
+    WriteMultipleRegisters {
+        unit: u8,
+        address: u16,
+        values: Vec<u16>,
+    },
+    /// Mask Write Register (0x16)
+    MaskWriteRegister {
+        unit: u8,
+        address: u16,
+        and_mask: u16,
+        or_mask: u16,
+    },
+    /// Read/Write Multiple Registers (0x17)
+    WriteAndReadRegisters {
+        unit: u8,
+        write_address: u16,
+        values: Vec<u16>,
+        read_address: u16,
+        read_quantity: u16,
+    },
+    /// Any other function code, `data` is the PDU without the function code
+    Custom {
+        unit: u8,
+        function: u8,
+        data: Vec<u8>,
+    },
+}
+
+/// An invalid request, answered with `Exception::IllegalDataValue` as the specification requires
+fn invalid(msg: String) -> Error {
+    Error::Request {
+        msg,
+        exception: Exception::IllegalDataValue,
+    }
+}
+
+impl Request {
+    /// `parse` - decode a request received by `modbus`
+    ///
+    /// The [`parse()`](#method.parse) function decodes the request `request` received with
+    /// [`receive()`](struct.Modbus.html#method.receive) on the context `modbus`, whose backend determines the
+    /// header and checksum around the PDU.
+    ///
+    /// # Return value
+    ///
+    /// The function returns the decoded request if successful. A truncated request, a quantity out of the range
+    /// the specification allows, a byte count not matching the quantity or an invalid coil value is reported as
+    /// `Error::Request`, its [`exception()`](enum.Error.html#method.exception) is the exception the server should
+    /// answer with.
+    ///
+    /// # Parameters
+    ///
+    /// * `modbus`  - context the request was received with
+    /// * `request` - the request, exactly as many bytes as `receive()` returned
+    pub fn parse(modbus: &Modbus, request: &[u8]) -> Result<Request, Error> {
+        let (unit, pdu) = modbus.split_request(request, request.len() as i32)?;
+        Request::decode(unit, pdu)
+    }
+
+    /// Decode the PDU (function code and data) of a request sent to `unit`
+    pub(crate) fn decode(unit: u8, pdu: &[u8]) -> Result<Request, Error> {
+        let (&function, data) = pdu
+            .split_first()
+            .ok_or_else(|| invalid("empty request".to_owned()))?;
+        let truncated = || {
+            invalid(format!(
+                "request with function code 0x{:02X} truncated",
+                function
+            ))
+        };
+        let word = |index: usize| -> Result<u16, Error> {
+            match data.get(index..index + 2) {
+                Some(bytes) => Ok(u16::from_be_bytes([bytes[0], bytes[1]])),
+                None => Err(truncated()),
+            }
+        };
+        let byte = |index: usize| -> Result<usize, Error> {
+            data.get(index)
+                .map(|&byte| byte as usize)
+                .ok_or_else(truncated)
+        };
+        let quantity = |quantity: u16, max: u32| {
+            if quantity >= 1 && quantity as u32 <= max {
+                Ok(quantity)
+            } else {
+                Err(invalid(format!(
+                    "quantity {} out of range 1..={}",
+                    quantity, max
+                )))
+            }
+        };
+        let byte_count = |byte_count: usize, expected: usize| {
+            if byte_count == expected {
+                Ok(byte_count)
+            } else {
+                Err(invalid(format!(
+                    "byte count {} does not match the quantity, expected {}",
+                    byte_count, expected
+                )))
+            }
+        };
+
+        let request = match function {
+            f if f == FunctionCode::ReadCoils as u8 => Request::ReadCoils {
+                unit,
+                address: word(0)?,
+                quantity: quantity(word(2)?, Modbus::MAX_READ_BITS)?,
+            },
+            f if f == FunctionCode::ReadDiscreteInputs as u8 => Request::ReadDiscreteInputs {
+                unit,
+                address: word(0)?,
+                quantity: quantity(word(2)?, Modbus::MAX_READ_BITS)?,
+            },
+            f if f == FunctionCode::ReadHoldingRegisters as u8 => Request::ReadHoldingRegisters {
+                unit,
+                address: word(0)?,
+                quantity: quantity(word(2)?, Modbus::MAX_READ_REGISTERS)?,
+            },
+            f if f == FunctionCode::ReadInputRegisters as u8 => Request::ReadInputRegisters {
+                unit,
+                address: word(0)?,
+                quantity: quantity(word(2)?, Modbus::MAX_READ_REGISTERS)?,
+            },
+            f if f == FunctionCode::WriteSingleCoil as u8 => Request::WriteSingleCoil {
+                unit,
+                address: word(0)?,
+                value: match word(2)? {
+                    0xFF00 => true,
+                    0x0000 => false,
+                    value => return Err(invalid(format!("invalid coil value 0x{:04X}", value))),
+                },
+            },
+            f if f == FunctionCode::WriteSingleRegister as u8 => Request::WriteSingleRegister {
+                unit,
+                address: word(0)?,
+                value: word(2)?,
+            },
+            f if f == FunctionCode::WriteMultipleCoils as u8 => {
+                let address = word(0)?;
+                let count = quantity(word(2)?, Modbus::MAX_WRITE_BITS)? as usize;
+                let bytes = byte_count(byte(4)?, count.div_ceil(8))?;
+                let bits = data.get(5..5 + bytes).ok_or_else(truncated)?;
+                Request::WriteMultipleCoils {
+                    unit,
+                    address,
+                    values: (0..count)
+                        .map(|bit| bits[bit / 8] & (1 << (bit % 8)) != 0)
+                        .collect(),
+                }
+            }
+            f if f == FunctionCode::WriteMultipleRegisters as u8 => {
+                let address = word(0)?;
+                let count = quantity(word(2)?, Modbus::MAX_WRITE_REGISTERS)? as usize;
+                byte_count(byte(4)?, 2 * count)?;
+                Request::WriteMultipleRegisters {
+                    unit,
+                    address,
+                    values: (0..count)
+                        .map(|index| word(5 + 2 * index))
+                        .collect::<Result<_, _>>()?,
+                }
+            }
+            f if f == FunctionCode::MaskWriteRegister as u8 => Request::MaskWriteRegister {
+                unit,
+                address: word(0)?,
+                and_mask: word(2)?,
+                or_mask: word(4)?,
+            },
+            f if f == FunctionCode::WriteAndReadRegisters as u8 => {
+                let read_address = word(0)?;
+                let read_quantity = quantity(word(2)?, Modbus::MAX_WR_READ_REGISTERS)?;
+                let write_address = word(4)?;
+                let count = quantity(word(6)?, Modbus::MAX_WR_WRITE_REGISTERS)? as usize;
+                byte_count(byte(8)?, 2 * count)?;
+                Request::WriteAndReadRegisters {
+                    unit,
+                    write_address,
+                    values: (0..count)
+                        .map(|index| word(9 + 2 * index))
+                        .collect::<Result<_, _>>()?,
+                    read_address,
+                    read_quantity,
+                }
+            }
+            function => Request::Custom {
+                unit,
+                function,
+                data: data.to_vec(),
+            },
+        };
+        Ok(request)
+    }
+
+    /// `unit` - the unit id the request was sent to
+    pub fn unit(&self) -> u8 {
+        match *self {
+            Request::ReadCoils { unit, .. }
+            | Request::ReadDiscreteInputs { unit, .. }
+            | Request::ReadHoldingRegisters { unit, .. }
+            | Request::ReadInputRegisters { unit, .. }
+            | Request::WriteSingleCoil { unit, .. }
+            | Request::WriteSingleRegister { unit, .. }
+            | Request::WriteMultipleCoils { unit, .. }
+            | Request::WriteMultipleRegisters { unit, .. }
+            | Request::MaskWriteRegister { unit, .. }
+            | Request::WriteAndReadRegisters { unit, .. }
+            | Request::Custom { unit, .. } => unit,
+        }
+    }
+
+    /// `function` - the function code of the request
+    pub fn function(&self) -> u8 {
+        match *self {
+            Request::ReadCoils { .. } => FunctionCode::ReadCoils as u8,
+            Request::ReadDiscreteInputs { .. } => FunctionCode::ReadDiscreteInputs as u8,
+            Request::ReadHoldingRegisters { .. } => FunctionCode::ReadHoldingRegisters as u8,
+            Request::ReadInputRegisters { .. } => FunctionCode::ReadInputRegisters as u8,
+            Request::WriteSingleCoil { .. } => FunctionCode::WriteSingleCoil as u8,
+            Request::WriteSingleRegister { .. } => FunctionCode::WriteSingleRegister as u8,
+            Request::WriteMultipleCoils { .. } => FunctionCode::WriteMultipleCoils as u8,
+            Request::WriteMultipleRegisters { .. } => FunctionCode::WriteMultipleRegisters as u8,
+            Request::MaskWriteRegister { .. } => FunctionCode::MaskWriteRegister as u8,
+            Request::WriteAndReadRegisters { .. } => FunctionCode::WriteAndReadRegisters as u8,
+            Request::Custom { function, .. } => function,
+        }
+    }
+
+    /// `is_write` - true if the request writes coils or holding registers
+    pub fn is_write(&self) -> bool {
+        matches!(
+            *self,
+            Request::WriteSingleCoil { .. }
+                | Request::WriteSingleRegister { .. }
+                | Request::WriteMultipleCoils { .. }
+                | Request::WriteMultipleRegisters { .. }
+                | Request::MaskWriteRegister { .. }
+                | Request::WriteAndReadRegisters { .. }
+        )
+    }
+}
@@ -1,8 +1,16 @@
+#[cfg(unix)]
+use crate::modbus_transport::termios_config;
 use crate::prelude::*;
+use crate::SerialConfig;
 use libc::{c_char, c_int};
 use libmodbus_sys as ffi;
 use std::ffi::CString;
+use std::io;
+use std::mem::ManuallyDrop;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
 use std::str;
+use std::sync::Mutex;
 
 #[derive(Debug, PartialEq)]
 #[allow(non_camel_case_types)]
@@ -18,6 +26,72 @@ pub enum RequestToSendMode {
     RtuRtsDown = ffi::MODBUS_RTU_RTS_DOWN as isize,
 }
 
+/// Drives the RTS pin, see [`rtu_set_custom_rts()`](struct.Modbus.html#method.rtu_set_custom_rts)
+type SetRts = Box<dyn FnMut(&Modbus, bool)>;
+
+/// A custom RTS function and the context it is called with, see
+/// [`rtu_set_custom_rts()`](struct.Modbus.html#method.rtu_set_custom_rts)
+pub(crate) struct CustomRts {
+    set_rts: SetRts,
+    /// Shares the libmodbus context with the owning `Modbus`, so it is never dropped
+    modbus: ManuallyDrop<Modbus>,
+    /// Set while `set_rts` runs, a libmodbus call made from it doesn't call it again
+    running: bool,
+}
+
+/// The custom RTS functions by the address of their libmodbus context, libmodbus passes no user data to them
+static CUSTOM_RTS: Mutex<Vec<(usize, usize)>> = Mutex::new(Vec::new());
+
+fn custom_rts_registry() -> std::sync::MutexGuard<'static, Vec<(usize, usize)>> {
+    CUSTOM_RTS.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// The function libmodbus calls, it calls the custom RTS function of `ctx`
+unsafe extern "C" fn set_rts_trampoline(ctx: *mut ffi::modbus_t, on: c_int) {
+    // the newest one, while a function is replaced both are registered
+    let custom_rts = custom_rts_registry()
+        .iter()
+        .rev()
+        .find(|&&(registered, _)| registered == ctx as usize)
+        .map(|&(_, custom_rts)| custom_rts as *mut CustomRts);
+    // the lock is released, the function may use other contexts
+    let custom_rts = match custom_rts {
+        Some(custom_rts) => &mut *custom_rts,
+        None => return,
+    };
+    if custom_rts.running {
+        return;
+    }
+    custom_rts.running = true;
+    let CustomRts {
+        set_rts, modbus, ..
+    } = custom_rts;
+    // unwinding into libmodbus is undefined behaviour
+    if panic::catch_unwind(AssertUnwindSafe(|| set_rts(modbus, on != 0))).is_err() {
+        std::process::abort();
+    }
+    custom_rts.running = false;
+}
+
+/// Unregister and drop a custom RTS function
+fn release_custom_rts(custom_rts: *mut CustomRts) {
+    if custom_rts.is_null() {
+        return;
+    }
+    custom_rts_registry().retain(|&(_, registered)| registered != custom_rts as usize);
+    unsafe { drop(Box::from_raw(custom_rts)) };
+}
+
+impl Modbus {
+    /// Unregister and drop the custom RTS function of the context, if any
+    ///
+    /// libmodbus keeps calling the trampoline, which does nothing without a registered function. Only call this
+    /// before the libmodbus context is freed or another function is registered.
+    pub(crate) fn clear_custom_rts(&mut self) {
+        release_custom_rts(std::mem::replace(&mut self.custom_rts, ptr::null_mut()));
+    }
+}
+
 /// The RTU backend (Remote Terminal Unit) is used in serial communication and makes use of a compact, binary
 /// representation of the data for protocol communication.
 /// The RTU format follows the commands/data with a cyclic redundancy check checksum as an error check mechanism to
@@ -40,8 +114,16 @@ pub enum RequestToSendMode {
 /// than response timeout of master
 /// (ortherwise other slaves may ignore master requests when one of the slave is not responding).
 ///
+/// The contexts of this trait can't enforce the t1.5 and t3.5 silent intervals of the specification, libmodbus has no
+/// means to. Only the RTU backend implemented in Rust does, see
+/// [`ModbusStream::new_rtu()`](struct.ModbusStream.html#method.new_rtu) and
+/// [`ModbusStream::set_rtu_timing()`](struct.ModbusStream.html#method.set_rtu_timing).
+///
 /// * Create a Modbus RTU context
-///     - [`new_rtu()`](struct.Modbus.html#method.new_rtu)
+///     - [`new_rtu()`](struct.Modbus.html#method.new_rtu), [`new_rtu_config()`](struct.Modbus.html#method.new_rtu_config)
+///
+/// * Get the parameters of the serial line
+///     - [`rtu_get_serial_config()`](struct.Modbus.html#method.rtu_get_serial_config)
 ///
 /// * Set the serial mode
 /// - [`rtu_get_serial_mode()`](struct.Modbus.html#method.rtu_get_serial_mode),
@@ -59,11 +141,15 @@ pub trait ModbusRTU {
         data_bit: i32,
         stop_bit: i32,
     ) -> Result<Modbus, Error>;
+    fn new_rtu_config(device: &str, config: SerialConfig) -> Result<Modbus, Error>;
+    fn rtu_get_serial_config(&self) -> Result<SerialConfig, Error>;
     fn rtu_get_serial_mode(&self) -> Result<SerialMode, Error>;
     fn rtu_set_serial_mode(&mut self, mode: SerialMode) -> Result<(), Error>;
     fn rtu_get_rts(&self) -> Result<RequestToSendMode, Error>;
     fn rtu_set_rts(&mut self, mode: RequestToSendMode) -> Result<(), Error>;
-    fn rtu_set_custom_rts(&mut self, _mode: RequestToSendMode) -> Result<i32, Error>;
+    fn rtu_set_custom_rts<F>(&mut self, set_rts: F) -> Result<(), Error>
+    where
+        F: FnMut(&Modbus, bool) + 'static;
     fn rtu_get_rts_delay(&self) -> Result<i32, Error>;
     fn rtu_set_rts_delay(&mut self, us: i32) -> Result<(), Error>;
 }
@@ -90,6 +176,10 @@ impl ModbusRTU for Modbus {
     ///    Once the modbus structure is initialized, you must set the slave of your device with
     ///    [`set_slave()`](#method.set_slave) and connect to the serial bus with [`connect()`](#method.connect).
     ///
+    /// The parameters are checked like [`SerialConfig::from_parts()`](struct.SerialConfig.html#method.from_parts)
+    /// does, an invalid one is an Error naming it. See [`new_rtu_config()`](#method.new_rtu_config) to pass them
+    /// typed.
+    ///
     /// # Examples
     ///
     /// ```
@@ -103,6 +193,8 @@ impl ModbusRTU for Modbus {
     ///     Ok(_) => {  }
     ///     Err(e) => println!("Error: {}", e),
     /// }
+    ///
+    /// assert!(Modbus::new_rtu("/dev/ttyUSB0", 115200, 'x', 8, 1).is_err());
     /// ```
     fn new_rtu(
         device: &str,
@@ -111,14 +203,38 @@ impl ModbusRTU for Modbus {
         data_bit: i32,
         stop_bit: i32,
     ) -> Result<Modbus, Error> {
+        let config =
+            SerialConfig::check(baud, parity, data_bit, stop_bit).map_err(|source| Error::Rtu {
+                msg: "new_rtu".to_owned(),
+                source,
+            })?;
+        Modbus::new_rtu_config(device, config)
+    }
+
+    /// `new_rtu_config` - create a libmodbus context for RTU with typed serial parameters
+    ///
+    /// The [`new_rtu_config()`](#method.new_rtu_config) function is [`new_rtu()`](#method.new_rtu) with the baud
+    /// rate, parity, data bits and stop bits of a [`SerialConfig`](struct.SerialConfig.html).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libmodbus::{BaudRate, DataBits, Modbus, ModbusRTU, Parity, SerialConfig, StopBits};
+    ///
+    /// let config = SerialConfig::new(BaudRate::B19200, Parity::Even, DataBits::Eight, StopBits::One);
+    /// let modbus = Modbus::new_rtu_config("/dev/ttyUSB0", config).unwrap();
+    ///
+    /// assert_eq!(modbus.rtu_get_serial_config().unwrap(), config);
+    /// ```
+    fn new_rtu_config(device: &str, config: SerialConfig) -> Result<Modbus, Error> {
         unsafe {
             let device = CString::new(device).unwrap();
             let ctx = ffi::modbus_new_rtu(
                 device.as_ptr(),
-                baud as c_int,
-                parity as c_char,
-                data_bit as c_int,
-                stop_bit as c_int,
+                config.baud_rate.value() as c_int,
+                config.parity.as_char() as c_char,
+                config.data_bits.bits() as c_int,
+                config.stop_bits.bits() as c_int,
             );
 
             if ctx.is_null() {
@@ -127,11 +243,49 @@ impl ModbusRTU for Modbus {
                     source: ::std::io::Error::last_os_error(),
                 })
             } else {
-                Ok(Modbus { ctx: ctx })
+                let mut modbus = Modbus::from_ctx(ctx);
+                modbus.serial = Some(config);
+                Ok(modbus)
             }
         }
     }
 
+    /// `rtu_get_serial_config` - get the parameters of the serial line
+    ///
+    /// The [`rtu_get_serial_config()`](#method.rtu_get_serial_config) function shall return the baud rate, parity,
+    /// data bits and stop bits of the context, e.g. to log them. Once connected, on unix platforms, they are read
+    /// back from the serial port, so they are the parameters the driver actually uses. Before, they are the
+    /// parameters the context was created with.
+    ///
+    /// This function can only be used with a context using a RTU backend.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use libmodbus::{Modbus, ModbusRTU};
+    /// let modbus = Modbus::new_rtu("/dev/ttyUSB0", 19200, 'E', 8, 1).unwrap();
+    /// modbus.connect().unwrap();
+    ///
+    /// println!("RTU on /dev/ttyUSB0 with {}", modbus.rtu_get_serial_config().unwrap());
+    /// ```
+    fn rtu_get_serial_config(&self) -> Result<SerialConfig, Error> {
+        let error = |source| Error::Rtu {
+            msg: "rtu_get_serial_config".to_owned(),
+            source,
+        };
+        let config = self
+            .serial
+            .ok_or_else(|| error(io::Error::from_raw_os_error(libc::EINVAL)))?;
+        #[cfg(unix)]
+        {
+            let socket = unsafe { ffi::modbus_get_socket(self.ctx) };
+            if socket != -1 {
+                return termios_config(socket).map_err(error);
+            }
+        }
+        Ok(config)
+    }
+
     /// `rtu_get_serial_mode` - get the current serial mode
     ///
     /// The [`rtu_get_serial_mode()`](#method.rtu_get_serial_mode) function shall return the serial mode currently
@@ -294,18 +448,75 @@ impl ModbusRTU for Modbus {
 
     /// `rtu_set_custom_rts` - set a function to be used for custom RTS implementation
     ///
-    /// The modbus_rtu_set_custom_rts() function shall set a custom function to be called when the RTS pin is to be set
-    /// before and after a transmission. By default this is set to an internal function that toggles the RTS pin using
-    /// an ioctl call.
+    /// The [`rtu_set_custom_rts()`](#method.rtu_set_custom_rts) function shall set a custom function to be called
+    /// when the RTS pin is to be set before and after a transmission. By default this is set to an internal function
+    /// that toggles the RTS pin using an ioctl call. A custom function drives the direction of the transceiver some
+    /// other way, e.g. with a GPIO or a vendor specific ioctl of an USB adapter.
     ///
-    /// Note that this function adheres to the RTS mode,
-    /// the values MODBUS_RTU_RTS_UP or MODBUS_RTU_RTS_DOWN must be used for the function to be called.
+    /// The function gets the context and `true` to enable the transmitter before a transmission, `false` to disable
+    /// it afterwards. The function is owned by the context until it is replaced or the context is dropped. libmodbus
+    /// calls made by the function don't call it again. A panic of the function aborts the process, it must not
+    /// unwind into libmodbus.
+    ///
+    /// Note that this function adheres to the RTS mode, the values `RequestToSendMode::RtuRtsUp` or
+    /// `RequestToSendMode::RtuRtsDown` must be set with [`rtu_set_rts()`](#method.rtu_set_rts) for the function to
+    /// be called.
     ///
     /// This function can only be used with a context using a RTU backend.
     ///
-    /// TODO: implement rtu_set_custom_rts()!
-    fn rtu_set_custom_rts(&mut self, _mode: RequestToSendMode) -> Result<i32, Error> {
-        unimplemented!()
+    /// Breaking change: earlier versions declared `rtu_set_custom_rts(&mut self, mode: RequestToSendMode) ->
+    /// Result<i32, Error>` in [`ModbusRTU`](trait.ModbusRTU.html), which was never implemented.
+    ///
+    /// # Return value
+    ///
+    /// The function return an OK Result if successful. Otherwise it contains an Error.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use libmodbus::{Modbus, ModbusRTU, RequestToSendMode};
+    /// let mut modbus = Modbus::new_rtu("/dev/ttyUSB0", 115200, 'N', 8, 1).unwrap();
+    ///
+    /// modbus.rtu_set_rts(RequestToSendMode::RtuRtsUp).unwrap();
+    /// modbus
+    ///     .rtu_set_custom_rts(|modbus, on| {
+    ///         // e.g. write `on` to the GPIO of the transceiver
+    ///         println!("transmitter of socket {:?} on: {}", modbus.get_socket(), on);
+    ///     })
+    ///     .unwrap();
+    /// ```
+    fn rtu_set_custom_rts<F>(&mut self, set_rts: F) -> Result<(), Error>
+    where
+        F: FnMut(&Modbus, bool) + 'static,
+    {
+        let mut modbus = Modbus::from_ctx(self.ctx);
+        modbus.stats = self.stats.clone();
+        modbus.serial = self.serial;
+        let custom_rts = Box::into_raw(Box::new(CustomRts {
+            set_rts: Box::new(set_rts),
+            modbus: ManuallyDrop::new(modbus),
+            running: false,
+        }));
+        custom_rts_registry().push((self.ctx as usize, custom_rts as usize));
+
+        unsafe {
+            match ffi::modbus_rtu_set_custom_rts(self.ctx, Some(set_rts_trampoline)) {
+                -1 => {
+                    let source = ::std::io::Error::last_os_error();
+                    // the previous function, if any, stays in place
+                    release_custom_rts(custom_rts);
+                    Err(Error::Rtu {
+                        msg: "rtu_set_custom_rts".to_owned(),
+                        source,
+                    })
+                }
+                0 => {
+                    release_custom_rts(std::mem::replace(&mut self.custom_rts, custom_rts));
+                    Ok(())
+                }
+                _ => panic!("libmodbus API incompatible response"),
+            }
+        }
     }
 
     /// `rtu_get_rts_delay` - get the current RTS delay in RTU
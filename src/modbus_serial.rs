@@ -0,0 +1,312 @@
+use crate::prelude::*;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+/// Baud rates libmodbus configures a serial port with, on all platforms
+const BAUD_RATES: &[u32] = &[
+    110, 300, 600, 1200, 2400, 4800, 9600, 19200, 38400, 57600, 115200, 230400,
+];
+
+/// Higher baud rates only Linux supports
+#[cfg(target_os = "linux")]
+const LINUX_BAUD_RATES: &[u32] = &[
+    460800, 500000, 576000, 921600, 1000000, 1152000, 1500000, 2000000, 2500000, 3000000, 3500000,
+    4000000,
+];
+#[cfg(not(target_os = "linux"))]
+const LINUX_BAUD_RATES: &[u32] = &[];
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg.to_owned())
+}
+
+/// An invalid parameter, reported like the parameters of [`new_rtu()`](struct.Modbus.html#method.new_rtu)
+fn rtu_error(source: io::Error) -> Error {
+    Error::Rtu {
+        msg: source.to_string(),
+        source,
+    }
+}
+
+/// Parity bit of a serial line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Parity {
+    None,
+    Even,
+    Odd,
+}
+
+impl Parity {
+    /// `as_char` - the parity as libmodbus expects it, `'N'`, `'E'` or `'O'`
+    pub fn as_char(self) -> char {
+        match self {
+            Parity::None => 'N',
+            Parity::Even => 'E',
+            Parity::Odd => 'O',
+        }
+    }
+}
+
+impl TryFrom<char> for Parity {
+    type Error = Error;
+
+    /// `'N'` for none, `'E'` for even or `'O'` for odd
+    fn try_from(parity: char) -> Result<Parity, Error> {
+        match parity {
+            'N' => Ok(Parity::None),
+            'E' => Ok(Parity::Even),
+            'O' => Ok(Parity::Odd),
+            _ => Err(rtu_error(invalid("parity must be 'N', 'E' or 'O'"))),
+        }
+    }
+}
+
+/// Number of data bits of a character on a serial line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DataBits {
+    Five = 5,
+    Six = 6,
+    Seven = 7,
+    Eight = 8,
+}
+
+impl DataBits {
+    /// `bits` - the number of data bits
+    pub fn bits(self) -> i32 {
+        self as i32
+    }
+}
+
+impl TryFrom<i32> for DataBits {
+    type Error = Error;
+
+    fn try_from(data_bit: i32) -> Result<DataBits, Error> {
+        match data_bit {
+            5 => Ok(DataBits::Five),
+            6 => Ok(DataBits::Six),
+            7 => Ok(DataBits::Seven),
+            8 => Ok(DataBits::Eight),
+            _ => Err(rtu_error(invalid("data bits must be 5, 6, 7 or 8"))),
+        }
+    }
+}
+
+/// Number of stop bits of a character on a serial line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StopBits {
+    One = 1,
+    Two = 2,
+}
+
+impl StopBits {
+    /// `bits` - the number of stop bits
+    pub fn bits(self) -> i32 {
+        self as i32
+    }
+}
+
+impl TryFrom<i32> for StopBits {
+    type Error = Error;
+
+    fn try_from(stop_bit: i32) -> Result<StopBits, Error> {
+        match stop_bit {
+            1 => Ok(StopBits::One),
+            2 => Ok(StopBits::Two),
+            _ => Err(rtu_error(invalid("stop bits must be 1 or 2"))),
+        }
+    }
+}
+
+/// Baud rate of a serial line, one of the standard rates
+///
+/// 110 to 230400 baud are supported on all platforms, Linux supports up to 4000000 baud.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BaudRate(u32);
+
+impl BaudRate {
+    pub const B1200: BaudRate = BaudRate(1200);
+    pub const B2400: BaudRate = BaudRate(2400);
+    pub const B4800: BaudRate = BaudRate(4800);
+    pub const B9600: BaudRate = BaudRate(9600);
+    pub const B19200: BaudRate = BaudRate(19200);
+    pub const B38400: BaudRate = BaudRate(38400);
+    pub const B57600: BaudRate = BaudRate(57600);
+    pub const B115200: BaudRate = BaudRate(115200);
+
+    /// `new` - check a baud rate
+    ///
+    /// # Return value
+    ///
+    /// The function returns the baud rate if the platform supports it, otherwise an Error.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use libmodbus::BaudRate;
+    ///
+    /// assert_eq!(BaudRate::new(19200).unwrap(), BaudRate::B19200);
+    /// assert!(BaudRate::new(12345).is_err());
+    /// ```
+    pub fn new(baud: u32) -> Result<BaudRate, Error> {
+        if BAUD_RATES.contains(&baud) || LINUX_BAUD_RATES.contains(&baud) {
+            Ok(BaudRate(baud))
+        } else {
+            Err(rtu_error(invalid("unsupported baud rate")))
+        }
+    }
+
+    /// `value` - the baud rate in bits per second
+    pub fn value(self) -> u32 {
+        self.0
+    }
+}
+
+impl TryFrom<i32> for BaudRate {
+    type Error = Error;
+
+    fn try_from(baud: i32) -> Result<BaudRate, Error> {
+        u32::try_from(baud)
+            .map_err(|_| rtu_error(invalid("unsupported baud rate")))
+            .and_then(BaudRate::new)
+    }
+}
+
+/// Parameters of a serial line, see [`new_rtu_config()`](struct.Modbus.html#method.new_rtu_config)
+///
+/// Only valid parameters can be expressed, e.g. there is no parity 'x' or 9 data bits.
+/// [`from_parts()`](#method.from_parts) checks the untyped parameters of
+/// [`new_rtu()`](struct.Modbus.html#method.new_rtu). The `Display` output is the usual short form, e.g.
+/// `19200 8E1`.
+///
+/// # Examples
+///
+/// ```rust
+/// use libmodbus::{BaudRate, DataBits, Parity, SerialConfig, StopBits};
+///
+/// let config = SerialConfig::new(BaudRate::B19200, Parity::Even, DataBits::Eight, StopBits::One);
+/// assert_eq!(config.to_string(), "19200 8E1");
+/// assert_eq!(SerialConfig::from_parts(19200, 'E', 8, 1).unwrap(), config);
+/// assert!(SerialConfig::from_parts(19200, 'x', 8, 1).is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SerialConfig {
+    pub baud_rate: BaudRate,
+    pub parity: Parity,
+    pub data_bits: DataBits,
+    pub stop_bits: StopBits,
+}
+
+impl SerialConfig {
+    /// `new` - create serial line parameters
+    pub fn new(
+        baud_rate: BaudRate,
+        parity: Parity,
+        data_bits: DataBits,
+        stop_bits: StopBits,
+    ) -> SerialConfig {
+        SerialConfig {
+            baud_rate,
+            parity,
+            data_bits,
+            stop_bits,
+        }
+    }
+
+    /// `from_parts` - check the parameters of [`new_rtu()`](struct.Modbus.html#method.new_rtu)
+    ///
+    /// # Return value
+    ///
+    /// The function returns the parameters if they are valid, otherwise an Error naming the invalid one.
+    ///
+    /// # Parameters
+    ///
+    /// * `baud`        - baud rate of the communication, e.g. 9600
+    /// * `parity`      - `'N'` for none, `'E'` for even or `'O'` for odd
+    /// * `data_bit`    - number of data bits, 5, 6, 7 or 8
+    /// * `stop_bit`    - number of stop bits, 1 or 2
+    pub fn from_parts(
+        baud: i32,
+        parity: char,
+        data_bit: i32,
+        stop_bit: i32,
+    ) -> Result<SerialConfig, Error> {
+        Ok(SerialConfig {
+            baud_rate: BaudRate::try_from(baud)?,
+            parity: Parity::try_from(parity)?,
+            data_bits: DataBits::try_from(data_bit)?,
+            stop_bits: StopBits::try_from(stop_bit)?,
+        })
+    }
+
+    /// `character_time` - time to transmit one character
+    ///
+    /// A character is a start bit, the data bits, the parity bit, if any, and the stop bits, 11 bits for the 8E1
+    /// of most devices.
+    pub fn character_time(&self) -> Duration {
+        let parity = if self.parity == Parity::None { 0 } else { 1 };
+        let bits = 1 + self.data_bits.bits() as u64 + parity + self.stop_bits.bits() as u64;
+        Duration::from_nanos(bits * 1_000_000_000 / self.baud_rate.value() as u64)
+    }
+
+    /// `inter_character_timeout` - longest silence allowed between two characters of a frame, t1.5
+    ///
+    /// 1.5 character times, above 19200 baud fixed to 750 µs as the Modbus serial line specification recommends.
+    pub fn inter_character_timeout(&self) -> Duration {
+        if self.baud_rate.value() > 19200 {
+            Duration::from_micros(750)
+        } else {
+            self.character_time() * 3 / 2
+        }
+    }
+
+    /// `inter_frame_delay` - silence between two frames, t3.5
+    ///
+    /// 3.5 character times, above 19200 baud fixed to 1750 µs as the Modbus serial line specification recommends.
+    pub fn inter_frame_delay(&self) -> Duration {
+        if self.baud_rate.value() > 19200 {
+            Duration::from_micros(1750)
+        } else {
+            self.character_time() * 7 / 2
+        }
+    }
+
+    /// [`from_parts()`](#method.from_parts) for the other backends, the error is the invalid parameter
+    pub(crate) fn check(
+        baud: i32,
+        parity: char,
+        data_bit: i32,
+        stop_bit: i32,
+    ) -> io::Result<SerialConfig> {
+        SerialConfig::from_parts(baud, parity, data_bit, stop_bit).map_err(|err| match err {
+            Error::Rtu { source, .. } => source,
+            err => io::Error::new(io::ErrorKind::InvalidInput, err.to_string()),
+        })
+    }
+}
+
+impl Default for SerialConfig {
+    /// 19200 baud, even parity, 8 data bits and 1 stop bit, the default of the Modbus serial line specification
+    fn default() -> SerialConfig {
+        SerialConfig::new(
+            BaudRate::B19200,
+            Parity::Even,
+            DataBits::Eight,
+            StopBits::One,
+        )
+    }
+}
+
+impl fmt::Display for SerialConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {}{}{}",
+            self.baud_rate.value(),
+            self.data_bits.bits(),
+            self.parity.as_char(),
+            self.stop_bits.bits()
+        )
+    }
+}
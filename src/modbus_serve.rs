@@ -0,0 +1,229 @@
+use crate::prelude::*;
+use crate::{ModbusServer, Statistics, Timeout};
+use libc::c_int;
+use std::mem;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Once};
+use std::time::{Duration, Instant};
+
+/// Interval in which [`serve()`](fn.serve.html) checks the stop conditions while no request arrives
+const TICK: Timeout = Timeout {
+    sec: 0,
+    usec: 100_000,
+};
+
+/// Number of SIGINT and SIGTERM signals caught since the first `serve()` loop stopping on signals started
+static SIGNALS: AtomicUsize = AtomicUsize::new(0);
+
+static INSTALL_SIGNAL_HANDLER: Once = Once::new();
+
+extern "C" fn on_signal(_signal: c_int) {
+    SIGNALS.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Install the handler counting SIGINT and SIGTERM, once per process
+///
+/// The handler is never removed: loops on other threads may still rely on it, and restoring the previous handlers
+/// in the right order isn't possible once several loops start and stop independently.
+fn install_signal_handler() {
+    INSTALL_SIGNAL_HANDLER.call_once(|| unsafe {
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = on_signal as extern "C" fn(c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        for &signal in &[libc::SIGINT, libc::SIGTERM] {
+            libc::sigaction(signal, &action, ptr::null_mut());
+        }
+    });
+}
+
+/// When to stop a [`serve()`](fn.serve.html) loop
+///
+/// The loop stops when [`stop()`](#method.stop) is called on the token, or one of its clones, e.g. from another
+/// thread. Optionally it also stops on SIGINT or SIGTERM, see [`set_stop_on_signals()`](#method.set_stop_on_signals),
+/// and when no request arrived for a while, see [`set_idle_timeout()`](#method.set_idle_timeout).
+///
+/// Clones share the stopped state, the settings are copied.
+///
+/// # Examples
+///
+/// ```rust
+/// use libmodbus::StopToken;
+/// use std::time::Duration;
+///
+/// let mut stop = StopToken::new();
+/// stop.set_stop_on_signals(true);
+/// stop.set_idle_timeout(Some(Duration::from_secs(600)));
+///
+/// let remote = stop.clone();
+/// std::thread::spawn(move || remote.stop()).join().unwrap();
+/// assert!(stop.is_stopped());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct StopToken {
+    stopped: Arc<AtomicBool>,
+    signals: bool,
+    idle_timeout: Option<Duration>,
+}
+
+impl StopToken {
+    /// `new` - create a token, stopping the loop only on [`stop()`](#method.stop)
+    pub fn new() -> StopToken {
+        StopToken::default()
+    }
+
+    /// `stop` - stop the loops using this token or one of its clones
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+    }
+
+    /// `is_stopped` - true if [`stop()`](#method.stop) was called, or a signal stopped the loop
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::SeqCst)
+    }
+
+    /// `get_stop_on_signals` - true if the loop stops on SIGINT and SIGTERM
+    pub fn get_stop_on_signals(&self) -> bool {
+        self.signals
+    }
+
+    /// `set_stop_on_signals` - stop the loop on SIGINT and SIGTERM
+    ///
+    /// The first loop started with this setting installs a handler for SIGINT and SIGTERM, which stays installed
+    /// for the rest of the process: afterwards these signals no longer terminate the process, even while no loop
+    /// runs. A caught signal stops every loop running with this setting. Off by default.
+    ///
+    /// Applications with signal handlers of their own leave this off and call [`stop()`](#method.stop) from their
+    /// handler instead.
+    pub fn set_stop_on_signals(&mut self, signals: bool) {
+        self.signals = signals;
+    }
+
+    /// `get_idle_timeout` - get the time without requests after which the loop stops
+    pub fn get_idle_timeout(&self) -> Option<Duration> {
+        self.idle_timeout
+    }
+
+    /// `set_idle_timeout` - set the time without requests after which the loop stops
+    ///
+    /// `None`, the default, never stops the loop for lack of requests.
+    pub fn set_idle_timeout(&mut self, idle_timeout: Option<Duration>) {
+        self.idle_timeout = idle_timeout;
+    }
+}
+
+/// `serve` - answer requests with a mapping until stopped
+///
+/// The [`serve()`](fn.serve.html) function receives requests on `modbus` and answers them from `mapping`, like a
+/// loop of [`receive()`](struct.Modbus.html#method.receive) and [`reply()`](struct.Modbus.html#method.reply), until
+/// `stop` is stopped, a signal arrives or the idle timeout of `stop` elapses.
+///
+/// While the loop runs, the [indication timeout](struct.Modbus.html#method.set_indication_timeout) of the context
+/// is set to 100 ms, so the stop conditions are checked at least that often. The previous indication timeout is
+/// restored when the function returns. These timeouts are not counted in the statistics.
+///
+/// If [autosave](struct.ModbusMapping.html#method.set_autosave) is set for `mapping`, it is saved periodically
+/// and when the loop stops. A failing save ends the loop.
+///
+/// A TCP context must be connected to a client, e.g. with [`tcp_accept()`](struct.Modbus.html#method.tcp_accept),
+/// before the loop is started.
+///
+/// Only [`Modbus`](struct.Modbus.html) contexts are served, not the Rust backends of a
+/// [`ModbusStream`](struct.ModbusStream.html).
+///
+/// # Return value
+///
+/// The function returns the [`Statistics`](struct.Statistics.html) of the context when the loop was stopped.
+/// Otherwise it returns an `Error::Serve` with the Error which ended the loop, e.g. if the client closed the
+/// connection, and the statistics gathered until then.
+///
+/// # Parameters
+///
+/// * `modbus`  - context to receive the requests with
+/// * `mapping` - mapping answering the requests
+/// * `stop`    - when to stop the loop
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use libmodbus::{serve, Modbus, ModbusMapping, ModbusTCP, StopToken};
+///
+/// let mut modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+/// let mut socket = modbus.tcp_listen(1).unwrap();
+/// modbus.tcp_accept(&mut socket).unwrap();
+/// let mapping = ModbusMapping::new(500, 500, 500, 500).unwrap();
+///
+/// let mut stop = StopToken::new();
+/// stop.set_stop_on_signals(true);
+///
+/// let statistics = serve(&mut modbus, &mapping, &stop).unwrap();
+/// println!("{} requests answered", statistics.total_requests());
+/// ```
+pub fn serve(
+    modbus: &mut Modbus,
+    mapping: &ModbusMapping,
+    stop: &StopToken,
+) -> Result<Statistics, Error> {
+    serve_with(modbus, mapping, stop, |_| {})
+}
+
+/// The loop of `serve()`, calling `tick` before each receive, at least every 100 ms
+pub(crate) fn serve_with(
+    modbus: &mut Modbus,
+    mapping: &ModbusMapping,
+    stop: &StopToken,
+    mut tick: impl FnMut(&ModbusMapping),
+) -> Result<Statistics, Error> {
+    let indication_timeout = modbus.get_indication_timeout()?;
+    modbus.set_indication_timeout(TICK)?;
+
+    if stop.signals {
+        install_signal_handler();
+    }
+    let signals = SIGNALS.load(Ordering::SeqCst);
+
+    let mut query = vec![0u8; Modbus::MAX_ADU_LENGTH];
+    let mut last_request = Instant::now();
+    let result = loop {
+        if stop.signals && SIGNALS.load(Ordering::SeqCst) != signals {
+            stop.stop();
+        }
+        if stop.is_stopped() {
+            break Ok(());
+        }
+        if let Some(idle_timeout) = stop.idle_timeout {
+            if last_request.elapsed() >= idle_timeout {
+                break Ok(());
+            }
+        }
+
+        tick(mapping);
+        if let Err(err) = mapping.autosave(false) {
+            break Err(err);
+        }
+        match modbus.receive_indication(&mut query, false) {
+            // a request for another slave
+            Ok(0) => {}
+            Ok(len) => {
+                last_request = Instant::now();
+                if let Err(err) = modbus.reply(&query, len, mapping) {
+                    break Err(err);
+                }
+            }
+            Err(Error::Server { ref source, .. })
+                if source.raw_os_error() == Some(libc::ETIMEDOUT) => {}
+            Err(err) => break Err(err),
+        }
+    };
+
+    let saved = mapping.autosave(true);
+    modbus.set_indication_timeout(indication_timeout)?;
+    match result.and(saved) {
+        Ok(()) => Ok(modbus.statistics()),
+        Err(source) => Err(Error::Serve {
+            statistics: Box::new(modbus.statistics()),
+            source: Box::new(source),
+        }),
+    }
+}
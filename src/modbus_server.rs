@@ -1,4 +1,5 @@
 use crate::prelude::*;
+use crate::RequestHandler;
 use libmodbus_sys as ffi;
 
 /// The server is waiting for request from clients and must answer when it is concerned by the request. The libmodbus
@@ -8,6 +9,7 @@ use libmodbus_sys as ffi;
 ///     - [`receive()`](struct.Modbus.html#method.receive)
 /// * Reply
 ///     - [`reply()`](struct.Modbus.html#method.reply), [`reply_exception()`](struct.Modbus.html#method.reply_exception)
+///     - [`reply_with()`](struct.Modbus.html#method.reply_with)
 ///
 pub trait ModbusServer {
     fn receive(&self, request: &mut [u8]) -> Result<i32, Error>;
@@ -17,6 +19,23 @@ pub trait ModbusServer {
         request_len: i32,
         modbus_mapping: &ModbusMapping,
     ) -> Result<i32, Error>;
+    /// `reply_with` - send a response computed by a request handler
+    ///
+    /// Implemented by [`Modbus`](struct.Modbus.html#method.reply_with) and
+    /// [`ModbusStream`](struct.ModbusStream.html#method.reply_with). Other implementors keep working without it:
+    /// the default implementation sends nothing and returns an Error with `ENOTSUP`.
+    fn reply_with(
+        &self,
+        request: &[u8],
+        request_len: i32,
+        handler: &mut dyn RequestHandler,
+    ) -> Result<i32, Error> {
+        let _ = (request, request_len, handler);
+        Err(Error::Server {
+            msg: "reply_with".to_owned(),
+            source: ::std::io::Error::from_raw_os_error(libc::ENOTSUP),
+        })
+    }
 }
 
 impl ModbusServer for Modbus {
@@ -40,18 +59,7 @@ impl ModbusServer for Modbus {
     /// assert!(modbus.receive(&mut query).is_ok());
     /// ```
     fn receive(&self, request: &mut [u8]) -> Result<i32, Error> {
-        assert!(request.len() <= Modbus::MAX_ADU_LENGTH as usize);
-
-        unsafe {
-            let len = ffi::modbus_receive(self.ctx, request.as_mut_ptr());
-            match len {
-                -1 => Err(Error::Server {
-                    msg: "receive".to_owned(),
-                    source: ::std::io::Error::last_os_error(),
-                }),
-                len => Ok(len),
-            }
-        }
+        self.receive_indication(request, true)
     }
 
     /// `modbus_reply` - send a reponse to the received request
@@ -62,6 +70,12 @@ impl ModbusServer for Modbus {
     /// according to the type of the manipulated data.
     /// If an error occurs, an exception response will be sent.
     ///
+    /// The access regions and validators of the mapping, see
+    /// [`set_access()`](struct.ModbusMapping.html#method.set_access) and
+    /// [`add_validator()`](struct.ModbusMapping.html#method.add_validator), are checked before and the observers
+    /// of the mapping, see [`add_observer()`](struct.ModbusMapping.html#method.add_observer), are notified after
+    /// the request is performed.
+    ///
     /// This function is designed for Modbus server.
     ///
     /// # Examples
@@ -80,6 +94,15 @@ impl ModbusServer for Modbus {
         request_len: i32,
         modbus_mapping: &ModbusMapping,
     ) -> Result<i32, Error> {
+        if let Err(exception) = modbus_mapping.check_request(self, request, request_len) {
+            // requests to the broadcast address are never answered on a serial line
+            let unit = request[self.get_header_length() as usize - 1];
+            if !self.is_tcp() && unit == ffi::MODBUS_BROADCAST_ADDRESS as u8 {
+                return Ok(0);
+            }
+            return self.reply_exception(request, exception);
+        }
+        let pending = modbus_mapping.prepare_write(self, request, request_len);
         unsafe {
             let len = ffi::modbus_reply(
                 self.ctx,
@@ -88,11 +111,117 @@ impl ModbusServer for Modbus {
                 modbus_mapping.modbus_mapping,
             );
             match len {
-                -1 => Err(Error::Server {
-                    msg: "reply".to_owned(),
-                    source: ::std::io::Error::last_os_error(),
-                }),
-                len => Ok(len),
+                -1 => {
+                    let source = ::std::io::Error::last_os_error();
+                    self.stats.record_error(&source);
+                    Err(Error::Server {
+                        msg: "reply".to_owned(),
+                        source,
+                    })
+                }
+                len => {
+                    self.stats.record_reply(len as usize, None);
+                    if let Some(pending) = pending {
+                        modbus_mapping.notify_write(pending);
+                    }
+                    Ok(len)
+                }
+            }
+        }
+    }
+
+    /// `reply_with` - send a response computed by a request handler
+    ///
+    /// The [`reply_with()`](#method.reply_with) function shall decode the received request and call the matching
+    /// method of the [`RequestHandler`](trait.RequestHandler.html) `handler`. The data returned by the handler is
+    /// encoded into a response, an [`Exception`](enum.Exception.html) returned by the handler is sent as exception
+    /// response like [`reply_exception()`](struct.Modbus.html#method.reply_exception) does.
+    ///
+    /// The unit id of the request is passed to [`select_unit()`](trait.RequestHandler.html#method.select_unit)
+    /// first, which may drop the request or answer it with an exception instead.
+    ///
+    /// Malformed requests, e.g. a quantity out of range or a byte count not matching the quantity, are answered
+    /// with `Exception::IllegalDataValue` without calling the handler. Requests sent to the broadcast address on
+    /// a serial line are passed to the handler but never answered.
+    ///
+    /// # Return value
+    ///
+    /// The function returns the length of the response sent (0 if no response was sent) if successful, or an
+    /// Error.
+    ///
+    /// # Parameters
+    ///
+    /// * `request`     - request received with [`receive()`](#method.receive)
+    /// * `request_len` - length of the request returned by [`receive()`](#method.receive)
+    /// * `handler`     - handler answering the request
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use libmodbus::{Exception, Modbus, ModbusServer, ModbusTCP, RequestHandler};
+    ///
+    /// struct Counter(u16);
+    ///
+    /// impl RequestHandler for Counter {
+    ///     fn read_holding_registers(&mut self, address: u16, quantity: u16) -> Result<Vec<u16>, Exception> {
+    ///         self.0 = self.0.wrapping_add(1);
+    ///         Ok(vec![self.0; quantity as usize])
+    ///     }
+    /// }
+    ///
+    /// let mut modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+    /// let mut socket = modbus.tcp_listen(1).unwrap();
+    /// modbus.tcp_accept(&mut socket).unwrap();
+    ///
+    /// let mut counter = Counter(0);
+    /// let mut query = vec![0; Modbus::MAX_ADU_LENGTH as usize];
+    /// loop {
+    ///     match modbus.receive(&mut query) {
+    ///         Ok(len) => modbus.reply_with(&query, len, &mut counter).unwrap(),
+    ///         Err(_) => break,
+    ///     };
+    /// }
+    /// ```
+    fn reply_with(
+        &self,
+        request: &[u8],
+        request_len: i32,
+        handler: &mut dyn RequestHandler,
+    ) -> Result<i32, Error> {
+        self.respond(request, request_len, handler)
+    }
+}
+
+impl Modbus {
+    /// Receive an indication, `record_timeout` is false if a timeout is not an error, e.g. for a server polling a
+    /// stop condition with the indication timeout
+    pub(crate) fn receive_indication(
+        &self,
+        request: &mut [u8],
+        record_timeout: bool,
+    ) -> Result<i32, Error> {
+        assert!(request.len() <= Modbus::MAX_ADU_LENGTH as usize);
+
+        unsafe {
+            let len = ffi::modbus_receive(self.ctx, request.as_mut_ptr());
+            match len {
+                -1 => {
+                    let source = ::std::io::Error::last_os_error();
+                    if record_timeout || source.raw_os_error() != Some(libc::ETIMEDOUT) {
+                        self.stats.record_error(&source);
+                    }
+                    Err(Error::Server {
+                        msg: "receive".to_owned(),
+                        source,
+                    })
+                }
+                len => {
+                    if len > 0 {
+                        let function = request[self.get_header_length() as usize];
+                        self.stats.record_indication(function, len as usize);
+                    }
+                    Ok(len)
+                }
             }
         }
     }
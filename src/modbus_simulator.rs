@@ -0,0 +1,529 @@
+use crate::modbus_serve::serve_with;
+use crate::prelude::*;
+use crate::{Statistics, StopToken, Table};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::f64::consts::PI;
+use std::io;
+use std::time::{Duration, Instant};
+
+/// How a simulated coil, discrete input or register changes over time
+///
+/// Values are computed from the time elapsed since the simulation started. Bits are set for every value other
+/// than 0, values of registers are rounded and clamped to `0..=65535`.
+///
+/// Writes of clients to a point with a behaviour other than `Latch` are overwritten at the next update.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "simulator", derive(serde::Deserialize))]
+#[cfg_attr(
+    feature = "simulator",
+    serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)
+)]
+pub enum Behaviour {
+    /// Always `value`
+    Constant { value: u16 },
+    /// `start`, incremented by `step` every `interval`, wrapping around at 65535
+    Counter {
+        start: u16,
+        step: u16,
+        #[cfg_attr(feature = "simulator", serde(deserialize_with = "seconds"))]
+        interval: Duration,
+    },
+    /// Sawtooth rising from `min` to `max` within `period`, then starting over
+    Ramp {
+        min: f64,
+        max: f64,
+        #[cfg_attr(feature = "simulator", serde(deserialize_with = "seconds"))]
+        period: Duration,
+    },
+    /// `offset + amplitude * sin(2π t / period)`
+    Sine {
+        offset: f64,
+        amplitude: f64,
+        #[cfg_attr(feature = "simulator", serde(deserialize_with = "seconds"))]
+        period: Duration,
+    },
+    /// Starts at `start` and moves by a random amount in `-step..step` every `interval`, within `min..=max`
+    RandomWalk {
+        start: f64,
+        step: f64,
+        min: f64,
+        max: f64,
+        #[cfg_attr(feature = "simulator", serde(deserialize_with = "seconds"))]
+        interval: Duration,
+    },
+    /// The value of another point, e.g. a holding register written by a client mirrored to an input register
+    Mirror { table: Table, address: u16 },
+    /// Starts at `initial` and keeps the value last written by a client
+    Latch { initial: u16 },
+}
+
+/// Durations are given in (fractional) seconds in simulation files
+#[cfg(feature = "simulator")]
+fn seconds<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    let seconds: f64 = serde::Deserialize::deserialize(deserializer)?;
+    match Duration::try_from_secs_f64(seconds) {
+        Ok(duration) if !duration.is_zero() => Ok(duration),
+        _ => Err(serde::de::Error::custom(format!(
+            "duration must be a positive number of seconds, got {}",
+            seconds
+        ))),
+    }
+}
+
+impl Behaviour {
+    /// The duration dividing the elapsed time, must not be zero
+    fn duration(&self) -> Option<Duration> {
+        match *self {
+            Behaviour::Counter { interval, .. } | Behaviour::RandomWalk { interval, .. } => {
+                Some(interval)
+            }
+            Behaviour::Ramp { period, .. } | Behaviour::Sine { period, .. } => Some(period),
+            Behaviour::Constant { .. } | Behaviour::Mirror { .. } | Behaviour::Latch { .. } => None,
+        }
+    }
+}
+
+/// A point of the mapping following a behaviour
+#[derive(Debug)]
+struct Point {
+    table: Table,
+    address: u16,
+    behaviour: Behaviour,
+    /// Current value and number of steps taken of a random walk
+    walk: (f64, u64),
+}
+
+/// Round and clamp a computed value to a register value
+fn register(value: f64) -> u16 {
+    value.round().max(0.0).min(u16::MAX as f64) as u16
+}
+
+/// Simulated device, a mapping whose coils, discrete inputs and registers follow [`Behaviour`](enum.Behaviour.html)s
+///
+/// The simulator owns a [`ModbusMapping`](struct.ModbusMapping.html), points of it are added with a behaviour with
+/// [`add()`](#method.add). [`serve()`](#method.serve) answers requests like [`serve()`](fn.serve.html) does and
+/// updates the points between the requests, at least every 100 ms. Points without behaviour keep their value, or
+/// the value written by a client, like in any mapping.
+///
+/// The context passed to `serve()` decides how the device is reached: TCP, TCP PI or RTU, e.g. on a
+/// [`PseudoTerminal`](struct.PseudoTerminal.html) to test serial clients without hardware.
+///
+/// With the `simulator` feature whole devices are described in TOML or YAML files, see
+/// [`from_toml()`](#method.from_toml), [`from_yaml()`](#method.from_yaml) and [`load()`](#method.load).
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use libmodbus::{Behaviour, Modbus, ModbusMapping, ModbusTCP, Simulator, StopToken, Table};
+/// use std::time::Duration;
+///
+/// let mut simulator = Simulator::new(ModbusMapping::new(0, 0, 10, 10).unwrap());
+/// simulator.add(Table::InputRegisters, 0, Behaviour::Sine {
+///     offset: 1000.0,
+///     amplitude: 500.0,
+///     period: Duration::from_secs(60),
+/// }).unwrap();
+/// simulator.add(Table::InputRegisters, 1, Behaviour::Mirror { table: Table::HoldingRegisters, address: 0 })
+///     .unwrap();
+///
+/// let mut modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+/// let mut socket = modbus.tcp_listen(1).unwrap();
+/// modbus.tcp_accept(&mut socket).unwrap();
+///
+/// simulator.serve(&mut modbus, &StopToken::new()).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct Simulator {
+    mapping: ModbusMapping,
+    points: Vec<Point>,
+    rng: StdRng,
+    start: Instant,
+}
+
+impl Simulator {
+    /// `new` - create a simulator of the points of `mapping`
+    ///
+    /// The simulation time starts now.
+    pub fn new(mapping: ModbusMapping) -> Simulator {
+        Simulator {
+            mapping,
+            points: Vec::new(),
+            rng: StdRng::from_entropy(),
+            start: Instant::now(),
+        }
+    }
+
+    /// `mapping` - the mapping holding the simulated values
+    pub fn mapping(&self) -> &ModbusMapping {
+        &self.mapping
+    }
+
+    /// `set_seed` - seed the random number generator of random walks, for reproducible simulations
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// `add` - let a point of the mapping follow a behaviour
+    ///
+    /// The point is set to the value of the behaviour right away. A point added twice follows the behaviour added
+    /// last.
+    ///
+    /// # Return value
+    ///
+    /// The function returns an Error if the point, or the point a `Behaviour::Mirror` mirrors, is outside of the
+    /// mapping, or if the period or interval of the behaviour is zero.
+    ///
+    /// # Parameters
+    ///
+    /// * `table`       - table of the point
+    /// * `address`     - address of the point
+    /// * `behaviour`   - how the value of the point changes
+    pub fn add(&mut self, table: Table, address: u16, behaviour: Behaviour) -> Result<(), Error> {
+        let invalid = |msg: String| Error::Simulator {
+            msg,
+            source: io::Error::from(io::ErrorKind::InvalidInput),
+        };
+        let outside = |table: Table, address: u16| {
+            invalid(format!("{:?} {} outside of the mapping", table, address))
+        };
+
+        self.mapping
+            .get_value(table, address)
+            .map_err(|_| outside(table, address))?;
+        if let Behaviour::Mirror {
+            table: source,
+            address: source_address,
+        } = behaviour
+        {
+            self.mapping
+                .get_value(source, source_address)
+                .map_err(|_| outside(source, source_address))?;
+        }
+        if behaviour.duration() == Some(Duration::from_secs(0)) {
+            return Err(invalid(format!(
+                "{:?} {} with a period or interval of zero",
+                table, address
+            )));
+        }
+
+        self.points
+            .retain(|point| (point.table, point.address) != (table, address));
+        let walk = match behaviour {
+            Behaviour::RandomWalk { start, .. } => (start, 0),
+            _ => (0.0, 0),
+        };
+        let initial = match behaviour {
+            Behaviour::Latch { initial } => Some(initial),
+            _ => None,
+        };
+        self.points.push(Point {
+            table,
+            address,
+            behaviour,
+            walk,
+        });
+
+        if let Some(initial) = initial {
+            // a latch is set once, not at every update
+            let _ = self.mapping.set_value(table, address, initial);
+        }
+        self.update();
+        Ok(())
+    }
+
+    /// `update` - set the points to the values of their behaviours now
+    ///
+    /// [`serve()`](#method.serve) calls this function between requests. Call it yourself to use the simulated
+    /// values without a server loop, e.g. with a [`TcpServer`](struct.TcpServer.html).
+    pub fn update(&mut self) {
+        let elapsed = self.start.elapsed();
+        self.update_at(elapsed);
+    }
+
+    /// `update_at` - set the points to the values of their behaviours `elapsed` after the start of the simulation
+    ///
+    /// Random walks only move forward, they take the steps due until `elapsed`.
+    pub fn update_at(&mut self, elapsed: Duration) {
+        let Simulator {
+            mapping,
+            points,
+            rng,
+            ..
+        } = self;
+        Simulator::update_points(mapping, points, rng, elapsed);
+    }
+
+    fn update_points(
+        mapping: &ModbusMapping,
+        points: &mut [Point],
+        rng: &mut StdRng,
+        elapsed: Duration,
+    ) {
+        let t = elapsed.as_secs_f64();
+        // mirrors last, so they see the values of this update
+        for mirrors in [false, true].iter() {
+            for point in points.iter_mut() {
+                let value = match point.behaviour {
+                    Behaviour::Mirror { table, address } if *mirrors => {
+                        mapping.get_value(table, address).ok()
+                    }
+                    Behaviour::Mirror { .. } => None,
+                    _ if *mirrors => None,
+                    Behaviour::Constant { value } => Some(value),
+                    Behaviour::Counter {
+                        start,
+                        step,
+                        interval,
+                    } => {
+                        let steps = (t / interval.as_secs_f64()) as u64;
+                        Some(start.wrapping_add(step.wrapping_mul(steps as u16)))
+                    }
+                    Behaviour::Ramp { min, max, period } => Some(register(
+                        min + (max - min) * (t / period.as_secs_f64()).fract(),
+                    )),
+                    Behaviour::Sine {
+                        offset,
+                        amplitude,
+                        period,
+                    } => Some(register(
+                        offset + amplitude * (2.0 * PI * t / period.as_secs_f64()).sin(),
+                    )),
+                    Behaviour::RandomWalk {
+                        step,
+                        min,
+                        max,
+                        interval,
+                        ..
+                    } => {
+                        let (ref mut value, ref mut taken) = point.walk;
+                        let steps = (t / interval.as_secs_f64()) as u64;
+                        while *taken < steps {
+                            if step > 0.0 {
+                                *value += rng.gen_range(-step, step);
+                            }
+                            *value = value.max(min).min(max);
+                            *taken += 1;
+                        }
+                        Some(register(*value))
+                    }
+                    Behaviour::Latch { .. } => None,
+                };
+                if let Some(value) = value {
+                    let _ = mapping.set_value(point.table, point.address, value);
+                }
+            }
+        }
+    }
+
+    /// `serve` - answer requests from the simulated mapping until stopped
+    ///
+    /// Like [`serve()`](fn.serve.html), the points are updated before each request and at least every 100 ms, and
+    /// only [`Modbus`](struct.Modbus.html) contexts are served. A [`ModbusStream`](struct.ModbusStream.html) can
+    /// answer from [`mapping()`](#method.mapping) in a loop of its own, calling [`update()`](#method.update).
+    ///
+    /// # Return value
+    ///
+    /// The function returns the [`Statistics`](struct.Statistics.html) of the context when the loop was stopped,
+    /// otherwise an `Error::Serve` with the Error which ended the loop and the statistics gathered until then.
+    ///
+    /// # Parameters
+    ///
+    /// * `modbus`  - context to receive the requests with, a TCP context must be connected to a client
+    /// * `stop`    - when to stop the loop
+    pub fn serve(&mut self, modbus: &mut Modbus, stop: &StopToken) -> Result<Statistics, Error> {
+        let Simulator {
+            mapping,
+            points,
+            rng,
+            start,
+        } = self;
+        serve_with(modbus, mapping, stop, |mapping| {
+            Simulator::update_points(mapping, points, rng, start.elapsed())
+        })
+    }
+}
+
+/// A simulation file
+#[cfg(feature = "simulator")]
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    seed: Option<u64>,
+    #[serde(default)]
+    tables: Tables,
+    #[serde(default, rename = "point")]
+    points: Vec<PointConfig>,
+}
+
+#[cfg(feature = "simulator")]
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Tables {
+    #[serde(default)]
+    coils: Block,
+    #[serde(default)]
+    discrete_inputs: Block,
+    #[serde(default)]
+    holding_registers: Block,
+    #[serde(default)]
+    input_registers: Block,
+}
+
+#[cfg(feature = "simulator")]
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Block {
+    #[serde(default)]
+    start: u16,
+    count: u16,
+}
+
+#[cfg(feature = "simulator")]
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PointConfig {
+    table: Table,
+    address: u16,
+    behaviour: Behaviour,
+}
+
+#[cfg(feature = "simulator")]
+impl Simulator {
+    /// `from_toml` - create a simulator from a simulation in TOML
+    ///
+    /// The `tables` section sets the start address and number of values of the tables of the mapping, omitted
+    /// tables are empty. Every `point` follows a [`Behaviour`](enum.Behaviour.html), given as inline table with the
+    /// name of the behaviour as `type` in snake case and its fields. Durations are given in seconds. The optional
+    /// `seed` makes random walks reproducible.
+    ///
+    /// Only available with the `simulator` feature.
+    ///
+    /// # Return value
+    ///
+    /// The function returns the simulator if successful, otherwise an Error describing the invalid simulation.
+    ///
+    /// # Parameters
+    ///
+    /// * `simulation`  - the simulation as TOML
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use libmodbus::{Simulator, Table};
+    ///
+    /// let simulator = Simulator::from_toml(r#"
+    ///     [tables]
+    ///     coils = { count = 8 }
+    ///     holding_registers = { start = 40000, count = 10 }
+    ///     input_registers = { count = 10 }
+    ///
+    ///     [[point]]
+    ///     table = "input_registers"
+    ///     address = 0
+    ///     behaviour = { type = "counter", start = 0, step = 1, interval = 1.0 }
+    ///
+    ///     [[point]]
+    ///     table = "input_registers"
+    ///     address = 1
+    ///     behaviour = { type = "mirror", table = "holding_registers", address = 40000 }
+    ///
+    ///     [[point]]
+    ///     table = "coils"
+    ///     address = 0
+    ///     behaviour = { type = "latch", initial = 1 }
+    /// "#).unwrap();
+    ///
+    /// assert_eq!(simulator.mapping().get_bits()[0], 1);
+    /// ```
+    pub fn from_toml(simulation: &str) -> Result<Simulator, Error> {
+        let config: Config = toml::from_str(simulation).map_err(|err| Error::Simulator {
+            msg: format!("invalid simulation: {}", err),
+            source: io::Error::new(io::ErrorKind::InvalidData, err),
+        })?;
+        Simulator::from_config(config)
+    }
+
+    /// `from_yaml` - create a simulator from a simulation in YAML
+    ///
+    /// The simulation has the same structure as in [`from_toml()`](#method.from_toml): the optional `seed`, the
+    /// `tables` mapping and the list of points under `point`.
+    ///
+    /// Only available with the `simulator` feature.
+    ///
+    /// # Return value
+    ///
+    /// The function returns the simulator if successful, otherwise an Error describing the invalid simulation.
+    ///
+    /// # Parameters
+    ///
+    /// * `simulation`  - the simulation as YAML
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use libmodbus::Simulator;
+    ///
+    /// let simulator = Simulator::from_yaml("
+    /// tables:
+    ///   input_registers: { count: 10 }
+    /// point:
+    ///   - table: input_registers
+    ///     address: 0
+    ///     behaviour: { type: constant, value: 42 }
+    /// ").unwrap();
+    ///
+    /// assert_eq!(simulator.mapping().get_input_registers()[0], 42);
+    /// ```
+    pub fn from_yaml(simulation: &str) -> Result<Simulator, Error> {
+        let config: Config = serde_yaml::from_str(simulation).map_err(|err| Error::Simulator {
+            msg: format!("invalid simulation: {}", err),
+            source: io::Error::new(io::ErrorKind::InvalidData, err),
+        })?;
+        Simulator::from_config(config)
+    }
+
+    fn from_config(config: Config) -> Result<Simulator, Error> {
+        let tables = &config.tables;
+        let mapping = ModbusMapping::new_start_address(
+            tables.coils.start,
+            tables.coils.count,
+            tables.discrete_inputs.start,
+            tables.discrete_inputs.count,
+            tables.holding_registers.start,
+            tables.holding_registers.count,
+            tables.input_registers.start,
+            tables.input_registers.count,
+        )?;
+        let mut simulator = Simulator::new(mapping);
+        if let Some(seed) = config.seed {
+            simulator.set_seed(seed);
+        }
+        for point in config.points {
+            simulator.add(point.table, point.address, point.behaviour)?;
+        }
+        Ok(simulator)
+    }
+
+    /// `load` - create a simulator from a simulation file
+    ///
+    /// Files ending in `.yaml` or `.yml` are read as YAML, see [`from_yaml()`](#method.from_yaml), all others as
+    /// TOML, see [`from_toml()`](#method.from_toml).
+    ///
+    /// Only available with the `simulator` feature.
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> Result<Simulator, Error> {
+        let simulation =
+            std::fs::read_to_string(path.as_ref()).map_err(|source| Error::Simulator {
+                msg: format!("could not read {}", path.as_ref().display()),
+                source,
+            })?;
+        match path
+            .as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+        {
+            Some("yaml") | Some("yml") => Simulator::from_yaml(&simulation),
+            _ => Simulator::from_toml(&simulation),
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use crate::prelude::*;
+use std::io;
+
+/// Magic and version of the binary format, see [`Snapshot::to_bytes()`](struct.Snapshot.html#method.to_bytes)
+const MAGIC: &[u8; 4] = b"MBS1";
+
+/// Contents of the four tables of a [`ModbusMapping`](struct.ModbusMapping.html), with their start addresses
+///
+/// Taken with [`snapshot()`](struct.ModbusMapping.html#method.snapshot) and loaded back with
+/// [`restore()`](struct.ModbusMapping.html#method.restore). Bits are stored as 0 or 1, like in the mapping.
+///
+/// A snapshot is written as compact binary with [`to_bytes()`](#method.to_bytes) or as CSV with
+/// [`to_csv()`](#method.to_csv). With the `serde` feature it implements `Serialize` and `Deserialize`, e.g. for
+/// JSON.
+///
+/// # Examples
+///
+/// ```rust
+/// use libmodbus::Snapshot;
+///
+/// let snapshot = Snapshot {
+///     start_registers: 100,
+///     registers: vec![1, 2, 3],
+///     ..Snapshot::default()
+/// };
+///
+/// assert_eq!(Snapshot::from_bytes(&snapshot.to_bytes()).unwrap(), snapshot);
+/// assert_eq!(Snapshot::from_csv(&snapshot.to_csv()).unwrap(), snapshot);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot {
+    pub start_bits: u16,
+    pub bits: Vec<u8>,
+    pub start_input_bits: u16,
+    pub input_bits: Vec<u8>,
+    pub start_registers: u16,
+    pub registers: Vec<u16>,
+    pub start_input_registers: u16,
+    pub input_registers: Vec<u16>,
+}
+
+/// An invalid binary or CSV snapshot
+fn invalid(msg: String) -> Error {
+    Error::Mapping {
+        msg,
+        source: io::Error::from(io::ErrorKind::InvalidData),
+    }
+}
+
+/// Table names of the CSV format
+const TABLES: [&str; 4] = [
+    "coils",
+    "discrete_inputs",
+    "holding_registers",
+    "input_registers",
+];
+
+impl Snapshot {
+    /// `to_bytes` - the snapshot in a compact binary format
+    ///
+    /// The format is the magic `MBS1` followed by the four tables coils, discrete inputs, holding registers and
+    /// input registers. Every table is its start address (u16) and number of values (u32), then the bits packed
+    /// eight per byte, least significant bit first, or the registers. All numbers are big endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        for (start, bits) in [
+            (self.start_bits, &self.bits),
+            (self.start_input_bits, &self.input_bits),
+        ]
+        .iter()
+        {
+            bytes.extend_from_slice(&start.to_be_bytes());
+            bytes.extend_from_slice(&(bits.len() as u32).to_be_bytes());
+            for chunk in bits.chunks(8) {
+                let byte = chunk.iter().enumerate().fold(0u8, |byte, (bit, &value)| {
+                    byte | (((value != 0) as u8) << bit)
+                });
+                bytes.push(byte);
+            }
+        }
+        for (start, registers) in [
+            (self.start_registers, &self.registers),
+            (self.start_input_registers, &self.input_registers),
+        ]
+        .iter()
+        {
+            bytes.extend_from_slice(&start.to_be_bytes());
+            bytes.extend_from_slice(&(registers.len() as u32).to_be_bytes());
+            for register in registers.iter() {
+                bytes.extend_from_slice(&register.to_be_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// `from_bytes` - read a snapshot written by [`to_bytes()`](#method.to_bytes)
+    ///
+    /// # Return value
+    ///
+    /// The function returns the snapshot if successful, otherwise an Error if `bytes` is not a valid snapshot.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Snapshot, Error> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(invalid("not a snapshot".to_owned()));
+        }
+        let mut reader = Reader(&bytes[MAGIC.len()..]);
+
+        let mut snapshot = Snapshot::default();
+        for (start, bits) in [
+            (&mut snapshot.start_bits, &mut snapshot.bits),
+            (&mut snapshot.start_input_bits, &mut snapshot.input_bits),
+        ]
+        .iter_mut()
+        {
+            let (address, count) = reader.header()?;
+            let packed = reader.take(count.div_ceil(8))?;
+            **start = address;
+            **bits = (0..count)
+                .map(|bit| (packed[bit / 8] >> (bit % 8)) & 1)
+                .collect();
+        }
+        for (start, registers) in [
+            (&mut snapshot.start_registers, &mut snapshot.registers),
+            (
+                &mut snapshot.start_input_registers,
+                &mut snapshot.input_registers,
+            ),
+        ]
+        .iter_mut()
+        {
+            let (address, count) = reader.header()?;
+            **start = address;
+            **registers = reader
+                .take(2 * count)?
+                .chunks(2)
+                .map(|value| u16::from_be_bytes([value[0], value[1]]))
+                .collect();
+        }
+        if !reader.0.is_empty() {
+            return Err(invalid("trailing bytes after the snapshot".to_owned()));
+        }
+        Ok(snapshot)
+    }
+
+    /// `to_csv` - the snapshot as CSV
+    ///
+    /// A header line `table,address,value` is followed by one line per value, e.g. `holding_registers,40001,17`.
+    /// The tables are named `coils`, `discrete_inputs`, `holding_registers` and `input_registers`. The start
+    /// address of an empty table is not stored.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("table,address,value\n");
+        let tables = [
+            (
+                self.start_bits,
+                self.bits.iter().map(|&bit| bit as u16).collect::<Vec<_>>(),
+            ),
+            (
+                self.start_input_bits,
+                self.input_bits.iter().map(|&bit| bit as u16).collect(),
+            ),
+            (self.start_registers, self.registers.clone()),
+            (self.start_input_registers, self.input_registers.clone()),
+        ];
+        for (name, (start, values)) in TABLES.iter().zip(tables.iter()) {
+            for (offset, value) in values.iter().enumerate() {
+                csv.push_str(&format!(
+                    "{},{},{}\n",
+                    name,
+                    *start as usize + offset,
+                    value
+                ));
+            }
+        }
+        csv
+    }
+
+    /// `from_csv` - read a snapshot written by [`to_csv()`](#method.to_csv)
+    ///
+    /// The lines of a table may be in any position, but their addresses must be ascending without gaps. Empty
+    /// lines are ignored.
+    ///
+    /// # Return value
+    ///
+    /// The function returns the snapshot if successful, otherwise an Error naming the first invalid line.
+    pub fn from_csv(csv: &str) -> Result<Snapshot, Error> {
+        let mut lines = csv
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty());
+        match lines.next() {
+            Some((_, header)) if header.trim() == "table,address,value" => {}
+            _ => return Err(invalid("missing header table,address,value".to_owned())),
+        }
+
+        // start address and values of the four tables
+        let mut tables: [(u16, Vec<u16>); 4] = Default::default();
+        for (index, line) in lines {
+            let error = |msg: &str| invalid(format!("line {}: {}", index + 1, msg));
+            let fields: Vec<&str> = line.trim().split(',').map(str::trim).collect();
+            let (name, address, value) = match fields[..] {
+                [name, address, value] => (name, address, value),
+                _ => return Err(error("expected table,address,value")),
+            };
+            let table = TABLES
+                .iter()
+                .position(|&table| table == name)
+                .ok_or_else(|| error("unknown table"))?;
+            let address: u16 = address.parse().map_err(|_| error("invalid address"))?;
+            let value: u16 = value.parse().map_err(|_| error("invalid value"))?;
+            if table < 2 && value > 1 {
+                return Err(error("bits must be 0 or 1"));
+            }
+
+            let (ref mut start, ref mut values) = tables[table];
+            if values.is_empty() {
+                *start = address;
+            } else if address as usize != *start as usize + values.len() {
+                return Err(error(
+                    "address not following the previous address of the table",
+                ));
+            }
+            values.push(value);
+        }
+
+        let [coils, discrete_inputs, holding_registers, input_registers] = tables;
+        let bits = |values: Vec<u16>| values.into_iter().map(|bit| bit as u8).collect();
+        Ok(Snapshot {
+            start_bits: coils.0,
+            bits: bits(coils.1),
+            start_input_bits: discrete_inputs.0,
+            input_bits: bits(discrete_inputs.1),
+            start_registers: holding_registers.0,
+            registers: holding_registers.1,
+            start_input_registers: input_registers.0,
+            input_registers: input_registers.1,
+        })
+    }
+}
+
+/// Reads the tables of a binary snapshot
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.0.len() < len {
+            return Err(invalid("snapshot truncated".to_owned()));
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    /// Start address and number of values of a table
+    fn header(&mut self) -> Result<(u16, usize), Error> {
+        let start = self.take(2)?;
+        let start = u16::from_be_bytes([start[0], start[1]]);
+        let count = self.take(4)?;
+        let count = u32::from_be_bytes([count[0], count[1], count[2], count[3]]) as usize;
+        if start as usize + count > 0x1_0000 {
+            return Err(invalid(format!(
+                "table {}..{} outside of the address space",
+                start,
+                start as usize + count
+            )));
+        }
+        Ok((start, count))
+    }
+}
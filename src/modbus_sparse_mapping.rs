@@ -0,0 +1,269 @@
+use crate::prelude::*;
+use crate::{Exception, RequestHandler, Table};
+use std::io;
+
+/// Contiguous values of a table from `start` on
+#[derive(Debug, Clone)]
+struct Block<T> {
+    start: u32,
+    values: Vec<T>,
+}
+
+impl<T> Block<T> {
+    fn end(&self) -> u32 {
+        self.start + self.values.len() as u32
+    }
+}
+
+/// The blocks of one table, sorted by start address, neither overlapping nor adjacent
+#[derive(Debug, Clone)]
+struct Blocks<T>(Vec<Block<T>>);
+
+impl<T: Copy + Default> Blocks<T> {
+    /// Add a block, merged with the blocks it touches. Fails if the block overlaps an existing one.
+    fn add(&mut self, address: u16, count: u16) -> Result<(), Error> {
+        let (start, end) = (address as u32, address as u32 + count as u32);
+        if count == 0 || end > 0x1_0000 {
+            return Err(Error::Mapping {
+                msg: format!("block {}..{} outside of the address space", start, end),
+                source: io::Error::from(io::ErrorKind::InvalidInput),
+            });
+        }
+        let index = self.0.partition_point(|block| block.start < start);
+        let overlaps_previous = index > 0 && self.0[index - 1].end() > start;
+        let overlaps_next = index < self.0.len() && self.0[index].start < end;
+        if overlaps_previous || overlaps_next {
+            return Err(Error::Mapping {
+                msg: format!("block {}..{} overlaps another block", start, end),
+                source: io::Error::from(io::ErrorKind::AlreadyExists),
+            });
+        }
+
+        self.0.insert(
+            index,
+            Block {
+                start,
+                values: vec![T::default(); count as usize],
+            },
+        );
+        // merge with the next and the previous block if they are adjacent
+        if index + 1 < self.0.len() && self.0[index + 1].start == end {
+            let next = self.0.remove(index + 1);
+            self.0[index].values.extend(next.values);
+        }
+        if index > 0 && self.0[index - 1].end() == start {
+            let block = self.0.remove(index);
+            self.0[index - 1].values.extend(block.values);
+        }
+        Ok(())
+    }
+
+    /// The block holding `address`
+    fn find(&self, address: u32) -> Option<usize> {
+        let index = self.0.partition_point(|block| block.start <= address);
+        if index > 0 && address < self.0[index - 1].end() {
+            Some(index - 1)
+        } else {
+            None
+        }
+    }
+
+    /// `count` values from `address`, if they are all in one block
+    fn get(&self, address: u16, count: usize) -> Option<&[T]> {
+        let block = &self.0[self.find(address as u32)?];
+        let offset = (address as u32 - block.start) as usize;
+        block.values.get(offset..offset + count)
+    }
+
+    fn get_mut(&mut self, address: u16, count: usize) -> Option<&mut [T]> {
+        let index = self.find(address as u32)?;
+        let block = &mut self.0[index];
+        let offset = (address as u32 - block.start) as usize;
+        block.values.get_mut(offset..offset + count)
+    }
+}
+
+/// Mapping made of several blocks per table, for large address spaces with gaps
+///
+/// A [`ModbusMapping`](struct.ModbusMapping.html) holds one contiguous block per table, from its start address
+/// on. Devices with addresses like 0–99, 3000–3100 and 40000–40200 would need the whole range in between. A
+/// `SparseMapping` allocates memory only for the blocks added with [`add_block()`](#method.add_block), every
+/// block with its own start address. Requests for addresses outside of the blocks, including requests crossing a
+/// gap between two blocks, are answered with `Exception::IllegalDataAddress`.
+///
+/// A `SparseMapping` is a [`RequestHandler`](trait.RequestHandler.html), answer requests with it with
+/// [`reply_with()`](struct.Modbus.html#method.reply_with).
+///
+/// # Examples
+///
+/// ```rust
+/// use libmodbus::{SparseMapping, Table};
+///
+/// let mut mapping = SparseMapping::new();
+/// mapping.add_block(Table::HoldingRegisters, 0, 100).unwrap();
+/// mapping.add_block(Table::HoldingRegisters, 3000, 101).unwrap();
+/// mapping.add_block(Table::HoldingRegisters, 40000, 201).unwrap();
+///
+/// mapping.get_registers_mut(Table::HoldingRegisters, 3000, 2).unwrap().copy_from_slice(&[1, 2]);
+/// assert_eq!(mapping.get_registers(Table::HoldingRegisters, 3000, 3), Some(&[1, 2, 0][..]));
+/// assert_eq!(mapping.get_registers(Table::HoldingRegisters, 99, 2), None);
+/// ```
+#[derive(Debug, Clone)]
+pub struct SparseMapping {
+    coils: Blocks<bool>,
+    discrete_inputs: Blocks<bool>,
+    holding_registers: Blocks<u16>,
+    input_registers: Blocks<u16>,
+}
+
+impl Default for SparseMapping {
+    fn default() -> SparseMapping {
+        SparseMapping {
+            coils: Blocks(Vec::new()),
+            discrete_inputs: Blocks(Vec::new()),
+            holding_registers: Blocks(Vec::new()),
+            input_registers: Blocks(Vec::new()),
+        }
+    }
+}
+
+impl SparseMapping {
+    /// `new` - create a mapping without any block
+    pub fn new() -> SparseMapping {
+        SparseMapping::default()
+    }
+
+    /// `add_block` - add a block of `count` values, initialized to 0, from `address` on to a table
+    ///
+    /// A block adjacent to another block of the table is merged with it, so requests may span both.
+    ///
+    /// # Return value
+    ///
+    /// The function returns an Error if the block overlaps a block of the table or exceeds the address space.
+    ///
+    /// # Parameters
+    ///
+    /// * `table`   - table to add the block to
+    /// * `address` - first address of the block
+    /// * `count`   - number of values of the block
+    pub fn add_block(&mut self, table: Table, address: u16, count: u16) -> Result<(), Error> {
+        match table {
+            Table::Coils => self.coils.add(address, count),
+            Table::DiscreteInputs => self.discrete_inputs.add(address, count),
+            Table::HoldingRegisters => self.holding_registers.add(address, count),
+            Table::InputRegisters => self.input_registers.add(address, count),
+        }
+    }
+
+    /// `blocks` - the blocks of a table as (start address, number of values), sorted by address
+    pub fn blocks(&self, table: Table) -> Vec<(u16, usize)> {
+        fn ranges<T>(blocks: &Blocks<T>) -> Vec<(u16, usize)> {
+            blocks
+                .0
+                .iter()
+                .map(|block| (block.start as u16, block.values.len()))
+                .collect()
+        }
+        match table {
+            Table::Coils => ranges(&self.coils),
+            Table::DiscreteInputs => ranges(&self.discrete_inputs),
+            Table::HoldingRegisters => ranges(&self.holding_registers),
+            Table::InputRegisters => ranges(&self.input_registers),
+        }
+    }
+
+    /// `get_bits` - `count` coils or discrete inputs from `address` on
+    ///
+    /// Returns `None` if the values are not all in one block or `table` is a register table.
+    pub fn get_bits(&self, table: Table, address: u16, count: u16) -> Option<&[bool]> {
+        match table {
+            Table::Coils => self.coils.get(address, count as usize),
+            Table::DiscreteInputs => self.discrete_inputs.get(address, count as usize),
+            _ => None,
+        }
+    }
+
+    /// `get_bits_mut` - mutable `count` coils or discrete inputs from `address` on
+    ///
+    /// Returns `None` if the values are not all in one block or `table` is a register table.
+    pub fn get_bits_mut(&mut self, table: Table, address: u16, count: u16) -> Option<&mut [bool]> {
+        match table {
+            Table::Coils => self.coils.get_mut(address, count as usize),
+            Table::DiscreteInputs => self.discrete_inputs.get_mut(address, count as usize),
+            _ => None,
+        }
+    }
+
+    /// `get_registers` - `count` holding or input registers from `address` on
+    ///
+    /// Returns `None` if the values are not all in one block or `table` is a bit table.
+    pub fn get_registers(&self, table: Table, address: u16, count: u16) -> Option<&[u16]> {
+        match table {
+            Table::HoldingRegisters => self.holding_registers.get(address, count as usize),
+            Table::InputRegisters => self.input_registers.get(address, count as usize),
+            _ => None,
+        }
+    }
+
+    /// `get_registers_mut` - mutable `count` holding or input registers from `address` on
+    ///
+    /// Returns `None` if the values are not all in one block or `table` is a bit table.
+    pub fn get_registers_mut(
+        &mut self,
+        table: Table,
+        address: u16,
+        count: u16,
+    ) -> Option<&mut [u16]> {
+        match table {
+            Table::HoldingRegisters => self.holding_registers.get_mut(address, count as usize),
+            Table::InputRegisters => self.input_registers.get_mut(address, count as usize),
+            _ => None,
+        }
+    }
+}
+
+impl RequestHandler for SparseMapping {
+    fn read_coils(&mut self, address: u16, quantity: u16) -> Result<Vec<bool>, Exception> {
+        self.get_bits(Table::Coils, address, quantity)
+            .map(|values| values.to_vec())
+            .ok_or(Exception::IllegalDataAddress)
+    }
+
+    fn read_discrete_inputs(
+        &mut self,
+        address: u16,
+        quantity: u16,
+    ) -> Result<Vec<bool>, Exception> {
+        self.get_bits(Table::DiscreteInputs, address, quantity)
+            .map(|values| values.to_vec())
+            .ok_or(Exception::IllegalDataAddress)
+    }
+
+    fn read_holding_registers(
+        &mut self,
+        address: u16,
+        quantity: u16,
+    ) -> Result<Vec<u16>, Exception> {
+        self.get_registers(Table::HoldingRegisters, address, quantity)
+            .map(|values| values.to_vec())
+            .ok_or(Exception::IllegalDataAddress)
+    }
+
+    fn read_input_registers(&mut self, address: u16, quantity: u16) -> Result<Vec<u16>, Exception> {
+        self.get_registers(Table::InputRegisters, address, quantity)
+            .map(|values| values.to_vec())
+            .ok_or(Exception::IllegalDataAddress)
+    }
+
+    fn write_multiple_coils(&mut self, address: u16, values: &[bool]) -> Result<(), Exception> {
+        self.get_bits_mut(Table::Coils, address, values.len() as u16)
+            .map(|bits| bits.copy_from_slice(values))
+            .ok_or(Exception::IllegalDataAddress)
+    }
+
+    fn write_multiple_registers(&mut self, address: u16, values: &[u16]) -> Result<(), Exception> {
+        self.get_registers_mut(Table::HoldingRegisters, address, values.len() as u16)
+            .map(|registers| registers.copy_from_slice(values))
+            .ok_or(Exception::IllegalDataAddress)
+    }
+}
@@ -0,0 +1,391 @@
+use crate::prelude::*;
+use crate::{error, Exception, FunctionCode};
+use libc::c_int;
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Upper bounds (in microseconds) of the latency histogram buckets. Everything slower than the last bound is
+/// counted in an additional overflow bucket.
+const LATENCY_BOUNDS_US: [u64; 14] = [
+    500, 1_000, 2_000, 5_000, 10_000, 20_000, 50_000, 100_000, 200_000, 500_000, 1_000_000,
+    2_000_000, 5_000_000, 10_000_000,
+];
+
+/// Latency histogram with fixed buckets from 500µs up to 10s
+///
+/// Each recorded latency is counted in the first bucket whose upper bound is greater or equal to the latency.
+/// Latencies above 10s end up in an overflow bucket without upper bound.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    counts: [u64; LATENCY_BOUNDS_US.len() + 1],
+    count: u64,
+    sum: Duration,
+    min: Option<Duration>,
+    max: Option<Duration>,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> LatencyHistogram {
+        LatencyHistogram {
+            counts: [0; LATENCY_BOUNDS_US.len() + 1],
+            count: 0,
+            sum: Duration::from_secs(0),
+            min: None,
+            max: None,
+        }
+    }
+}
+
+impl LatencyHistogram {
+    /// `record` - add one latency measurement to the histogram
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use libmodbus::LatencyHistogram;
+    /// use std::time::Duration;
+    ///
+    /// let mut histogram = LatencyHistogram::default();
+    /// histogram.record(Duration::from_millis(3));
+    ///
+    /// assert_eq!(histogram.count(), 1);
+    /// ```
+    pub fn record(&mut self, latency: Duration) {
+        let micros = latency.as_micros();
+        let index = LATENCY_BOUNDS_US
+            .iter()
+            .position(|&bound| micros <= bound as u128)
+            .unwrap_or(LATENCY_BOUNDS_US.len());
+
+        self.counts[index] += 1;
+        self.count += 1;
+        self.sum += latency;
+        self.min = Some(self.min.map_or(latency, |min| min.min(latency)));
+        self.max = Some(self.max.map_or(latency, |max| max.max(latency)));
+    }
+
+    /// Number of recorded measurements
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Smallest recorded latency, `None` if nothing was recorded yet
+    pub fn min(&self) -> Option<Duration> {
+        self.min
+    }
+
+    /// Largest recorded latency, `None` if nothing was recorded yet
+    pub fn max(&self) -> Option<Duration> {
+        self.max
+    }
+
+    /// Arithmetic mean of all recorded latencies, `None` if nothing was recorded yet
+    pub fn mean(&self) -> Option<Duration> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(Duration::from_nanos(
+                (self.sum.as_nanos() / self.count as u128) as u64,
+            ))
+        }
+    }
+
+    /// `buckets` - the histogram buckets
+    ///
+    /// Returns the upper bound of every bucket together with the number of measurements counted in it.
+    /// The last bucket is the overflow bucket, its upper bound is `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use libmodbus::LatencyHistogram;
+    /// use std::time::Duration;
+    ///
+    /// let mut histogram = LatencyHistogram::default();
+    /// histogram.record(Duration::from_millis(3));
+    ///
+    /// let (bound, count) = histogram.buckets().find(|(_, count)| *count > 0).unwrap();
+    /// assert_eq!(bound, Some(Duration::from_millis(5)));
+    /// assert_eq!(count, 1);
+    /// ```
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<Duration>, u64)> + '_ {
+        LATENCY_BOUNDS_US
+            .iter()
+            .map(|&bound| Some(Duration::from_micros(bound)))
+            .chain(std::iter::once(None))
+            .zip(self.counts.iter().cloned())
+    }
+
+    /// `percentile` - estimate a percentile from the buckets
+    ///
+    /// Returns the upper bound of the bucket containing the requested percentile (`0.0` to `100.0`). If the
+    /// percentile lies in the overflow bucket the largest recorded latency is returned.
+    /// Returns `None` if nothing was recorded yet.
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = ((percentile.clamp(0.0, 100.0) / 100.0) * self.count as f64).ceil() as u64;
+        let mut seen = 0;
+        for (bound, count) in self.buckets() {
+            seen += count;
+            if seen >= rank.max(1) {
+                return bound.or(self.max);
+            }
+        }
+        self.max
+    }
+}
+
+/// Communication statistics of a [`Modbus`](struct.Modbus.html) context
+///
+/// This is a snapshot, returned by [`Modbus::statistics()`](struct.Modbus.html#method.statistics) or
+/// [`StatisticsHandle::snapshot()`](struct.StatisticsHandle.html#method.snapshot).
+///
+/// * `requests` - sent (client) or received (server) requests, by function code
+/// * `exceptions` - exception responses received (client) or sent (server), by exception
+/// * `timeouts` - requests or indications which timed out
+/// * `crc_errors` - messages dropped because of an invalid CRC (RTU only)
+/// * `framing_errors` - malformed or unexpected messages, e.g. wrong length, slave or function code
+/// * `reconnects` - successful [`connect()`](struct.Modbus.html#method.connect) calls after the first one
+/// * `bytes_sent`, `bytes_received` - transferred ADU bytes, including header and checksum
+/// * `latency` - histogram of the time between request and response
+///
+/// The byte counters of the client calls of a libmodbus context are estimates: libmodbus doesn't tell how many bytes
+/// went over the wire, so they are computed from the function code and the quantities of each request, and a
+/// failed request counts the length of an exception response. Server contexts and the backends implemented in Rust,
+/// see [`ModbusStream`](struct.ModbusStream.html), count the bytes actually sent and received.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Statistics {
+    pub requests: HashMap<u8, u64>,
+    pub exceptions: HashMap<Exception, u64>,
+    pub timeouts: u64,
+    pub crc_errors: u64,
+    pub framing_errors: u64,
+    pub reconnects: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub latency: LatencyHistogram,
+}
+
+impl Statistics {
+    /// Number of requests over all function codes
+    pub fn total_requests(&self) -> u64 {
+        self.requests.values().sum()
+    }
+
+    /// Number of requests for the given function code
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use libmodbus::{FunctionCode, Statistics};
+    ///
+    /// let statistics = Statistics::default();
+    /// assert_eq!(statistics.requests_for(FunctionCode::ReadHoldingRegisters), 0);
+    /// ```
+    pub fn requests_for(&self, function: FunctionCode) -> u64 {
+        self.requests.get(&(function as u8)).cloned().unwrap_or(0)
+    }
+
+    /// Number of exception responses over all exception codes
+    pub fn total_exceptions(&self) -> u64 {
+        self.exceptions.values().sum()
+    }
+}
+
+#[derive(Debug, Default)]
+struct Recorder {
+    statistics: Statistics,
+    connected: bool,
+    indication_received: Option<Instant>,
+}
+
+/// Shared handle to the statistics of a [`Modbus`](struct.Modbus.html) context
+///
+/// The handle can be cloned and moved to another thread, e.g. to a monitoring task, while the context keeps
+/// recording into it.
+///
+/// # Examples
+///
+/// ```rust
+/// use libmodbus::{Modbus, ModbusTCP};
+///
+/// let modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+/// let handle = modbus.statistics_handle();
+///
+/// std::thread::spawn(move || {
+///     let statistics = handle.snapshot();
+///     println!("{} requests, {} timeouts", statistics.total_requests(), statistics.timeouts);
+/// })
+/// .join()
+/// .unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct StatisticsHandle {
+    inner: Arc<Mutex<Recorder>>,
+}
+
+impl StatisticsHandle {
+    /// `snapshot` - copy of the current statistics
+    pub fn snapshot(&self) -> Statistics {
+        self.lock().statistics.clone()
+    }
+
+    /// `reset` - reset all counters and the latency histogram to zero
+    pub fn reset(&self) {
+        self.lock().statistics = Statistics::default();
+    }
+
+    /// `snapshot_and_reset` - copy the current statistics and reset them in one step
+    ///
+    /// Useful for monitoring code that reports deltas per interval, no event is lost between the copy and the reset.
+    pub fn snapshot_and_reset(&self) -> Statistics {
+        std::mem::take(&mut self.lock().statistics)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Recorder> {
+        // A panic while recording can't leave the counters in an inconsistent state, so ignore poisoning.
+        self.inner.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    pub(crate) fn record_connect(&self) {
+        let mut recorder = self.lock();
+        if recorder.connected {
+            recorder.statistics.reconnects += 1;
+        }
+        recorder.connected = true;
+    }
+
+    /// Record a finished client transaction, `result` is the outcome of the request.
+    pub(crate) fn record_transaction(
+        &self,
+        function: u8,
+        bytes_sent: usize,
+        bytes_received: usize,
+        latency: Duration,
+        result: Result<(), &io::Error>,
+    ) {
+        let mut recorder = self.lock();
+        let statistics = &mut recorder.statistics;
+        *statistics.requests.entry(function).or_insert(0) += 1;
+        statistics.bytes_sent += bytes_sent as u64;
+        let answered = match result {
+            Ok(()) => true,
+            Err(err) => {
+                Self::record_failure(statistics, err);
+                // exception responses are complete round trips, too
+                error::exception_from_errno(err.raw_os_error()).is_some()
+            }
+        };
+        if answered {
+            statistics.bytes_received += bytes_received as u64;
+            statistics.latency.record(latency);
+        }
+    }
+
+    /// Record a raw request, sent without waiting for the confirmation.
+    pub(crate) fn record_sent(&self, function: u8, bytes_sent: usize) {
+        let mut recorder = self.lock();
+        let statistics = &mut recorder.statistics;
+        *statistics.requests.entry(function).or_insert(0) += 1;
+        statistics.bytes_sent += bytes_sent as u64;
+    }
+
+    /// Record a raw confirmation, received without a matching request.
+    pub(crate) fn record_received(&self, bytes_received: usize) {
+        self.lock().statistics.bytes_received += bytes_received as u64;
+    }
+
+    /// Record a received indication (server side).
+    pub(crate) fn record_indication(&self, function: u8, bytes_received: usize) {
+        let mut recorder = self.lock();
+        recorder.indication_received = Some(Instant::now());
+        let statistics = &mut recorder.statistics;
+        *statistics.requests.entry(function).or_insert(0) += 1;
+        statistics.bytes_received += bytes_received as u64;
+    }
+
+    /// Record a sent response (server side), `exception` is set for exception responses.
+    pub(crate) fn record_reply(&self, bytes_sent: usize, exception: Option<Exception>) {
+        let mut recorder = self.lock();
+        let received = recorder.indication_received.take();
+        let statistics = &mut recorder.statistics;
+        statistics.bytes_sent += bytes_sent as u64;
+        if let Some(exception) = exception {
+            *statistics.exceptions.entry(exception).or_insert(0) += 1;
+        }
+        if let Some(received) = received {
+            statistics.latency.record(received.elapsed());
+        }
+    }
+
+    /// Record a failed receive or reply.
+    pub(crate) fn record_error(&self, err: &io::Error) {
+        Self::record_failure(&mut self.lock().statistics, err);
+    }
+
+    fn record_failure(statistics: &mut Statistics, err: &io::Error) {
+        let errno = err.raw_os_error();
+        if let Some(exception) = error::exception_from_errno(errno) {
+            *statistics.exceptions.entry(exception).or_insert(0) += 1;
+        } else if errno == Some(libc::ETIMEDOUT) {
+            statistics.timeouts += 1;
+        } else if errno == Some(error::EMBBADCRC) {
+            statistics.crc_errors += 1;
+        } else if error::is_framing_errno(errno) || error::framing_violation(err).is_some() {
+            statistics.framing_errors += 1;
+        }
+    }
+}
+
+impl Modbus {
+    /// Length of the ADU for a PDU of `pdu_length` bytes in the current backend.
+    pub(crate) fn adu_length(&self, pdu_length: usize) -> usize {
+        let header_length = self.get_header_length().max(0) as usize;
+        // RTU has a one byte header (the slave id) and a two byte CRC, TCP a seven byte MBAP header.
+        let checksum_length = if header_length == 1 { 2 } else { 0 };
+        header_length + pdu_length + checksum_length
+    }
+
+    /// Run a libmodbus client call and record it in the statistics of the context.
+    ///
+    /// `request_pdu` and `response_pdu` are the expected PDU lengths of the transaction, libmodbus doesn't report the
+    /// bytes it actually transferred, so the byte counters are estimated from them. The errno of a failed call is
+    /// captured before anything else can overwrite it.
+    pub(crate) fn instrument<F>(
+        &self,
+        function: FunctionCode,
+        request_pdu: usize,
+        response_pdu: usize,
+        call: F,
+    ) -> Result<c_int, io::Error>
+    where
+        F: FnOnce() -> c_int,
+    {
+        let started = Instant::now();
+        let result = match call() {
+            -1 => Err(io::Error::last_os_error()),
+            rc => Ok(rc),
+        };
+        let latency = started.elapsed();
+
+        let response_pdu = match (&result, function) {
+            // the length of the slave id response is only known afterwards
+            (Ok(rc), FunctionCode::ReportSlaveId) => 2 + *rc as usize,
+            // exception response: function code and exception code
+            (Err(_), _) => 2,
+            _ => response_pdu,
+        };
+        self.stats.record_transaction(
+            function as u8,
+            self.adu_length(request_pdu),
+            self.adu_length(response_pdu),
+            latency,
+            result.as_ref().map(|_| ()),
+        );
+        result
+    }
+}
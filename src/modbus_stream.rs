@@ -0,0 +1,1458 @@
+use crate::error::{FramingViolation, EMBBADDATA, EMBBADEXC, EMBBADSLAVE, EMBMDATA};
+use crate::modbus_frame::{Adu, Framing};
+use crate::modbus_handler::{dispatch, encode_response, pack_bits};
+use crate::modbus_request::Request;
+#[cfg(feature = "tls")]
+use crate::modbus_tls::TlsTransport;
+#[cfg(unix)]
+use crate::modbus_transport::SerialPort;
+use crate::modbus_transport::{timed_out, TcpTransport, Transport, UdpTransport};
+use crate::prelude::*;
+#[cfg(feature = "tls")]
+use crate::TlsConfig;
+use crate::{
+    Exception, FunctionCode, ModbusClient, ModbusServer, RequestHandler, SerialConfig, Statistics,
+    StatisticsHandle, Timeout, UnitSelection,
+};
+use std::cell::{Cell, RefCell};
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+use std::net::TcpListener;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Opens the transport of a context on [`connect()`](struct.ModbusStream.html#method.connect), called with the
+/// response timeout of the context
+type Connector = Box<dyn Fn(Duration) -> io::Result<Box<dyn Transport>> + Send>;
+
+/// A Modbus context of a backend implemented in Rust instead of libmodbus
+///
+/// libmodbus only supports RTU and TCP. A `ModbusStream` frames the requests and responses itself, so it speaks
+/// protocols libmodbus doesn't:
+///
+/// * Modbus ASCII on a serial line, see [`new_ascii()`](#method.new_ascii)
+/// * Modbus RTU on a serial line with the timing of the specification, see [`new_rtu()`](#method.new_rtu)
+/// * Modbus RTU over TCP, as serial device servers in transparent mode carry it, see
+///   [`new_rtu_tcp()`](#method.new_rtu_tcp)
+/// * Modbus UDP, the MBAP header of Modbus TCP and the PDU in one datagram, see [`new_udp()`](#method.new_udp)
+/// * Modbus/TCP Security, Modbus TCP over TLS, see [`new_tls()`](#method.new_tls), with the `tls` feature
+/// * Any of the framings on a transport of your own, see [`from_transport()`](#method.from_transport)
+///
+/// The context offers the same client and server operations as a [`Modbus`](struct.Modbus.html) context:
+/// [`ModbusClient`](trait.ModbusClient.html) to send requests and [`ModbusServer`](trait.ModbusServer.html) to
+/// answer them, with a [`ModbusMapping`](struct.ModbusMapping.html) or a
+/// [`RequestHandler`](trait.RequestHandler.html). Errors are reported like libmodbus does, e.g. an exception
+/// response as `MODBUS_ENOBASE` + exception code, see [`Error::exception()`](enum.Error.html#method.exception).
+///
+/// The buffers `receive()` and `receive_confirmation()` fill hold the ADU in binary: for ASCII the slave id, the
+/// PDU and the LRC, for RTU the slave id, the PDU and the CRC, for UDP the MBAP header and the PDU.
+/// `Modbus::MAX_ADU_LENGTH` bytes are always enough. A context can be moved to another thread, e.g. a server context
+/// to a thread answering its client.
+///
+/// [`serve()`](fn.serve.html), [`TcpServer`](struct.TcpServer.html) and
+/// [`Simulator::serve()`](struct.Simulator.html#method.serve) drive [`Modbus`](struct.Modbus.html) contexts only,
+/// a `ModbusStream` server runs a loop of [`receive()`](trait.ModbusServer.html#tymethod.receive) and
+/// [`reply()`](trait.ModbusServer.html#tymethod.reply) or [`reply_with()`](trait.ModbusServer.html#tymethod.reply_with)
+/// of its own.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use libmodbus::{ModbusClient, ModbusStream};
+///
+/// let mut modbus = ModbusStream::new_ascii("/dev/ttyUSB0", 9600, 'E', 7, 1).unwrap();
+/// modbus.set_slave(17).unwrap();
+/// modbus.connect().unwrap();
+///
+/// let mut registers = [0u16; 4];
+/// modbus.read_registers(0, 4, &mut registers).unwrap();
+/// ```
+pub struct ModbusStream {
+    connector: Option<Connector>,
+    transport: RefCell<Option<Box<dyn Transport>>>,
+    framing: Framing,
+    /// Bytes received but not framed yet
+    buffer: RefCell<Vec<u8>>,
+    slave: u8,
+    transaction: Cell<u16>,
+    response_timeout: Duration,
+    byte_timeout: Duration,
+    /// Silence ending an RTU frame of unknown length
+    inter_frame_timeout: Duration,
+    indication_timeout: Option<Duration>,
+    /// Times a request is sent again without a response
+    retransmissions: u32,
+    /// Parameters of the serial line, the timing of RTU depends on them
+    serial: Option<SerialConfig>,
+    /// Enforce the silent intervals of the serial line specification
+    timing: bool,
+    /// End of the last frame sent or of the last bytes received, with the timing enforced
+    last_activity: Cell<Option<Instant>>,
+    stats: StatisticsHandle,
+}
+
+/// Convert a `Timeout` of the libmodbus API, rejecting `usec` out of 0 to 999999 like libmodbus does
+fn duration(timeout: Timeout, msg: &str) -> Result<Duration, Error> {
+    if timeout.usec > 999_999 {
+        return Err(Error::Modbus {
+            msg: msg.to_owned(),
+            source: errno(libc::EINVAL),
+        });
+    }
+    Ok(Duration::from_secs(timeout.sec as u64) + Duration::from_micros(timeout.usec as u64))
+}
+
+/// Convert to a `Timeout` of the libmodbus API
+fn timeout(duration: Duration) -> Timeout {
+    Timeout::new(duration.as_secs() as u32, duration.subsec_micros())
+}
+
+/// Check the port of a TCP or UDP context
+fn check_port(port: i32) -> io::Result<u16> {
+    u16::try_from(port).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid port"))
+}
+
+fn errno(errno: i32) -> io::Error {
+    io::Error::from_raw_os_error(errno)
+}
+
+fn client_error(msg: &str, source: io::Error) -> Error {
+    Error::Client {
+        msg: msg.to_owned(),
+        source,
+    }
+}
+
+fn be_u16(bytes: &[u8]) -> u16 {
+    u16::from_be_bytes([bytes[0], bytes[1]])
+}
+
+/// The request PDU of a function code with a list of 16 bit fields
+fn request_pdu(function: FunctionCode, fields: &[u16]) -> Vec<u8> {
+    let mut pdu = vec![function as u8];
+    for field in fields {
+        pdu.extend_from_slice(&field.to_be_bytes());
+    }
+    pdu
+}
+
+/// The data of a read response with its byte count, `None` if the count isn't `expected`
+fn read_data(response: &[u8], expected: usize) -> Option<&[u8]> {
+    match response {
+        [_, count, data @ ..] if *count as usize == expected && data.len() == expected => {
+            Some(data)
+        }
+        _ => None,
+    }
+}
+
+impl ModbusStream {
+    fn new(
+        connector: Option<Connector>,
+        transport: Option<Box<dyn Transport>>,
+        framing: Framing,
+    ) -> Self {
+        ModbusStream {
+            connector,
+            transport: RefCell::new(transport),
+            framing,
+            buffer: RefCell::new(Vec::new()),
+            slave: 0,
+            transaction: Cell::new(0),
+            // the defaults of libmodbus
+            response_timeout: Duration::from_millis(500),
+            byte_timeout: Duration::from_millis(500),
+            inter_frame_timeout: Duration::from_millis(100),
+            indication_timeout: None,
+            retransmissions: 0,
+            serial: None,
+            timing: false,
+            last_activity: Cell::new(None),
+            stats: StatisticsHandle::default(),
+        }
+    }
+
+    /// `new_ascii` - create a Modbus ASCII context
+    ///
+    /// The [`new_ascii()`](#method.new_ascii) function creates a context for Modbus ASCII on a serial line, with the
+    /// same parameters as [`new_rtu()`](struct.Modbus.html#method.new_rtu). The device is opened by
+    /// [`connect()`](#method.connect).
+    ///
+    /// An ASCII frame is a colon, the slave id, the PDU and the LRC checksum as hex digits and CR LF. Most ASCII
+    /// devices use 7 data bits. Only available on unix platforms.
+    ///
+    /// # Return value
+    ///
+    /// The function returns a Result containing the context if successful. Otherwise it contains an Error if one of
+    /// the parameters is invalid.
+    ///
+    /// # Parameters
+    ///
+    /// * `device`      - name of the serial port, e.g. `/dev/ttyS0` or `/dev/ttyUSB0`
+    /// * `baud`        - baud rate of the communication, e.g. 9600
+    /// * `parity`      - `'N'` for none, `'E'` for even or `'O'` for odd
+    /// * `data_bit`    - number of data bits, 5, 6, 7 or 8
+    /// * `stop_bit`    - number of stop bits, 1 or 2
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use libmodbus::ModbusStream;
+    ///
+    /// assert!(ModbusStream::new_ascii("/dev/ttyUSB0", 9600, 'E', 7, 1).is_ok());
+    /// assert!(ModbusStream::new_ascii("/dev/ttyUSB0", 9600, 'x', 7, 1).is_err());
+    /// ```
+    #[cfg(unix)]
+    pub fn new_ascii(
+        device: &str,
+        baud: i32,
+        parity: char,
+        data_bit: i32,
+        stop_bit: i32,
+    ) -> Result<ModbusStream, Error> {
+        // check the parameters now, the device is opened by connect()
+        let config = SerialConfig::check(baud, parity, data_bit, stop_bit).map_err(|source| {
+            Error::Ascii {
+                msg: "new_ascii".to_owned(),
+                source,
+            }
+        })?;
+        let device = device.to_owned();
+        let connector: Connector = Box::new(move |_| {
+            let port = SerialPort::open(&device, &config)?;
+            Ok(Box::new(port) as Box<dyn Transport>)
+        });
+        Ok(ModbusStream::new(Some(connector), None, Framing::Ascii))
+    }
+
+    /// `new_rtu` - create a Modbus RTU context on a serial line
+    ///
+    /// The [`new_rtu()`](#method.new_rtu) function creates a context for Modbus RTU on a serial line like
+    /// [`Modbus::new_rtu_config()`](struct.Modbus.html#method.new_rtu_config) does, but framed in Rust. Unlike
+    /// libmodbus it can enforce the timing of the Modbus serial line specification, see
+    /// [`set_rtu_timing()`](#method.set_rtu_timing). The device is opened by [`connect()`](#method.connect).
+    ///
+    /// Only available on unix platforms.
+    ///
+    /// # Return value
+    ///
+    /// The function returns a Result containing the context.
+    ///
+    /// # Parameters
+    ///
+    /// * `device`  - name of the serial port, e.g. `/dev/ttyS0` or `/dev/ttyUSB0`
+    /// * `config`  - parameters of the serial line
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use libmodbus::{ModbusClient, ModbusStream, SerialConfig};
+    ///
+    /// let mut modbus = ModbusStream::new_rtu("/dev/ttyUSB0", SerialConfig::default()).unwrap();
+    /// modbus.set_slave(1).unwrap();
+    /// modbus.set_rtu_timing(true).unwrap();
+    /// modbus.connect().unwrap();
+    ///
+    /// let mut registers = [0u16; 4];
+    /// modbus.read_registers(0, 4, &mut registers).unwrap();
+    /// ```
+    #[cfg(unix)]
+    pub fn new_rtu(device: &str, config: SerialConfig) -> Result<ModbusStream, Error> {
+        let device = device.to_owned();
+        let connector: Connector = Box::new(move |_| {
+            let port = SerialPort::open(&device, &config)?;
+            Ok(Box::new(port) as Box<dyn Transport>)
+        });
+        let mut modbus = ModbusStream::new(Some(connector), None, Framing::Rtu);
+        modbus.serial = Some(config);
+        Ok(modbus)
+    }
+
+    /// `new_rtu_tcp` - create a Modbus RTU over TCP context
+    ///
+    /// The [`new_rtu_tcp()`](#method.new_rtu_tcp) function creates a client context sending RTU frames, the slave
+    /// id, the PDU and the CRC, on a TCP connection instead of a serial line. Serial device servers in transparent
+    /// mode forward them to the serial line as they are. The connection is opened by [`connect()`](#method.connect).
+    /// A server context is created on an accepted connection with
+    /// [`rtu_tcp_accept()`](#method.rtu_tcp_accept).
+    ///
+    /// The length of a frame is given by its function code. Frames of other functions end with a silence of the
+    /// [inter-frame timeout](#method.set_inter_frame_timeout). As on a serial line, broadcasts aren't answered.
+    ///
+    /// # Return value
+    ///
+    /// The function returns a Result containing the context if successful. Otherwise it contains an Error if the
+    /// port is invalid.
+    ///
+    /// # Parameters
+    ///
+    /// * `ip`      - host name or IP address of the device server
+    /// * `port`    - TCP port of the device server, e.g. 4001
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use libmodbus::{ModbusClient, ModbusStream};
+    ///
+    /// let mut modbus = ModbusStream::new_rtu_tcp("192.168.127.254", 4001).unwrap();
+    /// modbus.set_slave(1).unwrap();
+    /// modbus.connect().unwrap();
+    ///
+    /// let mut registers = [0u16; 4];
+    /// modbus.read_registers(0, 4, &mut registers).unwrap();
+    /// ```
+    pub fn new_rtu_tcp(ip: &str, port: i32) -> Result<ModbusStream, Error> {
+        let port = check_port(port).map_err(|source| Error::Tcp {
+            msg: "new_rtu_tcp".to_owned(),
+            source,
+        })?;
+        let ip = ip.to_owned();
+        let connector: Connector = Box::new(move |_| {
+            let transport = TcpTransport::connect(&ip, port)?;
+            Ok(Box::new(transport) as Box<dyn Transport>)
+        });
+        Ok(ModbusStream::new(Some(connector), None, Framing::Rtu))
+    }
+
+    /// `rtu_tcp_accept` - accept a connection and create a Modbus RTU over TCP server context on it
+    ///
+    /// The context owns the accepted connection, the listener accepts further connections.
+    /// [`connect()`](#method.connect) does nothing on the context.
+    ///
+    /// # Return value
+    ///
+    /// The function returns a Result containing the context if successful. Otherwise it contains an Error.
+    ///
+    /// # Parameters
+    ///
+    /// * `listener`    - listener to accept the connection of
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use libmodbus::{Modbus, ModbusMapping, ModbusServer, ModbusStream};
+    /// use std::net::TcpListener;
+    ///
+    /// let listener = TcpListener::bind("0.0.0.0:4001").unwrap();
+    /// let mut modbus = ModbusStream::rtu_tcp_accept(&listener).unwrap();
+    /// modbus.set_slave(1).unwrap();
+    /// let mapping = ModbusMapping::new(500, 500, 500, 500).unwrap();
+    ///
+    /// let mut query = vec![0u8; Modbus::MAX_ADU_LENGTH];
+    /// while let Ok(rc) = modbus.receive(&mut query) {
+    ///     if rc > 0 {
+    ///         modbus.reply(&query, rc, &mapping).unwrap();
+    ///     }
+    /// }
+    /// ```
+    pub fn rtu_tcp_accept(listener: &TcpListener) -> Result<ModbusStream, Error> {
+        match listener
+            .accept()
+            .and_then(|(stream, _)| TcpTransport::from_stream(stream))
+        {
+            Ok(transport) => Ok(ModbusStream::new(
+                None,
+                Some(Box::new(transport)),
+                Framing::Rtu,
+            )),
+            Err(source) => Err(Error::Tcp {
+                msg: "rtu_tcp_accept".to_owned(),
+                source,
+            }),
+        }
+    }
+
+    /// `new_udp` - create a Modbus UDP client context
+    ///
+    /// The [`new_udp()`](#method.new_udp) function creates a client context sending each request in a datagram:
+    /// the MBAP header of Modbus TCP and the PDU. The socket is created by [`connect()`](#method.connect), and only
+    /// receives datagrams from the server.
+    ///
+    /// Datagrams get lost, so a request is sent again if no response arrived within the response timeout, twice by
+    /// default, see [`set_retransmissions()`](#method.set_retransmissions). Responses are matched to the request by
+    /// their transaction id, late responses to earlier requests are dropped.
+    ///
+    /// # Return value
+    ///
+    /// The function returns a Result containing the context if successful. Otherwise it contains an Error if the
+    /// port is invalid.
+    ///
+    /// # Parameters
+    ///
+    /// * `ip`      - host name or IP address of the server
+    /// * `port`    - UDP port of the server, e.g. 502
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use libmodbus::{ModbusClient, ModbusStream};
+    ///
+    /// let modbus = ModbusStream::new_udp("192.168.1.10", 502).unwrap();
+    /// modbus.connect().unwrap();
+    ///
+    /// let mut registers = [0u16; 4];
+    /// modbus.read_registers(0, 4, &mut registers).unwrap();
+    /// ```
+    pub fn new_udp(ip: &str, port: i32) -> Result<ModbusStream, Error> {
+        let port = check_port(port).map_err(|source| Error::Udp {
+            msg: "new_udp".to_owned(),
+            source,
+        })?;
+        let ip = ip.to_owned();
+        let connector: Connector = Box::new(move |_| {
+            let transport = UdpTransport::connect(&ip, port)?;
+            Ok(Box::new(transport) as Box<dyn Transport>)
+        });
+        let mut modbus = ModbusStream::new(Some(connector), None, Framing::Mbap);
+        modbus.retransmissions = 2;
+        Ok(modbus)
+    }
+
+    /// `udp_bind` - create a Modbus UDP server context
+    ///
+    /// The [`udp_bind()`](#method.udp_bind) function creates a server context on a socket bound to `ip` and `port`,
+    /// receiving the requests of any client. Each datagram received is one request, the response goes back to the
+    /// address it came from. [`connect()`](#method.connect) does nothing on the context.
+    ///
+    /// # Return value
+    ///
+    /// The function returns a Result containing the context if successful. Otherwise it contains an Error.
+    ///
+    /// # Parameters
+    ///
+    /// * `ip`      - IP address to receive on, e.g. `0.0.0.0` for all IPv4 addresses
+    /// * `port`    - UDP port to receive on, e.g. 502
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use libmodbus::{Modbus, ModbusMapping, ModbusServer, ModbusStream};
+    ///
+    /// let modbus = ModbusStream::udp_bind("0.0.0.0", 502).unwrap();
+    /// let mapping = ModbusMapping::new(500, 500, 500, 500).unwrap();
+    ///
+    /// let mut query = vec![0u8; Modbus::MAX_ADU_LENGTH];
+    /// loop {
+    ///     if let Ok(rc) = modbus.receive(&mut query) {
+    ///         modbus.reply(&query, rc, &mapping).unwrap();
+    ///     }
+    /// }
+    /// ```
+    pub fn udp_bind(ip: &str, port: i32) -> Result<ModbusStream, Error> {
+        match check_port(port).and_then(|port| UdpTransport::bind(ip, port)) {
+            Ok(transport) => Ok(ModbusStream::new(
+                None,
+                Some(Box::new(transport)),
+                Framing::Mbap,
+            )),
+            Err(source) => Err(Error::Udp {
+                msg: "udp_bind".to_owned(),
+                source,
+            }),
+        }
+    }
+
+    /// `new_tls` - create a Modbus/TCP Security client context
+    ///
+    /// The [`new_tls()`](#method.new_tls) function creates a client context for Modbus TCP over TLS, as the
+    /// Modbus/TCP Security protocol specifies. The connection is opened by [`connect()`](#method.connect): the
+    /// certificate of the server must be signed by a certificate authority of `config` and be valid for `ip`, and
+    /// the client authenticates with the certificate of `config`. A handshake taking longer than the
+    /// [response timeout](#method.set_response_timeout) fails.
+    ///
+    /// Only available with the `tls` feature.
+    ///
+    /// # Return value
+    ///
+    /// The function returns a Result containing the context if successful. Otherwise it contains an Error if the
+    /// port or the configuration is invalid.
+    ///
+    /// # Parameters
+    ///
+    /// * `ip`      - host name or IP address of the server, as in its certificate
+    /// * `port`    - TCP port of the server, 802 for Modbus/TCP Security
+    /// * `config`  - certificate and key of the client and the certificate authorities it trusts
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use libmodbus::{ModbusClient, ModbusStream, TlsConfig};
+    ///
+    /// let config = TlsConfig::from_pem_files("client.pem", "client.key", "ca.pem").unwrap();
+    /// let modbus = ModbusStream::new_tls("plc.example.com", 802, &config).unwrap();
+    /// modbus.connect().unwrap();
+    ///
+    /// let mut registers = [0u16; 4];
+    /// modbus.read_registers(0, 4, &mut registers).unwrap();
+    /// ```
+    #[cfg(feature = "tls")]
+    pub fn new_tls(ip: &str, port: i32, config: &TlsConfig) -> Result<ModbusStream, Error> {
+        let tls_error = |source| Error::Tls {
+            msg: "new_tls".to_owned(),
+            source,
+        };
+        let port = check_port(port).map_err(tls_error)?;
+        let config = std::sync::Arc::new(config.client_config().map_err(tls_error)?);
+        let ip = ip.to_owned();
+        let connector: Connector = Box::new(move |timeout| {
+            let transport = TlsTransport::connect(&ip, port, config.clone(), timeout)?;
+            Ok(Box::new(transport) as Box<dyn Transport>)
+        });
+        Ok(ModbusStream::new(Some(connector), None, Framing::Mbap))
+    }
+
+    /// `tls_accept` - accept a connection and create a Modbus/TCP Security server context on it
+    ///
+    /// The TLS handshake is completed before the function returns: the client must present a certificate signed by a
+    /// certificate authority of `config`. A client not completing the handshake within the default response timeout
+    /// is disconnected. The context owns the accepted connection, the listener accepts further connections.
+    ///
+    /// The role of the client, if any, is available with [`peer_role()`](#method.peer_role). To authorize the
+    /// requests, pass it to the [`RequestHandler`](trait.RequestHandler.html) answering them, see the example.
+    ///
+    /// Only available with the `tls` feature.
+    ///
+    /// # Return value
+    ///
+    /// The function returns a Result containing the context if successful. Otherwise it contains an Error, e.g. if
+    /// the client isn't authenticated or the handshake timed out.
+    ///
+    /// # Parameters
+    ///
+    /// * `listener`    - listener to accept the connection of
+    /// * `config`      - certificate and key of the server and the certificate authorities it trusts
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use libmodbus::{Exception, Modbus, ModbusServer, ModbusStream, RequestHandler, TlsConfig};
+    /// use std::net::TcpListener;
+    ///
+    /// /// Registers everyone reads, but only operators write
+    /// struct Registers {
+    ///     role: Option<String>,
+    ///     values: Vec<u16>,
+    /// }
+    ///
+    /// impl RequestHandler for Registers {
+    ///     fn read_holding_registers(&mut self, address: u16, quantity: u16) -> Result<Vec<u16>, Exception> {
+    ///         let (start, end) = (address as usize, address as usize + quantity as usize);
+    ///         self.values.get(start..end).map(|values| values.to_vec()).ok_or(Exception::IllegalDataAddress)
+    ///     }
+    ///
+    ///     fn write_multiple_registers(&mut self, address: u16, values: &[u16]) -> Result<(), Exception> {
+    ///         if self.role.as_deref() != Some("Operator") {
+    ///             return Err(Exception::IllegalFunction);
+    ///         }
+    ///         let (start, end) = (address as usize, address as usize + values.len());
+    ///         let registers = self.values.get_mut(start..end).ok_or(Exception::IllegalDataAddress)?;
+    ///         registers.copy_from_slice(values);
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let config = TlsConfig::from_pem_files("server.pem", "server.key", "ca.pem").unwrap();
+    /// let listener = TcpListener::bind("0.0.0.0:802").unwrap();
+    /// let modbus = ModbusStream::tls_accept(&listener, &config).unwrap();
+    /// let mut handler = Registers {
+    ///     role: modbus.peer_role(),
+    ///     values: vec![0; 500],
+    /// };
+    ///
+    /// let mut query = vec![0u8; Modbus::MAX_ADU_LENGTH];
+    /// while let Ok(rc) = modbus.receive(&mut query) {
+    ///     modbus.reply_with(&query, rc, &mut handler).unwrap();
+    /// }
+    /// ```
+    #[cfg(feature = "tls")]
+    pub fn tls_accept(listener: &TcpListener, config: &TlsConfig) -> Result<ModbusStream, Error> {
+        let modbus = ModbusStream::new(None, None, Framing::Mbap);
+        match config.server_config().and_then(|config| {
+            let (socket, _) = listener.accept()?;
+            TlsTransport::accept(socket, std::sync::Arc::new(config), modbus.response_timeout)
+        }) {
+            Ok(transport) => {
+                *modbus.transport.borrow_mut() = Some(Box::new(transport));
+                Ok(modbus)
+            }
+            Err(source) => Err(Error::Tls {
+                msg: "tls_accept".to_owned(),
+                source,
+            }),
+        }
+    }
+
+    /// `peer_role` - get the role of the other end of a Modbus/TCP Security connection
+    ///
+    /// The role is taken from the extension 1.3.6.1.4.1.50316.802.1 of the certificate the other end authenticated
+    /// with. `None` if its certificate has no role, or the context isn't a connected TLS context.
+    pub fn peer_role(&self) -> Option<String> {
+        self.transport
+            .borrow()
+            .as_ref()
+            .and_then(|transport| transport.peer_role())
+    }
+
+    /// `from_transport` - create a context on a custom transport
+    ///
+    /// The [`from_transport()`](#method.from_transport) function creates a context sending and receiving its
+    /// frames with `transport`, framed as `framing`: [`Framing::Rtu`](enum.Framing.html#variant.Rtu) with slave id
+    /// and CRC, [`Framing::Mbap`](enum.Framing.html#variant.Mbap) with the header of Modbus TCP or
+    /// [`Framing::Ascii`](enum.Framing.html#variant.Ascii). The transport is open already,
+    /// [`connect()`](#method.connect) doesn't change it, [`close()`](#method.close) closes it for good.
+    ///
+    /// Any `Read + Write` stream is a transport with [`IoTransport`](struct.IoTransport.html).
+    ///
+    /// # Parameters
+    ///
+    /// * `transport`   - moves the bytes of the frames
+    /// * `framing`     - how the frames are delimited
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use libmodbus::{Framing, IoTransport, ModbusClient, ModbusStream};
+    /// use std::net::TcpStream;
+    ///
+    /// // Modbus TCP through a tunnel forwarded to a local port
+    /// let stream = TcpStream::connect("127.0.0.1:15020").unwrap();
+    /// let mut modbus = ModbusStream::from_transport(IoTransport::new(stream), Framing::Mbap);
+    /// modbus.set_slave(1).unwrap();
+    ///
+    /// let mut registers = [0u16; 4];
+    /// modbus.read_registers(0, 4, &mut registers).unwrap();
+    /// ```
+    pub fn from_transport<T: Transport + 'static>(transport: T, framing: Framing) -> ModbusStream {
+        ModbusStream::new(None, Some(Box::new(transport)), framing)
+    }
+
+    /// Context on the master end of a pseudo-terminal, standing for a serial line with the parameters `serial`
+    #[cfg(unix)]
+    pub(crate) fn with_pty_master(
+        master: libc::c_int,
+        framing: Framing,
+        serial: Option<SerialConfig>,
+    ) -> ModbusStream {
+        let mut modbus = ModbusStream::new(
+            None,
+            Some(Box::new(SerialPort::from_pty_master(master))),
+            framing,
+        );
+        modbus.serial = serial;
+        modbus
+    }
+
+    /// `connect` - open the transport of the context
+    ///
+    /// An open transport is closed and opened again. A context created on an open transport, e.g. with
+    /// [`PseudoTerminal::into_ascii()`](struct.PseudoTerminal.html#method.into_ascii), stays as it is.
+    ///
+    /// # Return value
+    ///
+    /// The function return an OK Result if successful. Otherwise it contains an Error.
+    pub fn connect(&self) -> Result<(), Error> {
+        let connector = match self.connector {
+            Some(ref connector) => connector,
+            None => return Ok(()),
+        };
+        self.close();
+        match connector(self.response_timeout) {
+            Ok(transport) => {
+                *self.transport.borrow_mut() = Some(transport);
+                self.stats.record_connect();
+                Ok(())
+            }
+            Err(source) => Err(Error::Modbus {
+                msg: "connect".to_owned(),
+                source,
+            }),
+        }
+    }
+
+    /// `close` - close the transport of the context
+    pub fn close(&self) {
+        if let Some(mut transport) = self.transport.borrow_mut().take() {
+            transport.close();
+        }
+        self.buffer.borrow_mut().clear();
+    }
+
+    /// `flush` - discard data received but not read yet
+    pub fn flush(&self) -> Result<(), Error> {
+        self.buffer.borrow_mut().clear();
+        self.with_transport(|transport| transport.flush())
+            .map_err(|source| Error::Modbus {
+                msg: "flush".to_owned(),
+                source,
+            })
+    }
+
+    /// `set_slave` - set the slave number of the context
+    ///
+    /// A client sends its requests to this slave, 0 broadcasts them. A server answers the requests for this slave
+    /// and the broadcasts.
+    ///
+    /// # Parameters
+    ///
+    /// * `slave`   - slave number, 0 to 247
+    pub fn set_slave(&mut self, slave: u8) -> Result<(), Error> {
+        if self.framing.is_serial() && slave > 247 {
+            return Err(Error::Modbus {
+                msg: "set_slave".to_owned(),
+                source: io::Error::from_raw_os_error(libc::EINVAL),
+            });
+        }
+        self.slave = slave;
+        Ok(())
+    }
+
+    /// `get_slave` - get the slave number of the context
+    pub fn get_slave(&self) -> Result<u8, Error> {
+        Ok(self.slave)
+    }
+
+    /// `get_response_timeout` - get the timeout to wait for a response, 500 ms by default
+    pub fn get_response_timeout(&self) -> Result<Timeout, Error> {
+        Ok(timeout(self.response_timeout))
+    }
+
+    /// `set_response_timeout` - set the timeout to wait for a response
+    pub fn set_response_timeout(&mut self, timeout: Timeout) -> Result<(), Error> {
+        self.response_timeout = duration(timeout, "set_response_timeout")?;
+        Ok(())
+    }
+
+    /// `get_byte_timeout` - get the timeout between two bytes of a frame, 500 ms by default
+    pub fn get_byte_timeout(&self) -> Result<Timeout, Error> {
+        Ok(timeout(self.byte_timeout))
+    }
+
+    /// `set_byte_timeout` - set the timeout between two bytes of a frame
+    pub fn set_byte_timeout(&mut self, timeout: Timeout) -> Result<(), Error> {
+        self.byte_timeout = duration(timeout, "set_byte_timeout")?;
+        Ok(())
+    }
+
+    /// `get_inter_frame_timeout` - get the silence ending an RTU frame of unknown length, 100 ms by default
+    pub fn get_inter_frame_timeout(&self) -> Result<Timeout, Error> {
+        Ok(timeout(self.inter_frame_timeout))
+    }
+
+    /// `set_inter_frame_timeout` - set the silence ending an RTU frame of unknown length
+    ///
+    /// The length of an RTU frame is given by its function code, but not for every function, e.g. user defined
+    /// ones. Such a frame ends when no more bytes arrive for the inter-frame timeout, the 3.5 characters of silence
+    /// of a serial line. On TCP it must cover the delays of the network.
+    ///
+    /// A frame not complete within the byte timeout, even though its length is known, is discarded with a timeout
+    /// error.
+    pub fn set_inter_frame_timeout(&mut self, timeout: Timeout) -> Result<(), Error> {
+        self.inter_frame_timeout = duration(timeout, "set_inter_frame_timeout")?;
+        Ok(())
+    }
+
+    /// `get_rtu_timing` - get whether the timing of the serial line specification is enforced
+    pub fn get_rtu_timing(&self) -> bool {
+        self.timing
+    }
+
+    /// `set_rtu_timing` - enforce the timing of the Modbus serial line specification
+    ///
+    /// An RTU frame is preceded and followed by a silence of at least 3.5 characters, t3.5, and has no silence of
+    /// more than 1.5 characters, t1.5, between its characters, see
+    /// [`SerialConfig::inter_frame_delay()`](struct.SerialConfig.html#method.inter_frame_delay) and
+    /// [`SerialConfig::inter_character_timeout()`](struct.SerialConfig.html#method.inter_character_timeout).
+    /// Enforced, a frame is only sent after t3.5 of silence since the last frame on the line, and a silence of t3.5
+    /// ends a received frame instead of the byte and inter-frame timeouts.
+    ///
+    /// A received frame violating the timing is discarded with an error,
+    /// [`Error::framing_violation()`](enum.Error.html#method.framing_violation) tells which violation, counted as a
+    /// framing error in the [statistics](#method.statistics). The bytes of the frame still arriving are
+    /// discarded as well.
+    ///
+    /// Off by default, the timing of USB serial adapters is often too coarse for it.
+    ///
+    /// # Return value
+    ///
+    /// The function return an OK Result if successful. Otherwise it contains an Error if the context isn't an RTU
+    /// context on a serial line, see [`new_rtu()`](#method.new_rtu).
+    ///
+    /// # Parameters
+    ///
+    /// * `enabled` - enforce the timing
+    pub fn set_rtu_timing(&mut self, enabled: bool) -> Result<(), Error> {
+        if enabled && (self.framing != Framing::Rtu || self.serial.is_none()) {
+            return Err(Error::Rtu {
+                msg: "set_rtu_timing".to_owned(),
+                source: errno(libc::EINVAL),
+            });
+        }
+        self.timing = enabled;
+        self.last_activity.set(None);
+        Ok(())
+    }
+
+    /// `get_retransmissions` - get how often a request is sent again without a response
+    pub fn get_retransmissions(&self) -> u32 {
+        self.retransmissions
+    }
+
+    /// `set_retransmissions` - set how often a request is sent again without a response
+    ///
+    /// A request is sent again, with the same transaction id, if no response arrived within the response timeout.
+    /// 0 by default, 2 for [UDP](#method.new_udp) contexts. Requests are never sent again on a serial framing,
+    /// which can't tell the response to a request sent twice from the response to the next request.
+    ///
+    /// # Parameters
+    ///
+    /// * `retransmissions` - number of times to send a request again
+    pub fn set_retransmissions(&mut self, retransmissions: u32) {
+        self.retransmissions = retransmissions;
+    }
+
+    /// `get_indication_timeout` - get the timeout a server waits for a request, zero waits forever
+    pub fn get_indication_timeout(&self) -> Result<Timeout, Error> {
+        Ok(self.indication_timeout.map_or(Timeout::default(), timeout))
+    }
+
+    /// `set_indication_timeout` - set the timeout a server waits for a request
+    ///
+    /// A zero timeout, the default, waits forever.
+    pub fn set_indication_timeout(&mut self, timeout: Timeout) -> Result<(), Error> {
+        let timeout = duration(timeout, "set_indication_timeout")?;
+        self.indication_timeout = if timeout.is_zero() {
+            None
+        } else {
+            Some(timeout)
+        };
+        Ok(())
+    }
+
+    /// `statistics` - get the statistics of the context, see [`Statistics`](struct.Statistics.html)
+    pub fn statistics(&self) -> Statistics {
+        self.stats.snapshot()
+    }
+
+    /// `reset_statistics` - set all statistics of the context back to zero
+    pub fn reset_statistics(&self) {
+        self.stats.reset()
+    }
+
+    /// `reply_exception` - send an exception response to the received request
+    ///
+    /// # Return value
+    ///
+    /// The function returns the length of the response sent if successful. Otherwise it contains an Error.
+    ///
+    /// # Parameters
+    ///
+    /// * `request`         - the request, as received by [`receive()`](#method.receive)
+    /// * `exception_code`  - the exception to answer with
+    pub fn reply_exception(&self, request: &[u8], exception_code: Exception) -> Result<i32, Error> {
+        let adu = self.split_request(request, request.len() as i32)?;
+        let function = adu.pdu[0];
+        self.send_reply(
+            &adu,
+            &[function | 0x80, exception_code as u8],
+            Some(exception_code),
+        )
+    }
+
+    /// Run `f` on the open transport
+    fn with_transport<T>(
+        &self,
+        f: impl FnOnce(&mut dyn Transport) -> io::Result<T>,
+    ) -> io::Result<T> {
+        match self.transport.borrow_mut().as_mut() {
+            Some(transport) => f(transport.as_mut()),
+            None => Err(errno(libc::EBADF)),
+        }
+    }
+
+    /// The serial line parameters, if its timing is enforced
+    fn timed_serial(&self) -> Option<SerialConfig> {
+        self.serial.filter(|_| self.timing)
+    }
+
+    /// Send `frame`, after a silence of t3.5 since the last activity on the line if the timing is enforced
+    fn send_frame(&self, frame: &[u8]) -> io::Result<()> {
+        let config = match self.timed_serial() {
+            Some(config) => config,
+            None => return self.with_transport(|transport| transport.send(frame)),
+        };
+        if let Some(last) = self.last_activity.get() {
+            let ready = last + config.inter_frame_delay();
+            if let Some(wait) = ready.checked_duration_since(Instant::now()) {
+                thread::sleep(wait);
+            }
+        }
+        let result = self.with_transport(|transport| transport.send(frame));
+        // the line is busy until the last character is transmitted
+        self.last_activity.set(Some(
+            Instant::now() + config.character_time() * frame.len() as u32,
+        ));
+        result
+    }
+
+    /// Discard the rest of a frame violating the timing, the bytes arriving until a silence of `silence`
+    fn discard_frame(&self, silence: Duration) -> io::Result<()> {
+        let mut chunk = [0u8; 512];
+        let mut discarded = 0;
+        // a frame is at most an ADU long, the line may never become silent
+        while discarded < Modbus::RTU_MAX_ADU_LENGTH {
+            match self.with_transport(|transport| transport.receive(&mut chunk, Some(silence))) {
+                Ok(0) => break,
+                Ok(len) => {
+                    discarded += len;
+                    self.last_activity.set(Some(Instant::now()));
+                }
+                Err(err) if err.raw_os_error() == Some(libc::ETIMEDOUT) => break,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Receive the next frame, a request if `indication` or else a response, waiting at most `timeout` for its
+    /// first byte and the byte timeout for the others
+    ///
+    /// Returns the frame and its length on the transport. The bytes of a frame cut off by a timeout are discarded,
+    /// as are the bytes of a datagram after its frame. With the timing enforced a silence of t3.5 ends the frame,
+    /// one of more than t1.5 within it is a violation.
+    fn receive_frame(
+        &self,
+        timeout: Option<Duration>,
+        indication: bool,
+    ) -> io::Result<(Adu, usize)> {
+        let datagrams = self
+            .transport
+            .borrow()
+            .as_ref()
+            .is_some_and(|transport| transport.datagrams());
+        let timing = self.timed_serial();
+        let mut buffer = self.buffer.borrow_mut();
+        let mut chunk = [0u8; 512];
+        let mut last_chunk: Option<Instant> = None;
+        loop {
+            let skip = self.framing.sync(&buffer);
+            buffer.drain(..skip);
+            if let Some((len, adu)) = self.framing.decode(&buffer, indication) {
+                if datagrams {
+                    buffer.clear();
+                } else {
+                    buffer.drain(..len);
+                }
+                return adu.map(|adu| (adu, len));
+            }
+            if datagrams && !buffer.is_empty() {
+                // a frame is never split across datagrams
+                buffer.clear();
+                return Err(errno(EMBBADDATA));
+            }
+            let silence = !buffer.is_empty() && self.framing.ends_on_silence(&buffer, indication);
+            let timeout = if buffer.is_empty() {
+                timeout
+            } else if let Some(config) = timing {
+                Some(config.inter_frame_delay())
+            } else if silence {
+                Some(self.inter_frame_timeout)
+            } else {
+                Some(self.byte_timeout)
+            };
+            let len = match self.with_transport(|transport| transport.receive(&mut chunk, timeout))
+            {
+                Ok(len) => len,
+                Err(err) if silence && err.raw_os_error() == Some(libc::ETIMEDOUT) => {
+                    let len = buffer.len();
+                    let adu = self.framing.decode_silent(&buffer);
+                    buffer.clear();
+                    return adu.map(|adu| (adu, len));
+                }
+                Err(err) if timing.is_some() && err.raw_os_error() == Some(libc::ETIMEDOUT) => {
+                    buffer.clear();
+                    return Err(FramingViolation::IncompleteFrame.into());
+                }
+                Err(err) => {
+                    buffer.clear();
+                    return Err(err);
+                }
+            };
+            if len == 0 {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+            }
+            if let Some(config) = timing {
+                let now = Instant::now();
+                if let (Some(last), false) = (last_chunk, buffer.is_empty()) {
+                    // the silence before the first character of the chunk, which took its transmission time
+                    let gap = now
+                        .duration_since(last)
+                        .saturating_sub(config.character_time() * len as u32);
+                    if gap > config.inter_character_timeout() {
+                        buffer.clear();
+                        self.discard_frame(config.inter_frame_delay())?;
+                        return Err(FramingViolation::InterCharacterGap.into());
+                    }
+                }
+                last_chunk = Some(now);
+                self.last_activity.set(Some(now));
+            }
+            buffer.extend_from_slice(&chunk[..len]);
+        }
+    }
+
+    /// Send the request `pdu` to the slave and check the response with `parse`
+    ///
+    /// An exception response is an error with the errno libmodbus uses, as is a response `parse` rejects. Broadcast
+    /// writes on a serial line are not answered, `parse` gets the request then.
+    fn transact<T>(&self, pdu: &[u8], parse: impl FnOnce(&[u8]) -> Option<T>) -> io::Result<T> {
+        let started = Instant::now();
+        let function = pdu[0];
+        let transaction = self.transaction.get().wrapping_add(1);
+        self.transaction.set(transaction);
+        let frame = self.framing.encode(self.slave, transaction, pdu);
+
+        let broadcast = self.framing.is_serial() && self.slave == Modbus::BROADCAST_ADDRESS;
+        let mut received = 0;
+        let result = self
+            .send_frame(&frame)
+            .and_then(|()| {
+                if broadcast {
+                    return match function {
+                        0x05 | 0x06 | 0x0F | 0x10 | 0x16 => Ok(pdu.to_vec()),
+                        _ => Err(errno(libc::EINVAL)),
+                    };
+                }
+                let mut retransmissions = if self.framing.is_serial() {
+                    0
+                } else {
+                    self.retransmissions
+                };
+                // one response timeout per attempt, late responses to earlier requests don't extend it
+                let mut deadline = Instant::now() + self.response_timeout;
+                loop {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    let frame_received = if remaining.is_zero() {
+                        Err(timed_out())
+                    } else {
+                        self.receive_frame(Some(remaining), false)
+                    };
+                    let (adu, len) = match frame_received {
+                        Err(err)
+                            if retransmissions > 0
+                                && err.raw_os_error() == Some(libc::ETIMEDOUT) =>
+                        {
+                            retransmissions -= 1;
+                            self.send_frame(&frame)?;
+                            deadline = Instant::now() + self.response_timeout;
+                            continue;
+                        }
+                        result => result?,
+                    };
+                    received = len;
+                    // a late response to an earlier request
+                    if !self.framing.is_serial() && adu.transaction != transaction {
+                        continue;
+                    }
+                    if adu.unit != self.slave {
+                        return Err(errno(EMBBADSLAVE));
+                    }
+                    return Ok(adu.pdu);
+                }
+            })
+            .and_then(|response| match response.as_slice() {
+                [code, exception] if *code == function | 0x80 => {
+                    Err(match Exception::from_code(*exception) {
+                        Some(exception) => errno(Modbus::ENOBASE as i32 + exception as i32),
+                        None => errno(EMBBADEXC),
+                    })
+                }
+                [code, ..] if *code == function => {
+                    parse(&response).ok_or_else(|| errno(EMBBADDATA))
+                }
+                _ => Err(errno(EMBBADDATA)),
+            });
+
+        self.stats.record_transaction(
+            function,
+            frame.len(),
+            received,
+            started.elapsed(),
+            result.as_ref().map(|_| ()),
+        );
+        result
+    }
+
+    /// Read `num` bits with `function` into `dest`
+    fn read_bits_with(
+        &self,
+        msg: &str,
+        function: FunctionCode,
+        address: u16,
+        num: u16,
+        dest: &mut [u8],
+    ) -> Result<u16, Error> {
+        if num == 0 || num as u32 > Modbus::MAX_READ_BITS || dest.len() < num as usize {
+            return Err(client_error(msg, errno(EMBMDATA)));
+        }
+        let pdu = request_pdu(function, &[address, num]);
+        self.transact(&pdu, |response| {
+            let data = read_data(response, (num as usize).div_ceil(8))?;
+            for (bit, value) in dest[..num as usize].iter_mut().enumerate() {
+                *value = (data[bit / 8] >> (bit % 8)) & 1;
+            }
+            Some(num)
+        })
+        .map_err(|source| client_error(msg, source))
+    }
+
+    /// Read `num` registers with `function` into `dest`
+    fn read_registers_with(
+        &self,
+        msg: &str,
+        function: FunctionCode,
+        address: u16,
+        num: u16,
+        dest: &mut [u16],
+    ) -> Result<u16, Error> {
+        if num == 0 || num as u32 > Modbus::MAX_READ_REGISTERS || dest.len() < num as usize {
+            return Err(client_error(msg, errno(EMBMDATA)));
+        }
+        let pdu = request_pdu(function, &[address, num]);
+        self.transact(&pdu, |response| {
+            let data = read_data(response, 2 * num as usize)?;
+            for (value, bytes) in dest.iter_mut().zip(data.chunks(2)) {
+                *value = be_u16(bytes);
+            }
+            Some(num)
+        })
+        .map_err(|source| client_error(msg, source))
+    }
+
+    /// Send a write request whose response must echo the first `echo` bytes of the request
+    fn write_with(&self, msg: &str, pdu: &[u8], echo: usize) -> Result<(), Error> {
+        self.transact(pdu, |response| (response == &pdu[..echo]).then_some(()))
+            .map_err(|source| client_error(msg, source))
+    }
+
+    /// Unit and PDU of a request received with `receive()`
+    fn split_request(&self, request: &[u8], request_len: i32) -> Result<Adu, Error> {
+        let end = (request_len.max(0) as usize).min(request.len());
+        self.framing
+            .split(&request[..end])
+            .ok_or_else(|| Error::Request {
+                msg: "request too short".to_owned(),
+                exception: Exception::IllegalDataValue,
+            })
+    }
+
+    /// Send the response `pdu` to the request `adu`
+    fn send_reply(
+        &self,
+        adu: &Adu,
+        pdu: &[u8],
+        exception: Option<Exception>,
+    ) -> Result<i32, Error> {
+        let frame = self.framing.encode(adu.unit, adu.transaction, pdu);
+        match self.send_frame(&frame) {
+            Ok(()) => {
+                self.stats.record_reply(frame.len(), exception);
+                Ok(frame.len() as i32)
+            }
+            Err(source) => {
+                self.stats.record_error(&source);
+                Err(Error::Server {
+                    msg: "reply".to_owned(),
+                    source,
+                })
+            }
+        }
+    }
+}
+
+impl ModbusClient for ModbusStream {
+    /// `read_bits` - read many bits, see [`Modbus::read_bits()`](struct.Modbus.html#method.read_bits)
+    fn read_bits(&self, address: u16, num: u16, dest: &mut [u8]) -> Result<u16, Error> {
+        self.read_bits_with("read_bits", FunctionCode::ReadCoils, address, num, dest)
+    }
+
+    /// `read_input_bits` - read many input bits, see
+    /// [`Modbus::read_input_bits()`](struct.Modbus.html#method.read_input_bits)
+    fn read_input_bits(&self, address: u16, num: u16, dest: &mut [u8]) -> Result<u16, Error> {
+        self.read_bits_with(
+            "read_input_bits",
+            FunctionCode::ReadDiscreteInputs,
+            address,
+            num,
+            dest,
+        )
+    }
+
+    /// `read_registers` - read many registers, see
+    /// [`Modbus::read_registers()`](struct.Modbus.html#method.read_registers)
+    fn read_registers(&self, address: u16, num: u16, dest: &mut [u16]) -> Result<u16, Error> {
+        self.read_registers_with(
+            "read_registers",
+            FunctionCode::ReadHoldingRegisters,
+            address,
+            num,
+            dest,
+        )
+    }
+
+    /// `read_input_registers` - read many input registers, see
+    /// [`Modbus::read_input_registers()`](struct.Modbus.html#method.read_input_registers)
+    fn read_input_registers(&self, address: u16, num: u16, dest: &mut [u16]) -> Result<u16, Error> {
+        self.read_registers_with(
+            "read_input_registers",
+            FunctionCode::ReadInputRegisters,
+            address,
+            num,
+            dest,
+        )
+    }
+
+    /// `report_slave_id` - returns a description of the controller, see
+    /// [`Modbus::report_slave_id()`](struct.Modbus.html#method.report_slave_id)
+    fn report_slave_id(&self, max_dest: usize, dest: &mut [u8]) -> Result<u16, Error> {
+        self.transact(&[FunctionCode::ReportSlaveId as u8], |response| {
+            let data = read_data(response, *response.get(1)? as usize)?;
+            let len = data.len().min(max_dest).min(dest.len());
+            dest[..len].copy_from_slice(&data[..len]);
+            Some(data.len() as u16)
+        })
+        .map_err(|source| client_error("report_slave_id", source))
+    }
+
+    /// `write_bit` - write a single bit, see [`Modbus::write_bit()`](struct.Modbus.html#method.write_bit)
+    fn write_bit(&self, address: u16, status: bool) -> Result<(), Error> {
+        let value = if status { 0xFF00 } else { 0 };
+        let pdu = request_pdu(FunctionCode::WriteSingleCoil, &[address, value]);
+        self.write_with("write_bit", &pdu, pdu.len())
+    }
+
+    /// `write_bits` - write many bits, see [`Modbus::write_bits()`](struct.Modbus.html#method.write_bits)
+    fn write_bits(&self, address: u16, num: u16, src: &[u8]) -> Result<u16, Error> {
+        if num == 0 || num as u32 > Modbus::MAX_WRITE_BITS || src.len() < num as usize {
+            return Err(client_error("write_bits", errno(EMBMDATA)));
+        }
+        let bits: Vec<bool> = src[..num as usize].iter().map(|&bit| bit != 0).collect();
+        let packed = pack_bits(&bits);
+        let mut pdu = request_pdu(FunctionCode::WriteMultipleCoils, &[address, num]);
+        pdu.push(packed.len() as u8);
+        pdu.extend_from_slice(&packed);
+        self.write_with("write_bits", &pdu, 5).map(|()| num)
+    }
+
+    /// `write_register` - write a single register, see
+    /// [`Modbus::write_register()`](struct.Modbus.html#method.write_register)
+    fn write_register(&self, address: u16, value: u16) -> Result<(), Error> {
+        let pdu = request_pdu(FunctionCode::WriteSingleRegister, &[address, value]);
+        self.write_with("write_register", &pdu, pdu.len())
+    }
+
+    /// `write_registers` - write many registers, see
+    /// [`Modbus::write_registers()`](struct.Modbus.html#method.write_registers)
+    fn write_registers(&self, address: u16, num: u16, src: &[u16]) -> Result<u16, Error> {
+        if num == 0 || num as u32 > Modbus::MAX_WRITE_REGISTERS || src.len() < num as usize {
+            return Err(client_error("write_registers", errno(EMBMDATA)));
+        }
+        let mut pdu = request_pdu(FunctionCode::WriteMultipleRegisters, &[address, num]);
+        pdu.push(2 * num as u8);
+        for value in &src[..num as usize] {
+            pdu.extend_from_slice(&value.to_be_bytes());
+        }
+        self.write_with("write_registers", &pdu, 5).map(|()| num)
+    }
+
+    /// `write_and_read_registers` - write and read many registers in a single transaction, see
+    /// [`Modbus::write_and_read_registers()`](struct.Modbus.html#method.write_and_read_registers)
+    fn write_and_read_registers(
+        &self,
+        write_address: u16,
+        write_num: u16,
+        src: &[u16],
+        read_address: u16,
+        read_num: u16,
+        dest: &mut [u16],
+    ) -> Result<u16, Error> {
+        let msg = "write_and_read_registers";
+        if write_num == 0
+            || write_num as u32 > Modbus::MAX_WR_WRITE_REGISTERS
+            || src.len() < write_num as usize
+            || read_num == 0
+            || read_num as u32 > Modbus::MAX_WR_READ_REGISTERS
+            || dest.len() < read_num as usize
+        {
+            return Err(client_error(msg, errno(EMBMDATA)));
+        }
+        let mut pdu = request_pdu(
+            FunctionCode::WriteAndReadRegisters,
+            &[read_address, read_num, write_address, write_num],
+        );
+        pdu.push(2 * write_num as u8);
+        for value in &src[..write_num as usize] {
+            pdu.extend_from_slice(&value.to_be_bytes());
+        }
+        self.transact(&pdu, |response| {
+            let data = read_data(response, 2 * read_num as usize)?;
+            for (value, bytes) in dest.iter_mut().zip(data.chunks(2)) {
+                *value = be_u16(bytes);
+            }
+            Some(read_num)
+        })
+        .map_err(|source| client_error(msg, source))
+    }
+
+    /// `mask_write_register` - mask a single register, see
+    /// [`Modbus::mask_write_register()`](struct.Modbus.html#method.mask_write_register)
+    fn mask_write_register(&self, address: u16, and_mask: u16, or_mask: u16) -> Result<(), Error> {
+        let pdu = request_pdu(
+            FunctionCode::MaskWriteRegister,
+            &[address, and_mask, or_mask],
+        );
+        self.write_with("mask_write_register", &pdu, pdu.len())
+    }
+
+    /// `send_raw_request` - send a raw request, see
+    /// [`Modbus::send_raw_request()`](struct.Modbus.html#method.send_raw_request)
+    ///
+    /// `raw_request` is the slave id followed by the PDU, the framing is added.
+    fn send_raw_request(&self, raw_request: &mut [u8], lenght: usize) -> Result<u16, Error> {
+        let (unit, pdu) = match raw_request.get(..lenght) {
+            Some([unit, pdu @ ..]) if !pdu.is_empty() => (*unit, pdu),
+            _ => return Err(client_error("send_raw_request", errno(libc::EINVAL))),
+        };
+        let transaction = self.transaction.get().wrapping_add(1);
+        self.transaction.set(transaction);
+        let frame = self.framing.encode(unit, transaction, pdu);
+        match self.send_frame(&frame) {
+            Ok(()) => {
+                self.stats.record_sent(pdu[0], frame.len());
+                Ok(frame.len() as u16)
+            }
+            Err(source) => Err(client_error("send_raw_request", source)),
+        }
+    }
+
+    /// `receive_confirmation` - receive a confirmation request, see
+    /// [`Modbus::receive_confirmation()`](struct.Modbus.html#method.receive_confirmation)
+    fn receive_confirmation(&self, response: &mut [u8]) -> Result<u16, Error> {
+        match self.receive_frame(Some(self.response_timeout), false) {
+            Ok((adu, len)) => {
+                let binary = self.framing.binary(&adu);
+                if binary.len() > response.len() {
+                    return Err(client_error("receive_confirmation", errno(libc::EMSGSIZE)));
+                }
+                response[..binary.len()].copy_from_slice(&binary);
+                self.stats.record_received(len);
+                Ok(binary.len() as u16)
+            }
+            Err(source) => {
+                self.stats.record_error(&source);
+                Err(client_error("receive_confirmation", source))
+            }
+        }
+    }
+}
+
+impl ModbusServer for ModbusStream {
+    /// `receive` - receive an indication request
+    ///
+    /// Waits at most the [indication timeout](struct.ModbusStream.html#method.set_indication_timeout). On a serial
+    /// line requests for other slaves are skipped, 0 is returned for them.
+    fn receive(&self, request: &mut [u8]) -> Result<i32, Error> {
+        let (adu, len) = match self.receive_frame(self.indication_timeout, true) {
+            Ok(frame) => frame,
+            Err(source) => {
+                self.stats.record_error(&source);
+                return Err(Error::Server {
+                    msg: "receive".to_owned(),
+                    source,
+                });
+            }
+        };
+        if self.framing.is_serial()
+            && adu.unit != self.slave
+            && adu.unit != Modbus::BROADCAST_ADDRESS
+        {
+            return Ok(0);
+        }
+        let binary = self.framing.binary(&adu);
+        if binary.len() > request.len() {
+            return Err(Error::Server {
+                msg: "receive".to_owned(),
+                source: errno(libc::EMSGSIZE),
+            });
+        }
+        request[..binary.len()].copy_from_slice(&binary);
+        self.stats.record_indication(adu.pdu[0], len);
+        Ok(binary.len() as i32)
+    }
+
+    /// `reply` - send a response to the received request, see
+    /// [`Modbus::reply()`](struct.Modbus.html#method.reply)
+    fn reply(
+        &self,
+        request: &[u8],
+        request_len: i32,
+        modbus_mapping: &ModbusMapping,
+    ) -> Result<i32, Error> {
+        self.reply_with(request, request_len, &mut &*modbus_mapping)
+    }
+
+    /// `reply_with` - answer the received request with a handler, see
+    /// [`Modbus::reply_with()`](struct.Modbus.html#method.reply_with)
+    fn reply_with(
+        &self,
+        request: &[u8],
+        request_len: i32,
+        handler: &mut dyn RequestHandler,
+    ) -> Result<i32, Error> {
+        let adu = self.split_request(request, request_len)?;
+        // broadcasts are executed but never answered on a serial line
+        let answer = !self.framing.is_serial() || adu.unit != Modbus::BROADCAST_ADDRESS;
+
+        let selection = if answer {
+            handler.select_unit(adu.unit)
+        } else {
+            handler.select_broadcast()
+        };
+        let outcome = match selection {
+            UnitSelection::Handle => Request::decode(adu.unit, &adu.pdu)
+                .map_err(|err| err.exception().unwrap_or(Exception::IllegalDataValue))
+                .and_then(|decoded| {
+                    let response = dispatch(handler, &decoded)?;
+                    encode_response(&decoded, response).ok_or(Exception::SlaveOrServerFailure)
+                }),
+            UnitSelection::Exception(exception) => Err(exception),
+            UnitSelection::Unknown if !self.framing.is_serial() => Err(Exception::GatewayTarget),
+            UnitSelection::Unknown | UnitSelection::Ignore => return Ok(0),
+        };
+        if !answer {
+            return Ok(0);
+        }
+        match outcome {
+            Ok(pdu) => self.send_reply(&adu, &pdu, None),
+            Err(exception) => {
+                self.send_reply(&adu, &[adu.pdu[0] | 0x80, exception as u8], Some(exception))
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ModbusStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ModbusStream")
+            .field("framing", &self.framing)
+            .field("connected", &self.transport.borrow().is_some())
+            .field("slave", &self.slave)
+            .finish()
+    }
+}
+
+impl Drop for ModbusStream {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
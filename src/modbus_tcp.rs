@@ -1,4 +1,6 @@
 use crate::prelude::*;
+#[cfg(unix)]
+use crate::{modbus_tcp_listener, ModbusListener};
 use libmodbus_sys as ffi;
 use std::ffi::CString;
 
@@ -8,6 +10,9 @@ use std::ffi::CString;
 /// * Create a Modbus TCP context
 ///     - [`new_tcp()`](struct.Modbus.html#method.new_tcp)
 ///
+/// * Accept each client as a session of its own
+///     - [`tcp_listener()`](struct.Modbus.html#method.tcp_listener)
+///
 pub trait ModbusTCP {
     fn new_tcp(ip: &str, port: i32) -> Result<Modbus, Error>;
     fn tcp_accept(&mut self, socket: &mut i32) -> Result<i32, Error>;
@@ -49,7 +54,7 @@ impl ModbusTCP for Modbus {
                     source: ::std::io::Error::last_os_error(),
                 })
             } else {
-                Ok(Modbus { ctx: ctx })
+                Ok(Modbus::from_ctx(ctx))
             }
         }
     }
@@ -116,3 +121,34 @@ impl ModbusTCP for Modbus {
         }
     }
 }
+
+#[cfg(unix)]
+impl Modbus {
+    /// `tcp_listener` - create a listener accepting each client as a session of its own
+    ///
+    /// The [`tcp_listener()`](#method.tcp_listener) function listens like [`tcp_listen()`](#method.tcp_listen), but
+    /// returns a [`ModbusListener`](struct.ModbusListener.html) owning the socket. Its
+    /// [`accept()`](struct.ModbusListener.html#method.accept) creates a new context for each client, which can be
+    /// moved to a thread of its own, instead of connecting this context to the client.
+    ///
+    /// Only available on unix platforms.
+    ///
+    /// # Parameters
+    ///
+    /// * `num_connection`  - maximum number of incoming connections on the specified IP address
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use libmodbus::{Modbus, ModbusTCP};
+    ///
+    /// let mut modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+    /// let listener = modbus.tcp_listener(5).unwrap();
+    ///
+    /// let session = listener.accept().unwrap();
+    /// println!("client {} connected", session.peer_addr());
+    /// ```
+    pub fn tcp_listener(&mut self, num_connection: i32) -> Result<ModbusListener, Error> {
+        modbus_tcp_listener::tcp_listener(self, num_connection)
+    }
+}
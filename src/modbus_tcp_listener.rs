@@ -0,0 +1,380 @@
+use crate::prelude::*;
+use crate::{ModbusTCP, ModbusTCPPI};
+use std::io;
+use std::mem;
+use std::net::{SocketAddr, TcpListener};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+
+/// The backend of the contexts a listener creates for its clients
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Backend {
+    Tcp,
+    TcpPi,
+}
+
+impl Backend {
+    /// The backend serving clients of `addr`, TCP PI for IPv6 and TCP for IPv4
+    fn of(addr: &SocketAddr) -> Backend {
+        match addr {
+            SocketAddr::V4(_) => Backend::Tcp,
+            SocketAddr::V6(_) => Backend::TcpPi,
+        }
+    }
+
+    fn error(self, msg: &str, source: std::io::Error) -> Error {
+        match self {
+            Backend::Tcp => Error::Tcp {
+                msg: msg.to_owned(),
+                source,
+            },
+            Backend::TcpPi => Error::TcpPi {
+                msg: msg.to_owned(),
+                source,
+            },
+        }
+    }
+}
+
+/// Listening socket of a Modbus TCP or TCP PI server, accepting each client as a session of its own
+///
+/// Created by [`tcp_listener()`](struct.Modbus.html#method.tcp_listener) or
+/// [`tcp_pi_listener()`](struct.Modbus.html#method.tcp_pi_listener) with the socket options of libmodbus, by a
+/// [`ListenerBuilder`](struct.ListenerBuilder.html) to choose them, or from a `std::net::TcpListener` bound
+/// before. Unlike
+/// [`tcp_accept()`](struct.Modbus.html#method.tcp_accept), [`accept()`](#method.accept) leaves the listening
+/// context alone and returns a [`TcpSession`](struct.TcpSession.html) with a new context for the client. The
+/// listener owns the listening socket and closes it on drop.
+///
+/// Only available on unix platforms.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use libmodbus::{Modbus, ModbusMapping, ModbusServer, ModbusTCP};
+/// use std::thread;
+///
+/// let mut modbus = Modbus::new_tcp("0.0.0.0", 1502).unwrap();
+/// let listener = modbus.tcp_listener(5).unwrap();
+///
+/// loop {
+///     let session = listener.accept().unwrap();
+///     println!("client {} connected", session.peer_addr());
+///     thread::spawn(move || {
+///         let mapping = ModbusMapping::new(500, 500, 500, 500).unwrap();
+///         let modbus = session.into_modbus();
+///         let mut query = vec![0u8; Modbus::TCP_MAX_ADU_LENGTH];
+///         while let Ok(rc) = modbus.receive(&mut query) {
+///             modbus.reply(&query, rc, &mapping).unwrap();
+///         }
+///     });
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ModbusListener {
+    listener: TcpListener,
+    backend: Backend,
+}
+
+impl ModbusListener {
+    /// Take ownership of a socket returned by `tcp_listen()` or `tcp_pi_listen()`
+    fn from_socket(socket: RawFd, backend: Backend) -> ModbusListener {
+        ModbusListener {
+            listener: unsafe { TcpListener::from_raw_fd(socket) },
+            backend,
+        }
+    }
+
+    /// `accept` - accept the next client
+    ///
+    /// The [`accept()`](#method.accept) function waits for the next client to connect and creates a context of the
+    /// same backend as the listening one for it, with the defaults of libmodbus, e.g. no slave set. The listener
+    /// stays ready for further clients.
+    ///
+    /// # Return value
+    ///
+    /// The function returns the session of the client if successful, otherwise an Error.
+    pub fn accept(&self) -> Result<TcpSession, Error> {
+        let (stream, peer) = self
+            .listener
+            .accept()
+            .map_err(|source| self.backend.error("accept", source))?;
+        // the address of a connected context doesn't matter, it only uses the socket
+        let mut modbus = match self.backend {
+            Backend::Tcp => Modbus::new_tcp("0.0.0.0", Modbus::TCP_DEFAULT_PORT as i32)?,
+            Backend::TcpPi => Modbus::new_tcp_pi("::", "502")?,
+        };
+        // the context owns the socket now
+        modbus.set_socket(stream)?;
+        debug_assert!(modbus.custom_rts.is_null());
+        Ok(TcpSession { modbus, peer })
+    }
+
+    /// `local_addr` - the address the listener is bound to
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        self.listener
+            .local_addr()
+            .map_err(|source| self.backend.error("local_addr", source))
+    }
+}
+
+/// Serve the clients of a listener bound before, e.g. inherited from a service manager
+///
+/// The clients of an IPv4 listener get TCP contexts, those of an IPv6 listener TCP PI contexts.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use libmodbus::ModbusListener;
+/// use std::net::TcpListener;
+///
+/// let listener = ModbusListener::from(TcpListener::bind("127.0.0.1:0").unwrap());
+/// println!("listening on {}", listener.local_addr().unwrap());
+/// ```
+impl From<TcpListener> for ModbusListener {
+    fn from(listener: TcpListener) -> ModbusListener {
+        let backend = match listener.local_addr() {
+            Ok(addr) => Backend::of(&addr),
+            Err(_) => Backend::Tcp,
+        };
+        ModbusListener { listener, backend }
+    }
+}
+
+impl AsRawFd for ModbusListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.listener.as_raw_fd()
+    }
+}
+
+/// Builder of a [`ModbusListener`](struct.ModbusListener.html) with the socket options chosen
+///
+/// [`tcp_listen()`](struct.Modbus.html#method.tcp_listen) and
+/// [`tcp_pi_listen()`](struct.Modbus.html#method.tcp_pi_listen) bind to the address of the context with the socket
+/// options of libmodbus. The builder binds to any address, e.g. port 0 for a port chosen by the system, see
+/// [`local_addr()`](struct.ModbusListener.html#method.local_addr), and sets the options before binding. The clients
+/// of an IPv4 address get TCP contexts, those of an IPv6 address TCP PI contexts.
+///
+/// Only available on unix platforms.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use libmodbus::ListenerBuilder;
+///
+/// // IPv6 only, on a port chosen by the system, shared with other processes
+/// let listener = ListenerBuilder::new("[::]:0".parse().unwrap())
+///     .only_v6(true)
+///     .reuse_port(true)
+///     .listen()
+///     .unwrap();
+/// println!("listening on port {}", listener.local_addr().unwrap().port());
+/// ```
+#[derive(Debug, Clone)]
+pub struct ListenerBuilder {
+    addr: SocketAddr,
+    num_connection: i32,
+    reuse_address: bool,
+    reuse_port: bool,
+    only_v6: Option<bool>,
+}
+
+impl ListenerBuilder {
+    /// `new` - create a builder listening on `addr`
+    ///
+    /// By default the listener reuses the address, like libmodbus does, doesn't reuse the port, leaves IPv6 sockets
+    /// to the system default, dual-stack on most systems, and queues up to `SOMAXCONN` connections.
+    pub fn new(addr: SocketAddr) -> ListenerBuilder {
+        ListenerBuilder {
+            addr,
+            num_connection: libc::SOMAXCONN,
+            reuse_address: true,
+            reuse_port: false,
+            only_v6: None,
+        }
+    }
+
+    /// `num_connection` - set the maximum number of connections waiting to be accepted
+    pub fn num_connection(&mut self, num_connection: i32) -> &mut ListenerBuilder {
+        self.num_connection = num_connection;
+        self
+    }
+
+    /// `reuse_address` - set `SO_REUSEADDR`, to bind while connections of a previous listener linger
+    pub fn reuse_address(&mut self, reuse_address: bool) -> &mut ListenerBuilder {
+        self.reuse_address = reuse_address;
+        self
+    }
+
+    /// `reuse_port` - set `SO_REUSEPORT`, to share the address with other sockets setting it
+    pub fn reuse_port(&mut self, reuse_port: bool) -> &mut ListenerBuilder {
+        self.reuse_port = reuse_port;
+        self
+    }
+
+    /// `only_v6` - set `IPV6_V6ONLY`, to accept IPv6 clients only instead of IPv4 clients as well
+    ///
+    /// Only IPv6 addresses have the option, [`listen()`](#method.listen) fails for IPv4 addresses if it is set.
+    pub fn only_v6(&mut self, only_v6: bool) -> &mut ListenerBuilder {
+        self.only_v6 = Some(only_v6);
+        self
+    }
+
+    /// `listen` - create the socket, set its options, bind and listen
+    ///
+    /// # Return value
+    ///
+    /// The function returns the listener if successful, otherwise an Error.
+    pub fn listen(&self) -> Result<ModbusListener, Error> {
+        let backend = Backend::of(&self.addr);
+        let fail = |msg: &str| backend.error(msg, io::Error::last_os_error());
+        let domain = match self.addr {
+            SocketAddr::V4(_) => libc::AF_INET,
+            SocketAddr::V6(_) => libc::AF_INET6,
+        };
+        // close-on-exec from the start, a separate fcntl() would race with fork() and exec() of other threads
+        #[cfg(not(target_vendor = "apple"))]
+        let socket = unsafe { libc::socket(domain, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0) };
+        #[cfg(target_vendor = "apple")]
+        let socket = unsafe { libc::socket(domain, libc::SOCK_STREAM, 0) };
+        if socket == -1 {
+            return Err(fail("socket"));
+        }
+        // closes the socket on error
+        let listener = unsafe { TcpListener::from_raw_fd(socket) };
+        // the platform has no SOCK_CLOEXEC
+        #[cfg(target_vendor = "apple")]
+        if unsafe { libc::fcntl(socket, libc::F_SETFD, libc::FD_CLOEXEC) } == -1 {
+            return Err(fail("fcntl"));
+        }
+        let options = [
+            (
+                libc::SOL_SOCKET,
+                libc::SO_REUSEADDR,
+                Some(self.reuse_address),
+            ),
+            (libc::SOL_SOCKET, libc::SO_REUSEPORT, Some(self.reuse_port)),
+            (libc::IPPROTO_IPV6, libc::IPV6_V6ONLY, self.only_v6),
+        ];
+        for (level, name, value) in options.iter() {
+            if let Some(value) = value {
+                set_option(socket, *level, *name, *value)
+                    .map_err(|source| backend.error("setsockopt", source))?;
+            }
+        }
+        bind(socket, &self.addr).map_err(|source| backend.error("bind", source))?;
+        if unsafe { libc::listen(socket, self.num_connection) } == -1 {
+            return Err(fail("listen"));
+        }
+        Ok(ModbusListener { listener, backend })
+    }
+}
+
+fn set_option(socket: RawFd, level: i32, name: i32, value: bool) -> io::Result<()> {
+    let value = value as libc::c_int;
+    let rc = unsafe {
+        libc::setsockopt(
+            socket,
+            level,
+            name,
+            &value as *const libc::c_int as *const libc::c_void,
+            mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    match rc {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+fn bind(socket: RawFd, addr: &SocketAddr) -> io::Result<()> {
+    let rc = match addr {
+        SocketAddr::V4(addr) => {
+            let mut sin: libc::sockaddr_in = unsafe { mem::zeroed() };
+            sin.sin_family = libc::AF_INET as libc::sa_family_t;
+            sin.sin_port = addr.port().to_be();
+            sin.sin_addr.s_addr = u32::from_ne_bytes(addr.ip().octets());
+            unsafe {
+                libc::bind(
+                    socket,
+                    &sin as *const libc::sockaddr_in as *const libc::sockaddr,
+                    mem::size_of::<libc::sockaddr_in>() as libc::socklen_t,
+                )
+            }
+        }
+        SocketAddr::V6(addr) => {
+            let mut sin6: libc::sockaddr_in6 = unsafe { mem::zeroed() };
+            sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            sin6.sin6_port = addr.port().to_be();
+            sin6.sin6_flowinfo = addr.flowinfo();
+            sin6.sin6_addr.s6_addr = addr.ip().octets();
+            sin6.sin6_scope_id = addr.scope_id();
+            unsafe {
+                libc::bind(
+                    socket,
+                    &sin6 as *const libc::sockaddr_in6 as *const libc::sockaddr,
+                    mem::size_of::<libc::sockaddr_in6>() as libc::socklen_t,
+                )
+            }
+        }
+    };
+    match rc {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+/// The connection of one client, accepted by a [`ModbusListener`](struct.ModbusListener.html)
+///
+/// The session owns a context connected to the client, closed on drop, and can be moved to a thread serving the
+/// client. Use its [`modbus()`](#method.modbus) to receive and reply, e.g. with
+/// [`ModbusServer`](trait.ModbusServer.html).
+#[derive(Debug)]
+pub struct TcpSession {
+    modbus: Modbus,
+    peer: SocketAddr,
+}
+
+// SAFETY: The session is the only owner of its libmodbus context, it isn't shared with the listener or other
+// sessions. `Modbus` also holds `custom_rts`, a closure which isn't `Send`, but it stays null here: libmodbus rejects
+// `rtu_set_custom_rts()` on TCP contexts, and `accept()` only creates TCP contexts.
+unsafe impl Send for TcpSession {}
+
+impl TcpSession {
+    /// `peer_addr` - the address of the client
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.peer
+    }
+
+    /// `modbus` - the context connected to the client
+    pub fn modbus(&self) -> &Modbus {
+        &self.modbus
+    }
+
+    /// `modbus_mut` - the context connected to the client, to change its settings
+    pub fn modbus_mut(&mut self) -> &mut Modbus {
+        &mut self.modbus
+    }
+
+    /// `into_modbus` - take the context connected to the client out of the session
+    pub fn into_modbus(self) -> Modbus {
+        self.modbus
+    }
+}
+
+/// `tcp_listener` for [`ModbusTCP`](trait.ModbusTCP.html)
+pub(crate) fn tcp_listener(
+    modbus: &mut Modbus,
+    num_connection: i32,
+) -> Result<ModbusListener, Error> {
+    let socket = modbus.tcp_listen(num_connection)?;
+    Ok(ModbusListener::from_socket(socket, Backend::Tcp))
+}
+
+/// `tcp_pi_listener` for [`ModbusTCPPI`](trait.ModbusTCPPI.html)
+pub(crate) fn tcp_pi_listener(
+    modbus: &mut Modbus,
+    num_connection: i32,
+) -> Result<ModbusListener, Error> {
+    let socket = modbus.tcp_pi_listen(num_connection)?;
+    Ok(ModbusListener::from_socket(socket, Backend::TcpPi))
+}
@@ -1,4 +1,6 @@
 use crate::prelude::*;
+#[cfg(unix)]
+use crate::{modbus_tcp_listener, ModbusListener};
 use libmodbus_sys as ffi;
 use std::ffi::CString;
 
@@ -12,6 +14,9 @@ use std::ffi::CString;
 /// * Create a Modbus TCP context
 ///     - [`new_tcp_pi()`](struct.Modbus.html#method.new_tcp_pi)
 ///
+/// * Accept each client as a session of its own
+///     - [`tcp_pi_listener()`](struct.Modbus.html#method.tcp_pi_listener)
+///
 pub trait ModbusTCPPI {
     fn new_tcp_pi(node: &str, service: &str) -> Result<Modbus, Error>;
     fn tcp_pi_accept(&mut self, socket: &mut i32) -> Result<i32, Error>;
@@ -57,7 +62,7 @@ impl ModbusTCPPI for Modbus {
                     source: ::std::io::Error::last_os_error(),
                 })
             } else {
-                Ok(Modbus { ctx: ctx })
+                Ok(Modbus::from_ctx(ctx))
             }
         }
     }
@@ -138,3 +143,34 @@ impl ModbusTCPPI for Modbus {
         }
     }
 }
+
+#[cfg(unix)]
+impl Modbus {
+    /// `tcp_pi_listener` - create a listener accepting each client as a session of its own
+    ///
+    /// The [`tcp_pi_listener()`](#method.tcp_pi_listener) function listens like
+    /// [`tcp_pi_listen()`](#method.tcp_pi_listen), but returns a [`ModbusListener`](struct.ModbusListener.html)
+    /// owning the socket. Its [`accept()`](struct.ModbusListener.html#method.accept) creates a new TCP PI context for
+    /// each client, which can be moved to a thread of its own, instead of connecting this context to the client.
+    ///
+    /// Only available on unix platforms.
+    ///
+    /// # Parameters
+    ///
+    /// * `num_connection`  - maximum number of incoming connections on the specified node
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use libmodbus::{Modbus, ModbusTCPPI};
+    ///
+    /// let mut modbus = Modbus::new_tcp_pi("::0", "1502").unwrap();
+    /// let listener = modbus.tcp_pi_listener(5).unwrap();
+    ///
+    /// let session = listener.accept().unwrap();
+    /// println!("client {} connected", session.peer_addr());
+    /// ```
+    pub fn tcp_pi_listener(&mut self, num_connection: i32) -> Result<ModbusListener, Error> {
+        modbus_tcp_listener::tcp_pi_listener(self, num_connection)
+    }
+}
@@ -0,0 +1,297 @@
+use crate::prelude::*;
+use crate::{ModbusServer, RequestHandler};
+use libc::c_int;
+use std::io;
+use std::ptr;
+use std::time::{Duration, Instant};
+
+/// A client connection of a [`TcpServer`](struct.TcpServer.html)
+#[derive(Debug)]
+struct Connection {
+    socket: c_int,
+    last_activity: Instant,
+}
+
+/// Blocking Modbus TCP server for many clients at once
+///
+/// libmodbus contexts hold a single socket, so [`tcp_accept()`](struct.Modbus.html#method.tcp_accept) replaces the
+/// connection of the previous client. A `TcpServer` owns the listening socket and all client sockets instead,
+/// waits on all of them with `poll()` and switches the socket of the context with
+/// [`set_socket()`](struct.Modbus.html#method.set_socket) to the client a request arrived from. Each request is
+/// answered by a [`RequestHandler`](trait.RequestHandler.html), e.g. a [`ModbusMapping`](struct.ModbusMapping.html).
+///
+/// Connections closed by the client, or failing to receive or reply, are closed and removed. Optionally the number
+/// of connections is limited and idle connections are closed after a timeout.
+///
+/// The context must be a TCP or TCP PI context, the listening socket is created with
+/// [`tcp_listen()`](struct.Modbus.html#method.tcp_listen) or
+/// [`tcp_pi_listen()`](struct.Modbus.html#method.tcp_pi_listen). The Rust backends of a
+/// [`ModbusStream`](struct.ModbusStream.html) aren't supported.
+///
+/// Only available on unix platforms.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use libmodbus::{Modbus, ModbusMapping, ModbusTCP, TcpServer};
+/// use std::time::Duration;
+///
+/// let mut modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+/// let socket = modbus.tcp_listen(5).unwrap();
+/// let mut mapping = ModbusMapping::new(500, 500, 500, 500).unwrap();
+///
+/// let mut server = TcpServer::new(modbus, socket);
+/// server.set_max_connections(5);
+/// server.set_idle_timeout(Some(Duration::from_secs(60)));
+///
+/// server.serve(&mut mapping).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct TcpServer {
+    modbus: Modbus,
+    listener: c_int,
+    connections: Vec<Connection>,
+    max_connections: usize,
+    idle_timeout: Option<Duration>,
+    query: Vec<u8>,
+}
+
+impl TcpServer {
+    /// `new` - create a server from a context and its listening socket
+    ///
+    /// The server takes ownership of the listening socket and closes it, and all client connections, on drop.
+    ///
+    /// # Parameters
+    ///
+    /// * `modbus`      - TCP or TCP PI context
+    /// * `listener`    - socket returned by [`tcp_listen()`](struct.Modbus.html#method.tcp_listen) or
+    ///   [`tcp_pi_listen()`](struct.Modbus.html#method.tcp_pi_listen)
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use libmodbus::{Modbus, ModbusTCP, TcpServer};
+    ///
+    /// let mut modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+    /// let socket = modbus.tcp_listen(5).unwrap();
+    ///
+    /// let server = TcpServer::new(modbus, socket);
+    /// ```
+    pub fn new(modbus: Modbus, listener: i32) -> TcpServer {
+        TcpServer {
+            modbus,
+            listener,
+            connections: Vec::new(),
+            max_connections: usize::MAX,
+            idle_timeout: None,
+            query: vec![0; Modbus::TCP_MAX_ADU_LENGTH],
+        }
+    }
+
+    /// `modbus` - the context answering the requests
+    ///
+    /// E.g. to read the [`statistics()`](struct.Modbus.html#method.statistics) of the server.
+    pub fn modbus(&self) -> &Modbus {
+        &self.modbus
+    }
+
+    /// `connections` - number of open client connections
+    pub fn connections(&self) -> usize {
+        self.connections.len()
+    }
+
+    /// `get_max_connections` - get the maximum number of client connections
+    pub fn get_max_connections(&self) -> usize {
+        self.max_connections
+    }
+
+    /// `set_max_connections` - set the maximum number of client connections
+    ///
+    /// Clients connecting while the limit is reached are accepted and closed right away, so they fail fast
+    /// instead of waiting in the backlog of the listening socket. Not limited by default.
+    pub fn set_max_connections(&mut self, max_connections: usize) {
+        self.max_connections = max_connections;
+    }
+
+    /// `get_idle_timeout` - get the timeout after which idle connections are closed
+    pub fn get_idle_timeout(&self) -> Option<Duration> {
+        self.idle_timeout
+    }
+
+    /// `set_idle_timeout` - set the timeout after which idle connections are closed
+    ///
+    /// A connection is idle while no request is received on it. `None`, the default, keeps idle connections open.
+    pub fn set_idle_timeout(&mut self, idle_timeout: Option<Duration>) {
+        self.idle_timeout = idle_timeout;
+    }
+
+    /// `poll_once` - wait for requests and new connections once and handle them
+    ///
+    /// The [`poll_once()`](#method.poll_once) function waits until a client sends a request, a client connects or
+    /// `timeout` elapses, whatever comes first. Then every pending request is received and answered by `handler`,
+    /// new connections are accepted and connections idle for longer than the idle timeout are closed.
+    ///
+    /// A request started by a client is received completely before `poll_once` returns, the byte timeout of the
+    /// context limits how long a client can stall the server by sending a partial request.
+    ///
+    /// # Return value
+    ///
+    /// The function returns the number of requests answered if successful, or an Error if waiting or accepting
+    /// failed. Failing client connections are closed and not reported as error.
+    ///
+    /// # Parameters
+    ///
+    /// * `timeout` - how long to wait at most, `None` to wait forever
+    /// * `handler` - handler answering the requests
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use libmodbus::{Modbus, ModbusMapping, ModbusTCP, TcpServer};
+    /// use std::time::Duration;
+    ///
+    /// let mut modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+    /// let socket = modbus.tcp_listen(5).unwrap();
+    /// let mut mapping = ModbusMapping::new(500, 500, 500, 500).unwrap();
+    /// let mut server = TcpServer::new(modbus, socket);
+    ///
+    /// loop {
+    ///     server.poll_once(Some(Duration::from_millis(100)), &mut mapping).unwrap();
+    ///     // update the mapping with fresh process values
+    /// }
+    /// ```
+    pub fn poll_once(
+        &mut self,
+        timeout: Option<Duration>,
+        handler: &mut dyn RequestHandler,
+    ) -> Result<usize, Error> {
+        let mut fds: Vec<libc::pollfd> = Some(self.listener)
+            .into_iter()
+            .chain(self.connections.iter().map(|connection| connection.socket))
+            .map(|fd| libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            })
+            .collect();
+        let timeout = timeout.map_or(-1, |timeout| {
+            timeout.as_micros().div_ceil(1000).min(c_int::MAX as u128) as c_int
+        });
+
+        if unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) } == -1 {
+            let source = io::Error::last_os_error();
+            if source.kind() == io::ErrorKind::Interrupted {
+                return Ok(0);
+            }
+            return Err(Error::Server {
+                msg: "poll".to_owned(),
+                source,
+            });
+        }
+
+        let mut served = 0;
+        let mut closed = Vec::new();
+        for (pollfd, connection) in fds[1..].iter().zip(self.connections.iter_mut()) {
+            if pollfd.revents & (libc::POLLIN | libc::POLLHUP | libc::POLLERR) == 0 {
+                continue;
+            }
+            connection.last_activity = Instant::now();
+            self.modbus.set_socket(connection.socket)?;
+            match self.modbus.receive(&mut self.query) {
+                Ok(0) => {}
+                Ok(len) => match self.modbus.reply_with(&self.query, len, handler) {
+                    Ok(_) => served += 1,
+                    Err(_) => closed.push(connection.socket),
+                },
+                Err(_) => closed.push(connection.socket),
+            }
+        }
+        self.modbus.set_socket(-1)?;
+
+        if let Some(idle_timeout) = self.idle_timeout {
+            closed.extend(
+                self.connections
+                    .iter()
+                    .filter(|connection| connection.last_activity.elapsed() >= idle_timeout)
+                    .map(|connection| connection.socket),
+            );
+        }
+        if !closed.is_empty() {
+            self.connections.retain(|connection| {
+                if closed.contains(&connection.socket) {
+                    unsafe { libc::close(connection.socket) };
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+
+        if fds[0].revents & libc::POLLIN != 0 {
+            self.accept()?;
+        }
+
+        Ok(served)
+    }
+
+    /// `serve` - answer requests of all clients with `handler` until an error occurs
+    ///
+    /// Calls [`poll_once()`](#method.poll_once) in a loop, only returns if waiting or accepting failed. Each call
+    /// waits at most until the next connection reaches the idle timeout.
+    ///
+    /// # Parameters
+    ///
+    /// * `handler` - handler answering the requests
+    pub fn serve(&mut self, handler: &mut dyn RequestHandler) -> Result<(), Error> {
+        loop {
+            self.poll_once(self.idle_time_left(), handler)?;
+        }
+    }
+
+    /// Time until the first connection reaches the idle timeout, `None` if no connection can
+    fn idle_time_left(&self) -> Option<Duration> {
+        let idle_timeout = self.idle_timeout?;
+        self.connections
+            .iter()
+            .map(|connection| idle_timeout.saturating_sub(connection.last_activity.elapsed()))
+            .min()
+    }
+
+    /// Accept a pending connection, or close it right away if the connection limit is reached
+    fn accept(&mut self) -> Result<(), Error> {
+        let socket = unsafe { libc::accept(self.listener, ptr::null_mut(), ptr::null_mut()) };
+        if socket == -1 {
+            let source = io::Error::last_os_error();
+            return match source.kind() {
+                io::ErrorKind::Interrupted
+                | io::ErrorKind::WouldBlock
+                | io::ErrorKind::ConnectionAborted => Ok(()),
+                _ => Err(Error::Tcp {
+                    msg: "accept".to_owned(),
+                    source,
+                }),
+            };
+        }
+
+        if self.connections.len() >= self.max_connections {
+            unsafe { libc::close(socket) };
+        } else {
+            self.connections.push(Connection {
+                socket,
+                last_activity: Instant::now(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Drop for TcpServer {
+    fn drop(&mut self) {
+        // the client sockets are closed here, keep `Modbus::drop()` from closing one of them again
+        let _ = self.modbus.set_socket(-1);
+        for connection in self.connections.drain(..) {
+            unsafe { libc::close(connection.socket) };
+        }
+        unsafe { libc::close(self.listener) };
+    }
+}
@@ -0,0 +1,422 @@
+use crate::modbus_transport::{timed_out, Transport};
+use crate::prelude::*;
+use rustls::crypto::{ring, CryptoProvider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{
+    ClientConfig, ClientConnection, ConnectionCommon, RootCertStore, ServerConfig,
+    ServerConnection, StreamOwned,
+};
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// OID of the role extension of the Modbus/TCP Security protocol, 1.3.6.1.4.1.50316.802.1, in DER
+const ROLE_OID: &[u8] = &[
+    0x2B, 0x06, 0x01, 0x04, 0x01, 0x83, 0x89, 0x0C, 0x86, 0x22, 0x01,
+];
+
+/// Certificates and key of a Modbus/TCP Security client or server
+///
+/// Modbus/TCP Security authenticates both ends: the server and the client present a certificate, each signed by a
+/// certificate authority the other end trusts. A `TlsConfig` holds the certificate chain and the private key of one
+/// end and the certificate authorities it trusts. TLS 1.2 and 1.3 are supported.
+///
+/// Only available with the `tls` feature.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use libmodbus::{ModbusStream, TlsConfig};
+///
+/// let config = TlsConfig::from_pem_files("client.pem", "client.key", "ca.pem").unwrap();
+/// let modbus = ModbusStream::new_tls("192.168.1.10", 802, &config).unwrap();
+/// modbus.connect().unwrap();
+/// ```
+#[derive(Clone)]
+pub struct TlsConfig {
+    certificates: Vec<CertificateDer<'static>>,
+    key: Arc<PrivateKeyDer<'static>>,
+    roots: Arc<RootCertStore>,
+}
+
+fn tls_error(msg: &str, source: io::Error) -> Error {
+    Error::Tls {
+        msg: msg.to_owned(),
+        source,
+    }
+}
+
+fn invalid_data(err: impl fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
+
+/// The crypto provider of all connections, independent of the provider installed by the application
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+impl TlsConfig {
+    /// `from_pem` - create a configuration from PEM encoded certificates and key
+    ///
+    /// # Return value
+    ///
+    /// The function returns a Result containing the configuration if successful. Otherwise it contains an Error if
+    /// a certificate or the key can't be parsed, or no certificate authority is given.
+    ///
+    /// # Parameters
+    ///
+    /// * `certificates`    - the certificate of this end, followed by the intermediate certificates, if any
+    /// * `key`             - the private key of the certificate, PKCS#8, PKCS#1 or SEC1
+    /// * `ca`              - the certificate authorities the other end's certificate must be signed by
+    pub fn from_pem(certificates: &[u8], key: &[u8], ca: &[u8]) -> Result<TlsConfig, Error> {
+        let certificates = CertificateDer::pem_slice_iter(certificates)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| tls_error("certificates", invalid_data(err)))?;
+        if certificates.is_empty() {
+            return Err(tls_error("certificates", invalid_data("no certificate")));
+        }
+        let key = PrivateKeyDer::from_pem_slice(key)
+            .map_err(|err| tls_error("key", invalid_data(err)))?;
+
+        let mut roots = RootCertStore::empty();
+        for certificate in CertificateDer::pem_slice_iter(ca) {
+            let certificate = certificate.map_err(|err| tls_error("ca", invalid_data(err)))?;
+            roots
+                .add(certificate)
+                .map_err(|err| tls_error("ca", invalid_data(err)))?;
+        }
+        if roots.is_empty() {
+            return Err(tls_error("ca", invalid_data("no certificate authority")));
+        }
+
+        Ok(TlsConfig {
+            certificates,
+            key: Arc::new(key),
+            roots: Arc::new(roots),
+        })
+    }
+
+    /// `from_pem_files` - create a configuration from PEM files, see [`from_pem()`](#method.from_pem)
+    ///
+    /// # Parameters
+    ///
+    /// * `certificates`    - file with the certificate of this end, followed by the intermediate certificates
+    /// * `key`             - file with the private key of the certificate
+    /// * `ca`              - file with the certificate authorities the other end's certificate must be signed by
+    pub fn from_pem_files<P: AsRef<Path>>(
+        certificates: P,
+        key: P,
+        ca: P,
+    ) -> Result<TlsConfig, Error> {
+        let read =
+            |msg: &str, path: &Path| std::fs::read(path).map_err(|source| tls_error(msg, source));
+        TlsConfig::from_pem(
+            &read("certificates", certificates.as_ref())?,
+            &read("key", key.as_ref())?,
+            &read("ca", ca.as_ref())?,
+        )
+    }
+
+    /// Configuration of a client, authenticating with the certificate
+    pub(crate) fn client_config(&self) -> io::Result<ClientConfig> {
+        ClientConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .map_err(invalid_data)?
+            .with_root_certificates(self.roots.clone())
+            .with_client_auth_cert(self.certificates.clone(), self.key.clone_key())
+            .map_err(invalid_data)
+    }
+
+    /// Configuration of a server, requiring clients to authenticate
+    pub(crate) fn server_config(&self) -> io::Result<ServerConfig> {
+        let verifier = WebPkiClientVerifier::builder_with_provider(self.roots.clone(), provider())
+            .build()
+            .map_err(invalid_data)?;
+        ServerConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .map_err(invalid_data)?
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(self.certificates.clone(), self.key.clone_key())
+            .map_err(invalid_data)
+    }
+}
+
+impl fmt::Debug for TlsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // never print the key
+        f.debug_struct("TlsConfig")
+            .field("certificates", &self.certificates.len())
+            .field("roots", &self.roots.len())
+            .finish()
+    }
+}
+
+/// A DER element: tag, content and the bytes after it
+fn der(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = input.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (len, rest) = match first {
+        0..=0x7F => (first as usize, rest),
+        0x81..=0x84 => {
+            let count = (first & 0x7F) as usize;
+            let bytes = rest.get(..count)?;
+            (
+                bytes.iter().fold(0, |len, &byte| len << 8 | byte as usize),
+                &rest[count..],
+            )
+        }
+        _ => return None,
+    };
+    let content = rest.get(..len)?;
+    Some((tag, content, &rest[len..]))
+}
+
+/// The role of the Modbus/TCP Security protocol in a DER encoded X.509 certificate
+///
+/// The role is a UTF8String in the extension 1.3.6.1.4.1.50316.802.1.
+pub(crate) fn role(certificate: &[u8]) -> Option<String> {
+    let (_, certificate, _) = der(certificate)?;
+    let (_, mut tbs, _) = der(certificate)?;
+    // the extensions are the last field of the TBSCertificate, explicitly tagged [3]
+    let mut extensions = loop {
+        let (tag, content, rest) = der(tbs)?;
+        if tag == 0xA3 {
+            break der(content)?.1;
+        }
+        tbs = rest;
+    };
+    while let Some((_, extension, rest)) = der(extensions) {
+        extensions = rest;
+        let (_, oid, mut fields) = der(extension)?;
+        if oid != ROLE_OID {
+            continue;
+        }
+        // skip the critical flag, if any
+        loop {
+            let (tag, value, rest) = der(fields)?;
+            if tag == 0x04 {
+                let (tag, role, _) = der(value)?;
+                return match tag {
+                    0x0C => String::from_utf8(role.to_vec()).ok(),
+                    _ => None,
+                };
+            }
+            fields = rest;
+        }
+    }
+    None
+}
+
+/// The role in the certificate of the other end of a connection
+fn peer_role(certificates: Option<&[CertificateDer]>) -> Option<String> {
+    certificates?
+        .first()
+        .and_then(|certificate| role(certificate))
+}
+
+/// Either end of a TLS connection
+enum TlsStream {
+    Client(StreamOwned<ClientConnection, TcpStream>),
+    Server(StreamOwned<ServerConnection, TcpStream>),
+}
+
+/// A socket whose reads and writes fail once `deadline` passed
+struct DeadlineSocket<'a> {
+    socket: &'a mut TcpStream,
+    deadline: Instant,
+}
+
+impl DeadlineSocket<'_> {
+    /// The time left until the deadline, an error if there is none
+    fn remaining(&self) -> io::Result<Duration> {
+        match self.deadline.checked_duration_since(Instant::now()) {
+            Some(remaining) if !remaining.is_zero() => Ok(remaining),
+            _ => Err(timed_out()),
+        }
+    }
+}
+
+impl Read for DeadlineSocket<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.remaining()?;
+        self.socket.set_read_timeout(Some(remaining))?;
+        self.socket.read(buf)
+    }
+}
+
+impl Write for DeadlineSocket<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let remaining = self.remaining()?;
+        self.socket.set_write_timeout(Some(remaining))?;
+        self.socket.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.socket.flush()
+    }
+}
+
+/// Run the handshake of `conn` on `socket`, failing if it isn't complete within `timeout`
+///
+/// The deadline covers the whole handshake, so a peer sending a byte now and then can't stall it.
+fn complete_handshake<D>(
+    conn: &mut ConnectionCommon<D>,
+    socket: &mut TcpStream,
+    timeout: Duration,
+) -> io::Result<()> {
+    let mut deadline_socket = DeadlineSocket {
+        socket: &mut *socket,
+        deadline: Instant::now() + timeout,
+    };
+    while conn.is_handshaking() {
+        if let Err(err) = conn.complete_io(&mut deadline_socket) {
+            return Err(match err.kind() {
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => timed_out(),
+                _ => err,
+            });
+        }
+    }
+    socket.set_read_timeout(None)?;
+    socket.set_write_timeout(None)
+}
+
+/// A TLS connection over TCP
+pub(crate) struct TlsTransport {
+    stream: TlsStream,
+    peer_role: Option<String>,
+}
+
+impl TlsTransport {
+    /// Connect to `port` at `ip` and authenticate both ends, failing if the handshake takes longer than `timeout`
+    pub(crate) fn connect(
+        ip: &str,
+        port: u16,
+        config: Arc<ClientConfig>,
+        timeout: Duration,
+    ) -> io::Result<TlsTransport> {
+        let name = ServerName::try_from(ip.to_owned())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        let connection = ClientConnection::new(config, name).map_err(invalid_data)?;
+        TlsTransport::handshake(
+            TlsStream::Client(StreamOwned::new(
+                connection,
+                TcpStream::connect((ip, port))?,
+            )),
+            timeout,
+        )
+    }
+
+    /// Authenticate both ends of an accepted connection, failing if the handshake takes longer than `timeout`
+    pub(crate) fn accept(
+        socket: TcpStream,
+        config: Arc<ServerConfig>,
+        timeout: Duration,
+    ) -> io::Result<TlsTransport> {
+        let connection = ServerConnection::new(config).map_err(invalid_data)?;
+        TlsTransport::handshake(
+            TlsStream::Server(StreamOwned::new(connection, socket)),
+            timeout,
+        )
+    }
+
+    fn handshake(mut stream: TlsStream, timeout: Duration) -> io::Result<TlsTransport> {
+        let peer_role = match stream {
+            TlsStream::Client(ref mut stream) => {
+                stream.sock.set_nodelay(true)?;
+                complete_handshake(&mut stream.conn, &mut stream.sock, timeout)?;
+                peer_role(stream.conn.peer_certificates())
+            }
+            TlsStream::Server(ref mut stream) => {
+                stream.sock.set_nodelay(true)?;
+                complete_handshake(&mut stream.conn, &mut stream.sock, timeout)?;
+                peer_role(stream.conn.peer_certificates())
+            }
+        };
+        Ok(TlsTransport { stream, peer_role })
+    }
+
+    fn socket(&self) -> &TcpStream {
+        match self.stream {
+            TlsStream::Client(ref stream) => &stream.sock,
+            TlsStream::Server(ref stream) => &stream.sock,
+        }
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.stream {
+            TlsStream::Client(ref mut stream) => stream.read(buf),
+            TlsStream::Server(ref mut stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Transport for TlsTransport {
+    fn send(&mut self, adu: &[u8]) -> io::Result<()> {
+        match self.stream {
+            TlsStream::Client(ref mut stream) => {
+                stream.write_all(adu).and_then(|()| stream.flush())
+            }
+            TlsStream::Server(ref mut stream) => {
+                stream.write_all(adu).and_then(|()| stream.flush())
+            }
+        }
+    }
+
+    fn receive(&mut self, buf: &mut [u8], timeout: Option<Duration>) -> io::Result<usize> {
+        // a zero read timeout is rejected by the standard library
+        self.socket()
+            .set_read_timeout(timeout.map(|timeout| timeout.max(Duration::from_micros(1))))?;
+        loop {
+            match self.read(buf) {
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err)
+                    if err.kind() == io::ErrorKind::WouldBlock
+                        || err.kind() == io::ErrorKind::TimedOut =>
+                {
+                    return Err(timed_out())
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.socket().set_nonblocking(true)?;
+        let mut discard = [0u8; 512];
+        let result = loop {
+            match self.read(&mut discard) {
+                Ok(0) => break Ok(()),
+                Ok(_) => {}
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break Ok(()),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => break Err(err),
+            }
+        };
+        self.socket().set_nonblocking(false)?;
+        result
+    }
+
+    fn close(&mut self) {
+        match self.stream {
+            TlsStream::Client(ref mut stream) => {
+                stream.conn.send_close_notify();
+                let _ = stream.flush();
+            }
+            TlsStream::Server(ref mut stream) => {
+                stream.conn.send_close_notify();
+                let _ = stream.flush();
+            }
+        }
+        let _ = self.socket().shutdown(Shutdown::Both);
+    }
+
+    fn peer_role(&self) -> Option<String> {
+        self.peer_role.clone()
+    }
+}
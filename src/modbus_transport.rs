@@ -0,0 +1,607 @@
+#[cfg(unix)]
+use crate::{BaudRate, DataBits, Parity, SerialConfig, StopBits};
+use libc::c_int;
+use std::ffi::CString;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Moves the bytes of the backends implemented in Rust, see [`ModbusStream`](struct.ModbusStream.html)
+///
+/// A transport doesn't know about frames: [`send()`](#tymethod.send) gets complete ADUs, but
+/// [`receive()`](#tymethod.receive) may return any part of what arrived, the framing of the context splits it.
+///
+/// Implement it to run Modbus over a channel of your own, e.g. a USB HID bridge or an SSH tunnel, and create the
+/// context with [`ModbusStream::from_transport()`](struct.ModbusStream.html#method.from_transport). Any
+/// `Read + Write` stream is a transport with [`IoTransport`](struct.IoTransport.html).
+///
+/// # Examples
+///
+/// ```rust
+/// use libmodbus::Transport;
+/// use std::io;
+/// use std::time::Duration;
+///
+/// /// Answers nothing, every receive times out
+/// struct Silent;
+///
+/// impl Transport for Silent {
+///     fn send(&mut self, _adu: &[u8]) -> io::Result<()> {
+///         Ok(())
+///     }
+///
+///     fn receive(&mut self, _buf: &mut [u8], _timeout: Option<Duration>) -> io::Result<usize> {
+///         Err(io::Error::from_raw_os_error(libc::ETIMEDOUT))
+///     }
+///
+///     fn flush(&mut self) -> io::Result<()> {
+///         Ok(())
+///     }
+///
+///     fn close(&mut self) {}
+/// }
+/// ```
+pub trait Transport: Send {
+    /// Send a complete ADU
+    fn send(&mut self, adu: &[u8]) -> io::Result<()>;
+
+    /// Receive at least one byte into `buf`, waiting at most `timeout` for it, forever if `None`
+    ///
+    /// Returns the number of bytes received. Nothing arriving in time is an error with the raw OS error
+    /// `ETIMEDOUT`, like the timeouts of libmodbus. 0 is returned if the peer closed the connection.
+    fn receive(&mut self, buf: &mut [u8], timeout: Option<Duration>) -> io::Result<usize>;
+
+    /// Discard received bytes not read yet
+    fn flush(&mut self) -> io::Result<()>;
+
+    /// Close the transport, further sends and receives fail
+    fn close(&mut self);
+
+    /// Each [`receive()`](#tymethod.receive) returns one datagram, a whole frame or garbage, false by default
+    fn datagrams(&self) -> bool {
+        false
+    }
+
+    /// The Modbus/TCP Security role of the other end, see
+    /// [`ModbusStream::peer_role()`](struct.ModbusStream.html#method.peer_role), `None` by default
+    fn peer_role(&self) -> Option<String> {
+        None
+    }
+}
+
+/// The error of a timeout, reported like libmodbus does
+pub(crate) fn timed_out() -> io::Error {
+    io::Error::from_raw_os_error(libc::ETIMEDOUT)
+}
+
+/// Wait until `fd` is readable, at most `timeout`
+#[cfg(unix)]
+pub(crate) fn poll_readable(fd: c_int, timeout: Option<Duration>) -> io::Result<()> {
+    let timeout = timeout.map_or(-1, |timeout| {
+        // round up, so a timeout below a millisecond doesn't turn into a busy loop
+        timeout.as_micros().div_ceil(1000).min(c_int::MAX as u128) as c_int
+    });
+    let mut pollfd = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    loop {
+        match unsafe { libc::poll(&mut pollfd, 1, timeout) } {
+            -1 => {
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+            0 => return Err(timed_out()),
+            _ => return Ok(()),
+        }
+    }
+}
+
+/// A serial port, or a pseudo-terminal, configured for raw binary transfers
+#[cfg(unix)]
+#[derive(Debug)]
+pub(crate) struct SerialPort {
+    fd: c_int,
+    /// The master end of a pseudo-terminal, which reports a hangup while no client has the slave end open
+    pty_master: bool,
+}
+
+/// termios speeds of the baud rates of a [`SerialConfig`](struct.SerialConfig.html)
+#[cfg(unix)]
+const SPEEDS: &[(u32, libc::speed_t)] = &[
+    (110, libc::B110),
+    (300, libc::B300),
+    (600, libc::B600),
+    (1200, libc::B1200),
+    (2400, libc::B2400),
+    (4800, libc::B4800),
+    (9600, libc::B9600),
+    (19200, libc::B19200),
+    (38400, libc::B38400),
+    (57600, libc::B57600),
+    (115200, libc::B115200),
+    (230400, libc::B230400),
+    #[cfg(target_os = "linux")]
+    (460800, libc::B460800),
+    #[cfg(target_os = "linux")]
+    (500000, libc::B500000),
+    #[cfg(target_os = "linux")]
+    (576000, libc::B576000),
+    #[cfg(target_os = "linux")]
+    (921600, libc::B921600),
+    #[cfg(target_os = "linux")]
+    (1000000, libc::B1000000),
+    #[cfg(target_os = "linux")]
+    (1152000, libc::B1152000),
+    #[cfg(target_os = "linux")]
+    (1500000, libc::B1500000),
+    #[cfg(target_os = "linux")]
+    (2000000, libc::B2000000),
+    #[cfg(target_os = "linux")]
+    (2500000, libc::B2500000),
+    #[cfg(target_os = "linux")]
+    (3000000, libc::B3000000),
+    #[cfg(target_os = "linux")]
+    (3500000, libc::B3500000),
+    #[cfg(target_os = "linux")]
+    (4000000, libc::B4000000),
+];
+
+/// termios speed and control flags of a serial line configuration
+#[cfg(unix)]
+fn termios_settings(config: &SerialConfig) -> io::Result<(libc::speed_t, libc::tcflag_t)> {
+    let speed = SPEEDS
+        .iter()
+        .find(|&&(baud, _)| baud == config.baud_rate.value())
+        .map(|&(_, speed)| speed)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "unsupported baud rate"))?;
+    let size = match config.data_bits {
+        DataBits::Five => libc::CS5,
+        DataBits::Six => libc::CS6,
+        DataBits::Seven => libc::CS7,
+        DataBits::Eight => libc::CS8,
+    };
+    let parity = match config.parity {
+        Parity::None => 0,
+        Parity::Even => libc::PARENB,
+        Parity::Odd => libc::PARENB | libc::PARODD,
+    };
+    let stop = match config.stop_bits {
+        StopBits::One => 0,
+        StopBits::Two => libc::CSTOPB,
+    };
+    Ok((speed, size | parity | stop))
+}
+
+/// The configuration `fd`, a serial port, is set to right now
+#[cfg(unix)]
+pub(crate) fn termios_config(fd: c_int) -> io::Result<SerialConfig> {
+    let unsupported = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_owned());
+    let tios = unsafe {
+        let mut tios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(fd, &mut tios) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        tios
+    };
+    let speed = unsafe { libc::cfgetospeed(&tios) };
+    let baud = SPEEDS
+        .iter()
+        .find(|&&(_, known)| known == speed)
+        .map(|&(baud, _)| baud)
+        .ok_or_else(|| unsupported("unsupported baud rate"))?;
+    let data_bits = match tios.c_cflag & libc::CSIZE {
+        libc::CS5 => DataBits::Five,
+        libc::CS6 => DataBits::Six,
+        libc::CS7 => DataBits::Seven,
+        _ => DataBits::Eight,
+    };
+    let parity = if tios.c_cflag & libc::PARENB == 0 {
+        Parity::None
+    } else if tios.c_cflag & libc::PARODD == 0 {
+        Parity::Even
+    } else {
+        Parity::Odd
+    };
+    let stop_bits = if tios.c_cflag & libc::CSTOPB == 0 {
+        StopBits::One
+    } else {
+        StopBits::Two
+    };
+    let baud_rate = BaudRate::new(baud).map_err(|_| unsupported("unsupported baud rate"))?;
+    Ok(SerialConfig::new(baud_rate, parity, data_bits, stop_bits))
+}
+
+#[cfg(unix)]
+impl SerialPort {
+    /// Open `device` with a serial line configuration
+    pub(crate) fn open(device: &str, config: &SerialConfig) -> io::Result<SerialPort> {
+        let (speed, flags) = termios_settings(config)?;
+        let path = CString::new(device)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "device with a nul byte"))?;
+        let fd = unsafe {
+            libc::open(
+                path.as_ptr(),
+                libc::O_RDWR | libc::O_NOCTTY | libc::O_NONBLOCK | libc::O_CLOEXEC,
+            )
+        };
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        // the port closes the descriptor if the configuration fails
+        let port = SerialPort {
+            fd,
+            pty_master: false,
+        };
+        unsafe {
+            let mut tios: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(fd, &mut tios) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            libc::cfmakeraw(&mut tios);
+            tios.c_cflag &= !(libc::CSIZE | libc::PARENB | libc::PARODD | libc::CSTOPB);
+            tios.c_cflag |= libc::CREAD | libc::CLOCAL | flags;
+            tios.c_cc[libc::VMIN] = 0;
+            tios.c_cc[libc::VTIME] = 0;
+            if libc::cfsetispeed(&mut tios, speed) == -1
+                || libc::cfsetospeed(&mut tios, speed) == -1
+                || libc::tcsetattr(fd, libc::TCSANOW, &tios) == -1
+            {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(port)
+    }
+
+    /// Take ownership of the master end of a pseudo-terminal
+    pub(crate) fn from_pty_master(fd: c_int) -> SerialPort {
+        SerialPort {
+            fd,
+            pty_master: true,
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Transport for SerialPort {
+    fn send(&mut self, adu: &[u8]) -> io::Result<()> {
+        let mut sent = 0;
+        while sent < adu.len() {
+            let rest = &adu[sent..];
+            match unsafe { libc::write(self.fd, rest.as_ptr() as *const _, rest.len()) } {
+                -1 => {
+                    let err = io::Error::last_os_error();
+                    match err.kind() {
+                        io::ErrorKind::Interrupted => {}
+                        io::ErrorKind::WouldBlock => {
+                            let mut pollfd = libc::pollfd {
+                                fd: self.fd,
+                                events: libc::POLLOUT,
+                                revents: 0,
+                            };
+                            unsafe { libc::poll(&mut pollfd, 1, -1) };
+                        }
+                        _ => return Err(err),
+                    }
+                }
+                len => sent += len as usize,
+            }
+        }
+        Ok(())
+    }
+
+    fn receive(&mut self, buf: &mut [u8], timeout: Option<Duration>) -> io::Result<usize> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            let remaining = match deadline {
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(remaining) => Some(remaining),
+                    None => return Err(timed_out()),
+                },
+                None => None,
+            };
+            poll_readable(self.fd, remaining)?;
+            match unsafe { libc::read(self.fd, buf.as_mut_ptr() as *mut _, buf.len()) } {
+                -1 => {
+                    let err = io::Error::last_os_error();
+                    if self.pty_master && err.raw_os_error() == Some(libc::EIO) {
+                        // no client has the slave end open, wait for one
+                        let pause = Duration::from_millis(10);
+                        thread::sleep(remaining.map_or(pause, |remaining| remaining.min(pause)));
+                    } else if err.kind() != io::ErrorKind::Interrupted
+                        && err.kind() != io::ErrorKind::WouldBlock
+                    {
+                        return Err(err);
+                    }
+                }
+                len => return Ok(len as usize),
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match unsafe { libc::tcflush(self.fd, libc::TCIFLUSH) } {
+            -1 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+
+    fn close(&mut self) {
+        if self.fd != -1 {
+            unsafe { libc::close(self.fd) };
+            self.fd = -1;
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for SerialPort {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// Any `Read + Write` stream as a [`Transport`](trait.Transport.html)
+///
+/// A blocking read can't be interrupted, so the timeouts of the context are only kept if the stream's reads return
+/// on their own: a stream in non-blocking mode, failing with `WouldBlock` while nothing arrived, or one with a read
+/// timeout of its own, failing with `TimedOut`, is read again until the timeout of the context elapses. A stream
+/// blocking until data arrives waits as long as that takes.
+///
+/// [`flush()`](trait.Transport.html#tymethod.flush) only discards what the context received but didn't frame
+/// yet, [`close()`](trait.Transport.html#tymethod.close) drops the stream.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use libmodbus::{Framing, IoTransport, ModbusClient, ModbusStream};
+/// use std::os::unix::net::UnixStream;
+///
+/// let stream = UnixStream::connect("/run/modbus-bridge.sock").unwrap();
+/// let mut modbus = ModbusStream::from_transport(IoTransport::new(stream), Framing::Rtu);
+/// modbus.set_slave(1).unwrap();
+///
+/// let mut registers = [0u16; 4];
+/// modbus.read_registers(0, 4, &mut registers).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct IoTransport<S> {
+    stream: Option<S>,
+}
+
+impl<S: Read + Write + Send> IoTransport<S> {
+    /// `new` - a transport on `stream`
+    pub fn new(stream: S) -> IoTransport<S> {
+        IoTransport {
+            stream: Some(stream),
+        }
+    }
+
+    /// The stream, `EBADF` once closed
+    fn stream(&mut self) -> io::Result<&mut S> {
+        self.stream
+            .as_mut()
+            .ok_or_else(|| io::Error::from_raw_os_error(libc::EBADF))
+    }
+}
+
+impl<S: Read + Write + Send> Transport for IoTransport<S> {
+    fn send(&mut self, adu: &[u8]) -> io::Result<()> {
+        let stream = self.stream()?;
+        stream.write_all(adu)?;
+        stream.flush()
+    }
+
+    fn receive(&mut self, buf: &mut [u8], timeout: Option<Duration>) -> io::Result<usize> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let stream = self.stream()?;
+        loop {
+            match stream.read(buf) {
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err)
+                    if err.kind() == io::ErrorKind::WouldBlock
+                        || err.kind() == io::ErrorKind::TimedOut =>
+                {
+                    if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                        return Err(timed_out());
+                    }
+                    if err.kind() == io::ErrorKind::WouldBlock {
+                        // nothing to wait on, poll the stream again shortly
+                        thread::sleep(Duration::from_millis(1));
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream().map(|_| ())
+    }
+
+    fn close(&mut self) {
+        self.stream = None;
+    }
+}
+
+/// A TCP connection
+#[derive(Debug)]
+pub(crate) struct TcpTransport {
+    stream: TcpStream,
+}
+
+impl TcpTransport {
+    /// Connect to `port` at `ip`, a host name or an IPv4 or IPv6 address
+    pub(crate) fn connect(ip: &str, port: u16) -> io::Result<TcpTransport> {
+        TcpTransport::from_stream(TcpStream::connect((ip, port))?)
+    }
+
+    /// Take a connected stream, e.g. accepted by a listener
+    pub(crate) fn from_stream(stream: TcpStream) -> io::Result<TcpTransport> {
+        // like libmodbus, send the small Modbus frames right away
+        stream.set_nodelay(true)?;
+        Ok(TcpTransport { stream })
+    }
+}
+
+impl Transport for TcpTransport {
+    fn send(&mut self, adu: &[u8]) -> io::Result<()> {
+        self.stream.write_all(adu)
+    }
+
+    fn receive(&mut self, buf: &mut [u8], timeout: Option<Duration>) -> io::Result<usize> {
+        // a zero read timeout is rejected by the standard library
+        self.stream
+            .set_read_timeout(timeout.map(|timeout| timeout.max(Duration::from_micros(1))))?;
+        loop {
+            match self.stream.read(buf) {
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err)
+                    if err.kind() == io::ErrorKind::WouldBlock
+                        || err.kind() == io::ErrorKind::TimedOut =>
+                {
+                    return Err(timed_out())
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.set_nonblocking(true)?;
+        let mut discard = [0u8; 512];
+        let result = loop {
+            match self.stream.read(&mut discard) {
+                Ok(0) => break Ok(()),
+                Ok(_) => {}
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break Ok(()),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => break Err(err),
+            }
+        };
+        self.stream.set_nonblocking(false)?;
+        result
+    }
+
+    fn close(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// A UDP socket, connected to a server or bound to receive the requests of any client
+#[derive(Debug)]
+pub(crate) struct UdpTransport {
+    socket: UdpSocket,
+    /// The address replies are sent to, the sender of the last datagram received on a bound socket
+    peer: Option<SocketAddr>,
+    connected: bool,
+}
+
+/// The first address `ip` and `port` resolve to
+fn resolve(ip: &str, port: u16) -> io::Result<SocketAddr> {
+    (ip, port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address to resolve to"))
+}
+
+impl UdpTransport {
+    /// A socket sending to and receiving from `port` at `ip` only
+    pub(crate) fn connect(ip: &str, port: u16) -> io::Result<UdpTransport> {
+        let peer = resolve(ip, port)?;
+        let local: SocketAddr = if peer.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(peer)?;
+        Ok(UdpTransport {
+            socket,
+            peer: Some(peer),
+            connected: true,
+        })
+    }
+
+    /// A socket bound to `port` at `ip`, receiving from any address
+    pub(crate) fn bind(ip: &str, port: u16) -> io::Result<UdpTransport> {
+        Ok(UdpTransport {
+            socket: UdpSocket::bind(resolve(ip, port)?)?,
+            peer: None,
+            connected: false,
+        })
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send(&mut self, adu: &[u8]) -> io::Result<()> {
+        let sent = match self.peer {
+            Some(_) if self.connected => self.socket.send(adu)?,
+            Some(peer) => self.socket.send_to(adu, peer)?,
+            None => return Err(io::Error::from(io::ErrorKind::NotConnected)),
+        };
+        if sent != adu.len() {
+            return Err(io::Error::from(io::ErrorKind::WriteZero));
+        }
+        Ok(())
+    }
+
+    fn receive(&mut self, buf: &mut [u8], timeout: Option<Duration>) -> io::Result<usize> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            let remaining = match deadline {
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(remaining) if !remaining.is_zero() => Some(remaining),
+                    _ => return Err(timed_out()),
+                },
+                None => None,
+            };
+            self.socket.set_read_timeout(remaining)?;
+            match self.socket.recv_from(buf) {
+                // an empty datagram isn't a closed connection, there is none
+                Ok((0, _)) => {}
+                Ok((len, peer)) => {
+                    if !self.connected {
+                        self.peer = Some(peer);
+                    }
+                    return Ok(len);
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err)
+                    if err.kind() == io::ErrorKind::WouldBlock
+                        || err.kind() == io::ErrorKind::TimedOut =>
+                {
+                    return Err(timed_out())
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.socket.set_nonblocking(true)?;
+        let mut discard = [0u8; 512];
+        let result = loop {
+            match self.socket.recv_from(&mut discard) {
+                Ok(_) => {}
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break Ok(()),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => break Err(err),
+            }
+        };
+        self.socket.set_nonblocking(false)?;
+        result
+    }
+
+    fn close(&mut self) {
+        self.peer = None;
+    }
+
+    fn datagrams(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,260 @@
+use crate::{Exception, RequestHandler, UnitSelection};
+use std::collections::BTreeMap;
+
+/// What a [`UnitTable`](struct.UnitTable.html) does with requests sent to the broadcast address (0) on a serial line
+///
+/// Unit id 0 isn't a broadcast on TCP, requests to it are routed like those to any other unit id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Broadcast {
+    /// Pass the request to every unit of the table, the default
+    AllUnits,
+    /// Treat the broadcast address as this unit id
+    Unit(u8),
+    /// Drop requests to the broadcast address
+    Ignore,
+}
+
+/// The unit the calls following `select_unit()` are routed to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    None,
+    Unit(u8),
+    Default,
+    AllUnits,
+}
+
+/// Unit id → request handler table, to emulate several devices behind one server
+///
+/// A server answers requests for every unit id from the same [`ModbusMapping`](struct.ModbusMapping.html) or
+/// [`RequestHandler`](trait.RequestHandler.html). A `UnitTable` is itself a request handler that passes each request
+/// to the handler registered for the unit id it was sent to.
+///
+/// Requests for a unit id without a handler are passed to the default handler, if one is set with
+/// [`set_default()`](#method.set_default). Otherwise they are dropped silently on a serial line and answered with
+/// `Exception::GatewayTarget` on TCP, or as set with [`set_unknown_unit()`](#method.set_unknown_unit). Requests
+/// to the broadcast address on a serial line are handled as set with [`set_broadcast()`](#method.set_broadcast),
+/// on TCP unit id 0 is no broadcast and routed like any other unit id.
+///
+/// The handlers all have the same type `H`, e.g. `ModbusMapping`. Use `Box<dyn RequestHandler>` to mix different
+/// handlers in one table.
+///
+/// **Note:** libmodbus only receives RTU requests sent to the slave id of the context, see
+/// [`set_slave()`](struct.Modbus.html#method.set_slave), and to the broadcast address. Several unit ids are
+/// therefore only useful on TCP.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use libmodbus::{Modbus, ModbusMapping, ModbusServer, ModbusTCP, UnitTable};
+///
+/// let mut units = UnitTable::new();
+/// for unit in 1..=4 {
+///     units.insert(unit, ModbusMapping::new(0, 0, 100, 0).unwrap());
+/// }
+///
+/// let mut modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+/// let mut socket = modbus.tcp_listen(1).unwrap();
+/// modbus.tcp_accept(&mut socket).unwrap();
+///
+/// let mut query = vec![0; Modbus::MAX_ADU_LENGTH as usize];
+/// while let Ok(len) = modbus.receive(&mut query) {
+///     modbus.reply_with(&query, len, &mut units).unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct UnitTable<H: RequestHandler = Box<dyn RequestHandler>> {
+    units: BTreeMap<u8, H>,
+    default: Option<H>,
+    unknown: UnitSelection,
+    broadcast: Broadcast,
+    target: Target,
+}
+
+impl<H: RequestHandler> Default for UnitTable<H> {
+    fn default() -> UnitTable<H> {
+        UnitTable {
+            units: BTreeMap::new(),
+            default: None,
+            unknown: UnitSelection::Unknown,
+            broadcast: Broadcast::AllUnits,
+            target: Target::None,
+        }
+    }
+}
+
+impl<H: RequestHandler> UnitTable<H> {
+    /// `new` - create an empty table
+    pub fn new() -> UnitTable<H> {
+        UnitTable::default()
+    }
+
+    /// `insert` - register the handler of a unit id, returns the handler previously registered for it
+    pub fn insert(&mut self, unit: u8, handler: H) -> Option<H> {
+        self.units.insert(unit, handler)
+    }
+
+    /// `remove` - remove the handler of a unit id
+    pub fn remove(&mut self, unit: u8) -> Option<H> {
+        self.units.remove(&unit)
+    }
+
+    /// `get` - the handler of a unit id
+    pub fn get(&self, unit: u8) -> Option<&H> {
+        self.units.get(&unit)
+    }
+
+    /// `get_mut` - the handler of a unit id, e.g. to update the values of its mapping
+    pub fn get_mut(&mut self, unit: u8) -> Option<&mut H> {
+        self.units.get_mut(&unit)
+    }
+
+    /// `units` - the unit ids with a handler, in ascending order
+    pub fn units(&self) -> impl Iterator<Item = u8> + '_ {
+        self.units.keys().cloned()
+    }
+
+    /// `set_default` - set the handler answering unit ids without a handler of their own
+    pub fn set_default(&mut self, handler: Option<H>) {
+        self.default = handler;
+    }
+
+    /// `get_default_mut` - the handler answering unit ids without a handler of their own
+    pub fn get_default_mut(&mut self) -> Option<&mut H> {
+        self.default.as_mut()
+    }
+
+    /// `set_unknown_unit` - set how requests to unit ids without handler are answered if there is no default
+    ///
+    /// `UnitSelection::Unknown` by default: dropped on a serial line and answered with `Exception::GatewayTarget`
+    /// on TCP.
+    pub fn set_unknown_unit(&mut self, selection: UnitSelection) {
+        self.unknown = selection;
+    }
+
+    /// `set_broadcast` - set how requests to the broadcast address on a serial line are handled,
+    /// `Broadcast::AllUnits` by default
+    pub fn set_broadcast(&mut self, broadcast: Broadcast) {
+        self.broadcast = broadcast;
+    }
+
+    /// Call `f` with the handler(s) selected by the last `select_unit()`
+    fn route<T>(
+        &mut self,
+        mut f: impl FnMut(&mut H) -> Result<T, Exception>,
+    ) -> Result<T, Exception> {
+        let handler = match self.target {
+            Target::Unit(unit) => self.units.get_mut(&unit),
+            Target::Default => self.default.as_mut(),
+            Target::AllUnits => {
+                let mut result = None;
+                for handler in self.units.values_mut() {
+                    match (f(handler), &result) {
+                        (_, Some(Err(_))) => {}
+                        (Err(exception), _) => result = Some(Err(exception)),
+                        (Ok(value), None) => result = Some(Ok(value)),
+                        (Ok(_), Some(Ok(_))) => {}
+                    }
+                }
+                return result.unwrap_or(Err(Exception::GatewayTarget));
+            }
+            Target::None => None,
+        };
+        match handler {
+            Some(handler) => f(handler),
+            None => Err(Exception::GatewayTarget),
+        }
+    }
+}
+
+impl<H: RequestHandler> RequestHandler for UnitTable<H> {
+    fn select_unit(&mut self, unit: u8) -> UnitSelection {
+        let (target, selection) = match self.units.get_mut(&unit) {
+            Some(handler) => (Target::Unit(unit), handler.select_unit(unit)),
+            None => match self.default.as_mut() {
+                Some(handler) => (Target::Default, handler.select_unit(unit)),
+                None => (Target::None, self.unknown),
+            },
+        };
+        self.target = target;
+        selection
+    }
+
+    fn select_broadcast(&mut self) -> UnitSelection {
+        match self.broadcast {
+            Broadcast::AllUnits => {
+                self.target = Target::AllUnits;
+                UnitSelection::Handle
+            }
+            Broadcast::Ignore => {
+                self.target = Target::None;
+                UnitSelection::Ignore
+            }
+            Broadcast::Unit(unit) => self.select_unit(unit),
+        }
+    }
+
+    fn read_coils(&mut self, address: u16, quantity: u16) -> Result<Vec<bool>, Exception> {
+        self.route(|handler| handler.read_coils(address, quantity))
+    }
+
+    fn read_discrete_inputs(
+        &mut self,
+        address: u16,
+        quantity: u16,
+    ) -> Result<Vec<bool>, Exception> {
+        self.route(|handler| handler.read_discrete_inputs(address, quantity))
+    }
+
+    fn read_holding_registers(
+        &mut self,
+        address: u16,
+        quantity: u16,
+    ) -> Result<Vec<u16>, Exception> {
+        self.route(|handler| handler.read_holding_registers(address, quantity))
+    }
+
+    fn read_input_registers(&mut self, address: u16, quantity: u16) -> Result<Vec<u16>, Exception> {
+        self.route(|handler| handler.read_input_registers(address, quantity))
+    }
+
+    fn write_single_coil(&mut self, address: u16, value: bool) -> Result<(), Exception> {
+        self.route(|handler| handler.write_single_coil(address, value))
+    }
+
+    fn write_single_register(&mut self, address: u16, value: u16) -> Result<(), Exception> {
+        self.route(|handler| handler.write_single_register(address, value))
+    }
+
+    fn write_multiple_coils(&mut self, address: u16, values: &[bool]) -> Result<(), Exception> {
+        self.route(|handler| handler.write_multiple_coils(address, values))
+    }
+
+    fn write_multiple_registers(&mut self, address: u16, values: &[u16]) -> Result<(), Exception> {
+        self.route(|handler| handler.write_multiple_registers(address, values))
+    }
+
+    fn mask_write_register(
+        &mut self,
+        address: u16,
+        and_mask: u16,
+        or_mask: u16,
+    ) -> Result<(), Exception> {
+        self.route(|handler| handler.mask_write_register(address, and_mask, or_mask))
+    }
+
+    fn write_and_read_registers(
+        &mut self,
+        write_address: u16,
+        values: &[u16],
+        read_address: u16,
+        read_quantity: u16,
+    ) -> Result<Vec<u16>, Exception> {
+        self.route(|handler| {
+            handler.write_and_read_registers(write_address, values, read_address, read_quantity)
+        })
+    }
+
+    fn custom(&mut self, function: u8, data: &[u8]) -> Result<Vec<u8>, Exception> {
+        self.route(|handler| handler.custom(function, data))
+    }
+}
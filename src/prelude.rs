@@ -3,4 +3,4 @@ pub use crate::{
     set_bits_from_byte, set_bits_from_bytes, set_float_abcd, set_float_badc, set_float_cdab,
     set_float_dcba,
 };
-pub use crate::{Error, Modbus, ModbusMapping};
+pub use crate::{ByteOrder, Error, Modbus, ModbusMapping};
@@ -0,0 +1,87 @@
+// every test uses only some of the helpers
+#![allow(dead_code)]
+
+use libmodbus::{
+    Exception, Framing, Modbus, ModbusClient, ModbusMapping, ModbusServer, ModbusStream,
+    RequestHandler, Transport,
+};
+use std::thread;
+
+/// 16 holding registers with their address as value, address 0 is read only, and a user defined function 0x41
+/// reversing its data
+pub struct Registers(pub Vec<u16>);
+
+impl Registers {
+    pub fn new() -> Registers {
+        Registers((0..16).collect())
+    }
+}
+
+impl RequestHandler for Registers {
+    fn read_holding_registers(
+        &mut self,
+        address: u16,
+        quantity: u16,
+    ) -> Result<Vec<u16>, Exception> {
+        let (start, end) = (address as usize, address as usize + quantity as usize);
+        self.0
+            .get(start..end)
+            .map(|values| values.to_vec())
+            .ok_or(Exception::IllegalDataAddress)
+    }
+
+    fn write_multiple_registers(&mut self, address: u16, values: &[u16]) -> Result<(), Exception> {
+        let (start, end) = (address as usize, address as usize + values.len());
+        if start == 0 {
+            return Err(Exception::IllegalDataValue);
+        }
+        self.0
+            .get_mut(start..end)
+            .map(|registers| registers.copy_from_slice(values))
+            .ok_or(Exception::IllegalDataAddress)
+    }
+
+    fn custom(&mut self, function: u8, data: &[u8]) -> Result<Vec<u8>, Exception> {
+        match function {
+            0x41 => Ok(data.iter().rev().cloned().collect()),
+            _ => Err(Exception::IllegalFunction),
+        }
+    }
+}
+
+/// Read, write and read back the registers of a fresh `Registers` server, then get its exception for address 0
+///
+/// Sends 4 requests. Registers 3 and 4 are 30 and 40 afterwards.
+pub fn client_server<C: ModbusClient>(client: &C) {
+    let mut dest = vec![0u16; 4];
+    assert_eq!(client.read_registers(2, 4, &mut dest).unwrap(), 4);
+    assert_eq!(dest, vec![2, 3, 4, 5]);
+
+    assert_eq!(client.write_registers(3, 2, &[30, 40]).unwrap(), 2);
+    assert_eq!(client.read_registers(2, 4, &mut dest).unwrap(), 4);
+    assert_eq!(dest, vec![2, 30, 40, 5]);
+
+    let err = client.write_registers(0, 1, &[1]).unwrap_err();
+    assert_eq!(err.exception(), Some(Exception::IllegalDataValue));
+}
+
+/// Answer `requests` requests on `transport` with registers 0, 1, 2, ...
+pub fn start_server<T: Transport + Send + 'static>(
+    transport: T,
+    framing: Framing,
+    requests: usize,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut modbus = ModbusStream::from_transport(transport, framing);
+        modbus.set_slave(1).unwrap();
+        let mapping = ModbusMapping::new(0, 0, 16, 0).unwrap();
+        for (i, register) in mapping.get_registers_mut().iter_mut().enumerate() {
+            *register = i as u16;
+        }
+        let mut query = vec![0u8; Modbus::MAX_ADU_LENGTH];
+        for _ in 0..requests {
+            let rc = modbus.receive(&mut query).expect("Could not receive");
+            modbus.reply(&query, rc, &mapping).expect("Could not reply");
+        }
+    })
+}
@@ -112,6 +112,26 @@ fn set_response_timeout() {
     );
 }
 
+#[test]
+fn get_indication_timeout() {
+    let modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+    assert_eq!(
+        modbus.get_indication_timeout().unwrap(),
+        Timeout { sec: 0, usec: 0 }
+    );
+}
+
+#[test]
+fn set_indication_timeout() {
+    let mut modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+    let timeout = Timeout {
+        sec: 2,
+        usec: 250000,
+    };
+    assert!(modbus.set_indication_timeout(timeout).is_ok());
+    assert_eq!(modbus.get_indication_timeout().unwrap(), timeout);
+}
+
 #[test]
 fn set_error_recovery() {
     use libmodbus::ErrorRecoveryMode;
@@ -0,0 +1,94 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::Registers;
+use libmodbus::{Modbus, ModbusClient, ModbusServer, ModbusStream, PseudoTerminal, Timeout};
+use std::thread;
+
+#[test]
+fn new_ascii() {
+    assert!(ModbusStream::new_ascii("/dev/ttyUSB0", 9600, 'E', 7, 1).is_ok());
+    assert!(ModbusStream::new_ascii("/dev/ttyUSB0", 9600, 'x', 7, 1).is_err());
+    assert!(ModbusStream::new_ascii("/dev/ttyUSB0", 12345, 'N', 8, 1).is_err());
+    assert!(ModbusStream::new_ascii("/dev/ttyUSB0", 9600, 'N', 9, 1).is_err());
+    assert!(ModbusStream::new_ascii("/dev/ttyUSB0", 9600, 'N', 8, 3).is_err());
+
+    let modbus = ModbusStream::new_ascii("/dev/does-not-exist", 9600, 'N', 8, 1).unwrap();
+    assert!(modbus.connect().is_err());
+}
+
+#[test]
+fn set_slave() {
+    let mut modbus = ModbusStream::new_ascii("/dev/ttyUSB0", 9600, 'N', 8, 1).unwrap();
+    assert!(modbus.set_slave(247).is_ok());
+    assert_eq!(modbus.get_slave().unwrap(), 247);
+    assert!(modbus.set_slave(248).is_err());
+}
+
+#[test]
+fn set_timeouts() {
+    let mut modbus = ModbusStream::new_ascii("/dev/ttyUSB0", 9600, 'N', 8, 1).unwrap();
+    let timeout = Timeout::new(2, 999_999);
+    assert!(modbus.set_response_timeout(timeout).is_ok());
+    assert_eq!(modbus.get_response_timeout().unwrap(), timeout);
+    // usec out of range is rejected like libmodbus does
+    let timeout = Timeout::new(0, 5_000_000);
+    assert!(modbus.set_response_timeout(timeout).is_err());
+    assert!(modbus.set_byte_timeout(timeout).is_err());
+    assert!(modbus.set_indication_timeout(timeout).is_err());
+    assert_eq!(
+        modbus.get_response_timeout().unwrap(),
+        Timeout::new(2, 999_999)
+    );
+}
+
+#[test]
+fn client_server_on_pseudo_terminal() {
+    let pty = PseudoTerminal::open().expect("Could not open a pseudo-terminal");
+    let path = pty.path().to_owned();
+
+    let server_thread = thread::spawn(move || {
+        let mut modbus = pty
+            .into_ascii(1)
+            .expect("Could not create ASCII Server context");
+        // the server stops once the client is quiet
+        modbus
+            .set_indication_timeout(Timeout { sec: 1, usec: 0 })
+            .unwrap();
+        let mut handler = Registers::new();
+
+        loop {
+            let mut query = vec![0u8; Modbus::RTU_MAX_ADU_LENGTH];
+            match modbus.receive(&mut query) {
+                // a request for another slave
+                Ok(0) => continue,
+                Ok(rc) => modbus
+                    .reply_with(&query, rc, &mut handler)
+                    .expect("Could not reply"),
+                Err(_err) => break,
+            };
+        }
+        modbus.statistics().total_requests()
+    });
+
+    let mut client = ModbusStream::new_ascii(&path, 115200, 'N', 8, 1).unwrap();
+    client.set_slave(1).unwrap();
+    client.connect().expect("could not connect");
+
+    common::client_server(&client);
+
+    // nobody answers for slave 2
+    let mut dest = vec![0u16; 1];
+    client.set_slave(2).unwrap();
+    client
+        .set_response_timeout(Timeout {
+            sec: 0,
+            usec: 200_000,
+        })
+        .unwrap();
+    assert!(client.read_registers(2, 1, &mut dest).is_err());
+
+    client.close();
+    assert_eq!(server_thread.join().unwrap(), 4);
+}
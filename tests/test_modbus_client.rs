@@ -1,7 +1,80 @@
-use libmodbus::{FunctionCode, Modbus, ModbusClient, ModbusMapping, ModbusServer, ModbusTCP};
+use libmodbus::{
+    Error, Exception, FunctionCode, MaskWrite, Modbus, ModbusClient, ModbusMapping, ModbusServer,
+    ModbusTCP, RequestHandler,
+};
 use std::thread;
 use std::time::Duration;
 
+/// 8 holding registers of an older device without Mask Write Register (0x16), which may store something else than
+/// written
+struct Device {
+    registers: [u16; 8],
+    /// Store each value written plus one
+    altering: bool,
+}
+
+impl RequestHandler for Device {
+    fn read_holding_registers(
+        &mut self,
+        address: u16,
+        quantity: u16,
+    ) -> Result<Vec<u16>, Exception> {
+        let (start, end) = (address as usize, address as usize + quantity as usize);
+        self.registers
+            .get(start..end)
+            .map(|values| values.to_vec())
+            .ok_or(Exception::IllegalDataAddress)
+    }
+
+    fn write_multiple_registers(&mut self, address: u16, values: &[u16]) -> Result<(), Exception> {
+        let (start, end) = (address as usize, address as usize + values.len());
+        let registers = self
+            .registers
+            .get_mut(start..end)
+            .ok_or(Exception::IllegalDataAddress)?;
+        for (register, value) in registers.iter_mut().zip(values) {
+            *register = if self.altering { value + 1 } else { *value };
+        }
+        Ok(())
+    }
+
+    fn mask_write_register(
+        &mut self,
+        _address: u16,
+        _and_mask: u16,
+        _or_mask: u16,
+    ) -> Result<(), Exception> {
+        Err(Exception::IllegalFunction)
+    }
+}
+
+fn start_device(port: i32, altering: bool) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut modbus =
+            Modbus::new_tcp("127.0.0.1", port).expect("Could not create TCP Server context");
+        let mut socket = modbus
+            .tcp_listen(1)
+            .expect("Could not listen to TCP socket");
+        modbus
+            .tcp_accept(&mut socket)
+            .expect("Could not accept connection");
+        let mut device = Device {
+            registers: [0; 8],
+            altering,
+        };
+
+        loop {
+            let mut query = vec![0u8; Modbus::TCP_MAX_ADU_LENGTH];
+
+            match modbus.receive(&mut query) {
+                Ok(rc) => modbus.reply_with(&query, rc, &mut device),
+                Err(_err) => break,
+            }
+            .expect("Could not receive");
+        }
+    })
+}
+
 fn start_server(port: i32) -> thread::JoinHandle<()> {
     thread::spawn(move || {
         let mut modbus =
@@ -293,3 +366,145 @@ fn receive_confirmation() {
 
     let _ = server_thread.join();
 }
+
+#[test]
+fn mask_write_register_with_fallback() {
+    let port = 1514;
+    // Start modbus server
+    let server_thread = start_server(port);
+    thread::sleep(Duration::from_millis(200));
+
+    // connect client
+    match Modbus::new_tcp("127.0.0.1", port) {
+        Ok(client) => {
+            client.connect().expect("could not connect");
+            // libmodbus servers support function code 0x16, no emulation needed
+            assert_eq!(
+                client
+                    .mask_write_register_with_fallback(1, 0xF2, 0x25)
+                    .unwrap(),
+                MaskWrite::Native
+            );
+        }
+        _ => panic!("could not connect"),
+    }
+
+    let _ = server_thread.join();
+}
+
+#[test]
+fn write_register_verified() {
+    let port = 1515;
+    // Start modbus server
+    let server_thread = start_server(port);
+    thread::sleep(Duration::from_millis(200));
+
+    // connect client
+    match Modbus::new_tcp("127.0.0.1", port) {
+        Ok(client) => {
+            client.connect().expect("could not connect");
+            assert!(client.write_register_verified(1, 42).is_ok());
+            assert_eq!(
+                client.write_registers_verified(2, 3, &[1, 2, 3]).unwrap(),
+                3
+            );
+        }
+        _ => panic!("could not connect"),
+    }
+
+    let _ = server_thread.join();
+}
+
+#[test]
+fn write_bit_verified() {
+    let port = 1516;
+    // Start modbus server
+    let server_thread = start_server(port);
+    thread::sleep(Duration::from_millis(200));
+
+    // connect client
+    match Modbus::new_tcp("127.0.0.1", port) {
+        Ok(client) => {
+            client.connect().expect("could not connect");
+            assert!(client.write_bit_verified(1, true).is_ok());
+            assert_eq!(client.write_bits_verified(2, 3, &[1, 0, 1]).unwrap(), 3);
+        }
+        _ => panic!("could not connect"),
+    }
+
+    let _ = server_thread.join();
+}
+
+#[test]
+fn mask_write_register_emulated() {
+    let port = 1544;
+    let server_thread = start_device(port, false);
+    thread::sleep(Duration::from_millis(200));
+
+    let client = Modbus::new_tcp("127.0.0.1", port).unwrap();
+    client.connect().expect("could not connect");
+    client.write_register(1, 0x12).unwrap();
+    // the device answers 0x16 with an exception, read and write instead
+    match client
+        .mask_write_register_with_fallback(1, 0xF2, 0x25)
+        .unwrap()
+    {
+        MaskWrite::Emulated {
+            previous, written, ..
+        } => assert_eq!((previous, written), (0x12, 0x17)),
+        MaskWrite::Native => panic!("expected an emulated mask write"),
+    }
+    let mut dest = [0u16; 1];
+    client.read_registers(1, 1, &mut dest).unwrap();
+    assert_eq!(dest, [0x17]);
+    client.close();
+
+    let _ = server_thread.join();
+}
+
+#[test]
+fn write_registers_verify_mismatch() {
+    let port = 1545;
+    let server_thread = start_device(port, true);
+    thread::sleep(Duration::from_millis(200));
+
+    let client = Modbus::new_tcp("127.0.0.1", port).unwrap();
+    client.connect().expect("could not connect");
+    match client.write_register_verified(1, 42) {
+        Err(Error::Verify {
+            address,
+            written,
+            read_back,
+            ..
+        }) => assert_eq!((address, written, read_back), (1, 42, 43)),
+        result => panic!("expected a verify error, got {:?}", result),
+    }
+    // the first differing register is reported
+    match client.write_registers_verified(2, 3, &[1, 2, 3]) {
+        Err(Error::Verify {
+            address,
+            written,
+            read_back,
+            ..
+        }) => assert_eq!((address, written, read_back), (2, 1, 2)),
+        result => panic!("expected a verify error, got {:?}", result),
+    }
+    client.close();
+
+    let _ = server_thread.join();
+}
+
+#[test]
+fn write_verified_short_source() {
+    // not connected, nothing may be sent
+    let client = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+    let embmdata = Some(Modbus::ENOBASE as i32 + 16);
+    match client.write_registers_verified(0, 3, &[1, 2]) {
+        Err(Error::Client { source, .. }) => assert_eq!(source.raw_os_error(), embmdata),
+        result => panic!("expected a length error, got {:?}", result),
+    }
+    match client.write_bits_verified(0, 3, &[1]) {
+        Err(Error::Client { source, .. }) => assert_eq!(source.raw_os_error(), embmdata),
+        result => panic!("expected a length error, got {:?}", result),
+    }
+}
@@ -0,0 +1,145 @@
+mod common;
+
+use common::Registers;
+use libmodbus::{Exception, Modbus, ModbusClient, ModbusServer, ModbusTCP, RequestHandler};
+use std::thread;
+use std::time::Duration;
+
+fn start_server(port: i32) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut modbus =
+            Modbus::new_tcp("127.0.0.1", port).expect("Could not create TCP Server context");
+        let mut socket = modbus
+            .tcp_listen(1)
+            .expect("Could not listen to TCP socket");
+        modbus
+            .tcp_accept(&mut socket)
+            .expect("Could not accept connection");
+
+        let mut handler = Registers::new();
+
+        loop {
+            let mut query = vec![0u8; Modbus::TCP_MAX_ADU_LENGTH];
+
+            match modbus.receive(&mut query) {
+                Ok(rc) => modbus.reply_with(&query, rc, &mut handler),
+                Err(_err) => break,
+            }
+            .expect("Could not receive");
+        }
+    })
+}
+
+#[test]
+fn reply_with_handler() {
+    let port = 1517;
+    // Start modbus server
+    let server_thread = start_server(port);
+    thread::sleep(Duration::from_millis(200));
+
+    // connect client
+    match Modbus::new_tcp("127.0.0.1", port) {
+        Ok(client) => {
+            let mut dest = vec![0u16; 4];
+            client.connect().expect("could not connect");
+            common::client_server(&client);
+
+            assert!(client.write_register(5, 50).is_ok());
+            // mask write emulated by the default implementation of the handler
+            assert!(client.mask_write_register(6, 0x00F0, 0x0001).is_ok());
+            assert_eq!(client.read_registers(2, 4, &mut dest).unwrap(), 4);
+            assert_eq!(dest, vec![2, 30, 40, 50]);
+
+            let mut dest = vec![0u16; 1];
+            assert_eq!(client.read_registers(6, 1, &mut dest).unwrap(), 1);
+            assert_eq!(dest, vec![0x0001]);
+        }
+        _ => panic!("could not connect"),
+    }
+
+    let _ = server_thread.join();
+}
+
+#[test]
+fn reply_with_exception() {
+    let port = 1518;
+    // Start modbus server
+    let server_thread = start_server(port);
+    thread::sleep(Duration::from_millis(200));
+
+    // connect client
+    match Modbus::new_tcp("127.0.0.1", port) {
+        Ok(client) => {
+            let mut dest = vec![0u8; 1];
+            client.connect().expect("could not connect");
+            let err = client.write_register(16, 1).unwrap_err();
+            assert_eq!(err.exception(), Some(Exception::IllegalDataAddress));
+            // the handler does not implement coils
+            let err = client.read_bits(0, 1, &mut dest).unwrap_err();
+            assert_eq!(err.exception(), Some(Exception::IllegalFunction));
+        }
+        _ => panic!("could not connect"),
+    }
+
+    let _ = server_thread.join();
+}
+
+/// 8 holding registers keeping at most 100, like a device limiting a setpoint
+struct Clamped([u16; 8]);
+
+impl RequestHandler for Clamped {
+    fn read_holding_registers(
+        &mut self,
+        address: u16,
+        quantity: u16,
+    ) -> Result<Vec<u16>, Exception> {
+        let (start, end) = (address as usize, address as usize + quantity as usize);
+        self.0
+            .get(start..end)
+            .map(|values| values.to_vec())
+            .ok_or(Exception::IllegalDataAddress)
+    }
+
+    fn write_multiple_registers(&mut self, address: u16, values: &[u16]) -> Result<(), Exception> {
+        let (start, end) = (address as usize, address as usize + values.len());
+        let registers = self
+            .0
+            .get_mut(start..end)
+            .ok_or(Exception::IllegalDataAddress)?;
+        for (register, value) in registers.iter_mut().zip(values) {
+            *register = (*value).min(100);
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn reply_with_write_and_read_overlapping() {
+    let port = 1543;
+    let server_thread = thread::spawn(move || {
+        let mut modbus = Modbus::new_tcp("127.0.0.1", port).unwrap();
+        let mut socket = modbus.tcp_listen(1).unwrap();
+        modbus.tcp_accept(&mut socket).unwrap();
+        let mut handler = Clamped([0; 8]);
+        let mut query = vec![0u8; Modbus::TCP_MAX_ADU_LENGTH];
+        let rc = modbus.receive(&mut query).expect("Could not receive");
+        modbus
+            .reply_with(&query, rc, &mut handler)
+            .expect("Could not reply");
+    });
+    thread::sleep(Duration::from_millis(200));
+
+    let client = Modbus::new_tcp("127.0.0.1", port).unwrap();
+    client.connect().expect("could not connect");
+    // the registers read back are those of the handler, not the values written
+    let mut dest = [0u16; 4];
+    assert_eq!(
+        client
+            .write_and_read_registers(2, 2, &[50, 500], 1, 4, &mut dest)
+            .unwrap(),
+        4
+    );
+    assert_eq!(dest, [0, 50, 100, 0]);
+
+    server_thread.join().unwrap();
+}
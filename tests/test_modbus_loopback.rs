@@ -0,0 +1,112 @@
+mod common;
+
+use common::start_server;
+use libmodbus::{Framing, LoopbackFault, LoopbackTransport, ModbusClient, ModbusStream, Timeout};
+use std::time::{Duration, Instant};
+
+fn client(transport: LoopbackTransport, framing: Framing) -> ModbusStream {
+    let mut modbus = ModbusStream::from_transport(transport, framing);
+    modbus.set_slave(1).unwrap();
+    modbus
+        .set_response_timeout(Timeout::new(0, 100_000))
+        .unwrap();
+    modbus
+}
+
+#[test]
+fn client_server() {
+    for &framing in &[Framing::Rtu, Framing::Mbap, Framing::Ascii] {
+        let (client_end, server_end) = LoopbackTransport::pair();
+        let server_thread = start_server(server_end, framing, 2);
+        let modbus = client(client_end, framing);
+
+        let mut dest = [0u16; 3];
+        for _ in 0..2 {
+            assert_eq!(modbus.read_registers(4, 3, &mut dest).unwrap(), 3);
+            assert_eq!(dest, [4, 5, 6]);
+        }
+        server_thread.join().unwrap();
+        assert_eq!(modbus.statistics().total_requests(), 2);
+    }
+}
+
+#[test]
+fn latency() {
+    let (mut client_end, server_end) = LoopbackTransport::pair();
+    client_end.set_latency(Duration::from_millis(30));
+    assert_eq!(client_end.get_latency(), Duration::from_millis(30));
+    let server_thread = start_server(server_end, Framing::Rtu, 1);
+    let modbus = client(client_end, Framing::Rtu);
+
+    let started = Instant::now();
+    let mut dest = [0u16; 1];
+    modbus.read_registers(0, 1, &mut dest).unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(30));
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn dropped_frame() {
+    let (mut client_end, server_end) = LoopbackTransport::pair();
+    // the second request is lost
+    client_end.inject(1, LoopbackFault::Drop);
+    let server_thread = start_server(server_end, Framing::Mbap, 2);
+    let modbus = client(client_end, Framing::Mbap);
+
+    let mut dest = [0u16; 1];
+    assert!(modbus.read_registers(1, 1, &mut dest).is_ok());
+    assert!(modbus.read_registers(1, 1, &mut dest).is_err());
+    assert!(modbus.read_registers(1, 1, &mut dest).is_ok());
+    assert_eq!(modbus.statistics().timeouts, 1);
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn corrupted_byte() {
+    let (client_end, mut server_end) = LoopbackTransport::pair();
+    // a bit of the register value in the first response flips
+    server_end.inject(
+        0,
+        LoopbackFault::Corrupt {
+            offset: 4,
+            mask: 0x01,
+        },
+    );
+    let server_thread = start_server(server_end, Framing::Rtu, 2);
+    let modbus = client(client_end, Framing::Rtu);
+
+    let mut dest = [0u16; 1];
+    assert!(modbus.read_registers(3, 1, &mut dest).is_err());
+    assert_eq!(modbus.statistics().crc_errors, 1);
+    assert_eq!(modbus.read_registers(3, 1, &mut dest).unwrap(), 1);
+    assert_eq!(dest[0], 3);
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn delayed_frame() {
+    let (client_end, mut server_end) = LoopbackTransport::pair();
+    // the first response arrives after the response timeout
+    server_end.inject(0, LoopbackFault::Delay(Duration::from_millis(150)));
+    let server_thread = start_server(server_end, Framing::Mbap, 2);
+    let modbus = client(client_end, Framing::Mbap);
+
+    let mut dest = [0u16; 1];
+    assert!(modbus.read_registers(2, 1, &mut dest).is_err());
+    // the late response is skipped by its transaction id
+    assert_eq!(modbus.read_registers(5, 1, &mut dest).unwrap(), 1);
+    assert_eq!(dest[0], 5);
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn closed_end() {
+    let (client_end, server_end) = LoopbackTransport::pair();
+    let modbus = client(client_end, Framing::Rtu);
+    drop(server_end);
+
+    let mut dest = [0u16; 1];
+    assert!(modbus.read_registers(0, 1, &mut dest).is_err());
+    modbus.close();
+    assert!(modbus.read_registers(0, 1, &mut dest).is_err());
+}
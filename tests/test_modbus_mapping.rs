@@ -1,4 +1,9 @@
-use libmodbus::{Modbus, ModbusMapping, ModbusTCP};
+use libmodbus::{
+    Access, ByteOrder, Exception, Modbus, ModbusMapping, ModbusTCP, RequestHandler, Table,
+    Validator,
+};
+use std::cell::RefCell;
+use std::rc::Rc;
 
 #[test]
 fn new() {
@@ -72,3 +77,188 @@ fn get_registers_mut() {
 
     assert_eq!(modbus_mapping.get_input_registers_mut(), [0u16, 0, 0, 0, 0])
 }
+
+#[test]
+fn request_handler() {
+    let mut modbus_mapping = ModbusMapping::new_start_address(0, 0, 0, 0, 100, 5, 0, 0).unwrap();
+
+    assert!(modbus_mapping
+        .write_multiple_registers(101, &[1, 2])
+        .is_ok());
+    assert_eq!(modbus_mapping.get_registers(), [0u16, 1, 2, 0, 0]);
+    assert_eq!(
+        modbus_mapping.read_holding_registers(100, 3).unwrap(),
+        vec![0, 1, 2]
+    );
+    assert!(modbus_mapping
+        .mask_write_register(102, 0x00F0, 0x0001)
+        .is_ok());
+    assert_eq!(modbus_mapping.get_registers()[2], 0x0001);
+    assert_eq!(
+        modbus_mapping.read_holding_registers(99, 1),
+        Err(Exception::IllegalDataAddress)
+    );
+    assert_eq!(
+        modbus_mapping.read_holding_registers(104, 2),
+        Err(Exception::IllegalDataAddress)
+    );
+    assert_eq!(
+        modbus_mapping.read_coils(0, 1),
+        Err(Exception::IllegalDataAddress)
+    );
+}
+
+#[test]
+fn add_observer() {
+    let mut modbus_mapping = ModbusMapping::new(5, 5, 5, 5).unwrap();
+    let events = modbus_mapping.subscribe();
+
+    assert!(modbus_mapping.write_single_register(2, 42).is_ok());
+    assert!(modbus_mapping.write_single_register(2, 42).is_ok());
+    assert!(modbus_mapping
+        .write_multiple_coils(0, &[false, true])
+        .is_ok());
+
+    let event = events.try_recv().unwrap();
+    assert_eq!(event.table, Table::HoldingRegisters);
+    assert_eq!(
+        (event.address, event.old, event.new),
+        (2, vec![0], vec![42])
+    );
+    let event = events.try_recv().unwrap();
+    assert_eq!(event.table, Table::Coils);
+    assert_eq!(
+        (event.address, event.old, event.new),
+        (0, vec![0, 0], vec![0, 1])
+    );
+    assert!(events.try_recv().is_err());
+}
+
+#[test]
+fn observer_writes_mapping() {
+    let mut modbus_mapping = ModbusMapping::new(5, 5, 5, 5).unwrap();
+    let shared: Rc<RefCell<Option<Rc<ModbusMapping>>>> = Rc::new(RefCell::new(None));
+    let observed = Rc::clone(&shared);
+    // mirror register 0 to register 1
+    modbus_mapping.add_observer(move |event| {
+        if event.address == 0 {
+            if let Some(mapping) = observed.borrow().as_ref() {
+                let mut handler = &**mapping;
+                handler.write_single_register(1, event.new[0]).unwrap();
+            }
+        }
+    });
+    let modbus_mapping = Rc::new(modbus_mapping);
+    *shared.borrow_mut() = Some(Rc::clone(&modbus_mapping));
+
+    let mut handler = &*modbus_mapping;
+    assert!(handler.write_single_register(0, 42).is_ok());
+    assert_eq!(modbus_mapping.get_registers()[..2], [42, 42]);
+
+    // the observer is still registered
+    assert!(handler.write_single_register(0, 43).is_ok());
+    assert_eq!(modbus_mapping.get_registers()[..2], [43, 43]);
+
+    // break the cycle
+    shared.borrow_mut().take();
+}
+
+#[test]
+fn set_access() {
+    let mut modbus_mapping = ModbusMapping::new(5, 5, 5, 5).unwrap();
+    modbus_mapping.set_access(Table::HoldingRegisters, 0, 2, Access::ReadOnly);
+    modbus_mapping.set_access(Table::HoldingRegisters, 2, 1, Access::WriteOnly);
+    modbus_mapping.set_access(Table::HoldingRegisters, 3, 1, Access::Unmapped);
+
+    assert!(modbus_mapping.read_holding_registers(0, 2).is_ok());
+    assert_eq!(
+        modbus_mapping.write_single_register(1, 1),
+        Err(Exception::IllegalDataAddress)
+    );
+    assert_eq!(
+        modbus_mapping.read_holding_registers(0, 3),
+        Err(Exception::IllegalDataAddress)
+    );
+    assert!(modbus_mapping.write_single_register(2, 1).is_ok());
+    assert!(modbus_mapping.mask_write_register(2, 0, 3).is_ok());
+    assert_eq!(modbus_mapping.get_registers()[2], 3);
+    assert_eq!(
+        modbus_mapping.write_single_register(3, 1),
+        Err(Exception::IllegalDataAddress)
+    );
+    assert!(modbus_mapping.read_coils(0, 5).is_ok());
+}
+
+#[test]
+fn add_validator() {
+    let mut modbus_mapping = ModbusMapping::new(5, 5, 5, 5).unwrap();
+    modbus_mapping.add_validator(0, 2, Validator::Range { min: 10, max: 20 });
+    modbus_mapping.add_validator(1, 1, Validator::OneOf(vec![10, 15]));
+
+    assert!(modbus_mapping
+        .write_multiple_registers(0, &[20, 15])
+        .is_ok());
+    assert_eq!(
+        modbus_mapping.write_multiple_registers(0, &[20, 16]),
+        Err(Exception::IllegalDataValue)
+    );
+    assert_eq!(
+        modbus_mapping.write_single_register(0, 21),
+        Err(Exception::IllegalDataValue)
+    );
+    assert_eq!(modbus_mapping.get_registers()[0..2], [20u16, 15]);
+    assert!(modbus_mapping.write_single_register(2, 21).is_ok());
+}
+
+#[test]
+fn accessors_by_address() {
+    let modbus_mapping =
+        ModbusMapping::new_start_address(100, 5, 10, 5, 40000, 30, 30000, 4).unwrap();
+
+    modbus_mapping.set_coil(102, true).unwrap();
+    assert!(modbus_mapping.coil(102).unwrap());
+    assert_eq!(modbus_mapping.get_bits()[2], 1);
+    assert!(modbus_mapping.coil(99).is_err());
+    assert!(modbus_mapping.set_coil(105, true).is_err());
+
+    modbus_mapping
+        .set_input_bits(11..14, &[true, false, true])
+        .unwrap();
+    assert_eq!(
+        modbus_mapping.input_bits(10..15).unwrap(),
+        [false, true, false, true, false]
+    );
+    assert!(modbus_mapping.discrete_input(13).unwrap());
+    assert!(modbus_mapping.set_input_bits(11..14, &[true]).is_err());
+    assert!(modbus_mapping.input_bits(12..16).is_err());
+
+    modbus_mapping.set_holding(40010, 17).unwrap();
+    assert_eq!(modbus_mapping.holding(40010).unwrap(), 17);
+    assert_eq!(modbus_mapping.get_registers()[10], 17);
+    assert!(modbus_mapping.holding(40030).is_err());
+    assert!(modbus_mapping.holding(u16::MAX).is_err());
+
+    modbus_mapping
+        .set_holding_f32(40020, 123456.0, ByteOrder::CDAB)
+        .unwrap();
+    assert_eq!(
+        modbus_mapping.registers(40020..40022).unwrap(),
+        [0xF147, 0x0020]
+    );
+    assert_eq!(
+        modbus_mapping.holding_f32(40020, ByteOrder::CDAB).unwrap(),
+        123456.0
+    );
+    assert!(modbus_mapping
+        .set_holding_f32(40029, 1.0, ByteOrder::ABCD)
+        .is_err());
+
+    modbus_mapping
+        .set_input_register_f32(30002, 123456.0, ByteOrder::ABCD)
+        .unwrap();
+    assert_eq!(
+        modbus_mapping.input_registers(30000..30004).unwrap(),
+        [0, 0, 0x0020, 0xF147]
+    );
+    assert_eq!(modbus_mapping.input_register(30003).unwrap(), 0xF147);
+}
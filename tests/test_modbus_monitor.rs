@@ -0,0 +1,102 @@
+#![cfg(unix)]
+
+use libmodbus::{
+    Exception, MonitorEvent, MonitorResponse, PseudoTerminal, Request, RtuMonitor, SerialConfig,
+};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::thread;
+use std::time::Duration;
+
+/// Traffic on the line, each element followed by a silence
+const TRAFFIC: &[&[u8]] = &[
+    // read holding register 1 of slave 1, answered right away without a silence in between
+    &[
+        0x01, 0x03, 0x00, 0x01, 0x00, 0x01, 0xD5, 0xCA, 0x01, 0x03, 0x02, 0x12, 0x34, 0xB5, 0x33,
+    ],
+    // read coils 0 to 7 of slave 2, not answered
+    &[0x02, 0x01, 0x00, 0x00, 0x00, 0x08, 0x3D, 0xFF],
+    // write register 5 of slave 1, with a wrong CRC
+    &[0x01, 0x06, 0x00, 0x05, 0x00, 0x07, 0x00, 0x00],
+    // the same, answered with an exception
+    &[0x01, 0x06, 0x00, 0x05, 0x00, 0x07, 0xD8, 0x09],
+    &[0x01, 0x86, 0x02, 0xC3, 0xA1],
+    // write register 1 of all slaves
+    &[0x00, 0x06, 0x00, 0x01, 0x00, 0x2A, 0x58, 0x04],
+];
+
+#[test]
+fn open() {
+    assert!(RtuMonitor::open("/dev/ttyUSB0", 9600, 'x', 8, 1).is_err());
+    assert!(RtuMonitor::open("/dev/does-not-exist", 9600, 'N', 8, 1).is_err());
+}
+
+#[test]
+fn monitor_pseudo_terminal() {
+    let config = SerialConfig::from_parts(9600, 'N', 8, 1).unwrap();
+    let pty = PseudoTerminal::open().expect("Could not open a pseudo-terminal");
+    let path = pty.path().to_owned();
+    let mut monitor = pty.into_monitor(config);
+    monitor.set_response_timeout(Duration::from_secs(5));
+    assert_eq!(monitor.get_frame_silence(), config.inter_frame_delay());
+
+    // the masters and slaves on the line
+    let line_thread = thread::spawn(move || {
+        let mut line = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .expect("Could not open the slave end");
+        for frames in TRAFFIC {
+            line.write_all(frames).unwrap();
+            thread::sleep(Duration::from_millis(50));
+        }
+        line
+    });
+
+    let events: Vec<MonitorEvent> = monitor.by_ref().take(5).map(Result::unwrap).collect();
+    let transactions: Vec<_> = events
+        .iter()
+        .filter_map(|event| match event {
+            MonitorEvent::Transaction(transaction) => Some(transaction),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(transactions.len(), 4);
+
+    assert_eq!(
+        transactions[0].request,
+        Request::ReadHoldingRegisters {
+            unit: 1,
+            address: 1,
+            quantity: 1
+        }
+    );
+    assert_eq!(
+        transactions[0].response,
+        Some(MonitorResponse::Registers(vec![0x1234]))
+    );
+
+    // not answered before the next frame
+    assert_eq!(transactions[1].slave(), 2);
+    assert_eq!(transactions[1].function(), 0x01);
+    assert_eq!(transactions[1].response, None);
+    assert_eq!(events[2], MonitorEvent::CrcError(TRAFFIC[2].to_vec()));
+
+    assert_eq!(
+        transactions[2].request,
+        Request::WriteSingleRegister {
+            unit: 1,
+            address: 5,
+            value: 7
+        }
+    );
+    assert_eq!(
+        transactions[2].response,
+        Some(MonitorResponse::Exception(Exception::IllegalDataAddress))
+    );
+
+    assert_eq!(transactions[3].slave(), 0);
+    assert_eq!(transactions[3].response, None);
+    line_thread.join().unwrap();
+}
@@ -0,0 +1,35 @@
+#![cfg(unix)]
+
+use libmodbus::{serve, Modbus, ModbusClient, ModbusMapping, ModbusRTU, PseudoTerminal, StopToken};
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn serve_on_pseudo_terminal() {
+    let pty = PseudoTerminal::open().expect("Could not open a pseudo-terminal");
+    let path = pty.path().to_owned();
+    assert!(path.starts_with("/dev/"));
+
+    let stop = StopToken::new();
+    let server_stop = stop.clone();
+    let server_thread = thread::spawn(move || {
+        let mut modbus = pty
+            .into_modbus(1)
+            .expect("Could not create RTU Server context");
+        let mb_mapping = ModbusMapping::new(0, 0, 10, 0).expect("Failed to allocate the mapping");
+        mb_mapping.get_registers_mut()[0] = 1234;
+        serve(&mut modbus, &mb_mapping, &server_stop).expect("Could not serve")
+    });
+
+    let mut client = Modbus::new_rtu(&path, 115200, 'N', 8, 1).unwrap();
+    client.set_slave(1).unwrap();
+    client.connect().expect("could not connect");
+    let mut dest = vec![0u16; 1];
+    assert!(client.read_registers(0, 1, &mut dest).is_ok());
+    assert_eq!(dest, vec![1234]);
+
+    thread::sleep(Duration::from_millis(100));
+    stop.stop();
+    let statistics = server_thread.join().unwrap();
+    assert_eq!(statistics.total_requests(), 1);
+}
@@ -0,0 +1,86 @@
+use libmodbus::{Exception, Modbus, ModbusTCP, Request};
+
+/// A TCP ADU: MBAP header for unit 1 followed by `pdu`
+fn tcp_adu(pdu: &[u8]) -> Vec<u8> {
+    let mut adu = vec![0x00, 0x01, 0x00, 0x00, 0x00, pdu.len() as u8 + 1, 0x01];
+    adu.extend_from_slice(pdu);
+    adu
+}
+
+#[test]
+fn parse() {
+    let modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+
+    let request = Request::parse(&modbus, &tcp_adu(&[0x03, 0x00, 0x10, 0x00, 0x02])).unwrap();
+    assert_eq!(
+        request,
+        Request::ReadHoldingRegisters {
+            unit: 1,
+            address: 0x10,
+            quantity: 2,
+        }
+    );
+    assert_eq!((request.unit(), request.function()), (1, 0x03));
+    assert!(!request.is_write());
+
+    let request = Request::parse(
+        &modbus,
+        &tcp_adu(&[0x10, 0x00, 0x01, 0x00, 0x02, 0x04, 0x00, 0x0A, 0x01, 0x02]),
+    )
+    .unwrap();
+    assert_eq!(
+        request,
+        Request::WriteMultipleRegisters {
+            unit: 1,
+            address: 1,
+            values: vec![0x000A, 0x0102],
+        }
+    );
+    assert!(request.is_write());
+
+    let request = Request::parse(
+        &modbus,
+        &tcp_adu(&[0x0F, 0x00, 0x00, 0x00, 0x03, 0x01, 0x05]),
+    )
+    .unwrap();
+    assert_eq!(
+        request,
+        Request::WriteMultipleCoils {
+            unit: 1,
+            address: 0,
+            values: vec![true, false, true],
+        }
+    );
+
+    let request = Request::parse(&modbus, &tcp_adu(&[0x41, 0x01, 0x02])).unwrap();
+    assert_eq!(
+        request,
+        Request::Custom {
+            unit: 1,
+            function: 0x41,
+            data: vec![0x01, 0x02],
+        }
+    );
+}
+
+#[test]
+fn parse_invalid() {
+    let modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+
+    // quantity 0
+    let err = Request::parse(&modbus, &tcp_adu(&[0x03, 0x00, 0x00, 0x00, 0x00])).unwrap_err();
+    assert_eq!(err.exception(), Some(Exception::IllegalDataValue));
+    // byte count not matching the quantity
+    let err = Request::parse(
+        &modbus,
+        &tcp_adu(&[0x10, 0x00, 0x01, 0x00, 0x02, 0x02, 0x00, 0x0A]),
+    )
+    .unwrap_err();
+    assert_eq!(err.exception(), Some(Exception::IllegalDataValue));
+    // invalid coil value
+    let err = Request::parse(&modbus, &tcp_adu(&[0x05, 0x00, 0x01, 0x12, 0x34])).unwrap_err();
+    assert_eq!(err.exception(), Some(Exception::IllegalDataValue));
+    // truncated
+    assert!(Request::parse(&modbus, &tcp_adu(&[0x03, 0x00])).is_err());
+    assert!(Request::parse(&modbus, &tcp_adu(&[])).is_err());
+}
@@ -1,10 +1,67 @@
-use libmodbus::{Modbus, ModbusRTU, RequestToSendMode, SerialMode};
+use libmodbus::{
+    BaudRate, DataBits, Modbus, ModbusRTU, ModbusTCP, Parity, RequestToSendMode, SerialConfig,
+    SerialMode, StopBits,
+};
+#[cfg(unix)]
+use libmodbus::{ModbusClient, ModbusMapping, ModbusServer, PseudoTerminal};
+use std::cell::RefCell;
+use std::rc::Rc;
+#[cfg(unix)]
+use std::thread;
 
 #[test]
 fn new_rtu_context() {
     assert!(Modbus::new_rtu("/dev/ttyUSB0", 115200, 'N', 8, 1).is_ok());
 }
 
+#[test]
+fn new_rtu_invalid_parameters() {
+    assert!(Modbus::new_rtu("/dev/ttyUSB0", 115200, 'x', 8, 1).is_err());
+    assert!(Modbus::new_rtu("/dev/ttyUSB0", 115200, 'N', 9, 1).is_err());
+    assert!(Modbus::new_rtu("/dev/ttyUSB0", 115200, 'N', 8, 3).is_err());
+    assert!(Modbus::new_rtu("/dev/ttyUSB0", 12345, 'N', 8, 1).is_err());
+    assert!(Modbus::new_rtu("/dev/ttyUSB0", -9600, 'N', 8, 1).is_err());
+}
+
+#[test]
+fn serial_config() {
+    let config = SerialConfig::from_parts(9600, 'O', 7, 2).unwrap();
+    assert_eq!(config.baud_rate, BaudRate::B9600);
+    assert_eq!(config.parity, Parity::Odd);
+    assert_eq!(config.data_bits, DataBits::Seven);
+    assert_eq!(config.stop_bits, StopBits::Two);
+    assert_eq!(config.to_string(), "9600 7O2");
+    assert_eq!(SerialConfig::default().to_string(), "19200 8E1");
+}
+
+#[test]
+fn rtu_get_serial_config() {
+    let config = SerialConfig::new(
+        BaudRate::B38400,
+        Parity::None,
+        DataBits::Eight,
+        StopBits::Two,
+    );
+    let modbus = Modbus::new_rtu_config("/dev/ttyUSB0", config).unwrap();
+    assert_eq!(modbus.rtu_get_serial_config().unwrap(), config);
+
+    let modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+    assert!(modbus.rtu_get_serial_config().is_err());
+}
+
+#[test]
+#[cfg(unix)]
+fn rtu_get_serial_config_connected() {
+    let pty = PseudoTerminal::open().expect("Could not open a pseudo-terminal");
+    // a pseudo-terminal keeps the baud rate and the stop bits, but always has 8 data bits and no parity
+    let modbus = Modbus::new_rtu(pty.path(), 9600, 'N', 8, 2).unwrap();
+    modbus.connect().expect("could not connect");
+
+    // read back from the device
+    let config = modbus.rtu_get_serial_config().unwrap();
+    assert_eq!(config, SerialConfig::from_parts(9600, 'N', 8, 2).unwrap());
+}
+
 #[test]
 #[ignore]
 fn rtu_get_serial_mode() {
@@ -55,12 +112,76 @@ fn rtu_set_rts() {
 
 #[test]
 #[ignore]
+#[cfg(unix)]
+// needs a libmodbus built with RTS support (`HAVE_DECL_TIOCM_RTS`)
 fn rtu_set_custom_rts() {
-    #[cfg(target_os = "linux")]
-    let _modbus = Modbus::new_rtu("/dev/ttyUSB0", 115200, 'N', 8, 1).unwrap();
-    #[cfg(target_os = "windows")]
-    let _modbus = Modbus::new_rtu("COM2", 115200, 'N', 8, 1).unwrap();
-    // function pointer via ffi ???
+    let pty = PseudoTerminal::open().expect("Could not open a pseudo-terminal");
+    let path = pty.path().to_owned();
+    let server_thread = thread::spawn(move || {
+        let modbus = pty
+            .into_modbus(1)
+            .expect("Could not create RTU Server context");
+        let mb_mapping = ModbusMapping::new(0, 0, 10, 0).expect("Failed to allocate the mapping");
+        let mut query = vec![0u8; Modbus::RTU_MAX_ADU_LENGTH];
+        let rc = modbus.receive(&mut query).expect("Could not receive");
+        modbus
+            .reply(&query, rc, &mb_mapping)
+            .expect("Could not reply");
+    });
+
+    let mut modbus = Modbus::new_rtu(&path, 115200, 'N', 8, 1).unwrap();
+    modbus.set_slave(1).unwrap();
+    modbus.connect().expect("could not connect");
+    let calls = Rc::new(RefCell::new(Vec::new()));
+    let recorded = calls.clone();
+    modbus
+        .rtu_set_custom_rts(move |_modbus, on| recorded.borrow_mut().push(on))
+        .unwrap();
+    modbus.rtu_set_rts(RequestToSendMode::RtuRtsUp).unwrap();
+    calls.borrow_mut().clear();
+
+    let mut dest = vec![0u16; 1];
+    modbus.read_registers(0, 1, &mut dest).unwrap();
+    // enabled for the request, disabled for the response
+    assert_eq!(*calls.borrow(), vec![true, false]);
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn rtu_set_custom_rts_release() {
+    // no device needed, the function is only called when sending
+    let mut modbus = Modbus::new_rtu("/dev/ttyS0", 115200, 'N', 8, 1).unwrap();
+    let first = Rc::new(RefCell::new(0));
+    let recorded = first.clone();
+    modbus
+        .rtu_set_custom_rts(move |_modbus, _on| *recorded.borrow_mut() += 1)
+        .unwrap();
+    assert_eq!(Rc::strong_count(&first), 2);
+
+    // replacing the function drops the previous one
+    let second = Rc::new(RefCell::new(0));
+    let recorded = second.clone();
+    modbus
+        .rtu_set_custom_rts(move |_modbus, _on| *recorded.borrow_mut() += 1)
+        .unwrap();
+    assert_eq!(Rc::strong_count(&first), 1);
+    assert_eq!(Rc::strong_count(&second), 2);
+
+    // dropping the context drops the function
+    drop(modbus);
+    assert_eq!(Rc::strong_count(&second), 1);
+}
+
+#[test]
+fn rtu_set_custom_rts_not_rtu() {
+    let mut modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+    let calls = Rc::new(RefCell::new(0));
+    let recorded = calls.clone();
+    assert!(modbus
+        .rtu_set_custom_rts(move |_modbus, _on| *recorded.borrow_mut() += 1)
+        .is_err());
+    // the function is dropped with the error
+    assert_eq!(Rc::strong_count(&calls), 1);
 }
 
 #[test]
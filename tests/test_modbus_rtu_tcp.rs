@@ -0,0 +1,79 @@
+mod common;
+
+use common::Registers;
+use libmodbus::{Modbus, ModbusClient, ModbusServer, ModbusStream, Timeout};
+use std::net::TcpListener;
+use std::thread;
+
+fn start_server(port: u16) -> thread::JoinHandle<u64> {
+    let listener = TcpListener::bind(("127.0.0.1", port)).expect("Could not listen");
+    thread::spawn(move || {
+        let mut modbus =
+            ModbusStream::rtu_tcp_accept(&listener).expect("Could not accept connection");
+        modbus.set_slave(1).unwrap();
+        modbus
+            .set_inter_frame_timeout(Timeout {
+                sec: 0,
+                usec: 20_000,
+            })
+            .unwrap();
+        let mut handler = Registers::new();
+
+        loop {
+            let mut query = vec![0u8; Modbus::MAX_ADU_LENGTH];
+            match modbus.receive(&mut query) {
+                Ok(0) => continue,
+                Ok(rc) => modbus
+                    .reply_with(&query, rc, &mut handler)
+                    .expect("Could not reply"),
+                Err(_err) => break,
+            };
+        }
+        modbus.statistics().total_requests()
+    })
+}
+
+#[test]
+fn new_rtu_tcp() {
+    assert!(ModbusStream::new_rtu_tcp("127.0.0.1", 4001).is_ok());
+    assert!(ModbusStream::new_rtu_tcp("127.0.0.1", 65536).is_err());
+    assert!(ModbusStream::new_rtu_tcp("127.0.0.1", -1).is_err());
+}
+
+#[test]
+fn client_server() {
+    let server_thread = start_server(1527);
+
+    let mut client = ModbusStream::new_rtu_tcp("127.0.0.1", 1527).unwrap();
+    client.set_slave(1).unwrap();
+    client.connect().expect("could not connect");
+
+    common::client_server(&client);
+
+    // the length of a user defined function is unknown, the inter-frame timeout ends its frames
+    client
+        .set_inter_frame_timeout(Timeout {
+            sec: 0,
+            usec: 20_000,
+        })
+        .unwrap();
+    let mut raw_request = [1, 0x41, 1, 2, 3];
+    assert_eq!(client.send_raw_request(&mut raw_request, 5).unwrap(), 7);
+    let mut response = [0u8; Modbus::MAX_ADU_LENGTH];
+    assert_eq!(client.receive_confirmation(&mut response).unwrap(), 7);
+    assert_eq!(response[..5], [1, 0x41, 3, 2, 1]);
+
+    // nobody answers for slave 2
+    let mut dest = vec![0u16; 1];
+    client.set_slave(2).unwrap();
+    client
+        .set_response_timeout(Timeout {
+            sec: 0,
+            usec: 200_000,
+        })
+        .unwrap();
+    assert!(client.read_registers(2, 1, &mut dest).is_err());
+
+    client.close();
+    assert_eq!(server_thread.join().unwrap(), 5);
+}
@@ -0,0 +1,170 @@
+#![cfg(unix)]
+
+use libmodbus::{
+    BaudRate, DataBits, FramingViolation, Modbus, ModbusClient, ModbusMapping, ModbusServer,
+    ModbusStream, Parity, PseudoTerminal, SerialConfig, StopBits,
+};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::thread;
+use std::time::Duration;
+
+/// Read holding register 1 of slave 1
+const REQUEST: [u8; 8] = [0x01, 0x03, 0x00, 0x01, 0x00, 0x01, 0xD5, 0xCA];
+
+/// 110 baud 8N1, a character takes 91 ms: t1.5 is 136 ms and t3.5 318 ms
+fn slow_line() -> SerialConfig {
+    SerialConfig::new(
+        BaudRate::new(110).unwrap(),
+        Parity::None,
+        DataBits::Eight,
+        StopBits::One,
+    )
+}
+
+/// Write the parts of a request to the slave end of a pseudo-terminal, with a pause between them
+fn write_request(path: String, parts: &[&[u8]], pause: Duration) -> File {
+    let mut device = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .expect("Could not open the slave end");
+    // let the server wait for the request
+    thread::sleep(Duration::from_millis(100));
+    for (i, part) in parts.iter().enumerate() {
+        if i > 0 {
+            thread::sleep(pause);
+        }
+        device.write_all(part).unwrap();
+    }
+    device
+}
+
+#[test]
+fn timing_intervals() {
+    // 11 bits per character
+    let config = SerialConfig::default();
+    let character = Duration::from_nanos(11_000_000_000 / 19200);
+    assert_eq!(config.character_time(), character);
+    assert_eq!(config.inter_character_timeout(), character * 3 / 2);
+    assert_eq!(config.inter_frame_delay(), character * 7 / 2);
+
+    let config = SerialConfig::from_parts(9600, 'N', 8, 2).unwrap();
+    assert_eq!(
+        config.character_time(),
+        Duration::from_nanos(11_000_000_000 / 9600)
+    );
+
+    // fixed above 19200 baud
+    let config = SerialConfig::from_parts(38400, 'E', 8, 1).unwrap();
+    assert_eq!(config.inter_character_timeout(), Duration::from_micros(750));
+    assert_eq!(config.inter_frame_delay(), Duration::from_micros(1750));
+}
+
+#[test]
+fn set_rtu_timing() {
+    let mut modbus = ModbusStream::new_rtu("/dev/ttyUSB0", SerialConfig::default()).unwrap();
+    assert!(!modbus.get_rtu_timing());
+    assert!(modbus.set_rtu_timing(true).is_ok());
+    assert!(modbus.get_rtu_timing());
+
+    // not a serial line
+    let mut modbus = ModbusStream::new_rtu_tcp("127.0.0.1", 4001).unwrap();
+    assert!(modbus.set_rtu_timing(true).is_err());
+    let mut modbus = ModbusStream::new_ascii("/dev/ttyUSB0", 9600, 'E', 7, 1).unwrap();
+    assert!(modbus.set_rtu_timing(true).is_err());
+}
+
+#[test]
+fn client_server_with_timing() {
+    let config = SerialConfig::from_parts(9600, 'N', 8, 1).unwrap();
+    let pty = PseudoTerminal::open().expect("Could not open a pseudo-terminal");
+    let path = pty.path().to_owned();
+    let server_thread = thread::spawn(move || {
+        let mut modbus = pty.into_rtu(1, config).unwrap();
+        modbus.set_rtu_timing(true).unwrap();
+        let mb_mapping = ModbusMapping::new(0, 0, 10, 0).unwrap();
+        mb_mapping.get_registers_mut()[3] = 0x1234;
+        let mut query = vec![0u8; Modbus::RTU_MAX_ADU_LENGTH];
+        for _ in 0..2 {
+            let rc = modbus.receive(&mut query).unwrap();
+            modbus.reply(&query, rc, &mb_mapping).unwrap();
+        }
+    });
+
+    let mut modbus = ModbusStream::new_rtu(&path, config).unwrap();
+    modbus.set_slave(1).unwrap();
+    modbus.set_rtu_timing(true).unwrap();
+    modbus.connect().unwrap();
+    let mut dest = [0u16; 1];
+    for _ in 0..2 {
+        modbus.read_registers(3, 1, &mut dest).unwrap();
+        assert_eq!(dest[0], 0x1234);
+    }
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn inter_character_gap() {
+    let pty = PseudoTerminal::open().expect("Could not open a pseudo-terminal");
+    let path = pty.path().to_owned();
+    let mut modbus = pty.into_rtu(1, slow_line()).unwrap();
+    modbus.set_rtu_timing(true).unwrap();
+
+    // 180 ms of silence after the last character of the first part, more than t1.5
+    let client_thread = thread::spawn(move || {
+        write_request(
+            path,
+            &[&REQUEST[..2], &REQUEST[2..3], &REQUEST[3..]],
+            Duration::from_millis(270),
+        )
+    });
+    let mut query = vec![0u8; Modbus::RTU_MAX_ADU_LENGTH];
+    let err = modbus.receive(&mut query).unwrap_err();
+    assert_eq!(
+        err.framing_violation(),
+        Some(FramingViolation::InterCharacterGap)
+    );
+    assert_eq!(modbus.statistics().framing_errors, 1);
+    client_thread.join().unwrap();
+}
+
+#[test]
+fn incomplete_frame() {
+    let pty = PseudoTerminal::open().expect("Could not open a pseudo-terminal");
+    let path = pty.path().to_owned();
+    let mut modbus = pty.into_rtu(1, slow_line()).unwrap();
+    modbus.set_rtu_timing(true).unwrap();
+
+    let client_thread =
+        thread::spawn(move || write_request(path, &[&REQUEST[..4]], Duration::default()));
+    let mut query = vec![0u8; Modbus::RTU_MAX_ADU_LENGTH];
+    let err = modbus.receive(&mut query).unwrap_err();
+    assert_eq!(
+        err.framing_violation(),
+        Some(FramingViolation::IncompleteFrame)
+    );
+    assert_eq!(modbus.statistics().framing_errors, 1);
+    client_thread.join().unwrap();
+}
+
+#[test]
+fn request_within_timing() {
+    let pty = PseudoTerminal::open().expect("Could not open a pseudo-terminal");
+    let path = pty.path().to_owned();
+    let mut modbus = pty.into_rtu(1, slow_line()).unwrap();
+    modbus.set_rtu_timing(true).unwrap();
+
+    // the pause is shorter than the transmission of the second part
+    let client_thread = thread::spawn(move || {
+        write_request(
+            path,
+            &[&REQUEST[..2], &REQUEST[2..]],
+            Duration::from_millis(100),
+        )
+    });
+    let mut query = vec![0u8; Modbus::RTU_MAX_ADU_LENGTH];
+    assert_eq!(modbus.receive(&mut query).unwrap(), REQUEST.len() as i32);
+    assert_eq!(query[..REQUEST.len()], REQUEST);
+    client_thread.join().unwrap();
+}
@@ -0,0 +1,107 @@
+use libmodbus::{
+    serve, Error, Modbus, ModbusClient, ModbusMapping, ModbusTCP, Statistics, StopToken,
+};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn start_server(port: i32, stop: StopToken) -> thread::JoinHandle<Statistics> {
+    thread::spawn(move || {
+        let mut modbus =
+            Modbus::new_tcp("127.0.0.1", port).expect("Could not create TCP Server context");
+        let mut socket = modbus
+            .tcp_listen(1)
+            .expect("Could not listen to TCP socket");
+        modbus
+            .tcp_accept(&mut socket)
+            .expect("Could not accept connection");
+
+        let mb_mapping = ModbusMapping::new(0, 0, 10, 0).expect("Failed to allocate the mapping");
+
+        serve(&mut modbus, &mb_mapping, &stop).expect("Could not serve")
+    })
+}
+
+#[test]
+fn stop_token() {
+    let stop = StopToken::new();
+    let remote = stop.clone();
+    assert!(!stop.is_stopped());
+    remote.stop();
+    assert!(stop.is_stopped());
+}
+
+#[test]
+fn serve_until_stopped() {
+    let port = 1524;
+    let stop = StopToken::new();
+    // Start modbus server
+    let server_thread = start_server(port, stop.clone());
+    thread::sleep(Duration::from_millis(200));
+
+    let client = Modbus::new_tcp("127.0.0.1", port).unwrap();
+    client.connect().expect("could not connect");
+    let mut dest = vec![0u16; 2];
+    assert!(client.write_register(1, 42).is_ok());
+    assert!(client.read_registers(0, 2, &mut dest).is_ok());
+    assert_eq!(dest, vec![0, 42]);
+
+    // no request for a while, the server keeps waiting
+    thread::sleep(Duration::from_millis(300));
+    assert!(client.read_registers(0, 2, &mut dest).is_ok());
+
+    stop.stop();
+    let statistics = server_thread.join().unwrap();
+    assert_eq!(statistics.total_requests(), 3);
+    assert_eq!(statistics.timeouts, 0);
+}
+
+#[test]
+fn serve_idle_timeout() {
+    let port = 1525;
+    let mut stop = StopToken::new();
+    stop.set_idle_timeout(Some(Duration::from_millis(300)));
+    // Start modbus server
+    let start = Instant::now();
+    let server_thread = start_server(port, stop.clone());
+    thread::sleep(Duration::from_millis(200));
+
+    let client = Modbus::new_tcp("127.0.0.1", port).unwrap();
+    client.connect().expect("could not connect");
+
+    let statistics = server_thread.join().unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(300));
+    assert_eq!(statistics.total_requests(), 0);
+    assert!(!stop.is_stopped());
+}
+
+#[test]
+fn serve_error_statistics() {
+    let port = 1547;
+    let server_thread = thread::spawn(move || {
+        let mut modbus =
+            Modbus::new_tcp("127.0.0.1", port).expect("Could not create TCP Server context");
+        let mut socket = modbus
+            .tcp_listen(1)
+            .expect("Could not listen to TCP socket");
+        modbus
+            .tcp_accept(&mut socket)
+            .expect("Could not accept connection");
+
+        let mb_mapping = ModbusMapping::new(0, 0, 10, 0).expect("Failed to allocate the mapping");
+
+        serve(&mut modbus, &mb_mapping, &StopToken::new())
+    });
+    thread::sleep(Duration::from_millis(200));
+
+    let client = Modbus::new_tcp("127.0.0.1", port).unwrap();
+    client.connect().expect("could not connect");
+    let mut dest = vec![0u16; 2];
+    assert!(client.read_registers(0, 2, &mut dest).is_ok());
+    // closing the connection ends the loop with an error
+    drop(client);
+
+    match server_thread.join().unwrap() {
+        Err(Error::Serve { statistics, .. }) => assert_eq!(statistics.total_requests(), 1),
+        other => panic!("unexpected result {:?}", other),
+    }
+}
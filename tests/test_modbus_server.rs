@@ -1,32 +1,248 @@
-use libmodbus::{Modbus, ModbusServer, ModbusTCP};
+#[cfg(unix)]
+use libmodbus::IoTransport;
+use libmodbus::{
+    Access, Error, Exception, Framing, FunctionCode, LoopbackTransport, Modbus, ModbusClient,
+    ModbusMapping, ModbusServer, ModbusStream, ModbusTCP, RequestHandler, Table, Validator,
+    WriteEvent,
+};
+#[cfg(unix)]
+use std::os::unix::io::IntoRawFd;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::thread;
+use std::time::Duration;
 
+/// A client and a server connected in memory, like a socket pair
+fn loopback() -> (ModbusStream, ModbusStream) {
+    let (client, server) = LoopbackTransport::pair();
+    let mut client = ModbusStream::from_transport(client, Framing::Mbap);
+    client.set_slave(1).unwrap();
+    let mut server = ModbusStream::from_transport(server, Framing::Mbap);
+    server.set_slave(1).unwrap();
+    (client, server)
+}
+
+/// A client and a libmodbus TCP context serving it, connected by a socket pair
+#[cfg(unix)]
+fn libmodbus_pair() -> (ModbusStream, Modbus) {
+    let (client, server) = UnixStream::pair().unwrap();
+    let mut client = ModbusStream::from_transport(IoTransport::new(client), Framing::Mbap);
+    client.set_slave(1).unwrap();
+    let mut server_context = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+    server_context.set_socket(server.into_raw_fd()).unwrap();
+    (client, server_context)
+}
+
+/// Send a request with `client` and receive it with `server`
+fn check_receive<S: ModbusServer>(client: &ModbusStream, server: &S) {
+    let mut raw_request = [
+        1,
+        FunctionCode::ReadHoldingRegisters as u8,
+        0x00,
+        0x01,
+        0x00,
+        0x05,
+    ];
+    assert_eq!(client.send_raw_request(&mut raw_request, 6).unwrap(), 12);
+
+    // the MBAP header and the PDU
+    let mut query = vec![0; Modbus::MAX_ADU_LENGTH];
+    assert_eq!(server.receive(&mut query).unwrap(), 12);
+    assert_eq!(query[6..12], raw_request);
+}
+
+/// Send a request with `client`, answer it with `server` from a mapping and receive the response
+fn check_reply<S: ModbusServer>(client: &ModbusStream, server: &S) {
+    let mapping = ModbusMapping::new(0, 0, 10, 0).unwrap();
+    mapping.get_registers_mut()[1] = 0x1234;
+    let mut raw_request = [
+        1,
+        FunctionCode::ReadHoldingRegisters as u8,
+        0x00,
+        0x01,
+        0x00,
+        0x01,
+    ];
+    client.send_raw_request(&mut raw_request, 6).unwrap();
+
+    let mut query = vec![0; Modbus::MAX_ADU_LENGTH];
+    let rc = server.receive(&mut query).unwrap();
+    assert_eq!(server.reply(&query, rc, &mapping).unwrap(), 11);
+
+    let mut response = vec![0; Modbus::MAX_ADU_LENGTH];
+    assert_eq!(client.receive_confirmation(&mut response).unwrap(), 11);
+    assert_eq!(response[7..11], [0x03, 0x02, 0x12, 0x34]);
+}
+
+#[cfg(unix)]
 #[test]
-#[ignore]
 fn receive() {
-    let mut query = vec![0; Modbus::MAX_ADU_LENGTH as usize];
-    // create server
-    match Modbus::new_tcp("127.0.0.1", 1502) {
-        Ok(mut server) => {
-            let mut socket = server.tcp_listen(1).expect("could not listen");
-            server
-                .tcp_accept(&mut socket)
-                .expect("unable to accept TCP socket");
+    let (client, server) = libmodbus_pair();
+    check_receive(&client, &server);
+}
+
+#[cfg(unix)]
+#[test]
+fn reply() {
+    let (client, server) = libmodbus_pair();
+    check_reply(&client, &server);
+}
+
+#[test]
+fn stream_receive() {
+    let (client, server) = loopback();
+    check_receive(&client, &server);
+}
+
+#[test]
+fn stream_reply() {
+    let (client, server) = loopback();
+    check_reply(&client, &server);
+}
+
+#[test]
+fn reply_observers() {
+    let port = 1522;
+    // Start modbus server, it returns the write events observed
+    let server_thread = thread::spawn(move || {
+        let mut modbus =
+            Modbus::new_tcp("127.0.0.1", port).expect("Could not create TCP Server context");
+        let mut socket = modbus
+            .tcp_listen(1)
+            .expect("Could not listen to TCP socket");
+        modbus
+            .tcp_accept(&mut socket)
+            .expect("Could not accept connection");
+
+        let mut mb_mapping =
+            ModbusMapping::new(10, 0, 10, 0).expect("Failed to allocate the mapping");
+        let events = mb_mapping.subscribe();
+
+        loop {
+            let mut query = vec![0u8; Modbus::TCP_MAX_ADU_LENGTH];
+
+            match modbus.receive(&mut query) {
+                Ok(rc) => modbus.reply(&query, rc, &mb_mapping),
+                Err(_err) => break,
+            }
+            .expect("Could not receive");
         }
-        Err(err) => panic!("Could not create server: {}", err),
+        events.try_iter().collect::<Vec<WriteEvent>>()
+    });
+    thread::sleep(Duration::from_millis(200));
+
+    // connect client
+    match Modbus::new_tcp("127.0.0.1", port) {
+        Ok(client) => {
+            let mut dest = vec![0u16; 2];
+            client.connect().expect("could not connect");
+            assert!(client.write_registers(1, 2, &[11, 12]).is_ok());
+            // unchanged values are not reported
+            assert!(client.write_register(1, 11).is_ok());
+            assert!(client.write_bit(3, true).is_ok());
+            assert!(client.read_registers(1, 2, &mut dest).is_ok());
+        }
+        _ => panic!("could not connect"),
     }
 
+    let events = server_thread.join().unwrap();
+    assert_eq!(
+        events,
+        vec![
+            WriteEvent {
+                table: Table::HoldingRegisters,
+                address: 1,
+                old: vec![0, 0],
+                new: vec![11, 12],
+            },
+            WriteEvent {
+                table: Table::Coils,
+                address: 3,
+                old: vec![0],
+                new: vec![1],
+            },
+        ]
+    );
+}
+
+#[test]
+fn reply_access_and_validators() {
+    let port = 1523;
+    // Start modbus server
+    let server_thread = thread::spawn(move || {
+        let mut modbus =
+            Modbus::new_tcp("127.0.0.1", port).expect("Could not create TCP Server context");
+        let mut socket = modbus
+            .tcp_listen(1)
+            .expect("Could not listen to TCP socket");
+        modbus
+            .tcp_accept(&mut socket)
+            .expect("Could not accept connection");
+
+        let mut mb_mapping =
+            ModbusMapping::new(10, 0, 10, 0).expect("Failed to allocate the mapping");
+        mb_mapping.set_access(Table::HoldingRegisters, 0, 2, Access::ReadOnly);
+        mb_mapping.set_access(Table::Coils, 5, 5, Access::Unmapped);
+        mb_mapping.add_validator(2, 1, Validator::Range { min: 0, max: 100 });
+
+        loop {
+            let mut query = vec![0u8; Modbus::TCP_MAX_ADU_LENGTH];
+
+            match modbus.receive(&mut query) {
+                Ok(rc) => modbus.reply(&query, rc, &mb_mapping),
+                Err(_err) => break,
+            }
+            .expect("Could not receive");
+        }
+    });
+    thread::sleep(Duration::from_millis(200));
+
     // connect client
-    match Modbus::new_tcp("127.0.0.1", 1502) {
+    match Modbus::new_tcp("127.0.0.1", port) {
         Ok(client) => {
+            let mut dest = vec![0u8; 10];
             client.connect().expect("could not connect");
-            assert!(client.receive(&mut query).is_ok());
+            let err = client.write_register(1, 1).unwrap_err();
+            assert_eq!(err.exception(), Some(Exception::IllegalDataAddress));
+            let err = client.read_bits(0, 10, &mut dest).unwrap_err();
+            assert_eq!(err.exception(), Some(Exception::IllegalDataAddress));
+            let err = client.write_register(2, 101).unwrap_err();
+            assert_eq!(err.exception(), Some(Exception::IllegalDataValue));
+            assert!(client.write_register(2, 100).is_ok());
+            assert!(client.read_bits(0, 5, &mut dest).is_ok());
         }
         _ => panic!("could not connect"),
     }
+
+    let _ = server_thread.join();
 }
 
+/// A server implemented outside of the crate, without `reply_with()`
+struct Minimal;
+
+impl ModbusServer for Minimal {
+    fn receive(&self, _request: &mut [u8]) -> Result<i32, Error> {
+        Ok(0)
+    }
+
+    fn reply(
+        &self,
+        _request: &[u8],
+        _request_len: i32,
+        _modbus_mapping: &ModbusMapping,
+    ) -> Result<i32, Error> {
+        Ok(0)
+    }
+}
+
+struct Nothing;
+
+impl RequestHandler for Nothing {}
+
 #[test]
-#[ignore]
-fn reply() {
-    let _modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+fn reply_with_not_supported() {
+    match Minimal.reply_with(&[1, 3, 0, 0, 0, 1], 6, &mut Nothing) {
+        Err(Error::Server { source, .. }) => assert_eq!(source.raw_os_error(), Some(libc::ENOTSUP)),
+        other => panic!("unexpected result {:?}", other),
+    }
 }
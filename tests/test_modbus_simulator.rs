@@ -0,0 +1,213 @@
+use libmodbus::{Behaviour, ModbusMapping, Simulator, Table};
+use std::time::Duration;
+
+fn simulator() -> Simulator {
+    Simulator::new(ModbusMapping::new(8, 8, 10, 10).expect("Failed to allocate the mapping"))
+}
+
+#[test]
+fn behaviours() {
+    let mut simulator = simulator();
+    let behaviours = vec![
+        Behaviour::Constant { value: 7 },
+        Behaviour::Counter {
+            start: 65534,
+            step: 1,
+            interval: Duration::from_secs(1),
+        },
+        Behaviour::Ramp {
+            min: 0.0,
+            max: 100.0,
+            period: Duration::from_secs(10),
+        },
+        Behaviour::Sine {
+            offset: 1000.0,
+            amplitude: 500.0,
+            period: Duration::from_secs(4),
+        },
+        Behaviour::Mirror {
+            table: Table::HoldingRegisters,
+            address: 0,
+        },
+    ];
+    for (address, behaviour) in behaviours.into_iter().enumerate() {
+        simulator
+            .add(Table::InputRegisters, address as u16, behaviour)
+            .unwrap();
+    }
+    simulator
+        .add(Table::HoldingRegisters, 0, Behaviour::Latch { initial: 42 })
+        .unwrap();
+
+    simulator.update_at(Duration::from_secs(0));
+    assert_eq!(
+        &simulator.mapping().get_input_registers()[..5],
+        &[7, 65534, 0, 1000, 42]
+    );
+
+    simulator.update_at(Duration::from_millis(3000));
+    assert_eq!(
+        &simulator.mapping().get_input_registers()[..5],
+        &[7, 1, 30, 500, 42]
+    );
+
+    // writes of clients are overwritten, except on latches
+    simulator.mapping().get_input_registers_mut()[0] = 8;
+    simulator.mapping().get_registers_mut()[0] = 43;
+    simulator.update_at(Duration::from_millis(9000));
+    assert_eq!(
+        &simulator.mapping().get_input_registers()[..5],
+        &[7, 7, 90, 1500, 43]
+    );
+}
+
+#[test]
+fn bits() {
+    let mut simulator = simulator();
+    simulator
+        .add(Table::Coils, 0, Behaviour::Latch { initial: 1 })
+        .unwrap();
+    simulator
+        .add(
+            Table::DiscreteInputs,
+            0,
+            Behaviour::Mirror {
+                table: Table::Coils,
+                address: 0,
+            },
+        )
+        .unwrap();
+    simulator
+        .add(Table::DiscreteInputs, 1, Behaviour::Constant { value: 2 })
+        .unwrap();
+    assert_eq!(&simulator.mapping().get_input_bits()[..2], &[1, 1]);
+
+    simulator.mapping().get_bits_mut()[0] = 0;
+    simulator.update();
+    assert_eq!(&simulator.mapping().get_input_bits()[..2], &[0, 1]);
+}
+
+#[test]
+fn random_walk() {
+    let mut simulator = simulator();
+    simulator.set_seed(1);
+    simulator
+        .add(
+            Table::InputRegisters,
+            0,
+            Behaviour::RandomWalk {
+                start: 10.0,
+                step: 5.0,
+                min: 0.0,
+                max: 20.0,
+                interval: Duration::from_millis(100),
+            },
+        )
+        .unwrap();
+
+    let mut values = Vec::new();
+    for second in 1..=20 {
+        simulator.update_at(Duration::from_secs(second));
+        values.push(simulator.mapping().get_input_registers()[0]);
+    }
+    assert!(values.iter().all(|&value| value <= 20));
+    assert!(values.iter().any(|&value| value != 10));
+}
+
+#[test]
+fn add_invalid() {
+    let mut simulator = simulator();
+    assert!(simulator
+        .add(
+            Table::HoldingRegisters,
+            10,
+            Behaviour::Constant { value: 1 }
+        )
+        .is_err());
+    assert!(simulator
+        .add(
+            Table::InputRegisters,
+            0,
+            Behaviour::Mirror {
+                table: Table::Coils,
+                address: 8,
+            },
+        )
+        .is_err());
+    assert!(simulator
+        .add(
+            Table::InputRegisters,
+            0,
+            Behaviour::Counter {
+                start: 0,
+                step: 1,
+                interval: Duration::from_secs(0),
+            },
+        )
+        .is_err());
+}
+
+#[cfg(feature = "simulator")]
+#[test]
+fn from_toml() {
+    let simulator = Simulator::from_toml(
+        r#"
+        [tables]
+        holding_registers = { start = 100, count = 2 }
+        input_registers = { count = 2 }
+
+        [[point]]
+        table = "holding_registers"
+        address = 101
+        behaviour = { type = "constant", value = 5 }
+
+        [[point]]
+        table = "input_registers"
+        address = 1
+        behaviour = { type = "mirror", table = "holding_registers", address = 101 }
+        "#,
+    )
+    .unwrap();
+    assert_eq!(simulator.mapping().get_registers(), &[0, 5]);
+    assert_eq!(simulator.mapping().get_input_registers(), &[0, 5]);
+
+    // unknown behaviour, point outside of the mapping, zero, too short and too long periods
+    for point in &[
+        r#"{ table = "coils", address = 0, behaviour = { type = "blink" } }"#,
+        r#"{ table = "coils", address = 8, behaviour = { type = "constant", value = 1 } }"#,
+        r#"{ table = "coils", address = 0, behaviour = { type = "sine", offset = 0.0, amplitude = 1.0, period = 0 } }"#,
+        r#"{ table = "coils", address = 0, behaviour = { type = "sine", offset = 0.0, amplitude = 1.0, period = 1e-20 } }"#,
+        r#"{ table = "coils", address = 0, behaviour = { type = "sine", offset = 0.0, amplitude = 1.0, period = 1e30 } }"#,
+    ] {
+        let simulation = format!("point = [{}]\n\n[tables]\ncoils = {{ count = 8 }}", point);
+        assert!(Simulator::from_toml(&simulation).is_err());
+    }
+}
+
+#[cfg(feature = "simulator")]
+#[test]
+fn from_yaml() {
+    let simulator = Simulator::from_yaml(
+        r#"
+tables:
+  holding_registers: { start: 100, count: 2 }
+  input_registers: { count: 2 }
+point:
+  - table: holding_registers
+    address: 101
+    behaviour: { type: constant, value: 5 }
+  - table: input_registers
+    address: 1
+    behaviour: { type: mirror, table: holding_registers, address: 101 }
+"#,
+    )
+    .unwrap();
+    assert_eq!(simulator.mapping().get_registers(), &[0, 5]);
+    assert_eq!(simulator.mapping().get_input_registers(), &[0, 5]);
+
+    // unknown behaviour
+    assert!(Simulator::from_yaml(
+        "point: [{ table: coils, address: 0, behaviour: { type: blink } }]"
+    )
+    .is_err());
+}
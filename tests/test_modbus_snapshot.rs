@@ -0,0 +1,113 @@
+use libmodbus::{serve, Modbus, ModbusClient, ModbusMapping, ModbusTCP, Snapshot, StopToken};
+use std::thread;
+use std::time::Duration;
+
+fn snapshot() -> Snapshot {
+    Snapshot {
+        start_bits: 10,
+        bits: vec![1, 0, 1, 1, 0, 0, 0, 0, 1],
+        start_input_bits: 0,
+        input_bits: vec![],
+        start_registers: 40000,
+        registers: vec![17, 0xffff, 3],
+        start_input_registers: 5,
+        input_registers: vec![42],
+    }
+}
+
+#[test]
+fn bytes() {
+    let snapshot = snapshot();
+    let bytes = snapshot.to_bytes();
+    assert!(bytes.starts_with(b"MBS1"));
+    assert_eq!(Snapshot::from_bytes(&bytes).unwrap(), snapshot);
+}
+
+#[test]
+fn bytes_invalid() {
+    let bytes = snapshot().to_bytes();
+    assert!(Snapshot::from_bytes(b"MBS0").is_err());
+    assert!(Snapshot::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert!(Snapshot::from_bytes(&trailing).is_err());
+}
+
+#[test]
+fn csv() {
+    let snapshot = snapshot();
+    let csv = snapshot.to_csv();
+    assert!(csv.starts_with("table,address,value\ncoils,10,1\n"));
+    assert!(csv.contains("holding_registers,40001,65535\n"));
+    assert_eq!(Snapshot::from_csv(&csv).unwrap(), snapshot);
+}
+
+#[test]
+fn csv_invalid() {
+    assert!(Snapshot::from_csv("coils,0,1\n").is_err());
+    assert!(Snapshot::from_csv("table,address,value\ncoils,0,2\n").is_err());
+    assert!(Snapshot::from_csv("table,address,value\nregisters,0,1\n").is_err());
+    assert!(
+        Snapshot::from_csv("table,address,value\ninput_registers,0,1\ninput_registers,2,1\n")
+            .is_err()
+    );
+}
+
+#[test]
+fn snapshot_restore() {
+    let mapping = ModbusMapping::new_start_address(0, 0, 0, 0, 100, 4, 0, 0).unwrap();
+    mapping.get_registers_mut().copy_from_slice(&[1, 2, 3, 4]);
+    let snapshot = mapping.snapshot();
+    assert_eq!(snapshot.start_registers, 100);
+    assert_eq!(snapshot.registers, vec![1, 2, 3, 4]);
+
+    let other = ModbusMapping::new_start_address(0, 0, 0, 0, 102, 4, 0, 0).unwrap();
+    assert_eq!(other.restore(&snapshot), 2);
+    assert_eq!(other.get_registers(), [3, 4, 0, 0]);
+}
+
+#[test]
+fn save_load() {
+    let path = std::env::temp_dir().join(format!("libmodbus-snapshot-{}", std::process::id()));
+    let mapping = ModbusMapping::new(8, 0, 4, 0).unwrap();
+    mapping.get_bits_mut()[3] = 1;
+    mapping.get_registers_mut()[1] = 1234;
+    mapping.save(&path).unwrap();
+
+    let loaded = ModbusMapping::new(8, 0, 4, 0).unwrap();
+    assert_eq!(loaded.load(&path).unwrap(), 12);
+    assert_eq!(loaded.snapshot(), mapping.snapshot());
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(loaded.load(&path).is_err());
+}
+
+#[test]
+fn autosave() {
+    let path = std::env::temp_dir().join(format!("libmodbus-autosave-{}", std::process::id()));
+    let server_path = path.clone();
+    let server = thread::spawn(move || {
+        let mut modbus = Modbus::new_tcp("127.0.0.1", 1526).unwrap();
+        let mut socket = modbus.tcp_listen(1).unwrap();
+        modbus.tcp_accept(&mut socket).unwrap();
+
+        let mut mapping = ModbusMapping::new(0, 0, 4, 0).unwrap();
+        mapping.set_autosave(server_path, Duration::from_secs(60));
+        let mut stop = StopToken::new();
+        stop.set_idle_timeout(Some(Duration::from_millis(500)));
+        serve(&mut modbus, &mapping, &stop).unwrap();
+    });
+
+    thread::sleep(Duration::from_millis(200));
+    let client = Modbus::new_tcp("127.0.0.1", 1526).unwrap();
+    client.connect().unwrap();
+    client.write_register(2, 99).unwrap();
+    server.join().unwrap();
+
+    // saved when the loop stopped
+    let mapping = ModbusMapping::new(0, 0, 4, 0).unwrap();
+    assert_eq!(mapping.load(&path).unwrap(), 4);
+    assert_eq!(mapping.get_registers(), [0, 0, 99, 0]);
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,60 @@
+use libmodbus::{Exception, RequestHandler, SparseMapping, Table};
+
+#[test]
+fn add_block() {
+    let mut mapping = SparseMapping::new();
+    assert!(mapping.add_block(Table::HoldingRegisters, 0, 100).is_ok());
+    assert!(mapping
+        .add_block(Table::HoldingRegisters, 40000, 201)
+        .is_ok());
+    assert!(mapping
+        .add_block(Table::HoldingRegisters, 3000, 101)
+        .is_ok());
+    // adjacent blocks are merged
+    assert!(mapping.add_block(Table::HoldingRegisters, 100, 10).is_ok());
+    assert_eq!(
+        mapping.blocks(Table::HoldingRegisters),
+        vec![(0, 110), (3000, 101), (40000, 201)]
+    );
+
+    assert!(mapping.add_block(Table::HoldingRegisters, 3100, 1).is_err());
+    assert!(mapping
+        .add_block(Table::HoldingRegisters, 65535, 2)
+        .is_err());
+    assert!(mapping.add_block(Table::Coils, 3100, 1).is_ok());
+}
+
+#[test]
+fn request_handler() {
+    let mut mapping = SparseMapping::new();
+    mapping.add_block(Table::HoldingRegisters, 0, 100).unwrap();
+    mapping
+        .add_block(Table::HoldingRegisters, 3000, 101)
+        .unwrap();
+    mapping.add_block(Table::Coils, 10, 8).unwrap();
+
+    assert!(mapping.write_multiple_registers(3099, &[1, 2]).is_ok());
+    assert_eq!(mapping.read_holding_registers(3099, 2), Ok(vec![1, 2]));
+    assert_eq!(
+        mapping.read_holding_registers(99, 2),
+        Err(Exception::IllegalDataAddress)
+    );
+    assert_eq!(
+        mapping.write_multiple_registers(3100, &[1, 2]),
+        Err(Exception::IllegalDataAddress)
+    );
+    assert_eq!(
+        mapping.read_input_registers(0, 1),
+        Err(Exception::IllegalDataAddress)
+    );
+
+    assert!(mapping.write_single_coil(17, true).is_ok());
+    assert_eq!(
+        mapping.get_bits(Table::Coils, 16, 2),
+        Some(&[false, true][..])
+    );
+    assert_eq!(
+        mapping.write_single_coil(18, true),
+        Err(Exception::IllegalDataAddress)
+    );
+}
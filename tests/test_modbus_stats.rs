@@ -0,0 +1,99 @@
+use libmodbus::{
+    Exception, FunctionCode, LatencyHistogram, Modbus, ModbusClient, ModbusMapping, ModbusServer,
+    ModbusTCP,
+};
+use std::thread;
+use std::time::Duration;
+
+fn start_server(port: i32) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut modbus =
+            Modbus::new_tcp("127.0.0.1", port).expect("Could not create TCP Server context");
+        let mut socket = modbus
+            .tcp_listen(1)
+            .expect("Could not listen to TCP socket");
+        modbus
+            .tcp_accept(&mut socket)
+            .expect("Could not accept connection");
+
+        let mb_mapping =
+            ModbusMapping::new(10, 10, 10, 10).expect("Failed to allocate the mapping");
+
+        loop {
+            let mut query = vec![0u8; Modbus::TCP_MAX_ADU_LENGTH];
+
+            match modbus.receive(&mut query) {
+                Ok(rc) => modbus.reply(&query, rc, &mb_mapping),
+                Err(_err) => break,
+            }
+            .expect("Could not receive");
+        }
+    })
+}
+
+#[test]
+fn new_context_has_empty_statistics() {
+    let modbus = Modbus::new_tcp("127.0.0.1", 1502).unwrap();
+    let statistics = modbus.statistics();
+
+    assert_eq!(statistics.total_requests(), 0);
+    assert_eq!(statistics.total_exceptions(), 0);
+    assert_eq!(statistics.bytes_sent, 0);
+    assert_eq!(statistics.latency.count(), 0);
+}
+
+#[test]
+fn latency_histogram() {
+    let mut histogram = LatencyHistogram::default();
+    assert_eq!(histogram.mean(), None);
+    assert_eq!(histogram.percentile(50.0), None);
+
+    histogram.record(Duration::from_micros(300));
+    histogram.record(Duration::from_millis(4));
+    histogram.record(Duration::from_millis(6));
+    histogram.record(Duration::from_secs(20));
+
+    assert_eq!(histogram.count(), 4);
+    assert_eq!(histogram.min(), Some(Duration::from_micros(300)));
+    assert_eq!(histogram.max(), Some(Duration::from_secs(20)));
+    assert_eq!(histogram.percentile(50.0), Some(Duration::from_millis(5)));
+    assert_eq!(histogram.percentile(100.0), Some(Duration::from_secs(20)));
+    assert_eq!(histogram.buckets().map(|(_, count)| count).sum::<u64>(), 4);
+    assert_eq!(histogram.buckets().last(), Some((None, 1)));
+}
+
+#[test]
+fn client_statistics() {
+    let port = 1540;
+    let server_thread = start_server(port);
+    thread::sleep(Duration::from_millis(200));
+
+    let client = Modbus::new_tcp("127.0.0.1", port).unwrap();
+    client.connect().expect("could not connect");
+    let handle = client.statistics_handle();
+
+    let mut dest = vec![0u16; 10];
+    client.read_registers(0, 1, &mut dest).unwrap();
+    client.read_registers(0, 1, &mut dest).unwrap();
+    let err = client.read_registers(100, 1, &mut dest).unwrap_err();
+    assert_eq!(err.exception(), Some(Exception::IllegalDataAddress));
+
+    let statistics = handle.snapshot();
+    assert_eq!(
+        statistics.requests_for(FunctionCode::ReadHoldingRegisters),
+        3
+    );
+    assert_eq!(statistics.exceptions[&Exception::IllegalDataAddress], 1);
+    // MBAP header (7 bytes) + function, address and quantity (5 bytes)
+    assert_eq!(statistics.bytes_sent, 3 * 12);
+    // two responses with one register (11 bytes) and one exception response (9 bytes)
+    assert_eq!(statistics.bytes_received, 2 * 11 + 9);
+    assert_eq!(statistics.latency.count(), 3);
+    assert_eq!(statistics.reconnects, 0);
+
+    assert_eq!(handle.snapshot_and_reset(), statistics);
+    assert_eq!(client.statistics().total_requests(), 0);
+
+    client.close();
+    let _ = server_thread.join();
+}
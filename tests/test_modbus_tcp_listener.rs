@@ -0,0 +1,154 @@
+#![cfg(unix)]
+
+use libmodbus::{
+    ListenerBuilder, Modbus, ModbusClient, ModbusListener, ModbusMapping, ModbusServer, ModbusTCP,
+    ModbusTCPPI,
+};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+/// Accept `clients` clients, each answered with its number in register 0 by a thread of its own
+fn serve(listener: ModbusListener, clients: u16) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut workers = Vec::new();
+        for client in 0..clients {
+            let session = listener.accept().expect("Could not accept connection");
+            assert!(session.peer_addr().ip().is_loopback());
+            workers.push(thread::spawn(move || {
+                let mapping = ModbusMapping::new(0, 0, 1, 0).unwrap();
+                mapping.get_registers_mut()[0] = client;
+                let modbus = session.into_modbus();
+                let mut query = vec![0u8; Modbus::TCP_MAX_ADU_LENGTH];
+                let rc = modbus.receive(&mut query).expect("Could not receive");
+                modbus.reply(&query, rc, &mapping).expect("Could not reply");
+            }));
+        }
+        for worker in workers {
+            worker.join().unwrap();
+        }
+    })
+}
+
+#[test]
+fn tcp_listener() {
+    let mut server = Modbus::new_tcp("127.0.0.1", 1541).unwrap();
+    let listener = server.tcp_listener(2).expect("Could not listen");
+    assert_eq!(listener.local_addr().unwrap().port(), 1541);
+    let server_thread = serve(listener, 2);
+
+    // both clients are connected at the same time
+    let first = Modbus::new_tcp("127.0.0.1", 1541).unwrap();
+    first.connect().unwrap();
+    let second = Modbus::new_tcp("127.0.0.1", 1541).unwrap();
+    second.connect().unwrap();
+
+    let mut dest = [0u16; 1];
+    second.read_registers(0, 1, &mut dest).unwrap();
+    assert_eq!(dest[0], 1);
+    first.read_registers(0, 1, &mut dest).unwrap();
+    assert_eq!(dest[0], 0);
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn tcp_pi_listener() {
+    let mut server = Modbus::new_tcp_pi("::1", "1542").unwrap();
+    let listener = server.tcp_pi_listener(1).expect("Could not listen");
+    let server_thread = serve(listener, 1);
+
+    let client = Modbus::new_tcp_pi("::1", "1542").unwrap();
+    client.connect().unwrap();
+    let mut dest = [0u16; 1];
+    client.read_registers(0, 1, &mut dest).unwrap();
+    assert_eq!(dest[0], 0);
+    server_thread.join().unwrap();
+}
+
+/// Read register 0 of the server on `port` of the IPv4 loopback address
+fn read_register(port: u16) -> u16 {
+    let client = Modbus::new_tcp("127.0.0.1", port as i32).unwrap();
+    client.connect().unwrap();
+    let mut dest = [0u16; 1];
+    client.read_registers(0, 1, &mut dest).unwrap();
+    dest[0]
+}
+
+#[test]
+fn ephemeral_port() {
+    let listener = ListenerBuilder::new("127.0.0.1:0".parse().unwrap())
+        .num_connection(1)
+        .listen()
+        .expect("Could not listen");
+    let port = listener.local_addr().unwrap().port();
+    assert_ne!(port, 0);
+    let server_thread = serve(listener, 1);
+
+    assert_eq!(read_register(port), 0);
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn reuse_port() {
+    let mut builder = ListenerBuilder::new("127.0.0.1:0".parse().unwrap());
+    builder.reuse_port(true);
+    let first = builder.listen().unwrap();
+    let addr = first.local_addr().unwrap();
+
+    // a second listener on the same port only binds if both set the option
+    assert!(ListenerBuilder::new(addr).listen().is_err());
+    let second = ListenerBuilder::new(addr)
+        .reuse_port(true)
+        .listen()
+        .unwrap();
+    assert_eq!(second.local_addr().unwrap(), addr);
+}
+
+#[test]
+fn only_v6() {
+    // skip hosts without IPv6
+    let listener = match ListenerBuilder::new("[::]:0".parse().unwrap())
+        .only_v6(true)
+        .listen()
+    {
+        Ok(listener) => listener,
+        Err(_) => return,
+    };
+    let port = listener.local_addr().unwrap().port();
+    assert!(TcpStream::connect(("127.0.0.1", port)).is_err());
+    assert!(TcpStream::connect(("::1", port)).is_ok());
+
+    // the option only exists for IPv6
+    assert!(ListenerBuilder::new("127.0.0.1:0".parse().unwrap())
+        .only_v6(true)
+        .listen()
+        .is_err());
+}
+
+#[test]
+fn from_std_listener() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server_thread = serve(ModbusListener::from(listener), 1);
+
+    assert_eq!(read_register(port), 0);
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn set_socket_stream() {
+    let listener = ListenerBuilder::new("127.0.0.1:0".parse().unwrap())
+        .listen()
+        .unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server_thread = serve(listener, 1);
+
+    // connected without libmodbus
+    let mut client = Modbus::new_tcp("127.0.0.1", addr.port() as i32).unwrap();
+    client
+        .set_socket(TcpStream::connect(addr).unwrap())
+        .unwrap();
+    let mut dest = [0u16; 1];
+    client.read_registers(0, 1, &mut dest).unwrap();
+    assert_eq!(dest[0], 0);
+    server_thread.join().unwrap();
+}
@@ -0,0 +1,163 @@
+#![cfg(unix)]
+
+use libmodbus::{Modbus, ModbusClient, ModbusMapping, ModbusTCP, TcpServer};
+use std::io::Read;
+use std::net::TcpStream;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Serve until `requests` requests are answered or 5 seconds passed
+fn start_server(port: i32, max_connections: usize, requests: usize) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut modbus =
+            Modbus::new_tcp("127.0.0.1", port).expect("Could not create TCP Server context");
+        let socket = modbus
+            .tcp_listen(5)
+            .expect("Could not listen to TCP socket");
+
+        let mut mb_mapping = ModbusMapping::new(
+            Modbus::MAX_READ_BITS,
+            Modbus::MAX_READ_BITS,
+            Modbus::MAX_READ_REGISTERS,
+            Modbus::MAX_READ_REGISTERS,
+        )
+        .expect("Failed to allocate the mapping");
+
+        let mut server = TcpServer::new(modbus, socket);
+        server.set_max_connections(max_connections);
+
+        let start = Instant::now();
+        let mut served = 0;
+        while served < requests && start.elapsed() < Duration::from_secs(5) {
+            served += server
+                .poll_once(Some(Duration::from_millis(100)), &mut mb_mapping)
+                .expect("Could not poll");
+        }
+    })
+}
+
+#[test]
+fn serve_many_clients() {
+    let port = 1519;
+    // Start modbus server
+    let server_thread = start_server(port, 5, 4);
+    thread::sleep(Duration::from_millis(200));
+
+    // connect both clients before the first request
+    let client1 = Modbus::new_tcp("127.0.0.1", port).unwrap();
+    let client2 = Modbus::new_tcp("127.0.0.1", port).unwrap();
+    client1.connect().expect("could not connect");
+    client2.connect().expect("could not connect");
+
+    assert!(client1.write_register(1, 11).is_ok());
+    assert!(client2.write_register(2, 22).is_ok());
+
+    let mut dest = vec![0u16; 2];
+    assert_eq!(client1.read_registers(1, 2, &mut dest).unwrap(), 2);
+    assert_eq!(dest, vec![11, 22]);
+    let mut dest = vec![0u16; 2];
+    assert_eq!(client2.read_registers(1, 2, &mut dest).unwrap(), 2);
+    assert_eq!(dest, vec![11, 22]);
+
+    let _ = server_thread.join();
+}
+
+#[test]
+fn max_connections() {
+    let port = 1520;
+    // Start modbus server
+    let server_thread = start_server(port, 1, 2);
+    thread::sleep(Duration::from_millis(200));
+
+    let mut dest = vec![0u16; 1];
+    let client1 = Modbus::new_tcp("127.0.0.1", port).unwrap();
+    client1.connect().expect("could not connect");
+    assert!(client1.read_registers(0, 1, &mut dest).is_ok());
+
+    // accepted by the kernel but closed by the server right away
+    let client2 = Modbus::new_tcp("127.0.0.1", port).unwrap();
+    client2.connect().expect("could not connect");
+    assert!(client2.read_registers(0, 1, &mut dest).is_err());
+
+    assert!(client1.read_registers(0, 1, &mut dest).is_ok());
+
+    let _ = server_thread.join();
+}
+
+#[test]
+fn idle_timeout() {
+    let port = 1546;
+    let server_thread = thread::spawn(move || {
+        let mut modbus =
+            Modbus::new_tcp("127.0.0.1", port).expect("Could not create TCP Server context");
+        let socket = modbus
+            .tcp_listen(5)
+            .expect("Could not listen to TCP socket");
+        let mut mb_mapping =
+            ModbusMapping::new(0, 0, 1, 0).expect("Failed to allocate the mapping");
+
+        let mut server = TcpServer::new(modbus, socket);
+        server.set_idle_timeout(Some(Duration::from_millis(200)));
+
+        // wait for the client, then until its connection is closed again
+        let start = Instant::now();
+        while server.connections() == 0 && start.elapsed() < Duration::from_secs(5) {
+            server
+                .poll_once(Some(Duration::from_millis(50)), &mut mb_mapping)
+                .expect("Could not poll");
+        }
+        assert_eq!(server.connections(), 1);
+        while server.connections() > 0 && start.elapsed() < Duration::from_secs(5) {
+            server
+                .poll_once(Some(Duration::from_millis(50)), &mut mb_mapping)
+                .expect("Could not poll");
+        }
+        assert_eq!(server.connections(), 0);
+    });
+    thread::sleep(Duration::from_millis(200));
+
+    // connect without sending a request
+    let mut client = TcpStream::connect(("127.0.0.1", port as u16)).expect("could not connect");
+    client
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let start = Instant::now();
+    let mut buf = [0u8; 1];
+    assert_eq!(client.read(&mut buf).expect("connection not closed"), 0);
+    assert!(start.elapsed() >= Duration::from_millis(150));
+
+    server_thread.join().expect("server failed");
+}
+
+#[test]
+fn serve_idle_timeout() {
+    let port = 1548;
+    // runs until the test process exits
+    thread::spawn(move || {
+        let mut modbus =
+            Modbus::new_tcp("127.0.0.1", port).expect("Could not create TCP Server context");
+        let socket = modbus
+            .tcp_listen(5)
+            .expect("Could not listen to TCP socket");
+        let mut mb_mapping =
+            ModbusMapping::new(0, 0, 1, 0).expect("Failed to allocate the mapping");
+
+        let mut server = TcpServer::new(modbus, socket);
+        server.set_idle_timeout(Some(Duration::from_millis(400)));
+        server.serve(&mut mb_mapping)
+    });
+    thread::sleep(Duration::from_millis(200));
+
+    let start = Instant::now();
+    let mut idle = TcpStream::connect(("127.0.0.1", port as u16)).expect("could not connect");
+    idle.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    // activity on another connection doesn't extend the wait for the idle one
+    thread::sleep(Duration::from_millis(250));
+    let _other = TcpStream::connect(("127.0.0.1", port as u16)).expect("could not connect");
+
+    let mut buf = [0u8; 1];
+    assert_eq!(idle.read(&mut buf).expect("connection not closed"), 0);
+    assert!(start.elapsed() >= Duration::from_millis(350));
+    assert!(start.elapsed() < Duration::from_millis(600));
+}
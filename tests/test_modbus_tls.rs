@@ -0,0 +1,178 @@
+#![cfg(feature = "tls")]
+
+mod common;
+
+use common::Registers;
+use libmodbus::{
+    Exception, Modbus, ModbusClient, ModbusServer, ModbusStream, RequestHandler, TlsConfig,
+};
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A PEM file generated by `tests/tls/generate.sh`
+fn pem(name: &str) -> PathBuf {
+    [env!("CARGO_MANIFEST_DIR"), "tests", "tls", name]
+        .iter()
+        .collect()
+}
+
+fn config(name: &str) -> TlsConfig {
+    TlsConfig::from_pem_files(
+        pem(&format!("{}.pem", name)),
+        pem(&format!("{}.key", name)),
+        pem("ca.pem"),
+    )
+    .expect("Could not load the certificates")
+}
+
+/// The registers of `common::Registers`, written by operators only
+struct Authorized {
+    registers: Registers,
+    role: Option<String>,
+}
+
+impl RequestHandler for Authorized {
+    fn read_holding_registers(
+        &mut self,
+        address: u16,
+        quantity: u16,
+    ) -> Result<Vec<u16>, Exception> {
+        self.registers.read_holding_registers(address, quantity)
+    }
+
+    fn write_multiple_registers(&mut self, address: u16, values: &[u16]) -> Result<(), Exception> {
+        if self.role.as_deref() != Some("Operator") {
+            return Err(Exception::IllegalFunction);
+        }
+        self.registers.write_multiple_registers(address, values)
+    }
+}
+
+#[test]
+fn config_from_pem() {
+    assert!(TlsConfig::from_pem_files(pem("server.pem"), pem("server.key"), pem("ca.pem")).is_ok());
+    assert!(TlsConfig::from_pem(b"", b"", b"").is_err());
+    assert!(
+        TlsConfig::from_pem_files(pem("server.pem"), pem("missing.key"), pem("ca.pem")).is_err()
+    );
+    assert!(ModbusStream::new_tls("127.0.0.1", 65536, &config("client")).is_err());
+}
+
+#[test]
+fn client_server_with_role() {
+    let listener = TcpListener::bind("127.0.0.1:1530").expect("Could not listen");
+    let server_thread = thread::spawn(move || {
+        let modbus =
+            ModbusStream::tls_accept(&listener, &config("server")).expect("Could not accept");
+        let mut handler = Authorized {
+            registers: Registers::new(),
+            role: modbus.peer_role(),
+        };
+
+        loop {
+            let mut query = vec![0u8; Modbus::MAX_ADU_LENGTH];
+            match modbus.receive(&mut query) {
+                Ok(rc) => modbus
+                    .reply_with(&query, rc, &mut handler)
+                    .expect("Could not reply"),
+                Err(_err) => break,
+            };
+        }
+        handler.role
+    });
+
+    let client = ModbusStream::new_tls("127.0.0.1", 1530, &config("client")).unwrap();
+    client.connect().expect("could not connect");
+
+    // the client is an operator
+    common::client_server(&client);
+
+    client.close();
+    assert_eq!(server_thread.join().unwrap().as_deref(), Some("Operator"));
+}
+
+#[test]
+fn untrusted_client() {
+    let listener = TcpListener::bind("127.0.0.1:1531").expect("Could not listen");
+    let server_thread =
+        thread::spawn(move || ModbusStream::tls_accept(&listener, &config("server")).is_err());
+
+    let untrusted =
+        TlsConfig::from_pem_files(pem("untrusted.pem"), pem("untrusted.key"), pem("ca.pem"))
+            .unwrap();
+    let client = ModbusStream::new_tls("127.0.0.1", 1531, &untrusted).unwrap();
+    // with TLS 1.3 the client learns of the rejection with its first request
+    let mut dest = vec![0u16; 1];
+    assert!(client
+        .connect()
+        .and_then(|()| client.read_registers(0, 1, &mut dest))
+        .is_err());
+
+    assert!(server_thread.join().unwrap());
+}
+
+#[test]
+fn silent_client() {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Could not listen");
+    // connects but never starts the handshake
+    let _client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+
+    let started = Instant::now();
+    match ModbusStream::tls_accept(&listener, &config("server")) {
+        Err(libmodbus::Error::Tls { source, .. }) => {
+            assert_eq!(source.raw_os_error(), Some(libc::ETIMEDOUT))
+        }
+        result => panic!("expected a timeout, got {:?}", result.map(|_| ())),
+    }
+    // the default response timeout
+    assert!(started.elapsed() >= Duration::from_millis(500));
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn slow_client() {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Could not listen");
+    let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    // the header of a handshake record of 256 bytes, followed by one byte every 100 ms
+    let client_thread = thread::spawn(move || {
+        let _ = client.write_all(&[0x16, 0x03, 0x01, 0x01, 0x00]);
+        for _ in 0..30 {
+            thread::sleep(Duration::from_millis(100));
+            if client.write_all(&[0]).is_err() {
+                break;
+            }
+        }
+    });
+
+    let started = Instant::now();
+    match ModbusStream::tls_accept(&listener, &config("server")) {
+        Err(libmodbus::Error::Tls { source, .. }) => {
+            assert_eq!(source.raw_os_error(), Some(libc::ETIMEDOUT))
+        }
+        result => panic!("expected a timeout, got {:?}", result.map(|_| ())),
+    }
+    // one response timeout for the whole handshake
+    assert!(started.elapsed() < Duration::from_millis(1500));
+    let _ = client_thread.join();
+}
+
+#[test]
+fn silent_server() {
+    // accepted by the kernel, but nobody answers the handshake
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Could not listen");
+    let port = listener.local_addr().unwrap().port() as i32;
+
+    let client = ModbusStream::new_tls("127.0.0.1", port, &config("client")).unwrap();
+    let started = Instant::now();
+    match client.connect() {
+        Err(libmodbus::Error::Modbus { source, .. }) => {
+            assert_eq!(source.raw_os_error(), Some(libc::ETIMEDOUT))
+        }
+        result => panic!("expected a timeout, got {:?}", result),
+    }
+    assert!(started.elapsed() >= Duration::from_millis(500));
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
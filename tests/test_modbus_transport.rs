@@ -0,0 +1,106 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::start_server;
+use libmodbus::{Error, Framing, IoTransport, ModbusClient, ModbusStream, Timeout, Transport};
+use std::io;
+use std::os::unix::net::UnixStream;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Records the frames sent, receives nothing
+struct Recorder(Arc<Mutex<Vec<Vec<u8>>>>);
+
+impl Transport for Recorder {
+    fn send(&mut self, adu: &[u8]) -> io::Result<()> {
+        self.0.lock().unwrap().push(adu.to_vec());
+        Ok(())
+    }
+
+    fn receive(&mut self, _buf: &mut [u8], timeout: Option<Duration>) -> io::Result<usize> {
+        thread::sleep(timeout.unwrap_or_default());
+        Err(io::Error::from_raw_os_error(libc::ETIMEDOUT))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn close(&mut self) {}
+}
+
+#[test]
+fn custom_transport() {
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    let mut modbus = ModbusStream::from_transport(Recorder(sent.clone()), Framing::Rtu);
+    modbus.set_slave(1).unwrap();
+    modbus
+        .set_response_timeout(Timeout::new(0, 10_000))
+        .unwrap();
+
+    let mut dest = [0u16; 1];
+    match modbus.read_registers(1, 1, &mut dest) {
+        Err(Error::Client { source, .. }) => {
+            assert_eq!(source.raw_os_error(), Some(libc::ETIMEDOUT))
+        }
+        result => panic!("expected a timeout, got {:?}", result),
+    }
+    // RTU framing: slave id, PDU and CRC
+    assert_eq!(
+        *sent.lock().unwrap(),
+        vec![vec![0x01, 0x03, 0x00, 0x01, 0x00, 0x01, 0xD5, 0xCA]]
+    );
+}
+
+#[test]
+fn rtu_over_read_write() {
+    let (client, server) = UnixStream::pair().unwrap();
+    let server_thread = start_server(IoTransport::new(server), Framing::Rtu, 2);
+
+    let mut modbus = ModbusStream::from_transport(IoTransport::new(client), Framing::Rtu);
+    modbus.set_slave(1).unwrap();
+    // connect() keeps the transport
+    modbus.connect().unwrap();
+    let mut dest = [0u16; 3];
+    for _ in 0..2 {
+        assert_eq!(modbus.read_registers(2, 3, &mut dest).unwrap(), 3);
+        assert_eq!(dest, [2, 3, 4]);
+    }
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn mbap_over_read_write() {
+    let (client, server) = UnixStream::pair().unwrap();
+    let server_thread = start_server(IoTransport::new(server), Framing::Mbap, 1);
+
+    let mut modbus = ModbusStream::from_transport(IoTransport::new(client), Framing::Mbap);
+    modbus.set_slave(1).unwrap();
+    let mut dest = [0u16; 2];
+    assert_eq!(modbus.read_registers(7, 2, &mut dest).unwrap(), 2);
+    assert_eq!(dest, [7, 8]);
+    server_thread.join().unwrap();
+
+    // closed for good
+    modbus.close();
+    assert!(modbus.read_registers(7, 2, &mut dest).is_err());
+}
+
+#[test]
+fn read_write_timeout() {
+    let (client, _server) = UnixStream::pair().unwrap();
+    // the timeout of the context is only kept if the reads return
+    client.set_nonblocking(true).unwrap();
+    let mut modbus = ModbusStream::from_transport(IoTransport::new(client), Framing::Mbap);
+    modbus
+        .set_response_timeout(Timeout::new(0, 50_000))
+        .unwrap();
+
+    let started = Instant::now();
+    let mut dest = [0u16; 1];
+    assert!(modbus.read_registers(0, 1, &mut dest).is_err());
+    assert!(started.elapsed() >= Duration::from_millis(50));
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use common::Registers;
+use libmodbus::{Modbus, ModbusClient, ModbusServer, ModbusStream, Timeout};
+use std::net::UdpSocket;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Answer requests until none arrives for a second, the first one only after `delay`
+fn start_server(port: i32, delay: Duration) -> thread::JoinHandle<u64> {
+    let mut modbus = ModbusStream::udp_bind("127.0.0.1", port).expect("Could not bind");
+    thread::spawn(move || {
+        modbus
+            .set_indication_timeout(Timeout { sec: 1, usec: 0 })
+            .unwrap();
+        let mut handler = Registers::new();
+
+        let mut first = true;
+        loop {
+            let mut query = vec![0u8; Modbus::MAX_ADU_LENGTH];
+            match modbus.receive(&mut query) {
+                Ok(rc) => {
+                    if first {
+                        thread::sleep(delay);
+                        first = false;
+                    }
+                    modbus
+                        .reply_with(&query, rc, &mut handler)
+                        .expect("Could not reply")
+                }
+                Err(_err) => break,
+            };
+        }
+        modbus.statistics().total_requests()
+    })
+}
+
+#[test]
+fn new_udp() {
+    assert!(ModbusStream::new_udp("127.0.0.1", 502).is_ok());
+    assert!(ModbusStream::new_udp("127.0.0.1", 65536).is_err());
+    assert_eq!(
+        ModbusStream::new_udp("127.0.0.1", 502)
+            .unwrap()
+            .get_retransmissions(),
+        2
+    );
+    assert!(ModbusStream::udp_bind("127.0.0.1", -1).is_err());
+}
+
+#[test]
+fn client_server() {
+    let server_thread = start_server(1528, Duration::from_millis(0));
+
+    let client = ModbusStream::new_udp("127.0.0.1", 1528).unwrap();
+    client.connect().expect("could not connect");
+
+    common::client_server(&client);
+
+    assert_eq!(server_thread.join().unwrap(), 4);
+}
+
+#[test]
+fn retransmission() {
+    // the response to the first request arrives after the response timeout
+    let server_thread = start_server(1529, Duration::from_millis(300));
+
+    let mut client = ModbusStream::new_udp("127.0.0.1", 1529).unwrap();
+    client
+        .set_response_timeout(Timeout {
+            sec: 0,
+            usec: 200_000,
+        })
+        .unwrap();
+    client.connect().expect("could not connect");
+
+    // sent again after the response timeout, answered twice
+    let mut dest = vec![0u16; 4];
+    assert_eq!(client.read_registers(2, 4, &mut dest).unwrap(), 4);
+    assert_eq!(dest, vec![2, 3, 4, 5]);
+
+    // the second response to the first request is dropped by its transaction id
+    let mut dest = vec![0u16; 2];
+    assert_eq!(client.read_registers(8, 2, &mut dest).unwrap(), 2);
+    assert_eq!(dest, vec![8, 9]);
+
+    assert_eq!(server_thread.join().unwrap(), 3);
+}
+
+#[test]
+fn stale_responses() {
+    // answers every request with a stream of responses to other transactions
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = server.local_addr().unwrap().port();
+    let server_thread = thread::spawn(move || {
+        let mut request = [0u8; Modbus::MAX_ADU_LENGTH];
+        let (_, client) = server.recv_from(&mut request).unwrap();
+        let stale = request[1].wrapping_add(100);
+        let response = [
+            request[0], stale, 0, 0, 0, 5, request[6], 0x03, 0x02, 0x00, 0x01,
+        ];
+        let started = Instant::now();
+        while started.elapsed() < Duration::from_millis(1500) {
+            server.send_to(&response, client).unwrap();
+            thread::sleep(Duration::from_millis(20));
+        }
+    });
+
+    let mut client = ModbusStream::new_udp("127.0.0.1", port as i32).unwrap();
+    client.set_retransmissions(0);
+    client
+        .set_response_timeout(Timeout {
+            sec: 0,
+            usec: 200_000,
+        })
+        .unwrap();
+    client.connect().expect("could not connect");
+
+    let started = Instant::now();
+    let mut dest = vec![0u16; 1];
+    assert!(client.read_registers(0, 1, &mut dest).is_err());
+    // the stale responses don't restart the response timeout
+    assert!(started.elapsed() < Duration::from_millis(1000));
+
+    server_thread.join().unwrap();
+}
@@ -0,0 +1,143 @@
+use libmodbus::{
+    Broadcast, Exception, Modbus, ModbusClient, ModbusMapping, ModbusServer, ModbusTCP,
+    RequestHandler, UnitSelection, UnitTable,
+};
+use std::thread;
+use std::time::Duration;
+
+/// A single holding register
+struct Register(u16);
+
+impl RequestHandler for Register {
+    fn read_holding_registers(
+        &mut self,
+        address: u16,
+        quantity: u16,
+    ) -> Result<Vec<u16>, Exception> {
+        match (address, quantity) {
+            (0, 1) => Ok(vec![self.0]),
+            _ => Err(Exception::IllegalDataAddress),
+        }
+    }
+
+    fn write_multiple_registers(&mut self, address: u16, values: &[u16]) -> Result<(), Exception> {
+        match (address, values) {
+            (0, [value]) => {
+                self.0 = *value;
+                Ok(())
+            }
+            _ => Err(Exception::IllegalDataAddress),
+        }
+    }
+}
+
+fn start_server(port: i32) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut modbus =
+            Modbus::new_tcp("127.0.0.1", port).expect("Could not create TCP Server context");
+        let mut socket = modbus
+            .tcp_listen(1)
+            .expect("Could not listen to TCP socket");
+        modbus
+            .tcp_accept(&mut socket)
+            .expect("Could not accept connection");
+
+        let mut units = UnitTable::new();
+        for unit in 1..=2 {
+            let mb_mapping =
+                ModbusMapping::new(0, 0, 10, 0).expect("Failed to allocate the mapping");
+            mb_mapping.get_registers_mut()[0] = unit as u16 * 100;
+            units.insert(unit, mb_mapping);
+        }
+
+        loop {
+            let mut query = vec![0u8; Modbus::TCP_MAX_ADU_LENGTH];
+
+            match modbus.receive(&mut query) {
+                Ok(rc) => modbus.reply_with(&query, rc, &mut units),
+                Err(_err) => break,
+            }
+            .expect("Could not receive");
+        }
+    })
+}
+
+#[test]
+fn route_units() {
+    let mut units = UnitTable::new();
+    units.insert(1, Register(1));
+    units.insert(2, Register(2));
+
+    assert_eq!(units.select_unit(2), UnitSelection::Handle);
+    assert_eq!(units.read_holding_registers(0, 1), Ok(vec![2]));
+    assert_eq!(units.select_unit(3), UnitSelection::Unknown);
+    assert_eq!(
+        units.read_holding_registers(0, 1),
+        Err(Exception::GatewayTarget)
+    );
+
+    // unit id 0 is no broadcast on TCP
+    assert_eq!(units.select_unit(0), UnitSelection::Unknown);
+    assert_eq!(
+        units.write_single_register(0, 6),
+        Err(Exception::GatewayTarget)
+    );
+    assert_eq!(units.get(1).unwrap().0, 1);
+
+    // broadcast writes on a serial line reach every unit
+    assert_eq!(units.select_broadcast(), UnitSelection::Handle);
+    assert!(units.write_single_register(0, 7).is_ok());
+    assert_eq!(units.get(1).unwrap().0, 7);
+    assert_eq!(units.get(2).unwrap().0, 7);
+
+    units.set_broadcast(Broadcast::Ignore);
+    assert_eq!(units.select_broadcast(), UnitSelection::Ignore);
+    units.set_broadcast(Broadcast::Unit(1));
+    assert_eq!(units.select_broadcast(), UnitSelection::Handle);
+    assert!(units.write_single_register(0, 8).is_ok());
+    assert_eq!(units.get(1).unwrap().0, 8);
+    assert_eq!(units.get(2).unwrap().0, 7);
+
+    units.set_default(Some(Register(42)));
+    assert_eq!(units.select_unit(3), UnitSelection::Handle);
+    assert_eq!(units.read_holding_registers(0, 1), Ok(vec![42]));
+}
+
+#[test]
+fn reply_with_units() {
+    let port = 1521;
+    // Start modbus server
+    let server_thread = start_server(port);
+    thread::sleep(Duration::from_millis(200));
+
+    // connect client
+    match Modbus::new_tcp("127.0.0.1", port) {
+        Ok(mut client) => {
+            let mut dest = vec![0u16; 1];
+            client.connect().expect("could not connect");
+
+            client.set_slave(1).unwrap();
+            assert!(client.read_registers(0, 1, &mut dest).is_ok());
+            assert_eq!(dest, vec![100]);
+
+            client.set_slave(2).unwrap();
+            assert!(client.read_registers(0, 1, &mut dest).is_ok());
+            assert_eq!(dest, vec![200]);
+
+            client.set_slave(3).unwrap();
+            let err = client.read_registers(0, 1, &mut dest).unwrap_err();
+            assert_eq!(err.exception(), Some(Exception::GatewayTarget));
+
+            // unit id 0 is no broadcast on TCP
+            client.set_slave(0).unwrap();
+            let err = client.write_register(0, 7).unwrap_err();
+            assert_eq!(err.exception(), Some(Exception::GatewayTarget));
+            client.set_slave(1).unwrap();
+            assert!(client.read_registers(0, 1, &mut dest).is_ok());
+            assert_eq!(dest, vec![100]);
+        }
+        _ => panic!("could not connect"),
+    }
+
+    let _ = server_thread.join();
+}
@@ -0,0 +1,49 @@
+#!/bin/sh
+# Generate the certificates of tests/test_modbus_tls.rs, valid for 100 years:
+#
+# * ca.pem                      - the certificate authority of the server and the client
+# * server.pem, server.key      - the server, for 127.0.0.1 and localhost
+# * client.pem, client.key      - the client, with the Modbus/TCP Security role "Operator"
+# * untrusted.pem, untrusted.key - a self-signed client the server doesn't trust
+set -e
+cd "$(dirname "$0")"
+
+key() {
+    openssl genpkey -algorithm EC -pkeyopt ec_paramgen_curve:P-256 -out "$1"
+}
+
+key ca.key
+openssl req -x509 -new -key ca.key -days 36500 -subj "/CN=libmodbus test CA" \
+    -addext "basicConstraints=critical,CA:TRUE" -addext "keyUsage=critical,keyCertSign,cRLSign" -out ca.pem
+
+sign() {
+    openssl req -new -key "$1.key" -subj "/CN=$1" -out "$1.csr"
+    openssl x509 -req -in "$1.csr" -CA ca.pem -CAkey ca.key -CAcreateserial -days 36500 -extfile "$1.ext" \
+        -out "$1.pem"
+    rm "$1.csr" "$1.ext"
+}
+
+key server.key
+cat > server.ext <<END
+basicConstraints = critical,CA:FALSE
+keyUsage = critical,digitalSignature
+extendedKeyUsage = serverAuth
+subjectAltName = IP:127.0.0.1,DNS:localhost
+END
+sign server
+
+# the role extension of the Modbus/TCP Security protocol specification
+key client.key
+cat > client.ext <<END
+basicConstraints = critical,CA:FALSE
+keyUsage = critical,digitalSignature
+extendedKeyUsage = clientAuth
+1.3.6.1.4.1.50316.802.1 = ASN1:UTF8String:Operator
+END
+sign client
+
+key untrusted.key
+openssl req -x509 -new -key untrusted.key -days 36500 -subj "/CN=untrusted" \
+    -addext "extendedKeyUsage=clientAuth" -out untrusted.pem
+
+rm -f ca.key ca.srl